@@ -142,6 +142,9 @@ bool AsapIndexManager::load(const Settings& settings, std::vector<TableData>& ta
             if (table.contains("hashFromVbs") && table["hashFromVbs"].is_string()) tableData.hashFromVbs = table["hashFromVbs"].get<std::string>();
             if (table.contains("hasDiffVbs") && table["hasDiffVbs"].is_boolean()) tableData.hasDiffVbs = table["hasDiffVbs"].get<bool>();
             if (table.contains("isPatched") && table["isPatched"].is_boolean()) tableData.isPatched = table["isPatched"].get<bool>();
+            if (table.contains("adultScore") && table["adultScore"].is_number()) tableData.adultScore = table["adultScore"].get<double>();
+            if (table.contains("isAdult") && table["isAdult"].is_boolean()) tableData.isAdult = table["isAdult"].get<bool>();
+            if (table.contains("mediaScore") && table["mediaScore"].is_number_integer()) tableData.mediaScore = table["mediaScore"].get<int>();
             // if (table.contains("isManualVpsId") && table["isManualVpsId"].is_boolean()) tableData.isManualVpsId = table["isManualVpsId"].get<bool>();
             // EXTRA FILES SCAN Boolean flags (ensure they are boolean type in JSON)
             if (table.contains("hasAltSound") && table["hasAltSound"].is_boolean()) tableData.hasAltSound = table["hasAltSound"].get<bool>();
@@ -285,6 +288,9 @@ bool AsapIndexManager::save(const Settings& settings, const std::vector<TableDat
         tableJson["hashFromVbs"] = table.hashFromVbs;
         tableJson["hasDiffVbs"] = table.hasDiffVbs;
         tableJson["isPatched"] = table.isPatched;
+        tableJson["adultScore"] = table.adultScore;
+        tableJson["isAdult"] = table.isAdult;
+        tableJson["mediaScore"] = table.mediaScore;
         // tableJson["isManualVpsId"] = table.isManualVpsId;
         // EXTRA FILE SCAN (Boolean flags)
         tableJson["hasAltSound"] = table.hasAltSound;
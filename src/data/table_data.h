@@ -115,6 +115,9 @@ struct TableData {
     // --------------- OPERATIONAL TAGS ------------------
     double matchConfidence = 0.0f;    ///< Confidence score of match with vpsdb
     bool isManualVpsId = false;      ///< Marks table with user added vpsdb ID for matching (skips matchmaking)
+    double adultScore = 0.0;         ///< Likelihood (0-1) of adult content, from vpx_get_content_rating
+    bool isAdult = false;            ///< adultScore over the wrapper threshold, hidden in filtered views
+    int mediaScore = 0;              ///< Media completeness (0-100), from vpx_score_table_media
     // file internals related
     std::string hashFromVpx;         ///< SHA256 hash of internal .vpx VB script
     std::string hashFromVbs;         ///< SHA256 hash of (patched) sidecar vb script
@@ -21,6 +21,7 @@
 #include <mutex>
 #include <atomic>
 #include <rapidfuzz/rapidfuzz_all.hpp>
+#include <nlohmann/json.hpp>
 
 using rapidfuzz::fuzz::partial_ratio;
 using rapidfuzz::fuzz::ratio;
@@ -239,12 +240,32 @@ std::vector<TableData> FileScanner::scan(const Settings& settings, LoadingProgre
             table.folderLastModified = folderLastModified;
 
             // --- VPX GameData ---
-            char* code_ptr = get_vpx_gamedata_code(table.vpxFile.c_str());
+            // One open for the script and the rating below, so the table is parsed once.
+            VpxHandle* vpx_handle = vpx_open(table.vpxFile.c_str());
             std::string vpx_script;
-            if (code_ptr) {
-                vpx_script = std::string(code_ptr);
-                free_rust_string(code_ptr);
-                table.hashFromVpx = calculate_string_sha256(vpx_script);
+            if (vpx_handle) {
+                char* code_ptr = vpx_handle_get_gamedata_code(vpx_handle);
+                if (code_ptr) {
+                    vpx_script = std::string(code_ptr);
+                    vpx_free_string(code_ptr);
+                    table.hashFromVpx = calculate_string_sha256(vpx_script);
+                }
+            }
+
+            // --- Content rating (streaming-safe / family filter) ---
+            // An unreadable table is still rated by the file names next to it.
+            char* rating_ptr = vpx_handle ? vpx_handle_get_content_rating(vpx_handle)
+                                          : vpx_get_content_rating(table.vpxFile.c_str());
+            if (vpx_handle) vpx_close(vpx_handle);
+            if (rating_ptr) {
+                try {
+                    nlohmann::json rating = nlohmann::json::parse(rating_ptr);
+                    table.adultScore = rating.value("score", 0.0);
+                    table.isAdult = rating.value("adult", false);
+                } catch (const nlohmann::json::exception& e) {
+                    LOG_ERROR("Invalid content rating for " + table.vpxFile + ": " + e.what());
+                }
+                vpx_free_string(rating_ptr);
             }
 
             // --- VBS Detection ---
@@ -321,6 +342,23 @@ std::vector<TableData> FileScanner::scan(const Settings& settings, LoadingProgre
             table.hasFlyerFront = !table.flyerFront.empty();
             table.hasFlyerBack = !table.flyerBack.empty();
 
+            // --- Media completeness ("needs media work" sorting) ---
+            nlohmann::json mediaRow = {
+                {"hasWheelImage", table.hasWheelImage}, {"hasPlayfieldImage", table.hasPlayfieldImage},
+                {"hasPlayfieldVideo", table.hasPlayfieldVideo}, {"hasBackglassImage", table.hasBackglassImage},
+                {"hasBackglassVideo", table.hasBackglassVideo}, {"hasDmdImage", table.hasDmdImage},
+                {"hasDmdVideo", table.hasDmdVideo}, {"hasTableMusic", table.hasTableMusic},
+                {"hasLaunchAudio", table.hasLaunchAudio}};
+            char* media_ptr = vpx_score_table_media(mediaRow.dump().c_str(), nullptr);
+            if (media_ptr) {
+                try {
+                    table.mediaScore = nlohmann::json::parse(media_ptr).value("score", 0);
+                } catch (const nlohmann::json::exception& e) {
+                    LOG_ERROR("Invalid media score for " + table.vpxFile + ": " + e.what());
+                }
+                vpx_free_string(media_ptr);
+            }
+
             // --- Folder assets ---
             table.hasPup = PathUtils::getPupPath(table.folder);
             table.hasAltMusic = PathUtils::getAltMusic(table.folder);
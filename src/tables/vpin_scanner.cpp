@@ -8,6 +8,7 @@
 #include <future>
 #include <vector>
 #include <atomic>
+#include <ctime>
 
 namespace fs = std::filesystem;
 using json = nlohmann::json;
@@ -16,6 +17,12 @@ using json = nlohmann::json;
 void VPinScanner::scanFiles(std::vector<TableData>& tables, LoadingProgress* progress) {
     LOG_DEBUG("Starting scan with vpin for " + std::to_string(tables.size()) + " tables.");
 
+    if (vpx_get_ffi_abi_version() != VPX_FFI_ABI_VERSION) {
+        LOG_ERROR("vpin wrapper library ABI version " + std::to_string(vpx_get_ffi_abi_version()) +
+                  " does not match its header (" + std::to_string(VPX_FFI_ABI_VERSION) + "), skipping vpin scan.");
+        return;
+    }
+
     if (progress) {
         std::lock_guard<std::mutex> lock(progress->mutex);
         progress->currentTask = "Scanning VPX files with vpin...";
@@ -28,6 +35,9 @@ void VPinScanner::scanFiles(std::vector<TableData>& tables, LoadingProgress* pro
 
     std::vector<std::future<void>> futures;
     std::atomic<int> processedVpin(0);
+    std::mutex failuresMutex;
+    json failures = json::array();
+    const auto started = static_cast<uint64_t>(std::time(nullptr));
     const size_t maxThreads = std::max(1u, std::thread::hardware_concurrency());
 
     for (auto& table : tables) {
@@ -47,18 +57,27 @@ void VPinScanner::scanFiles(std::vector<TableData>& tables, LoadingProgress* pro
             std::this_thread::yield();
         }
 
-        futures.push_back(std::async(std::launch::async, [&table, progress, &processedVpin]() {
+        futures.push_back(std::async(std::launch::async, [&table, progress, &processedVpin, &failures, &failuresMutex]() {
             std::string vpxFile = table.vpxFile;
             LOG_DEBUG("Processing VPX file with VPin: " + vpxFile);
-            char* json_result = get_vpx_table_info_as_json(vpxFile.c_str());
+            int32_t errorCode = VPX_ERROR_CODE_OK;
+            char* json_result = vpx_get_table_info_as_json_bytes(
+                reinterpret_cast<const uint8_t*>(vpxFile.data()), vpxFile.size(), nullptr, &errorCode);
             if (!json_result) {
-                LOG_ERROR("Failed to get metadata for " + vpxFile);
+                char* errorMessage = vpx_get_last_error_message();
+                std::string reason = errorMessage ? errorMessage : "error code " + std::to_string(errorCode);
+                vpx_free_string(errorMessage);
+                LOG_ERROR("Failed to get metadata for " + vpxFile + " (" + reason + ")");
+                {
+                    std::lock_guard<std::mutex> lock(failuresMutex);
+                    failures.push_back({{"path", vpxFile}, {"code", errorCode}, {"error", reason}});
+                }
                 if (progress) {
                     std::lock_guard<std::mutex> lock(progress->mutex);
                     progress->numNoMatch++;
                     progress->logMessages.push_back("DEBUG: Failed to process: " + vpxFile);
                 }
-                free_rust_string(json_result);
+                vpx_free_string(json_result);
                 if (progress) {
                     std::lock_guard<std::mutex> lock(progress->mutex);
                     progress->currentTablesLoaded = ++processedVpin;
@@ -121,7 +140,7 @@ void VPinScanner::scanFiles(std::vector<TableData>& tables, LoadingProgress* pro
                 }
             }
 
-            free_rust_string(json_result);
+            vpx_free_string(json_result);
             if (progress) {
                 std::lock_guard<std::mutex> lock(progress->mutex);
                 progress->currentTablesLoaded = ++processedVpin;
@@ -138,5 +157,10 @@ void VPinScanner::scanFiles(std::vector<TableData>& tables, LoadingProgress* pro
         }
     }
 
+    json run = {{"source", "frontend"}, {"started", started}, {"files", tables.size()}, {"errors", failures}};
+    if (vpx_record_scan_run(run.dump().c_str()) != VPX_ERROR_CODE_OK) {
+        LOG_DEBUG("Scan run not journaled.");
+    }
+
     LOG_INFO("Scan Completed.");
 }
@@ -0,0 +1,24 @@
+// build.rs
+//
+// Generates include/vpin_wrapper.h from the exported functions in src/, so
+// the C header can't drift from the Rust side. There is no hand-written
+// copy: a header that fails to generate fails the build.
+
+fn main() {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR not set");
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir))
+        .expect("failed to read cbindgen.toml");
+
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-changed=src");
+
+    let bindings = cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(config)
+        .generate()
+        .unwrap_or_else(|e| panic!("cbindgen failed to generate include/vpin_wrapper.h: {}", e));
+    let include_dir = format!("{}/include", crate_dir);
+    std::fs::create_dir_all(&include_dir).expect("failed to create include/");
+    bindings.write_to_file(format!("{}/vpin_wrapper.h", include_dir));
+}
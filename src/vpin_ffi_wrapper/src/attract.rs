@@ -0,0 +1,182 @@
+//! Attract-mode rotation plans: which tables the cabinet shows while idle,
+//! in what order and for how long each.
+//!
+//! The order is a weighted shuffle of the index rows the rules let in, so
+//! favorites, well-rated tables (as for `vpx_pick_random_table`) and tables
+//! added lately come up earlier, and every table shows once before any
+//! shows again.
+
+use std::os::raw::c_char;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::VpxError;
+use crate::picks::{preference, Rng};
+use crate::{filter, index, library, store};
+
+/// Seconds in a day.
+const DAY: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct AttractRules {
+    /// Only tables passing this smart filter; empty for all.
+    filter: String,
+    /// Entries in the rotation; 0 shows every table once.
+    count: usize,
+    /// Seconds each table stays on screen.
+    dwell_seconds: u64,
+    /// Dwell for favorites; `dwell_seconds` when unset.
+    favorite_dwell_seconds: Option<u64>,
+    /// Dwell for new tables; `dwell_seconds` when unset.
+    new_dwell_seconds: Option<u64>,
+    favorite_weight: f64,
+    rating_weight: f64,
+    /// A table whose file changed in the last `new_days` counts as new.
+    new_days: u64,
+    /// Weight of a new table compared to any other.
+    new_weight: f64,
+    include_hidden: bool,
+    /// Seed for a repeatable plan; random without.
+    seed: Option<u64>,
+}
+
+impl Default for AttractRules {
+    fn default() -> Self {
+        AttractRules {
+            filter: String::new(),
+            count: 0,
+            dwell_seconds: 30,
+            favorite_dwell_seconds: None,
+            new_dwell_seconds: None,
+            favorite_weight: 2.0,
+            rating_weight: 1.0,
+            new_days: 14,
+            new_weight: 2.0,
+            include_hidden: false,
+            seed: None,
+        }
+    }
+}
+
+const KNOWN_KEYS: &[&str] = &[
+    "filter",
+    "count",
+    "dwell_seconds",
+    "favorite_dwell_seconds",
+    "new_dwell_seconds",
+    "favorite_weight",
+    "rating_weight",
+    "new_days",
+    "new_weight",
+    "include_hidden",
+    "seed",
+];
+
+/// Most entries a plan gets, whatever `count` says.
+const MAX_ENTRIES: usize = 10_000;
+
+struct Candidate<'a> {
+    vpx_file: &'a str,
+    weight: f64,
+    dwell_seconds: u64,
+    favorite: bool,
+    new: bool,
+}
+
+fn candidates<'a>(index_doc: &'a Value, rules: &AttractRules) -> Result<Vec<Candidate<'a>>, VpxError> {
+    let expr = Some(rules.filter.trim()).filter(|f| !f.is_empty()).map(filter::parse).transpose()?;
+    let new_since = store::now().saturating_sub(rules.new_days * DAY);
+    let candidates = index::tables(index_doc)
+        .iter()
+        .filter(|row| rules.include_hidden || row["hidden"].as_bool() != Some(true))
+        .filter(|row| expr.as_ref().is_none_or(|expr| filter::matches(row, expr)))
+        .filter_map(|row| {
+            let vpx_file = row["vpxFile"].as_str().filter(|f| !f.is_empty())?;
+            let favorite = row["favorite"].as_bool() == Some(true);
+            let new = rules.new_days > 0 && row["fileLastModified"].as_u64().is_some_and(|t| t >= new_since);
+            let mut weight = preference(row, rules.favorite_weight, rules.rating_weight);
+            let mut dwell_seconds = rules.dwell_seconds;
+            if favorite {
+                dwell_seconds = rules.favorite_dwell_seconds.unwrap_or(dwell_seconds);
+            }
+            if new {
+                weight *= rules.new_weight.max(0.0);
+                dwell_seconds = rules.new_dwell_seconds.unwrap_or(dwell_seconds);
+            }
+            Some(Candidate { vpx_file, weight, dwell_seconds, favorite, new })
+        })
+        .filter(|candidate| candidate.weight > 0.0)
+        .collect();
+    Ok(candidates)
+}
+
+/// `candidates` in a weighted random order: each one's key is u^(1/weight),
+/// highest first, so heavier tables tend to come earlier.
+fn weighted_shuffle<'c, 'a>(candidates: &'c [Candidate<'a>], rng: &mut Rng) -> Vec<&'c Candidate<'a>> {
+    let mut keyed: Vec<(f64, &Candidate)> = candidates.iter().map(|c| (rng.next().powf(1.0 / c.weight), c)).collect();
+    keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+    keyed.into_iter().map(|(_, candidate)| candidate).collect()
+}
+
+fn build(index_doc: &Value, rules: &AttractRules) -> Result<Value, VpxError> {
+    let candidates = candidates(index_doc, rules)?;
+    let count = match rules.count {
+        0 => candidates.len(),
+        count => count,
+    }
+    .min(MAX_ENTRIES);
+
+    let mut rng = Rng::new(rules.seed);
+    let mut rotation: Vec<&Candidate> = Vec::new();
+    while rotation.len() < count && !candidates.is_empty() {
+        let mut round = weighted_shuffle(&candidates, &mut rng);
+        // A new round does not start with the table that just ended the last one.
+        if round.len() > 1 && rotation.last().is_some_and(|last| last.vpx_file == round[0].vpx_file) {
+            round.swap(0, 1);
+        }
+        rotation.extend(round);
+    }
+    rotation.truncate(count);
+
+    let entries: Vec<Value> = rotation
+        .iter()
+        .map(|c| {
+            json!({ "vpx_file": c.vpx_file, "dwell_seconds": c.dwell_seconds, "favorite": c.favorite, "new": c.new })
+        })
+        .collect();
+    let total_seconds: u64 = rotation.iter().map(|c| c.dwell_seconds).sum();
+    Ok(json!({ "candidates": candidates.len(), "total_seconds": total_seconds, "rotation": entries }))
+}
+
+/// Plans an attract-mode rotation over the frontend's index (`{"tables":
+/// [...]}`): a weighted shuffle of the rows `rules_json` lets in, every
+/// table once per round, favorites, well-rated and new tables earlier.
+/// `rules_json` (nullable): `{"filter": "", "count": 0 (every table once),
+/// "dwell_seconds": 30, "favorite_dwell_seconds": null,
+/// "new_dwell_seconds": null, "favorite_weight": 2, "rating_weight": 1,
+/// "new_days": 14, "new_weight": 2, "include_hidden": false, "seed": null}`;
+/// a table is new when its file changed in the last `new_days`.
+///
+/// Returns `{"candidates", "total_seconds", "rotation": [{"vpx_file",
+/// "dwell_seconds", "favorite", "new"}]}`, or null on failure. Release with
+/// `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_build_attract_playlist(
+    index_json: *const c_char,
+    rules_json: *const c_char,
+) -> *mut c_char {
+    let result = unsafe { library::options_from_c::<AttractRules>(rules_json, KNOWN_KEYS) }
+        .and_then(|rules| {
+            let mut index_doc = index::parse(unsafe { crate::str_from_c(index_json, "index") }?)?;
+            store::annotate_index(&mut index_doc);
+            build(&index_doc, &rules)
+        })
+        .and_then(|plan| crate::into_c_string(plan.to_string()));
+
+    crate::envelope::finish("vpx_build_attract_playlist", result)
+}
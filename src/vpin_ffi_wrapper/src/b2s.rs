@@ -0,0 +1,197 @@
+//! Library-wide backglass audit.
+//!
+//! B2S Server loads `<table name>.directb2s` from the table's folder and
+//! nothing else, but downloads rarely come named after the table they go
+//! with (`Attack from Mars (Bally 1995) VPW.directb2s` next to
+//! `Attack From Mars (Bally 1995) 2.1.vpx`). Tables without an exact match
+//! are paired with the closest backglass by title so the frontend can offer
+//! the rename.
+
+use std::collections::{BTreeSet, HashSet};
+use std::ffi::OsStr;
+use std::fs;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::{library, paths, physics, scan};
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct B2sOptions {
+    /// Lowest title similarity (shared words over all words) for a fuzzy match.
+    min_similarity: f64,
+    /// Fuzzy-match backglasses in other folders too, not only the table's.
+    search_library: bool,
+}
+
+impl Default for B2sOptions {
+    fn default() -> Self {
+        B2sOptions { min_similarity: 0.6, search_library: true }
+    }
+}
+
+const KNOWN_KEYS: &[&str] = &["min_similarity", "search_library"];
+
+/// Words too common in table names to tell two apart.
+const STOP_WORDS: &[&str] = &["the", "a", "an", "and", "of", "vpx", "vpw", "b2s", "directb2s", "mod", "fs", "ds"];
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension().and_then(OsStr::to_str).is_some_and(|e| extensions.iter().any(|x| e.eq_ignore_ascii_case(x)))
+}
+
+fn stem(path: &Path) -> &str {
+    path.file_stem().and_then(OsStr::to_str).unwrap_or_default()
+}
+
+/// Version numbers (`2.1`, `v1`) split into words like these.
+fn is_version(word: &str) -> bool {
+    let digits = word.strip_prefix('v').unwrap_or(word);
+    !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+/// A file name reduced to what identifies the game: its title words and year.
+struct Title {
+    words: BTreeSet<String>,
+    year: Option<String>,
+}
+
+impl Title {
+    fn of(path: &Path) -> Title {
+        let (title, _, year) = scan::from_filename(stem(path));
+        let words = title
+            .split(|c: char| !c.is_alphanumeric())
+            .map(str::to_lowercase)
+            .filter(|w| !w.is_empty() && !STOP_WORDS.contains(&w.as_str()))
+            .filter(|w| !is_version(w))
+            .collect();
+        Title { words, year }
+    }
+
+    fn similarity(&self, other: &Title) -> f64 {
+        if self.year.is_some() && other.year.is_some() && self.year != other.year {
+            return 0.0;
+        }
+        let all = self.words.union(&other.words).count();
+        if all == 0 {
+            return 0.0;
+        }
+        self.words.intersection(&other.words).count() as f64 / all as f64
+    }
+}
+
+fn audit(tables_dir: &Path, options: &B2sOptions) -> Result<Value, VpxError> {
+    if !tables_dir.is_dir() {
+        return Err(VpxError::new(VpxErrorCode::OpenFailed, format!("'{}' is not a folder", tables_dir.display())));
+    }
+    let mut files = paths::files_under(tables_dir);
+    files.sort();
+    let tables: Vec<&PathBuf> = files.iter().filter(|f| has_extension(f, &["vpx"])).collect();
+    let backglasses: Vec<&PathBuf> = files.iter().filter(|f| has_extension(f, &["directb2s"])).collect();
+    let titles: Vec<Title> = backglasses.iter().map(|b| Title::of(b)).collect();
+
+    // Exact names first: a backglass B2S already loads is no candidate for
+    // another table.
+    let exact_matches: Vec<Option<&PathBuf>> = tables
+        .iter()
+        .map(|table| {
+            let dir = paths::table_dir(table);
+            backglasses
+                .iter()
+                .copied()
+                .find(|b| paths::table_dir(b) == dir && stem(b).eq_ignore_ascii_case(stem(table)))
+        })
+        .collect();
+    let mut used: HashSet<&Path> = exact_matches.iter().flatten().map(|b| b.as_path()).collect();
+
+    let (mut exact, mut fuzzy) = (0, 0);
+    let mut missing = Vec::new();
+    let mut results = Vec::new();
+    for (table, exact_match) in tables.iter().zip(&exact_matches) {
+        if let Some(b2s) = exact_match {
+            exact += 1;
+            results.push(json!({ "vpx_file": table, "match": "exact", "b2s_path": b2s }));
+            continue;
+        }
+
+        // The closest title; on a tie, one in the table's own folder.
+        let dir = paths::table_dir(table);
+        let title = Title::of(table);
+        let best = backglasses
+            .iter()
+            .zip(&titles)
+            .filter(|(b, _)| !exact_matches.contains(&Some(*b)))
+            .map(|(b, t)| (b, title.similarity(t), paths::table_dir(b) == dir))
+            .filter(|(_, similarity, same_folder)| {
+                *similarity >= options.min_similarity && (options.search_library || *same_folder)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1).then(a.2.cmp(&b.2)));
+        match best {
+            Some((b2s, similarity, same_folder)) => {
+                fuzzy += 1;
+                used.insert(b2s);
+                let rename_to = dir.join(format!("{}.directb2s", stem(table)));
+                results.push(json!({
+                    "vpx_file": table,
+                    "match": "fuzzy",
+                    "b2s_path": b2s,
+                    "similarity": physics::round(similarity),
+                    "same_folder": same_folder,
+                    "rename_to": rename_to,
+                }));
+            }
+            None => {
+                missing.push(table);
+                results.push(json!({ "vpx_file": table, "match": "none", "b2s_path": null }));
+            }
+        }
+    }
+
+    let orphaned: Vec<Value> = backglasses
+        .iter()
+        .filter(|b| !used.contains(b.as_path()))
+        .map(|b| json!({ "path": b, "size": fs::metadata(b).map(|m| m.len()).unwrap_or(0) }))
+        .collect();
+
+    Ok(json!({
+        "tables": tables.len(),
+        "backglasses": backglasses.len(),
+        "exact": exact,
+        "fuzzy": fuzzy,
+        "missing": missing,
+        "results": results,
+        "orphaned": orphaned,
+    }))
+}
+
+/// Audits the backglasses of every table under `tables_dir`. A table
+/// matches exactly when a `.directb2s` with its file name sits in its
+/// folder (what B2S loads), fuzzily when a backglass's title and year are
+/// close enough to the table's (`rename_to` is the name B2S would load),
+/// or not at all. Backglasses no table matched are orphaned.
+///
+/// `options_json` (nullable): `min_similarity` (0-1, default 0.6),
+/// `search_library` (fuzzy-match across folders, default true).
+///
+/// Returns `{"tables", "backglasses", "exact", "fuzzy", "missing":
+/// [vpx paths], "results": [{"vpx_file", "match": "exact" | "fuzzy" |
+/// "none", "b2s_path", "similarity", "same_folder", "rename_to"}],
+/// "orphaned": [{"path", "size"}]}`, or null on failure. Release with
+/// `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_audit_b2s(tables_dir: *const c_char, options_json: *const c_char) -> *mut c_char {
+    let result = unsafe { crate::path_from_c(tables_dir) }
+        .and_then(|dir| {
+            let options: B2sOptions = unsafe { library::options_from_c(options_json, KNOWN_KEYS) }?;
+            audit(&dir, &options)
+        })
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_audit_b2s", result)
+}
@@ -0,0 +1,91 @@
+//! Table info for many paths in one call.
+//!
+//! A startup scan asking for each table on its own crosses the FFI boundary
+//! thousands of times and has to sort out a null per file. Handing the
+//! whole list over at once costs one call, reads the tables in parallel and
+//! gives one document with every file's data or error, the same entries a
+//! TableInfoBatch job returns.
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::options::CallOptions;
+use crate::{jobs, profiles};
+
+/// `paths[index]` as a path, or why it can't be one.
+///
+/// # Safety
+/// `path` must be null or a valid NUL-terminated string.
+unsafe fn path_at(index: usize, path: *const c_char) -> Result<PathBuf, VpxError> {
+    if path.is_null() {
+        return Err(VpxError::new(VpxErrorCode::NullArgument, format!("Path {} is null.", index)));
+    }
+    unsafe { crate::path_from_c(path) }
+}
+
+/// The entry for a path that couldn't be read from the array.
+fn bad_path(path: Option<String>, e: VpxError) -> Value {
+    json!({ "path": path, "ok": false, "error": { "code": e.code as i32, "message": e.message } })
+}
+
+/// Table info for `count` paths at once: the payload of a TableInfoBatch
+/// job, `[{"path", "ok", "profile", "data"} / {"path", "ok", "profile",
+/// "error"}, ...]`, one entry per path in the order given. A null or
+/// unusable path gets an error entry without `profile` (and with `path`
+/// null for a null one) instead of failing the call. Tables are read in parallel, each with its scan
+/// profile, and the run is journaled with source `"batch_call"`.
+/// `options_json` (nullable) as for the `_ex` calls.
+///
+/// Returns null on failure (no paths array, bad options). Release with
+/// `vpx_free_string`.
+///
+/// # Safety
+/// `paths` must be null (with `count` 0) or point to `count` pointers, each
+/// null or a valid NUL-terminated string. `options_json` must be null or a
+/// valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_table_info_batch_as_json(
+    paths: *const *const c_char,
+    count: usize,
+    options_json: *const c_char,
+) -> *mut c_char {
+    let started = Instant::now();
+    let result = unsafe { CallOptions::from_c(options_json) }.and_then(|options| {
+        if paths.is_null() && count > 0 {
+            return Err(VpxError::new(VpxErrorCode::NullArgument, "Paths array is null."));
+        }
+        let raw: &[*const c_char] = match count {
+            0 => &[],
+            _ => unsafe { std::slice::from_raw_parts(paths, count) },
+        };
+        // Bad paths get their entry up front; the raw pointers stay on this thread.
+        let parsed: Vec<Result<PathBuf, Value>> = raw
+            .iter()
+            .enumerate()
+            .map(|(index, &path)| {
+                unsafe { path_at(index, path) }.map_err(|e| {
+                    let shown =
+                        (!path.is_null()).then(|| unsafe { CStr::from_ptr(path) }.to_string_lossy().into_owned());
+                    bad_path(shown, e)
+                })
+            })
+            .collect();
+        let entries: Vec<Value> = crate::parallel::map(&parsed, |path| match path {
+            Ok(path) => {
+                let profile = profiles::for_table(path, &options);
+                jobs::batch_entry(path, profile, profiles::scan(path, &options, profile))
+            }
+            Err(entry) => entry.clone(),
+        });
+        let elapsed = started.elapsed().as_secs();
+        crate::journal::record_batch("batch_call", crate::store::now().saturating_sub(elapsed), &entries);
+        crate::into_c_string(Value::Array(entries).to_string())
+    });
+
+    crate::envelope::finish("vpx_get_table_info_batch_as_json", result)
+}
@@ -0,0 +1,44 @@
+//! Length-prefixed byte buffers.
+//!
+//! A C string ends at the first NUL, so a script with a stray NUL in it
+//! cannot be returned as one, and neither can binary data. The `_as_buffer`
+//! calls return a `VpxBuffer` instead: a pointer and a length, by value.
+
+/// Bytes handed over to C. `data` is null on failure; an empty result has a
+/// non-null `data` and `len` 0. Release with `vpx_free_buffer`.
+#[repr(C)]
+pub struct VpxBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl VpxBuffer {
+    pub(crate) fn new(bytes: Vec<u8>) -> VpxBuffer {
+        let bytes = Box::into_raw(bytes.into_boxed_slice());
+        let buffer = VpxBuffer { data: bytes.cast(), len: bytes.len() };
+        // Just made from the boxed slice, so valid for `len` bytes.
+        let content = unsafe { std::slice::from_raw_parts(buffer.data, buffer.len) };
+        crate::allocations::track(buffer.data, crate::allocations::Kind::Buffer, buffer.len, content);
+        buffer
+    }
+
+    pub(crate) fn null() -> VpxBuffer {
+        VpxBuffer { data: std::ptr::null_mut(), len: 0 }
+    }
+}
+
+/// Releases a buffer returned by one of the `_as_buffer` calls.
+///
+/// # Safety
+/// `buffer` must come from the wrapper, unchanged, and not be freed twice.
+/// A buffer with a null `data` is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_free_buffer(buffer: VpxBuffer) {
+    if buffer.data.is_null() {
+        return;
+    }
+    crate::allocations::untrack(buffer.data);
+    unsafe {
+        _ = Box::from_raw(std::ptr::slice_from_raw_parts_mut(buffer.data, buffer.len));
+    }
+}
@@ -0,0 +1,368 @@
+//! Library bundles: the frontend's curated state in one portable `.zip`, to
+//! move to another cabinet or restore after a reinstall without rescanning.
+//!
+//! ```text
+//! manifest.json        format, version, created, tables_path, counts
+//! index.json           asapcab_index.json as it was
+//! user_store.json      the wrapper's user store (stats, ...), if configured
+//! overrides/<rel>.json per-table override files, relative to tables_path
+//! ```
+//!
+//! Table files and media are not included: they are big, and usually come
+//! over by copying the tables folder. On import, paths under the bundle's
+//! `tables_path` are rebased onto this library's.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{Cursor, Read, Write};
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::library::LibraryConfig;
+use crate::relocate::fix_paths;
+use crate::{index, locks, paths, store};
+
+const BUNDLE_FORMAT: &str = "asapcab-library-bundle";
+/// Newest bundle format this build writes and understands.
+const BUNDLE_VERSION: u64 = 1;
+
+const MANIFEST: &str = "manifest.json";
+const INDEX: &str = "index.json";
+const USER_STORE: &str = "user_store.json";
+const OVERRIDES: &str = "overrides/";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ImportOptions {
+    /// Report what would change, write nothing.
+    dry_run: bool,
+    /// Keep index rows and store entries the bundle doesn't have (the
+    /// bundle still wins for tables in both). Otherwise they are replaced.
+    merge: bool,
+    /// Replace override files that already exist.
+    overwrite: bool,
+}
+
+const KNOWN_KEYS: &[&str] = &["dry_run", "merge", "overwrite"];
+
+fn io_error(what: &str, path: &Path, e: impl std::fmt::Display) -> VpxError {
+    VpxError::new(VpxErrorCode::OpenFailed, format!("Failed to {} '{}': {}", what, path.display(), e))
+}
+
+fn invalid(message: String) -> VpxError {
+    VpxError::new(VpxErrorCode::InvalidArgument, message)
+}
+
+/// The per-table override the frontend reads: `<table stem>.json` beside the `.vpx`.
+fn override_path(vpx_file: &Path) -> PathBuf {
+    vpx_file.with_extension("json")
+}
+
+fn to_json(value: &Value) -> Result<Vec<u8>, VpxError> {
+    serde_json::to_vec_pretty(value)
+        .map_err(|e| VpxError::new(VpxErrorCode::AllocFailed, format!("Failed to serialize bundle: {}", e)))
+}
+
+fn export(dest: &Path, library: &LibraryConfig) -> Result<Value, VpxError> {
+    crate::safe_mode::check("exporting library bundles")?;
+    let index_path = library
+        .index_path
+        .as_ref()
+        .ok_or_else(|| invalid("Library config has no \"index_path\".".to_string()))?;
+    let index_doc = index::load(index_path)?;
+    let store_doc = match store::configured() {
+        true => Some(store::load()?),
+        false => None,
+    };
+
+    let mut overrides: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+    let mut skipped = Vec::new();
+    for vpx_file in index::tables(&index_doc).iter().filter_map(|row| row["vpxFile"].as_str()) {
+        let path = override_path(Path::new(vpx_file));
+        let source = library.readable_path(&path);
+        if !source.is_file() {
+            continue;
+        }
+        // Stored relative to the tables folder so they can land elsewhere.
+        let relative = library.tables_path.as_ref().and_then(|root| path.strip_prefix(root).ok());
+        let Some(relative) = relative.and_then(Path::to_str) else {
+            skipped.push(json!({ "path": path, "reason": "not under tables_path" }));
+            continue;
+        };
+        let data = fs::read(&source).map_err(|e| io_error("read", &source, e))?;
+        overrides.insert(relative.replace('\\', "/"), data);
+    }
+
+    let manifest = json!({
+        "format": BUNDLE_FORMAT,
+        "version": BUNDLE_VERSION,
+        "created": store::now(),
+        "tables_path": library.tables_path,
+        "tables": index::tables(&index_doc).len(),
+        "overrides": overrides.len(),
+        "user_store": store_doc.is_some(),
+    });
+
+    let (manifest_data, index_data) = (to_json(&manifest)?, to_json(&index_doc)?);
+    let store_data = store_doc.as_ref().map(to_json).transpose()?;
+    let write = || -> zip::result::ZipResult<Vec<u8>> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        let add = |zip: &mut zip::ZipWriter<Cursor<Vec<u8>>>, name: &str, data: &[u8]| {
+            zip.start_file(name, options)?;
+            zip.write_all(data).map_err(zip::result::ZipError::Io)
+        };
+        add(&mut zip, MANIFEST, &manifest_data)?;
+        add(&mut zip, INDEX, &index_data)?;
+        if let Some(store_data) = &store_data {
+            add(&mut zip, USER_STORE, store_data)?;
+        }
+        for (relative, data) in &overrides {
+            add(&mut zip, &format!("{}{}", OVERRIDES, relative), data)?;
+        }
+        Ok(zip.finish()?.into_inner())
+    };
+    let data = write().map_err(|e| io_error("write bundle", dest, e))?;
+    paths::write_atomic(dest, &data).map_err(|e| io_error("write", dest, e))?;
+
+    Ok(json!({
+        "ok": true,
+        "bundle": dest,
+        "size": data.len(),
+        "manifest": manifest,
+        "skipped": skipped,
+    }))
+}
+
+fn read_entry(archive: &mut zip::ZipArchive<File>, name: &str, src: &Path) -> Result<Option<Vec<u8>>, VpxError> {
+    let mut file = match archive.by_name(name) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(io_error("read bundle", src, e)),
+    };
+    let mut data = Vec::new();
+    file.read_to_end(&mut data).map_err(|e| io_error("read bundle", src, e))?;
+    Ok(Some(data))
+}
+
+fn read_json(archive: &mut zip::ZipArchive<File>, name: &str, src: &Path) -> Result<Option<Value>, VpxError> {
+    let Some(data) = read_entry(archive, name, src)? else { return Ok(None) };
+    serde_json::from_slice(&data)
+        .map(Some)
+        .map_err(|e| VpxError::new(VpxErrorCode::ParseFailed, format!("Invalid {} in bundle: {}", name, e)))
+}
+
+/// Store entries keyed by their rebased `.vpx` path.
+fn rebase_store(store_doc: &mut Value, mapping: &[(PathBuf, PathBuf)]) {
+    let Some(tables) = store_doc["tables"].as_object_mut() else { return };
+    let rebased: Map<String, Value> = std::mem::take(tables)
+        .into_iter()
+        .map(|(vpx_file, mut entry)| {
+            let mut key = Value::String(vpx_file);
+            fix_paths(&mut key, mapping);
+            fix_paths(&mut entry, mapping);
+            (key.as_str().unwrap_or_default().to_string(), entry)
+        })
+        .collect();
+    *tables = rebased;
+}
+
+fn import(src: &Path, library: &LibraryConfig, options: &ImportOptions) -> Result<Value, VpxError> {
+    let file = File::open(src).map_err(|e| io_error("open", src, e))?;
+    let mut archive =
+        zip::ZipArchive::new(file).map_err(|e| invalid(format!("'{}' is not a bundle: {}", src.display(), e)))?;
+
+    let manifest = read_json(&mut archive, MANIFEST, src)?.unwrap_or(Value::Null);
+    if manifest["format"] != BUNDLE_FORMAT {
+        return Err(invalid(format!("'{}' is not a library bundle", src.display())));
+    }
+    if manifest["version"].as_u64().unwrap_or(0) > BUNDLE_VERSION {
+        return Err(VpxError::new(
+            VpxErrorCode::ParseFailed,
+            format!("Bundle '{}' is from a newer version", src.display()),
+        ));
+    }
+    let index_path = library
+        .index_path
+        .as_ref()
+        .ok_or_else(|| invalid("Library config has no \"index_path\".".to_string()))?;
+
+    // Tables from another cabinet's folder land in this one's.
+    let mut mapping = Vec::new();
+    if let (Some(from), Some(to)) = (manifest["tables_path"].as_str(), &library.tables_path) {
+        if Path::new(from) != to.as_path() {
+            mapping.push((PathBuf::from(from), to.clone()));
+        }
+    }
+
+    // Index.
+    let mut bundle_index = match read_json(&mut archive, INDEX, src)? {
+        Some(doc) if doc["tables"].is_array() => doc,
+        _ => return Err(invalid(format!("Bundle '{}' has no valid {}", src.display(), INDEX))),
+    };
+    index::tables_mut(&mut bundle_index).iter_mut().for_each(|row| fix_paths(row, &mapping));
+    let missing_tables: Vec<&Value> = index::tables(&bundle_index)
+        .iter()
+        .map(|row| &row["vpxFile"])
+        .filter(|vpx_file| !vpx_file.as_str().is_some_and(|p| Path::new(p).is_file()))
+        .collect();
+    let missing_tables = json!(missing_tables);
+    let new_index = match options.merge {
+        true => {
+            let mut merged = index::load(index_path)?;
+            for row in index::tables(&bundle_index) {
+                index::upsert(&mut merged, row.clone());
+            }
+            merged
+        }
+        false => bundle_index,
+    };
+
+    // User store.
+    let mut bundle_store = read_json(&mut archive, USER_STORE, src)?;
+    if let Some(store_doc) = &mut bundle_store {
+        if !store_doc.is_object() {
+            return Err(VpxError::new(
+                VpxErrorCode::ParseFailed,
+                format!("The user store in '{}' is not a JSON object", src.display()),
+            ));
+        }
+        if store_doc["version"].as_u64().unwrap_or(0) > store::STORE_VERSION {
+            return Err(VpxError::new(
+                VpxErrorCode::ParseFailed,
+                format!("The user store in '{}' is from a newer version", src.display()),
+            ));
+        }
+        rebase_store(store_doc, &mapping);
+    }
+    let store_status = match (&bundle_store, store::configured()) {
+        (None, _) => "absent",
+        (Some(_), false) => "skipped",
+        (Some(_), true) => "restored",
+    };
+
+    // Overrides, only into table folders that exist here.
+    let names: Vec<String> = archive.file_names().filter(|n| n.starts_with(OVERRIDES)).map(String::from).collect();
+    let mut overrides = Vec::new();
+    let mut skipped = Vec::new();
+    for name in names {
+        let safe = archive.by_name(&name).ok().and_then(|f| f.enclosed_name());
+        let relative = safe.as_deref().and_then(|p| p.strip_prefix(OVERRIDES).ok()).map(Path::to_path_buf);
+        let (Some(relative), Some(root)) = (relative, &library.tables_path) else {
+            skipped.push(json!({ "path": name, "reason": "needs tables_path" }));
+            continue;
+        };
+        let target = root.join(&relative);
+        if !paths::table_dir(&target).is_dir() {
+            skipped.push(json!({ "path": target, "reason": "table folder not found" }));
+            continue;
+        }
+        // Tables on a read-only share keep their overrides in its overlay.
+        let target = library.writable_path(&target)?;
+        if target.exists() && !options.overwrite {
+            skipped.push(json!({ "path": target, "reason": "exists" }));
+        } else {
+            overrides.push((name, target));
+        }
+    }
+
+    if !options.dry_run {
+        crate::safe_mode::check("importing library bundles")?;
+        // Check them all first, so a file in use doesn't leave the bundle half restored.
+        for (_, target) in &overrides {
+            locks::check_not_in_use(target)?;
+        }
+        for (name, target) in &overrides {
+            let data = read_entry(&mut archive, name, src)?.unwrap_or_default();
+            if let Some(dir) = target.parent() {
+                fs::create_dir_all(dir).map_err(|e| io_error("create", dir, e))?;
+            }
+            paths::write_atomic(target, &data).map_err(|e| io_error("write", target, e))?;
+        }
+        if let (Some(bundle_store), true) = (bundle_store, store::configured()) {
+            store::update(|store_doc| {
+                match (options.merge, bundle_store["tables"].as_object()) {
+                    (true, Some(tables)) => {
+                        for (vpx_file, entry) in tables {
+                            store_doc["tables"][vpx_file.as_str()] = entry.clone();
+                        }
+                    }
+                    _ => *store_doc = bundle_store,
+                }
+                Ok(())
+            })?;
+        }
+        index::save(index_path, &new_index)?;
+    }
+
+    Ok(json!({
+        "ok": true,
+        "dry_run": options.dry_run,
+        "bundle": src,
+        "manifest": manifest,
+        "rebased": mapping.first().map(|(from, to)| json!({ "from": from, "to": to })),
+        "tables": index::tables(&new_index).len(),
+        "missing_tables": missing_tables,
+        "user_store": store_status,
+        "overrides": overrides.iter().map(|(_, target)| target).collect::<Vec<_>>(),
+        "skipped": skipped,
+    }))
+}
+
+/// Writes the library's curated state to a bundle at `dest_path`: the index
+/// (`index_path`), the user store (stats and the rest, when configured) and
+/// the per-table override files, which are stored relative to `tables_path`.
+///
+/// Returns `{"ok", "bundle", "size", "manifest", "skipped"}`, or null on
+/// failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_export_library_bundle(
+    dest_path: *const c_char,
+    library_config_json: *const c_char,
+) -> *mut c_char {
+    let result = unsafe { crate::path_from_c(dest_path) }
+        .and_then(|dest| Ok((dest, unsafe { LibraryConfig::from_c(library_config_json) }?)))
+        .and_then(|(dest, library)| export(&dest, &library))
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_export_library_bundle", result)
+}
+
+/// Restores a bundle written by `vpx_export_library_bundle` into the
+/// library: the index (needs `index_path`), the user store and the override
+/// files of tables whose folders exist here. Paths under the bundle's
+/// `tables_path` are rebased onto `tables_path`.
+///
+/// `options_json` (nullable): `dry_run`, `merge` (keep tables the bundle
+/// doesn't have instead of replacing everything), `overwrite` (replace
+/// existing override files). Returns the report, with `missing_tables` for
+/// rows whose `.vpx` isn't here (yet), or null on failure (`FileInUse` when
+/// another program has an override file open). Release with
+/// `vpx_free_string`.
+///
+/// # Safety
+/// All arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_import_library_bundle(
+    src_path: *const c_char,
+    library_config_json: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    let result = unsafe { crate::path_from_c(src_path) }
+        .and_then(|src| Ok((src, unsafe { LibraryConfig::from_c(library_config_json) }?)))
+        .and_then(|(src, library)| {
+            let options: ImportOptions = unsafe { crate::library::options_from_c(options_json, KNOWN_KEYS) }?;
+            let summary = format!("Import bundle {}", src.display());
+            crate::oplog::record("bundle_import", summary, || import(&src, &library, &options))
+        })
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_import_library_bundle", result)
+}
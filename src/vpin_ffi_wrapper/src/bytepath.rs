@@ -0,0 +1,115 @@
+//! Raw byte path entry points.
+//!
+//! A Linux file name is whatever bytes it was created with, and tables
+//! unpacked from old archives often carry Latin-1 names, which the regular
+//! calls reject as invalid UTF-8 before even trying to open them. These
+//! variants take the path as bytes with a length and open it as it is.
+
+use std::os::raw::c_char;
+use std::path::PathBuf;
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::handle::{OpenTable, VpxHandle};
+use crate::options::CallOptions;
+
+/// The `len` bytes at `path` as a path, without any decoding on Unix.
+///
+/// # Safety
+/// `path` must be null or valid for reads of `len` bytes.
+unsafe fn path_from_bytes(path: *const u8, len: usize) -> Result<PathBuf, VpxError> {
+    if path.is_null() {
+        return Err(VpxError::new(VpxErrorCode::NullArgument, "Input file path is null."));
+    }
+    let bytes = unsafe { std::slice::from_raw_parts(path, len) };
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Ok(PathBuf::from(std::ffi::OsStr::from_bytes(bytes)))
+    }
+    #[cfg(not(unix))]
+    {
+        std::str::from_utf8(bytes)
+            .map(PathBuf::from)
+            .map_err(|e| VpxError::new(VpxErrorCode::InvalidUtf8Path, format!("Invalid UTF-8 in path: {}", e)))
+    }
+}
+
+/// `vpx_get_table_info_as_json_err` with the path as `len` raw bytes (no
+/// NUL needed), so file names that are not UTF-8 open too.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `vpx_file_path` must be null or valid for reads of `len` bytes,
+/// `options_json` null or a valid NUL-terminated string, `error_code` null
+/// or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_table_info_as_json_bytes(
+    vpx_file_path: *const u8,
+    len: usize,
+    options_json: *const c_char,
+    error_code: *mut i32,
+) -> *mut c_char {
+    let result = unsafe { CallOptions::from_c(options_json) }
+        .and_then(|options| Ok((unsafe { path_from_bytes(vpx_file_path, len) }?, options)))
+        .and_then(|(path, options)| crate::read_table_info_json(&path, &options))
+        .and_then(crate::into_c_string);
+    unsafe { crate::set_error_code(error_code, &result) };
+
+    crate::envelope::finish("vpx_get_table_info_as_json_bytes", result)
+}
+
+/// `vpx_get_gamedata_code_err` with the path as `len` raw bytes.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// As for `vpx_get_table_info_as_json_bytes`.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_gamedata_code_bytes(
+    vpx_file_path: *const u8,
+    len: usize,
+    options_json: *const c_char,
+    error_code: *mut i32,
+) -> *mut c_char {
+    let result = unsafe { CallOptions::from_c(options_json) }
+        .and_then(|options| Ok((unsafe { path_from_bytes(vpx_file_path, len) }?, options)))
+        .and_then(|(path, options)| crate::read_gamedata_code(&path, &options))
+        .and_then(crate::into_c_string);
+    unsafe { crate::set_error_code(error_code, &result) };
+
+    match result {
+        Ok(ptr) => ptr,
+        Err(e) => {
+            log_error!("vpx_get_gamedata_code_bytes: {}", e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// `vpx_open_err` with the path as `len` raw bytes.
+///
+/// Returns null on failure.
+///
+/// # Safety
+/// As for `vpx_get_table_info_as_json_bytes`.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_open_bytes(
+    vpx_file_path: *const u8,
+    len: usize,
+    options_json: *const c_char,
+    error_code: *mut i32,
+) -> *mut VpxHandle {
+    let result = unsafe { CallOptions::from_c(options_json) }
+        .and_then(|options| Ok((unsafe { path_from_bytes(vpx_file_path, len) }?, options)))
+        .and_then(|(path, options)| OpenTable::open(&path, &options));
+    unsafe { crate::set_error_code(error_code, &result) };
+
+    match result {
+        Ok(table) => VpxHandle::into_raw(table),
+        Err(e) => {
+            log_error!("vpx_open_bytes: {}", e);
+            std::ptr::null_mut()
+        }
+    }
+}
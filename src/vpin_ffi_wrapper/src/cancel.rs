@@ -0,0 +1,198 @@
+//! Cancellation tokens for the blocking parse calls.
+//!
+//! A big table on a spinning disk takes seconds to read, and the frontend
+//! wants out as soon as the user moves on. It creates a token, passes it to
+//! one of the `_cancel` calls and cancels it from any thread; the call
+//! stops at the next stream boundary with `VpxErrorCode::Cancelled`.
+//!
+//! The token is in effect on the calling thread for the duration of the
+//! call, so the code in between only asks `check` and never carries it.
+//!
+//! A per-call `timeout_ms` uses the same checks with a deadline instead of
+//! a token. Since a table that sends vpin into a loop never reaches the
+//! next check, the read runs on a thread of its own and the call stops
+//! waiting for it at the deadline; the abandoned read ends at its next
+//! check, or whenever vpin gets out of it.
+
+use std::cell::{Cell, RefCell};
+use std::os::raw::c_char;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::handle::VpxHandle;
+use crate::progress;
+use crate::registry::{self, Registry};
+
+/// Opaque to C: created with `vpx_cancel_token_new`. The pointer is an id
+/// into the token registry, never an address.
+pub struct VpxCancelToken {
+    _opaque: [u8; 0],
+}
+
+static TOKENS: Registry<AtomicBool> = Registry::new();
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<AtomicBool>>> = const { RefCell::new(None) };
+    /// When the read on this thread has to be done by, for `with_timeout`.
+    static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+/// Runs `f` with `token` (nullable) as the thread's token, restoring the
+/// previous one afterwards, panics included. The token stays usable for the
+/// call even if it is freed meanwhile; an unknown one is ignored.
+pub(crate) fn scope<T>(token: *const VpxCancelToken, f: impl FnOnce() -> T) -> T {
+    struct Restore(Option<Arc<AtomicBool>>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+        }
+    }
+    let token = TOKENS.get(registry::from_ptr(token));
+    let _restore = Restore(CURRENT.with(|current| current.replace(token)));
+    f()
+}
+
+/// Fails with `Cancelled` if the thread's token has been cancelled, or
+/// with `Timeout` once its deadline has passed.
+pub(crate) fn check(path: &Path) -> Result<(), VpxError> {
+    let cancelled = CURRENT.with(|current| current.borrow().as_ref().is_some_and(|t| t.load(Ordering::Relaxed)));
+    if cancelled {
+        return Err(VpxError::new(VpxErrorCode::Cancelled, format!("Cancelled while reading '{}'", path.display())));
+    }
+    match DEADLINE.with(Cell::get) {
+        Some(deadline) if Instant::now() >= deadline => {
+            Err(VpxError::new(VpxErrorCode::Timeout, format!("Timed out reading '{}'", path.display())))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Runs the read `f` of `path`, giving up on it with `Timeout` after
+/// `timeout_ms` (none or 0: no limit, and `f` runs right here). The read
+/// keeps the caller's token and job.
+pub(crate) fn with_timeout<T: Send + 'static>(
+    path: &Path,
+    timeout_ms: Option<u64>,
+    f: impl FnOnce() -> Result<T, VpxError> + Send + 'static,
+) -> Result<T, VpxError> {
+    let Some(timeout_ms) = timeout_ms.filter(|&ms| ms > 0) else { return f() };
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+    let token = CURRENT.with(|current| current.borrow().clone());
+    let job_id = progress::current_job();
+    let (done, result) = mpsc::channel();
+    thread::Builder::new()
+        .name("vpx-timed-read".to_string())
+        .spawn(move || {
+            CURRENT.with(|current| *current.borrow_mut() = token);
+            DEADLINE.with(|current| current.set(Some(deadline)));
+            // The caller may not be listening any more.
+            _ = done.send(progress::in_job(job_id, f));
+        })
+        .map_err(|e| VpxError::new(VpxErrorCode::AllocFailed, format!("Failed to start a read thread: {}", e)))?;
+    match result.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+        Ok(result) => result,
+        Err(RecvTimeoutError::Timeout) => Err(VpxError::new(
+            VpxErrorCode::Timeout,
+            format!("Gave up on '{}' after {} ms", path.display(), timeout_ms),
+        )),
+        Err(RecvTimeoutError::Disconnected) => {
+            Err(VpxError::new(VpxErrorCode::Panic, format!("Read thread for '{}' died", path.display())))
+        }
+    }
+}
+
+/// A new, not yet cancelled token. Release with `vpx_cancel_token_free`.
+#[no_mangle]
+pub extern "C" fn vpx_cancel_token_new() -> *mut VpxCancelToken {
+    registry::to_ptr(TOKENS.insert(AtomicBool::new(false)))
+}
+
+/// Cancels every call using `token`, now and later. Safe to call from any
+/// thread while the calls run; does nothing for null or a freed token.
+///
+/// # Safety
+/// `token` must be null or from `vpx_cancel_token_new`.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_cancel_token_cancel(token: *const VpxCancelToken) {
+    if let Some(token) = TOKENS.get(registry::from_ptr(token)) {
+        token.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Whether `token` has been cancelled; false for null or a freed token.
+///
+/// # Safety
+/// `token` must be null or from `vpx_cancel_token_new`.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_cancel_token_is_cancelled(token: *const VpxCancelToken) -> bool {
+    TOKENS.get(registry::from_ptr(token)).is_some_and(|token| token.load(Ordering::Relaxed))
+}
+
+/// Releases a token. Calls still using it keep it until they return, still
+/// honouring a cancel that came before the free.
+///
+/// # Safety
+/// `token` must be null or from `vpx_cancel_token_new`.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_cancel_token_free(token: *mut VpxCancelToken) {
+    if !token.is_null() && TOKENS.remove(registry::from_ptr(token)).is_none() {
+        log_warn!("vpx_cancel_token_free: token is not live (freed twice?)");
+    }
+}
+
+/// `vpx_get_table_info_as_json_err` that stops early once `token`
+/// (nullable) is cancelled, with `Cancelled` in `error_code`.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `vpx_file_path` and `options_json` must be null or valid NUL-terminated
+/// strings, `token` null or from `vpx_cancel_token_new`, `error_code` null or valid for a
+/// write.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_table_info_as_json_cancel(
+    vpx_file_path: *const c_char,
+    options_json: *const c_char,
+    token: *const VpxCancelToken,
+    error_code: *mut i32,
+) -> *mut c_char {
+    scope(token, || unsafe { crate::vpx_get_table_info_as_json_err(vpx_file_path, options_json, error_code) })
+}
+
+/// `vpx_get_gamedata_code_err` that stops early once `token` is cancelled.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// As for `vpx_get_table_info_as_json_cancel`.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_gamedata_code_cancel(
+    vpx_file_path: *const c_char,
+    options_json: *const c_char,
+    token: *const VpxCancelToken,
+    error_code: *mut i32,
+) -> *mut c_char {
+    scope(token, || unsafe { crate::vpx_get_gamedata_code_err(vpx_file_path, options_json, error_code) })
+}
+
+/// `vpx_open_err` that stops early once `token` is cancelled. Queries on
+/// the handle are not affected by it.
+///
+/// Returns null on failure.
+///
+/// # Safety
+/// As for `vpx_get_table_info_as_json_cancel`.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_open_cancel(
+    vpx_file_path: *const c_char,
+    options_json: *const c_char,
+    token: *const VpxCancelToken,
+    error_code: *mut i32,
+) -> *mut VpxHandle {
+    scope(token, || unsafe { crate::handle::vpx_open_err(vpx_file_path, options_json, error_code) })
+}
@@ -0,0 +1,352 @@
+//! Table cards: one printable info card per table (title, maker, year,
+//! authors, the rules summary and a high score) for users who print
+//! physical cards or show them on a third screen.
+//!
+//! Cards are PDF by default, one 6 x 4 inch page each, set in the standard
+//! PDF fonts every viewer and printer has, so the wrapper needs no fonts of
+//! its own. A card can also be SVG, filled in from a template with
+//! `{{field}}` placeholders, for a layout of the user's own.
+
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::options::CallOptions;
+use crate::{index, library, paths, provenance};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum CardFormat {
+    Pdf,
+    Svg,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct CardOptions {
+    /// PDF, or SVG (the only format a template can be); PDF without a
+    /// template, SVG with one when missing.
+    format: Option<CardFormat>,
+    /// Rules summary wrapped at this many characters.
+    rules_wrap: usize,
+    /// Most lines of rules on a card.
+    rules_lines: usize,
+    /// Overwrite cards already in `dest_dir`.
+    overwrite: bool,
+}
+
+impl Default for CardOptions {
+    fn default() -> Self {
+        CardOptions { format: None, rules_wrap: 60, rules_lines: 14, overwrite: true }
+    }
+}
+
+const KNOWN_KEYS: &[&str] = &["format", "rules_wrap", "rules_lines", "overwrite"];
+
+/// The card used without a template: 6 x 4 inches.
+const DEFAULT_TEMPLATE: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="6in" height="4in" viewBox="0 0 600 400">
+  <rect width="600" height="400" fill="#fdfaf2" stroke="#222" stroke-width="4"/>
+  <text x="30" y="56" font-family="sans-serif" font-size="32" font-weight="bold" fill="#111">{{title}}</text>
+  <text x="30" y="86" font-family="sans-serif" font-size="18" fill="#444">{{manufacturer}} {{year}}</text>
+  <text x="30" y="108" font-family="sans-serif" font-size="13" fill="#666">{{authors}}</text>
+  <line x1="30" y1="122" x2="570" y2="122" stroke="#999"/>
+  <text transform="translate(30,132)" font-family="serif" font-size="13" fill="#222">{{rules}}</text>
+  <text x="570" y="380" text-anchor="end" font-family="sans-serif" font-size="16" fill="#111">{{highscore}}</text>
+</svg>
+"##;
+
+/// Escapes text for SVG content and attributes.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters are not allowed in XML at all.
+            c if c.is_control() && c != '\n' && c != '\t' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The first of `fields` the row has, a list joined with commas.
+fn row_text(row: &Value, fields: &[&str]) -> String {
+    let text = |value: &Value| match value {
+        Value::String(s) => s.trim().to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::Array(items) => items.iter().filter_map(Value::as_str).collect::<Vec<_>>().join(", "),
+        _ => String::new(),
+    };
+    fields.iter().map(|field| text(&row[*field])).find(|t| !t.is_empty()).unwrap_or_default()
+}
+
+/// One `<tspan>` per line, each starting at x = 0 of the enclosing `<text>`.
+fn rules_tspans(rules: &str) -> String {
+    rules
+        .lines()
+        .map(|line| match line.trim() {
+            "" => r#"<tspan x="0" dy="1.3em"> </tspan>"#.to_string(),
+            line => format!(r#"<tspan x="0" dy="1.3em">{}</tspan>"#, escape(line)),
+        })
+        .collect()
+}
+
+/// What goes on a card.
+struct Card {
+    title: String,
+    manufacturer: String,
+    year: String,
+    authors: String,
+    highscore: String,
+    rules: String,
+}
+
+/// The card for one index row; the table file is read for its rules, and
+/// a card without them is still a card.
+fn card(row: &Value, vpx_path: &Path, options: &CardOptions) -> (Card, Option<String>) {
+    let lenient = CallOptions { lenient: true, ..CallOptions::default() };
+    let (rules, warning) = match crate::read_table_info_value(vpx_path, &lenient) {
+        Ok(table_info) => (crate::rules::summary_text(&table_info, options.rules_wrap, options.rules_lines), None),
+        Err(e) => (String::new(), Some(e.message)),
+    };
+    let stem = vpx_path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let mut title = row_text(row, &["bestTitle", "title", "tableName"]);
+    if title.is_empty() {
+        title = stem;
+    }
+    let card = Card {
+        title,
+        manufacturer: row_text(row, &["bestManufacturer", "manufacturer", "tableManufacturer"]),
+        year: row_text(row, &["bestYear", "year", "tableYear"]),
+        authors: row_text(row, &["tableAuthor", "vpsAuthors", "authors"]),
+        highscore: row_text(row, &["highscore", "highScore"]),
+        rules,
+    };
+    (card, warning)
+}
+
+/// `card` filled into an SVG template.
+fn render_svg(template: &str, card: &Card) -> Vec<u8> {
+    let fields = [
+        ("title", escape(&card.title)),
+        ("manufacturer", escape(&card.manufacturer)),
+        ("year", escape(&card.year)),
+        ("authors", escape(&card.authors)),
+        ("highscore", escape(&card.highscore)),
+        ("rules", rules_tspans(&card.rules)),
+    ];
+    let svg = fields
+        .iter()
+        .fold(template.to_string(), |svg, (field, value)| svg.replace(&format!("{{{{{}}}}}", field), value));
+    svg.into_bytes()
+}
+
+/// Helvetica advance widths for ' '..='~', in 1/1000 em (from its AFM).
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556, 556, 556, 556, 556,
+    556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833,
+    722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556,
+    556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334,
+    260, 334, 584,
+];
+
+/// Width of `text` set in Helvetica at `size`.
+fn helvetica_width(text: &str, size: f32) -> f32 {
+    let units: u32 = text
+        .chars()
+        .map(|c| match c {
+            ' '..='~' => u32::from(HELVETICA_WIDTHS[c as usize - 0x20]),
+            _ => 556,
+        })
+        .sum();
+    units as f32 * size / 1000.0
+}
+
+/// `text` as a PDF string in WinAnsiEncoding; what it can't encode is `?`.
+fn pdf_string(text: &str) -> Vec<u8> {
+    let mut out = vec![b'('];
+    for c in text.chars().filter(|c| !c.is_control()) {
+        match crate::options::cp1252_byte(c).unwrap_or(b'?') {
+            byte @ (b'(' | b')' | b'\\') => out.extend([b'\\', byte]),
+            byte => out.push(byte),
+        }
+    }
+    out.push(b')');
+    out
+}
+
+/// The built-in card as a one-page PDF: the layout of `DEFAULT_TEMPLATE`,
+/// drawn in its 600 x 400 units scaled onto a 6 x 4 inch page.
+fn render_pdf(card: &Card) -> Vec<u8> {
+    // (font resource, size, rgb, x, y from the top, text)
+    let mut lines: Vec<(&str, f32, &str, f32, f32, String)> = vec![
+        ("F2", 32.0, "0.067 0.067 0.067", 30.0, 56.0, card.title.clone()),
+        ("F1", 18.0, "0.267 0.267 0.267", 30.0, 86.0, format!("{} {}", card.manufacturer, card.year)),
+        ("F1", 13.0, "0.4 0.4 0.4", 30.0, 108.0, card.authors.clone()),
+    ];
+    for (n, line) in card.rules.lines().enumerate() {
+        lines.push(("F3", 13.0, "0.133 0.133 0.133", 30.0, 132.0 + 16.9 * (n + 1) as f32, line.trim_end().to_string()));
+    }
+    let highscore_x = 570.0 - helvetica_width(&card.highscore, 16.0);
+    lines.push(("F1", 16.0, "0.067 0.067 0.067", highscore_x, 380.0, card.highscore.clone()));
+
+    let mut content = b"0.72 0 0 0.72 0 0 cm\n0.992 0.98 0.949 rg 0.133 0.133 0.133 RG 4 w 0 0 600 400 re B\n\
+        0.6 0.6 0.6 RG 1 w 30 278 m 570 278 l S\n"
+        .to_vec();
+    for (font, size, rgb, x, y, text) in lines.iter().filter(|line| !line.5.trim().is_empty()) {
+        content.extend(format!("BT /{} {} Tf {} rg {:.1} {:.1} Td ", font, size, rgb, x, 400.0 - y).into_bytes());
+        content.extend(pdf_string(text));
+        content.extend(b" Tj ET\n");
+    }
+
+    let font = |name: &str| format!("<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>", name);
+    let objects: Vec<Vec<u8>> = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_vec(),
+        b"<< /Type /Page /Parent 2 0 R /MediaBox [0 0 432 288] /Contents 4 0 R \
+          /Resources << /Font << /F1 5 0 R /F2 6 0 R /F3 7 0 R >> >> >>"
+            .to_vec(),
+        [format!("<< /Length {} >>\nstream\n", content.len()).into_bytes(), content, b"\nendstream".to_vec()].concat(),
+        font("Helvetica").into_bytes(),
+        font("Helvetica-Bold").into_bytes(),
+        font("Times-Roman").into_bytes(),
+    ];
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (n, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend(format!("{} 0 obj\n", n + 1).into_bytes());
+        pdf.extend(object);
+        pdf.extend(b"\nendobj\n");
+    }
+    let xref = pdf.len();
+    pdf.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).into_bytes());
+    for offset in offsets {
+        pdf.extend(format!("{:010} 00000 n \n", offset).into_bytes());
+    }
+    pdf.extend(
+        format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).into_bytes(),
+    );
+    pdf
+}
+
+/// A card file name in `dest_dir` no other card of this run has taken.
+fn card_path(dest_dir: &Path, vpx_path: &Path, extension: &str, taken: &mut Vec<PathBuf>) -> PathBuf {
+    let stem = vpx_path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "table".into());
+    let mut path = dest_dir.join(format!("{}.{}", stem, extension));
+    let mut n = 2;
+    while taken.contains(&path) {
+        path = dest_dir.join(format!("{} ({}).{}", stem, n, extension));
+        n += 1;
+    }
+    taken.push(path.clone());
+    path
+}
+
+/// Writes the cards, as SVG filled into `template` when there is one.
+fn export(
+    index_doc: &Value,
+    template: Option<&str>,
+    dest_dir: &Path,
+    options: &CardOptions,
+) -> Result<Value, VpxError> {
+    crate::safe_mode::check("exporting table cards")?;
+    let format = options.format.unwrap_or(if template.is_some() { CardFormat::Svg } else { CardFormat::Pdf });
+    if format == CardFormat::Pdf && template.is_some() {
+        return Err(VpxError::new(
+            VpxErrorCode::InvalidArgument,
+            "Card templates are SVG; pass \"format\": \"svg\" with a template.",
+        ));
+    }
+    let extension = match format {
+        CardFormat::Pdf => "pdf",
+        CardFormat::Svg => "svg",
+    };
+    let (mut written, mut skipped, mut failed, mut warnings) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    let mut taken = Vec::new();
+    for row in index::tables(index_doc) {
+        let Some(vpx_file) = row["vpxFile"].as_str().filter(|f| !f.is_empty()) else { continue };
+        let vpx_path = Path::new(vpx_file);
+        let path = card_path(dest_dir, vpx_path, extension, &mut taken);
+        if !options.overwrite && path.exists() {
+            skipped.push(json!({ "vpx_file": vpx_file, "card": path }));
+            continue;
+        }
+        let (card, warning) = card(row, vpx_path, options);
+        if let Some(warning) = warning {
+            warnings.push(json!({ "vpx_file": vpx_file, "warning": warning }));
+        }
+        let data = match template {
+            Some(template) => render_svg(template, &card),
+            None if format == CardFormat::Svg => render_svg(DEFAULT_TEMPLATE, &card),
+            None => render_pdf(&card),
+        };
+        match paths::write_atomic(&path, &data) {
+            Ok(()) => {
+                if let Err(e) = provenance::stamp(&path, vpx_path, "table_cards") {
+                    warnings.push(json!({ "vpx_file": vpx_file, "warning": e.message }));
+                }
+                written.push(json!({ "vpx_file": vpx_file, "card": path }))
+            }
+            Err(e) => failed.push(json!({ "vpx_file": vpx_file, "error": e.to_string() })),
+        }
+    }
+    Ok(json!({ "written": written, "skipped": skipped, "failed": failed, "warnings": warnings }))
+}
+
+/// Writes a printable info card per row of the frontend's index
+/// (`{"tables": [...]}`) into `dest_dir`, named after the `.vpx` file: a
+/// 6 x 4 inch PDF page, or SVG. `template_path` (nullable for the built-in
+/// card) is an SVG file with the placeholders `{{title}}`, `{{manufacturer}}`, `{{year}}`,
+/// `{{authors}}`, `{{highscore}}` (the row's `highscore` field, if any) and
+/// `{{rules}}`, the rules summary as one `<tspan x="0" dy="1.3em">` per
+/// line, meant inside a `<text>` placed with `transform`. `options_json`
+/// (nullable): `{"format": "pdf"|"svg", "rules_wrap": 60, "rules_lines":
+/// 14, "overwrite": true}`; `format` defaults to PDF without a template and
+/// SVG with one, and a template can't be PDF (`InvalidArgument`).
+/// Each card is stamped with its table's provenance (`vpx_read_media_provenance`).
+///
+/// Returns `{"written": [{"vpx_file", "card"}], "skipped", "failed":
+/// [{"vpx_file", "error"}], "warnings": [{"vpx_file", "warning"}]}` (a
+/// table that could not be read gets a card without rules and a warning),
+/// or null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// All arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_export_table_cards(
+    index_json: *const c_char,
+    template_path: *const c_char,
+    dest_dir: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    let result = unsafe { library::options_from_c::<CardOptions>(options_json, KNOWN_KEYS) }
+        .and_then(|options| {
+            let index_doc = index::parse(unsafe { crate::str_from_c(index_json, "index") }?)?;
+            let dest_dir = unsafe { crate::path_from_c(dest_dir) }?;
+            let template = match template_path.is_null() {
+                true => None,
+                false => {
+                    let path = unsafe { crate::path_from_c(template_path) }?;
+                    let template = std::fs::read_to_string(&path).map_err(|e| {
+                        VpxError::new(
+                            VpxErrorCode::OpenFailed,
+                            format!("Failed to read card template '{}': {}", path.display(), e),
+                        )
+                    })?;
+                    Some(template)
+                }
+            };
+            export(&index_doc, template.as_deref(), &dest_dir, &options)
+        })
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_export_table_cards", result)
+}
@@ -0,0 +1,276 @@
+//! Locale-aware sort keys.
+//!
+//! Plain code point order puts "Élvira" after "Zaccaria" and "Terminator
+//! 10" before "Terminator 2". A collator applies the root order instead:
+//! accents and case only break ties, punctuation is ignored and numbers
+//! sort by value. A locale then moves its own letters: Swedish puts Å, Ä
+//! and Ö after Z, Spanish Ñ after N, German phone books read Ä as AE. The
+//! result is a key the frontend compares as a plain string, so it needs no
+//! collation library of its own.
+
+use std::os::raw::c_char;
+
+use serde_json::json;
+
+/// Letters a language sorts apart from their base letter, with the primary
+/// text they sort as. `{`, `|` and `}` follow `z` in a key, so `"z{"` is a
+/// letter after Z and `"n{"` one between N and O.
+type Tailoring = &'static [(char, &'static str)];
+
+const DANISH: Tailoring = &[('æ', "z{"), ('ä', "z{"), ('ø', "z|"), ('ö', "z|"), ('å', "z}")];
+const SWEDISH: Tailoring = &[('å', "z{"), ('ä', "z|"), ('æ', "z|"), ('ö', "z}"), ('ø', "z}")];
+const GERMAN_PHONEBOOK: Tailoring = &[('ä', "ae"), ('ö', "oe"), ('ü', "ue")];
+
+/// Languages by tailoring; those with none sort in the root order under
+/// their own name.
+const LOCALES: &[(&str, Tailoring)] = &[
+    ("cs", &[('č', "c{"), ('ř', "r{"), ('š', "s{"), ('ž', "z{")]),
+    ("da", DANISH),
+    ("de", &[]),
+    ("en", &[]),
+    ("es", &[('ñ', "n{")]),
+    ("fi", SWEDISH),
+    ("fr", &[]),
+    ("it", &[]),
+    ("nb", DANISH),
+    ("nl", &[]),
+    ("nn", DANISH),
+    ("no", DANISH),
+    (
+        "pl",
+        &[
+            ('ą', "a{"),
+            ('ć', "c{"),
+            ('ę', "e{"),
+            ('ł', "l{"),
+            ('ń', "n{"),
+            ('ó', "o{"),
+            ('ś', "s{"),
+            ('ź', "z{"),
+            ('ż', "z|"),
+        ],
+    ),
+    ("pt", &[]),
+    ("sk", &[('č', "c{"), ('š', "s{"), ('ž', "z{")]),
+    ("sv", SWEDISH),
+    ("tr", &[('ç', "c{"), ('ğ', "g{"), ('ı', "h{"), ('ö', "o{"), ('ş', "s{"), ('ü', "u{")]),
+];
+
+/// Separates the levels of a key; below every character a level holds.
+const LEVEL_SEPARATOR: char = '\u{1}';
+
+/// `c` without its accent, as one or more lowercase ASCII letters where a
+/// mapping is known.
+fn fold_char(c: char, out: &mut String) {
+    let folded = match c {
+        'à'..='å' | 'À'..='Å' | 'ā' | 'Ā' | 'ă' | 'Ă' | 'ą' | 'Ą' => "a",
+        'æ' | 'Æ' => "ae",
+        'ç' | 'Ç' | 'ć' | 'Ć' | 'č' | 'Č' => "c",
+        'ď' | 'Ď' | 'đ' | 'Đ' | 'ð' | 'Ð' => "d",
+        'è'..='ë' | 'È'..='Ë' | 'ē' | 'Ē' | 'ę' | 'Ę' | 'ě' | 'Ě' => "e",
+        'ğ' | 'Ğ' => "g",
+        'ì'..='ï' | 'Ì'..='Ï' | 'ī' | 'Ī' | 'ı' | 'İ' => "i",
+        'ł' | 'Ł' => "l",
+        'ñ' | 'Ñ' | 'ń' | 'Ń' | 'ň' | 'Ň' => "n",
+        'ò'..='ö' | 'Ò'..='Ö' | 'ø' | 'Ø' | 'ō' | 'Ō' | 'ő' | 'Ő' => "o",
+        'œ' | 'Œ' => "oe",
+        'ř' | 'Ř' => "r",
+        'ß' => "ss",
+        'ś' | 'Ś' | 'š' | 'Š' | 'ş' | 'Ş' => "s",
+        'ť' | 'Ť' | 'ţ' | 'Ţ' => "t",
+        'þ' | 'Þ' => "th",
+        'ù'..='ü' | 'Ù'..='Ü' | 'ū' | 'Ū' | 'ů' | 'Ů' | 'ű' | 'Ű' => "u",
+        'ý' | 'Ý' | 'ÿ' | 'Ÿ' => "y",
+        'ź' | 'Ź' | 'ż' | 'Ż' | 'ž' | 'Ž' => "z",
+        _ => {
+            out.extend(c.to_lowercase());
+            return;
+        }
+    };
+    out.push_str(folded);
+}
+
+/// Lowercase, accents folded, punctuation dropped, runs of spaces single.
+pub(crate) fn fold(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            fold_char(c, &mut out);
+        } else if is_word_break(c) && !out.is_empty() && !out.ends_with(' ') {
+            out.push(' ');
+        }
+    }
+    out.trim_end().to_string()
+}
+
+fn is_word_break(c: char) -> bool {
+    c.is_whitespace() || c == '-' || c == '_' || c == '/'
+}
+
+pub(crate) struct Collator {
+    name: &'static str,
+    tailoring: Tailoring,
+    /// French Canadian compares accents from the end of the word.
+    backwards_accents: bool,
+    /// Folded leading words sorted past.
+    articles: Vec<String>,
+    natural_numbers: bool,
+}
+
+impl Collator {
+    /// A collator for `locale` (`sv`, `de-DE`, `pt_BR.UTF-8`,
+    /// `de@collation=phonebook`); unknown languages sort in the root order.
+    pub(crate) fn new(locale: &str, articles: &[String], natural_numbers: bool) -> Collator {
+        let locale = locale.trim().to_ascii_lowercase().replace('_', "-");
+        let phonebook = locale.contains("phonebook") || locale.contains("phonebk");
+        let mut parts = locale.split(['-', '.', '@']);
+        let (language, region) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+        let (name, tailoring, backwards_accents) = match language {
+            "de" if phonebook => ("de-phonebook", GERMAN_PHONEBOOK, false),
+            "fr" if region == "ca" => ("fr-CA", &[][..], true),
+            _ => match LOCALES.iter().find(|(l, _)| *l == language) {
+                Some((name, tailoring)) => (*name, *tailoring, false),
+                None => ("root", &[][..], false),
+            },
+        };
+        let articles = articles.iter().map(|a| fold(a)).filter(|a| !a.is_empty()).collect();
+        Collator { name, tailoring, backwards_accents, articles, natural_numbers }
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Turkish has a dotless i, so its capital I lowercases to ı.
+    fn lower(&self, c: char) -> char {
+        match (self.name, c) {
+            ("tr", 'I') => 'ı',
+            ("tr", 'İ') => 'i',
+            _ => c.to_lowercase().next().unwrap_or(c),
+        }
+    }
+
+    fn primary_of(&self, c: char, out: &mut String) {
+        let lower = self.lower(c);
+        match self.tailoring.iter().find(|(letter, _)| *letter == lower) {
+            Some((_, primary)) => out.push_str(primary),
+            None => fold_char(lower, out),
+        }
+    }
+
+    /// The title's words without punctuation, a leading article dropped.
+    fn words(&self, title: &str) -> Vec<Vec<char>> {
+        let mut words = Vec::new();
+        let mut word = Vec::new();
+        for c in title.chars() {
+            if c.is_alphanumeric() {
+                word.push(c);
+            } else if is_word_break(c) && !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+        if !word.is_empty() {
+            words.push(word);
+        }
+        if words.len() > 1 && self.articles.contains(&fold(&words[0].iter().collect::<String>())) {
+            words.remove(0);
+        }
+        words
+    }
+
+    /// The key `title` sorts by: letters as the locale orders them, then
+    /// accents, then case (lowercase first), each level compared only when
+    /// the ones before are equal. Digit runs are their length and value, so
+    /// they compare by value as text.
+    pub(crate) fn key(&self, title: &str) -> String {
+        let (mut primary, mut secondary, mut tertiary) = (String::new(), String::new(), String::new());
+        for (n, word) in self.words(title).iter().enumerate() {
+            if n > 0 {
+                primary.push(' ');
+                secondary.push(' ');
+            }
+            let mut i = 0;
+            while i < word.len() {
+                let c = word[i];
+                if self.natural_numbers && c.is_ascii_digit() {
+                    let end = i + word[i..].iter().take_while(|c| c.is_ascii_digit()).count();
+                    let run: String = word[i..end].iter().collect();
+                    let value = match run.trim_start_matches('0') {
+                        "" => "0",
+                        value => value,
+                    };
+                    primary.push(char::from(b'0' + value.len().min(40) as u8));
+                    primary.push_str(value);
+                    secondary.push_str(&run);
+                    tertiary.extend(std::iter::repeat_n('0', end - i));
+                    i = end;
+                    continue;
+                }
+                self.primary_of(c, &mut primary);
+                secondary.push(self.lower(c));
+                tertiary.push(if c.is_uppercase() { '1' } else { '0' });
+                i += 1;
+            }
+        }
+        if self.backwards_accents {
+            secondary = secondary.chars().rev().collect();
+        }
+        format!("{}{}{}{}{}", primary, LEVEL_SEPARATOR, secondary, LEVEL_SEPARATOR, tertiary)
+    }
+
+    /// The letter `title` files under: its first letter without accents,
+    /// one the locale sorts apart as is ("Ä" in Swedish), `#` for digits and
+    /// other scripts, or none for a title without letters.
+    pub(crate) fn initial(&self, title: &str) -> Option<String> {
+        let c = *self.words(title).first()?.first()?;
+        let mut primary = String::new();
+        self.primary_of(c, &mut primary);
+        let mut chars = primary.chars();
+        Some(match (chars.next(), chars.next()) {
+            (_, Some('{' | '|' | '}')) => c.to_uppercase().collect(),
+            (Some(first), _) if first.is_ascii_alphabetic() => first.to_ascii_uppercase().to_string(),
+            _ => "#".to_string(),
+        })
+    }
+}
+
+/// Makes the key `title` sorts by in `locale` (null for the root order):
+/// a language code with an optional region and variant, as ICU or POSIX
+/// write them (`sv`, `pt-BR`, `fr_CA.UTF-8`, `de-u-co-phonebk`). Keys
+/// compare with plain byte order (`strcmp`, `std::string::operator<`).
+///
+/// The root order ignores punctuation and a leading "The", folds accents
+/// and case into tie-breaks and sorts numbers by value. Danish, Norwegian,
+/// Swedish, Finnish, Spanish, Polish, Czech, Slovak and Turkish sort their
+/// own letters apart; German phone book order (`de@collation=phonebook`)
+/// reads Ä, Ö and Ü as AE, OE and UE; French Canadian compares accents
+/// from the end. Other languages, German, French and Portuguese included,
+/// use the root order.
+///
+/// Returns `{"sort_key", "initial", "locale"}`, `initial` being the letter
+/// the title files under (`#` for digits, null without letters) and
+/// `locale` the rules applied (`"root"` for unknown languages), or null
+/// when `title` is null or not UTF-8. Release with `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_make_sort_key(title: *const c_char, locale: *const c_char) -> *mut c_char {
+    let locale = match locale.is_null() {
+        true => Ok(""),
+        false => unsafe { crate::str_from_c(locale, "locale") },
+    };
+    let result = locale.and_then(|locale| Ok((locale, unsafe { crate::str_from_c(title, "title") }?))).and_then(
+        |(locale, title)| {
+            let collator = Collator::new(locale, &["the".to_string()], true);
+            let key = json!({
+                "sort_key": collator.key(title),
+                "initial": collator.initial(title),
+                "locale": collator.name(),
+            });
+            crate::into_c_string(key.to_string())
+        },
+    );
+
+    crate::envelope::finish("vpx_make_sort_key", result)
+}
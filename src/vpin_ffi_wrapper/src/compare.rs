@@ -0,0 +1,199 @@
+//! Comparison of two .vpx files, for the "replace table?" dialog.
+//!
+//! Each side is reduced to a snapshot (table info, item counts per type,
+//! image signatures, script hash, file size) and the snapshots are diffed.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::os::raw::c_char;
+use std::path::Path;
+
+use serde_json::{json, Map, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::options::CallOptions;
+
+struct ImageSignature {
+    name: String,
+    width: u32,
+    height: u32,
+    size: usize,
+    sha256: String,
+}
+
+struct Snapshot {
+    file_size: u64,
+    version: String,
+    table_info: Value,
+    item_counts: BTreeMap<String, usize>,
+    /// Keyed by lowercased name; VPX looks images up case-insensitively.
+    images: BTreeMap<String, ImageSignature>,
+    script_sha256: String,
+    script_lines: usize,
+}
+
+fn parse_error(path: &Path, what: &str, e: std::io::Error) -> VpxError {
+    VpxError::new(
+        VpxErrorCode::ParseFailed,
+        format!("Failed to read {} for '{}': {}", what, path.display(), e),
+    )
+}
+
+fn snapshot(path: &Path) -> Result<Snapshot, VpxError> {
+    crate::catch_panic(path, || {
+        let file_size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        let mut vpx_file = crate::open_vpx(path, &CallOptions::default())?;
+        let version = vpx_file.read_version().map_err(|e| parse_error(path, "version", e))?;
+        let table_info = vpx_file.read_tableinfo().map_err(|e| parse_error(path, "table info", e))?;
+        let gamedata = vpx_file.read_gamedata().map_err(|e| parse_error(path, "gamedata", e))?;
+        let items = vpx_file.read_gameitems().map_err(|e| parse_error(path, "game items", e))?;
+        let images = vpx_file.read_images().map_err(|e| parse_error(path, "images", e))?;
+
+        let mut item_counts = BTreeMap::new();
+        for item in &items {
+            *item_counts.entry(item.type_name()).or_insert(0) += 1;
+        }
+        let images = images
+            .into_iter()
+            .map(|image| {
+                let data: &[u8] = match (&image.jpeg, &image.bits) {
+                    (Some(jpeg), _) => &jpeg.data,
+                    (None, Some(bits)) => &bits.lzw_compressed_data,
+                    (None, None) => &[],
+                };
+                let signature = ImageSignature {
+                    width: image.width,
+                    height: image.height,
+                    size: data.len(),
+                    sha256: crate::sha256_hex(data),
+                    name: image.name.clone(),
+                };
+                (image.name.to_lowercase(), signature)
+            })
+            .collect();
+        let code = &gamedata.code.string;
+
+        Ok(Snapshot {
+            file_size,
+            version: version.to_string(),
+            table_info: crate::table_info_to_json(table_info),
+            item_counts,
+            images,
+            script_sha256: crate::sha256_hex(code.as_bytes()),
+            script_lines: code.lines().count(),
+        })
+    })
+}
+
+/// `{"field": {"a", "b"}}` for every key whose value differs.
+fn diff_objects(a: &Value, b: &Value, skip: &[&str]) -> Map<String, Value> {
+    let empty = Map::new();
+    let a = a.as_object().unwrap_or(&empty);
+    let b = b.as_object().unwrap_or(&empty);
+    let keys: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+    keys.into_iter()
+        .filter(|key| !skip.contains(&key.as_str()))
+        .filter_map(|key| {
+            let (va, vb) = (a.get(key).unwrap_or(&Value::Null), b.get(key).unwrap_or(&Value::Null));
+            (va != vb).then(|| (key.clone(), json!({ "a": va, "b": vb })))
+        })
+        .collect()
+}
+
+fn compare(path_a: &Path, path_b: &Path) -> Result<Value, VpxError> {
+    let a = snapshot(path_a)?;
+    let b = snapshot(path_b)?;
+    let mut summary: Vec<String> = Vec::new();
+
+    let info_changed = diff_objects(&a.table_info, &b.table_info, &["properties"]);
+    let properties_changed = diff_objects(&a.table_info["properties"], &b.table_info["properties"], &[]);
+    if !info_changed.is_empty() || !properties_changed.is_empty() {
+        let fields: BTreeSet<&str> = info_changed.keys().chain(properties_changed.keys()).map(String::as_str).collect();
+        summary.push(format!("Table info changed: {}", fields.into_iter().collect::<Vec<_>>().join(", ")));
+    }
+
+    let types: BTreeSet<&String> = a.item_counts.keys().chain(b.item_counts.keys()).collect();
+    let mut items_changed = Map::new();
+    for item_type in types {
+        let (ca, cb) = (a.item_counts.get(item_type).copied().unwrap_or(0), b.item_counts.get(item_type).copied().unwrap_or(0));
+        if ca != cb {
+            items_changed.insert(item_type.clone(), json!({ "a": ca, "b": cb }));
+            summary.push(format!("{}: {} -> {}", item_type, ca, cb));
+        }
+    }
+
+    let added: Vec<&str> = b.images.iter().filter(|(k, _)| !a.images.contains_key(*k)).map(|(_, i)| i.name.as_str()).collect();
+    let removed: Vec<&str> = a.images.iter().filter(|(k, _)| !b.images.contains_key(*k)).map(|(_, i)| i.name.as_str()).collect();
+    let mut changed = Vec::new();
+    for (key, ia) in &a.images {
+        let Some(ib) = b.images.get(key) else { continue };
+        if ia.sha256 != ib.sha256 {
+            changed.push(json!({
+                "name": ib.name,
+                "a": { "width": ia.width, "height": ia.height, "size": ia.size },
+                "b": { "width": ib.width, "height": ib.height, "size": ib.size },
+            }));
+        }
+    }
+    for (count, what) in [(added.len(), "added"), (removed.len(), "removed"), (changed.len(), "changed")] {
+        if count > 0 {
+            summary.push(format!("{} image(s) {}", count, what));
+        }
+    }
+
+    let script_changed = a.script_sha256 != b.script_sha256;
+    if script_changed {
+        summary.push(format!("Script changed ({} -> {} lines)", a.script_lines, b.script_lines));
+    }
+    if a.version != b.version {
+        summary.push(format!("File version {} -> {}", a.version, b.version));
+    }
+
+    let side = |path: &Path, s: &Snapshot| {
+        json!({
+            "path": path,
+            "file_size": s.file_size,
+            "version": s.version,
+            "script_sha256": s.script_sha256,
+            "item_count": s.item_counts.values().sum::<usize>(),
+            "image_count": s.images.len(),
+        })
+    };
+
+    Ok(json!({
+        "a": side(path_a, &a),
+        "b": side(path_b, &b),
+        "identical": summary.is_empty() && a.file_size == b.file_size,
+        "table_info": { "changed": info_changed, "properties_changed": properties_changed },
+        "items": { "changed": items_changed },
+        "images": { "added": added, "removed": removed, "changed": changed },
+        "script": {
+            "changed": script_changed,
+            "lines": { "a": a.script_lines, "b": b.script_lines },
+        },
+        "file_size": {
+            "a": a.file_size,
+            "b": b.file_size,
+            "delta": b.file_size as i64 - a.file_size as i64,
+        },
+        "summary": summary,
+    }))
+}
+
+/// Compares two .vpx files (typically the installed table `a` and an
+/// incoming replacement `b`): table info, item counts per type, images,
+/// script hash and file size, plus a human-readable `"summary"` list.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_compare_files(vpx_file_a: *const c_char, vpx_file_b: *const c_char) -> *mut c_char {
+    let result = unsafe { crate::path_from_c(vpx_file_a) }
+        .and_then(|a| Ok((a, unsafe { crate::path_from_c(vpx_file_b) }?)))
+        .and_then(|(a, b)| compare(&a, &b))
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_compare_files", result)
+}
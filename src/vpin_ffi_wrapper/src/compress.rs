@@ -0,0 +1,89 @@
+//! Compressed payloads.
+//!
+//! A table info batch over a whole library comes back as tens of megabytes
+//! of JSON, copied across the FFI and then held twice while the frontend
+//! parses it. With the per-call option `"compress"` the buffer calls and job
+//! results hand the payload over compressed instead, behind a header giving
+//! its uncompressed size so the frontend can allocate once. zstd is what
+//! the frontend already links; gzip is there for builds without it.
+
+use std::io::Write;
+
+use serde::Deserialize;
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::options::CallOptions;
+
+const MAGIC: &[u8; 4] = b"VPXZ";
+/// Size of the header in front of a compressed payload: magic, codec
+/// byte, three zero bytes, uncompressed length (u64 LE).
+pub const VPX_COMPRESSED_HEADER_SIZE: usize = 16;
+const HEADER_LEN: usize = VPX_COMPRESSED_HEADER_SIZE;
+/// Below this a payload is stored rather than compressed.
+const DEFAULT_MIN_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Fails for a codec this build was compiled without.
+    pub(crate) fn check_available(self) -> Result<(), VpxError> {
+        match self {
+            Compression::Zstd if !cfg!(feature = "zstd") => Err(VpxError::new(
+                VpxErrorCode::InvalidArgument,
+                "This build has no zstd support; use \"compress\": \"gzip\".",
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn compress_failed(e: impl std::fmt::Display) -> VpxError {
+    VpxError::new(VpxErrorCode::AllocFailed, format!("Compression failed: {}", e))
+}
+
+fn gzip(payload: &[u8]) -> Result<Vec<u8>, VpxError> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(payload).map_err(compress_failed)?;
+    encoder.finish().map_err(compress_failed)
+}
+
+#[cfg(feature = "zstd")]
+fn zstd(payload: &[u8]) -> Result<Vec<u8>, VpxError> {
+    zstd::bulk::compress(payload, zstd::DEFAULT_COMPRESSION_LEVEL).map_err(compress_failed)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd(_payload: &[u8]) -> Result<Vec<u8>, VpxError> {
+    Compression::Zstd.check_available().map(|_| Vec::new())
+}
+
+/// `payload` the way the call's options ask for it: untouched without
+/// `"compress"`, otherwise the header followed by the frame (codec 0, the
+/// bytes as they are, when it is under `compress_min_bytes`).
+pub(crate) fn encode(payload: Vec<u8>, options: &CallOptions) -> Result<Vec<u8>, VpxError> {
+    let compression = options.compress;
+    if compression == Compression::None {
+        return Ok(payload);
+    }
+    let len = payload.len();
+    let (codec, frame) = if len < options.compress_min_bytes.unwrap_or(DEFAULT_MIN_BYTES) {
+        (0u8, payload)
+    } else if compression == Compression::Gzip {
+        (1, gzip(&payload)?)
+    } else {
+        (2, zstd(&payload)?)
+    };
+    let mut out = Vec::with_capacity(HEADER_LEN + frame.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&[codec, 0, 0, 0]);
+    out.extend_from_slice(&(len as u64).to_le_bytes());
+    out.extend_from_slice(&frame);
+    Ok(out)
+}
@@ -0,0 +1,202 @@
+//! Wrapper-wide configuration.
+//!
+//! Set with `vpx_wrapper_init(config_json)` and read back with
+//! `vpx_wrapper_get_config()`. Every key is optional and missing keys keep
+//! their defaults. Unknown keys are ignored with a warning, so a newer
+//! frontend can still initialise an older library.
+
+use std::collections::BTreeMap;
+use std::os::raw::c_char;
+use std::sync::{OnceLock, RwLock};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{VpxError, VpxErrorCode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LogLevelSetting {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum IoStrategy {
+    /// Read streams straight from the file.
+    File,
+    /// Load the whole file into memory first (fewer seeks on spinning disks).
+    Memory,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Config {
+    /// Job queue worker threads. 0 = one per CPU core.
+    pub thread_count: usize,
+    pub log_level: LogLevelSetting,
+    pub io_strategy: IoStrategy,
+    /// Larger files are refused with `LimitExceeded`. 0 = no limit.
+    pub max_file_size_mb: u64,
+    /// JSON file for per-table user data (play stats, ...). Empty = none.
+    pub user_store_path: String,
+    /// Folder for the undo log and the backups it restores. Empty = no undo.
+    pub operation_log_dir: String,
+    /// Operations that can still be undone; older backups are deleted.
+    pub operation_log_limit: usize,
+    /// JSON-lines journal of scan runs and their errors. Empty = none.
+    pub scan_journal_path: String,
+    /// Scan runs kept in the journal; older ones are dropped.
+    pub scan_journal_limit: usize,
+    /// Folder for diagnostic bundles of tables that fail to parse. Empty = none.
+    pub diagnostic_dir: String,
+    /// Wrap what the JSON calls return in `{"ok", "error", "data"}`.
+    pub result_envelope: bool,
+    /// Record every string, buffer and result handed out until it is freed,
+    /// for `vpx_ffi_dump_allocations`.
+    pub track_allocations: bool,
+    /// Refuse every call that changes table files or writes to disk; can't
+    /// be turned off again (see `safe_mode`).
+    pub safe_mode: bool,
+    /// Scan profile of tables no `scan_profile_dirs` entry covers (see
+    /// `profiles`).
+    pub scan_profile: String,
+    /// Scan profile per library folder; the deepest folder holding a table
+    /// wins.
+    pub scan_profile_dirs: BTreeMap<String, String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            thread_count: 0,
+            log_level: LogLevelSetting::Warn,
+            io_strategy: IoStrategy::File,
+            max_file_size_mb: 0,
+            user_store_path: String::new(),
+            operation_log_dir: String::new(),
+            operation_log_limit: 50,
+            scan_journal_path: String::new(),
+            scan_journal_limit: 50,
+            diagnostic_dir: String::new(),
+            result_envelope: false,
+            track_allocations: false,
+            safe_mode: false,
+            scan_profile: "full".to_string(),
+            scan_profile_dirs: BTreeMap::new(),
+        }
+    }
+}
+
+const KNOWN_KEYS: &[&str] = &[
+    "thread_count",
+    "log_level",
+    "io_strategy",
+    "max_file_size_mb",
+    "user_store_path",
+    "operation_log_dir",
+    "operation_log_limit",
+    "scan_journal_path",
+    "scan_journal_limit",
+    "diagnostic_dir",
+    "result_envelope",
+    "track_allocations",
+    "safe_mode",
+    "scan_profile",
+    "scan_profile_dirs",
+];
+
+fn config_lock() -> &'static RwLock<Config> {
+    static CONFIG: OnceLock<RwLock<Config>> = OnceLock::new();
+    CONFIG.get_or_init(|| RwLock::new(Config::default()))
+}
+
+/// Snapshot of the current configuration.
+pub(crate) fn get() -> Config {
+    config_lock().read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+fn apply(mut config: Config) {
+    crate::logging::set_max_level(match config.log_level {
+        LogLevelSetting::Off => 0,
+        LogLevelSetting::Error => 1,
+        LogLevelSetting::Warn => 2,
+        LogLevelSetting::Info => 3,
+        LogLevelSetting::Debug => 4,
+    });
+    crate::allocations::set_enabled(config.track_allocations);
+    config.safe_mode = crate::safe_mode::request(config.safe_mode);
+    if let Some(workers) = crate::jobs::worker_count() {
+        if config.thread_count != 0 && config.thread_count != workers {
+            log_warn!(
+                "thread_count {} ignored, {} job workers are already running",
+                config.thread_count,
+                workers
+            );
+        }
+    }
+    *config_lock().write().unwrap_or_else(|e| e.into_inner()) = config;
+}
+
+/// Logs a warning for each key of `object` not in `known`.
+pub(crate) fn warn_unknown_keys(context: &str, object: &serde_json::Map<String, Value>, known: &[&str]) {
+    for key in object.keys().filter(|key| !known.contains(&key.as_str())) {
+        log_warn!("Ignoring unknown {} key '{}'", context, key);
+    }
+}
+
+fn parse(config_json: &str) -> Result<Config, VpxError> {
+    let value: Value = serde_json::from_str(config_json).map_err(|e| {
+        VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid config JSON: {}", e))
+    })?;
+    let Some(object) = value.as_object() else {
+        return Err(VpxError::new(VpxErrorCode::InvalidArgument, "Config must be a JSON object."));
+    };
+    warn_unknown_keys("config", object, KNOWN_KEYS);
+    let config: Config = serde_json::from_value(value)
+        .map_err(|e| VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid config: {}", e)))?;
+    crate::profiles::check_config(&config)?;
+    Ok(config)
+}
+
+/// Configures the wrapper. `config_json` may be null, which resets to the
+/// defaults. Can be called again later; `thread_count` only takes effect if
+/// no job has been submitted yet. Returns a `VpxErrorCode`; on error the
+/// previous configuration is kept.
+///
+/// # Safety
+/// `config_json` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_wrapper_init(config_json: *const c_char) -> i32 {
+    let config = if config_json.is_null() {
+        Ok(Config::default())
+    } else {
+        unsafe { crate::str_from_c(config_json, "config") }.and_then(parse)
+    };
+    match config {
+        Ok(config) => {
+            let summary = serde_json::to_string(&config).unwrap_or_default();
+            apply(config);
+            log_info!("vpx_wrapper_init: {}", summary);
+            VpxErrorCode::Ok as i32
+        }
+        Err(e) => {
+            log_error!("vpx_wrapper_init: {}", e);
+            e.code as i32
+        }
+    }
+}
+
+/// The effective configuration as JSON (`thread_count` resolved to the real
+/// worker count). Release with `vpx_free_string`.
+#[no_mangle]
+pub extern "C" fn vpx_wrapper_get_config() -> *mut c_char {
+    let mut config = get();
+    config.thread_count = crate::jobs::worker_count().unwrap_or_else(|| crate::jobs::resolve_thread_count(config.thread_count));
+    let json = serde_json::to_string(&config).unwrap_or_else(|_| "{}".to_string());
+    crate::envelope::finish("vpx_wrapper_get_config", crate::into_c_string(json))
+}
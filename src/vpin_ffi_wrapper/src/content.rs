@@ -0,0 +1,140 @@
+//! Adult-content heuristic for streaming-safe / family views.
+//!
+//! Looks for keywords in the table info text, in the script's string
+//! literals (image, sound and object names end up there) and in the file
+//! names next to the table. Each hit carries a weight and they combine as
+//! independent evidence, `1 - prod(1 - w)`, into a 0..1 score.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::os::raw::c_char;
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::handle::{self, OpenTable, VpxHandle};
+use crate::options::CallOptions;
+use crate::paths;
+use crate::vbs::{self, Token};
+
+/// Score at which a table is flagged.
+const ADULT_THRESHOLD: f64 = 0.5;
+
+/// Keyword -> weight. Matched against whole words only.
+const KEYWORDS: &[(&str, f64)] = &[
+    ("porn", 0.6), ("porno", 0.6), ("xxx", 0.6), ("nsfw", 0.6), ("nude", 0.6), ("nudes", 0.6),
+    ("naked", 0.6), ("topless", 0.6), ("erotic", 0.6), ("erotica", 0.6), ("hentai", 0.6),
+    ("boobs", 0.5), ("hustler", 0.5),
+    ("playboy", 0.35), ("stripper", 0.35), ("striptease", 0.35), ("sexy", 0.35), ("sex", 0.35),
+    ("lingerie", 0.35), ("uncensored", 0.35),
+    ("adult", 0.15), ("bikini", 0.15),
+];
+
+/// How much a hit counts depending on where it was found.
+const TABLE_INFO_FACTOR: f64 = 1.0;
+const ASSET_FACTOR: f64 = 0.8;
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// Adult keywords found in `text`, with their weights.
+pub(crate) fn keyword_hits(text: &str) -> Vec<(&'static str, f64)> {
+    let found: BTreeSet<String> = words(text).collect();
+    KEYWORDS.iter().filter(|(k, _)| found.contains(*k)).copied().collect()
+}
+
+fn table_info_text(table_info: &Value) -> String {
+    let mut text: Vec<&str> = ["table_name", "table_blurb", "table_description", "table_rules"]
+        .iter()
+        .filter_map(|key| table_info[*key].as_str())
+        .collect();
+    if let Some(properties) = table_info["properties"].as_object() {
+        text.extend(properties.values().filter_map(Value::as_str));
+    }
+    text.join(" ")
+}
+
+/// The script's string literals, where asset names end up; identifiers,
+/// comments and credits are left out.
+fn script_literals(code: &str) -> String {
+    let statements = vbs::tokenize(code);
+    let literals: Vec<&str> = statements.iter().flat_map(|s| &s.tokens).filter_map(Token::string).collect();
+    literals.join(" ")
+}
+
+/// Rates the table at `path`. Unreadable streams just contribute nothing.
+pub(crate) fn rate(path: &Path) -> Value {
+    let mut table = OpenTable::open(path, &CallOptions::default()).ok();
+    rate_table(path, table.as_mut())
+}
+
+/// `rate` with the table already open (None if it couldn't be), so what it
+/// has read before isn't read again.
+fn rate_table(path: &Path, table: Option<&mut OpenTable>) -> Value {
+    let mut sources: Vec<(&str, String, f64)> = Vec::new();
+    if let Some(table) = table {
+        if let Ok(info) = table.table_info() {
+            sources.push(("table_info", table_info_text(&info), TABLE_INFO_FACTOR));
+        }
+        if let Ok(code) = table.code() {
+            sources.push(("script", script_literals(&code), ASSET_FACTOR));
+        }
+    }
+    let folder: Vec<String> = fs::read_dir(paths::table_dir(path))
+        .map(|d| d.flatten().map(|e| e.file_name().to_string_lossy().into_owned()).collect())
+        .unwrap_or_default();
+    sources.push(("files", folder.join(" "), ASSET_FACTOR));
+
+    let mut clean = 1.0;
+    let mut matches = Vec::new();
+    for (source, text, factor) in &sources {
+        for (keyword, weight) in keyword_hits(text) {
+            clean *= 1.0 - weight * factor;
+            matches.push(json!({ "keyword": keyword, "source": source }));
+        }
+    }
+    let score = ((1.0 - clean) * 100.0).round() / 100.0;
+    json!({
+        "score": score,
+        "adult": score >= ADULT_THRESHOLD,
+        "matches": matches,
+    })
+}
+
+/// Flags tables likely to contain adult content, from keywords in the table
+/// info, script strings and file names next to the table.
+///
+/// Returns `{"score": 0.0-1.0, "adult": bool, "matches": [{"keyword",
+/// "source"}]}`, or null on bad arguments. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `vpx_file_path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_content_rating(vpx_file_path: *const c_char) -> *mut c_char {
+    let result = unsafe { crate::path_from_c(vpx_file_path) }
+        .and_then(|path| crate::catch_panic(&path, || Ok(rate(&path))))
+        .and_then(|rating| crate::into_c_string(rating.to_string()));
+
+    crate::envelope::finish("vpx_get_content_rating", result)
+}
+
+/// `vpx_get_content_rating` for an open table. A scan that already read the
+/// script through the handle doesn't make this parse the file again.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `handle` must be null or from `vpx_open`; closed ones are rejected.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_handle_get_content_rating(handle: *const VpxHandle) -> *mut c_char {
+    let result = handle::with_table(handle, |table| {
+        let path = table.path().to_path_buf();
+        Ok(rate_table(&path, Some(table)))
+    })
+    .and_then(|rating| crate::into_c_string(rating.to_string()));
+
+    crate::envelope::finish("vpx_handle_get_content_rating", result)
+}
@@ -0,0 +1,160 @@
+//! Diagnostic bundles: the raw streams behind a failed parse, for bug
+//! reports.
+//!
+//! Most of a table is images and sounds nobody needs to reproduce a
+//! TableInfo or GameData parse error, and users can't attach a 300 MB file
+//! to an issue anyway. With `diagnostic_dir` configured, a read that fails
+//! to parse or panics leaves a small zip there with the streams involved,
+//! the file's storage directory and the error; `vpx_write_diagnostic_bundle`
+//! makes one on demand.
+
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::{config, paths, store};
+
+const BUNDLE_FORMAT: &str = "vpx-diagnostics";
+const BUNDLE_VERSION: u32 = 1;
+const MANIFEST: &str = "manifest.json";
+const STREAMS: &str = "streams";
+
+/// Streams are cut off here; a script that big is the exception.
+const MAX_STREAM_BYTES: u64 = 16 * 1024 * 1024;
+
+/// What the table info reads.
+pub(crate) const TABLE_INFO_STREAMS: &[&str] = &["/TableInfo", "/GameStg/Version"];
+/// What reading the script needs.
+pub(crate) const GAMEDATA_STREAMS: &[&str] = &["/GameStg/GameData", "/GameStg/Version"];
+/// An on-demand bundle: everything the wrapper parses except game items,
+/// images, sounds and fonts.
+const ALL_STREAMS: &[&str] =
+    &["/TableInfo", "/GameStg/Version", "/GameStg/GameData", "/GameStg/CustomInfoTags", "/GameStg/MAC"];
+
+fn io_error(what: &str, path: &Path, e: impl std::fmt::Display) -> VpxError {
+    VpxError::new(VpxErrorCode::OpenFailed, format!("Failed to {} '{}': {}", what, path.display(), e))
+}
+
+/// Writes the bundle for `vpx_path` into `dest_dir` as `<table>.diag.zip`,
+/// replacing an older one of the same table.
+fn write_bundle(vpx_path: &Path, dest_dir: &Path, prefixes: &[&str], error: Value) -> Result<Value, VpxError> {
+    crate::safe_mode::check("writing diagnostic bundles")?;
+    let mut compound = cfb::open(vpx_path).map_err(|e| io_error("open as a compound file", vpx_path, e))?;
+    let entries: Vec<(PathBuf, u64, bool)> =
+        compound.walk().filter(|e| !e.is_root()).map(|e| (e.path().to_path_buf(), e.len(), e.is_stream())).collect();
+
+    let mut streams = Vec::new();
+    let mut files = Vec::new();
+    for (path, len, _) in
+        entries.iter().filter(|(path, _, stream)| *stream && prefixes.iter().any(|p| path.starts_with(p)))
+    {
+        let mut data = Vec::new();
+        let read = compound.open_stream(path).and_then(|stream| stream.take(MAX_STREAM_BYTES).read_to_end(&mut data));
+        let name = path.to_string_lossy().trim_start_matches('/').to_string();
+        match read {
+            Ok(_) => {
+                streams.push(json!({ "path": name, "len": len, "truncated": *len > MAX_STREAM_BYTES }));
+                files.push((format!("{}/{}", STREAMS, name), data));
+            }
+            Err(e) => streams.push(json!({ "path": name, "len": len, "error": e.to_string() })),
+        }
+    }
+
+    let metadata = fs::metadata(vpx_path).map_err(|e| io_error("read", vpx_path, e))?;
+    let manifest = json!({
+        "format": BUNDLE_FORMAT,
+        "version": BUNDLE_VERSION,
+        "created": store::now(),
+        "wrapper_version": env!("CARGO_PKG_VERSION"),
+        "table": { "path": vpx_path, "size": metadata.len() },
+        "error": error,
+        "entries": entries
+            .iter()
+            .map(|(path, len, stream)| json!({ "path": path.to_string_lossy(), "len": len, "stream": stream }))
+            .collect::<Vec<_>>(),
+        "streams": streams,
+    });
+    let manifest_data = serde_json::to_vec_pretty(&manifest)
+        .map_err(|e| VpxError::new(VpxErrorCode::AllocFailed, format!("JSON serialization failed: {}", e)))?;
+
+    let write = || -> zip::result::ZipResult<Vec<u8>> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        zip.start_file(MANIFEST, options)?;
+        zip.write_all(&manifest_data)?;
+        for (name, data) in &files {
+            zip.start_file(name.as_str(), options)?;
+            zip.write_all(data)?;
+        }
+        Ok(zip.finish()?.into_inner())
+    };
+    let stem = vpx_path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "table".into());
+    let dest = dest_dir.join(format!("{}.diag.zip", stem));
+    let data = write().map_err(|e| io_error("write bundle", &dest, e))?;
+    fs::create_dir_all(dest_dir).map_err(|e| io_error("create", dest_dir, e))?;
+    paths::write_atomic(&dest, &data).map_err(|e| io_error("write", &dest, e))?;
+    Ok(json!({ "bundle": dest, "size": data.len(), "streams": files.len() }))
+}
+
+/// Leaves a bundle in `diagnostic_dir` for a read of `prefixes` that failed
+/// with `error`, if it is the kind of failure one helps with (a parse error
+/// or a panic) and a folder is configured. Never fails the read it reports.
+pub(crate) fn capture(vpx_path: &Path, prefixes: &[&str], error: &VpxError) {
+    let dir = config::get().diagnostic_dir;
+    // Tables parsed from a buffer have no file to take the streams from.
+    if dir.is_empty() || crate::safe_mode::enabled() || !vpx_path.is_file() || !matches!(error.code, VpxErrorCode::ParseFailed | VpxErrorCode::Panic) {
+        return;
+    }
+    let error = json!({ "code": error.code as i32, "message": error.message });
+    match write_bundle(vpx_path, Path::new(&dir), prefixes, error) {
+        Ok(report) => log_info!("Diagnostic bundle for '{}': {}", vpx_path.display(), report["bundle"]),
+        Err(e) => log_warn!("No diagnostic bundle for '{}': {}", vpx_path.display(), e),
+    }
+}
+
+/// Writes a diagnostic bundle of the table at `vpx_file_path` to attach to
+/// a bug report: a zip with `manifest.json` (the file's storage directory,
+/// the wrapper version and `error_message`, if given) and the TableInfo,
+/// Version, GameData, CustomInfoTags and MAC streams as they are in the
+/// file, under `streams/`. Images, sounds and game items are left out.
+/// `dest_dir` is nullable for the configured `diagnostic_dir`.
+///
+/// Returns `{"bundle", "size", "streams"}`, or null on failure. Release
+/// with `vpx_free_string`.
+///
+/// # Safety
+/// All arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_write_diagnostic_bundle(
+    vpx_file_path: *const c_char,
+    dest_dir: *const c_char,
+    error_message: *const c_char,
+) -> *mut c_char {
+    let result = unsafe { crate::path_from_c(vpx_file_path) }
+        .and_then(|vpx_path| {
+            let dest_dir = match dest_dir.is_null() {
+                true => match config::get().diagnostic_dir {
+                    dir if dir.is_empty() => {
+                        return Err(VpxError::new(
+                            VpxErrorCode::InvalidArgument,
+                            "No destination folder and no diagnostic_dir configured.",
+                        ))
+                    }
+                    dir => PathBuf::from(dir),
+                },
+                false => unsafe { crate::path_from_c(dest_dir) }?,
+            };
+            let error = match error_message.is_null() {
+                true => Value::Null,
+                false => json!({ "message": unsafe { crate::str_from_c(error_message, "error message") }? }),
+            };
+            write_bundle(&vpx_path, &dest_dir, ALL_STREAMS, error)
+        })
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_write_diagnostic_bundle", result)
+}
@@ -0,0 +1,521 @@
+//! Download folder intake.
+//!
+//! Tables dropped into a downloads folder shouldn't go into the library
+//! before anyone has looked at them. `vpx_process_download_folder` checks
+//! each new `.vpx`, `.zip`, `.7z` or `.rar` pack or unpacked table folder
+//! there: it must be within the size limit, every archive entry must
+//! extract with a good CRC and a safe name, and every table in it must
+//! parse. 7z and RAR packs are read with 7-Zip, unpacking only their
+//! tables; without 7-Zip they are listed as unsupported and left alone. What passes moves to
+//! the staging folder and is listed in its `pending.json` as a pending
+//! import, with the tables found, their hashes and whether the library
+//! already has them. What fails moves to the quarantine folder with the
+//! reason beside it. Downloads still being written are left for the next
+//! call. Importing a pending item is `vpx_import_table` on its staged path.
+//!
+//! Split archives (`.zip.001`, `.7z.001`, `.part1.rar`) are one download:
+//! a set with parts missing waits for them, and a complete one is checked
+//! and staged whole (7z and RAR sets only with 7-Zip, like single packs).
+
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek};
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::handle::OpenTable;
+use crate::library::LibraryConfig;
+use crate::multipart::{Format, PartSet};
+use crate::options::CallOptions;
+use crate::{config, hashes, index, oplog, paths, script, sevenzip, store};
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct DownloadOptions {
+    /// Check and report only: move nothing, write nothing.
+    dry_run: bool,
+    /// Where checked downloads wait for import. Default `<downloads>/.vpx-pending`.
+    staging_dir: Option<PathBuf>,
+    /// Where failed downloads go. Default `<downloads>/.vpx-quarantine`.
+    quarantine_dir: Option<PathBuf>,
+    /// Bigger downloads (unpacked, for archives) are quarantined. Default
+    /// the configured `max_file_size_mb`; 0 = no limit.
+    max_size_mb: Option<u64>,
+    /// Files changed more recently than this are still downloading.
+    settle_seconds: u64,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        DownloadOptions {
+            dry_run: false,
+            staging_dir: None,
+            quarantine_dir: None,
+            max_size_mb: None,
+            settle_seconds: 30,
+        }
+    }
+}
+
+const KNOWN_KEYS: &[&str] = &["dry_run", "staging_dir", "quarantine_dir", "max_size_mb", "settle_seconds"];
+
+const STAGING_DIR: &str = ".vpx-pending";
+const QUARANTINE_DIR: &str = ".vpx-quarantine";
+const MANIFEST: &str = "pending.json";
+const FORMAT: &str = "vpx-pending-imports";
+const VERSION: u32 = 1;
+
+/// What browsers and download managers name files they are still writing.
+const PARTIAL_EXTENSIONS: &[&str] = &["part", "partial", "crdownload", "download", "tmp"];
+/// Archive members a table pack has no business shipping; reported for review.
+const EXECUTABLE_EXTENSIONS: &[&str] = &["exe", "com", "bat", "cmd", "ps1", "scr", "msi", "vbe", "jse", "lnk"];
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension().and_then(OsStr::to_str).is_some_and(|e| extensions.iter().any(|x| e.eq_ignore_ascii_case(x)))
+}
+
+fn io_error(what: &str, path: &Path, e: io::Error) -> VpxError {
+    VpxError::new(VpxErrorCode::OpenFailed, format!("Failed to {} '{}': {}", what, path.display(), e))
+}
+
+/// Why `path` can't be checked yet, if it is still being downloaded.
+fn still_downloading(path: &Path, settle: Duration) -> Option<String> {
+    if has_extension(path, PARTIAL_EXTENSIONS) {
+        return Some("partial download".to_string());
+    }
+    let files = match path.is_dir() {
+        true => paths::files_under(path),
+        false => vec![path.to_path_buf()],
+    };
+    let newest = files.iter().filter_map(|file| fs::metadata(file).and_then(|m| m.modified()).ok()).max();
+    if newest.is_some_and(|modified| SystemTime::now().duration_since(modified).unwrap_or_default() < settle) {
+        return Some("changed too recently".to_string());
+    }
+    files.iter().find_map(|file| crate::locks::check_not_in_use(file).err().map(|e| e.message))
+}
+
+/// Scripts the library already has, by SHA-256 (the index's `hashFromVpx`).
+fn known_scripts(library: &LibraryConfig) -> Vec<(String, String)> {
+    let Some(index_path) = &library.index_path else { return Vec::new() };
+    let index_doc = index::load(index_path).unwrap_or_else(|e| {
+        log_warn!("Download check without duplicates: {}", e);
+        json!({ "tables": [] })
+    });
+    index::tables(&index_doc)
+        .iter()
+        .filter_map(|row| {
+            let hash = row["hashFromVpx"].as_str().filter(|h| !h.is_empty())?;
+            Some((hash.to_ascii_lowercase(), row["vpxFile"].as_str().unwrap_or_default().to_string()))
+        })
+        .collect()
+}
+
+/// What the review list says about one table of a download.
+fn describe_table(file: &Path, mut table: OpenTable, known: &[(String, String)]) -> Result<Value, String> {
+    let info = table.table_info().map_err(|e| e.message)?;
+    let code = table.code_ref().map_err(|e| e.message)?;
+    let script_sha256 = crate::sha256_hex(code.as_bytes());
+    let duplicate_of = known.iter().find(|(hash, _)| *hash == script_sha256).map(|(_, vpx_file)| vpx_file);
+    Ok(json!({
+        "file": file,
+        "title": info["table_name"],
+        "author": info["author_name"],
+        "version": info["table_version"],
+        "rom_name": script::rom_name(code),
+        "script_sha256": script_sha256,
+        "duplicate_of": duplicate_of,
+    }))
+}
+
+/// The tables of a `.zip` pack, after checking every entry.
+fn check_zip<R: Read + Seek>(
+    zip: R,
+    limit: u64,
+    known: &[(String, String)],
+    warnings: &mut Vec<String>,
+) -> Result<Vec<Value>, String> {
+    let mut archive = zip::ZipArchive::new(zip).map_err(|e| format!("not a readable zip: {}", e))?;
+    let mut unpacked = 0u64;
+    let mut executables = Vec::new();
+    let mut tables = Vec::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(|e| format!("unreadable entry {}: {}", i, e))?;
+        if entry.is_dir() {
+            continue;
+        }
+        let Some(relative) = entry.enclosed_name() else {
+            return Err(format!("unsafe entry name '{}'", entry.name()));
+        };
+        unpacked = unpacked.saturating_add(entry.size());
+        if limit > 0 && unpacked > limit {
+            return Err(format!("unpacks to more than the {} MiB limit", limit / (1024 * 1024)));
+        }
+        if has_extension(&relative, EXECUTABLE_EXTENSIONS) {
+            executables.push(relative.to_string_lossy().into_owned());
+        }
+        // Reading an entry to the end checks its CRC.
+        if has_extension(&relative, &["vpx"]) {
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).map_err(|e| format!("corrupt entry '{}': {}", relative.display(), e))?;
+            let name = relative.to_string_lossy();
+            let table = OpenTable::from_bytes(&name, data, &CallOptions::default()).map_err(|e| e.message)?;
+            tables.push(describe_table(&relative, table, known)?);
+        } else {
+            io::copy(&mut entry, &mut io::sink())
+                .map_err(|e| format!("corrupt entry '{}': {}", relative.display(), e))?;
+        }
+    }
+    if !executables.is_empty() {
+        warnings.push(format!("executable files: {}", executables.join(", ")));
+    }
+    Ok(tables)
+}
+
+/// The tables of a `.7z` or `.rar` pack, checked like `check_zip` does: the
+/// listing for names, sizes and executables, `7z t` for the CRCs, and only
+/// the `.vpx` members unpacked, to parse them.
+fn check_pack(
+    path: &Path,
+    limit: u64,
+    known: &[(String, String)],
+    warnings: &mut Vec<String>,
+) -> Result<Vec<Value>, String> {
+    let members = sevenzip::list(path).map_err(|e| e.message)?;
+    let mut unpacked = 0u64;
+    let mut executables = Vec::new();
+    let mut tables = Vec::new();
+    for member in &members {
+        let Some(relative) = &member.relative else {
+            return Err(format!("unsafe entry name '{}'", member.name));
+        };
+        unpacked = unpacked.saturating_add(member.size);
+        if limit > 0 && unpacked > limit {
+            return Err(format!("unpacks to more than the {} MiB limit", limit / (1024 * 1024)));
+        }
+        if has_extension(relative, EXECUTABLE_EXTENSIONS) {
+            executables.push(relative.to_string_lossy().into_owned());
+        }
+    }
+    sevenzip::test(path).map_err(|e| e.message)?;
+    for member in &members {
+        let Some(relative) = member.relative.as_ref().filter(|r| has_extension(r, &["vpx"])) else { continue };
+        let data = sevenzip::read(path, member).map_err(|e| e.message)?;
+        let name = relative.to_string_lossy();
+        let table = OpenTable::from_bytes(&name, data, &CallOptions::default()).map_err(|e| e.message)?;
+        tables.push(describe_table(relative, table, known)?);
+    }
+    if !executables.is_empty() {
+        warnings.push(format!("executable files: {}", executables.join(", ")));
+    }
+    Ok(tables)
+}
+
+/// Checks one download, `path` or the split archive `set` it is a part of:
+/// `Ok` with its review entry, `Err` with why it goes to quarantine.
+fn check(path: &Path, set: Option<&PartSet>, limit: u64, known: &[(String, String)]) -> Result<Value, String> {
+    let (kind, files) = match (set, path.is_dir()) {
+        (Some(set), _) => (
+            match set.format {
+                Format::Zip => "zip_parts",
+                Format::SevenZip => "7z_parts",
+                Format::Rar => "rar_parts",
+            },
+            set.paths(),
+        ),
+        (None, true) => ("folder", paths::files_under(path)),
+        (None, false) if has_extension(path, &["zip"]) => ("zip", vec![path.to_path_buf()]),
+        (None, false) if has_extension(path, &["7z"]) => ("7z", vec![path.to_path_buf()]),
+        (None, false) if has_extension(path, &["rar"]) => ("rar", vec![path.to_path_buf()]),
+        (None, false) => ("vpx", vec![path.to_path_buf()]),
+    };
+    let size: u64 = files.iter().filter_map(|file| fs::metadata(file).ok()).map(|m| m.len()).sum();
+    if limit > 0 && size > limit {
+        return Err(format!("{} MiB is over the {} MiB limit", size / (1024 * 1024), limit / (1024 * 1024)));
+    }
+    let mut warnings = Vec::new();
+    let tables = match (kind, set) {
+        ("zip", _) => check_zip(File::open(path).map_err(|e| e.to_string())?, limit, known, &mut warnings)?,
+        ("7z" | "rar", _) => check_pack(path, limit, known, &mut warnings)?,
+        (_, Some(set)) if set.format == Format::Zip => {
+            check_zip(set.reader().map_err(|e| e.to_string())?, limit, known, &mut warnings)?
+        }
+        // 7-Zip reads the set from its first part.
+        (_, Some(set)) => check_pack(set.first(), limit, known, &mut warnings)?,
+        _ => files
+            .iter()
+            .filter(|file| has_extension(file, &["vpx"]))
+            .map(|file| {
+                let table = OpenTable::open(file, &CallOptions::default()).map_err(|e| e.message)?;
+                let relative = match kind {
+                    "folder" => file.strip_prefix(path).unwrap_or(file),
+                    _ => Path::new(file.file_name().unwrap_or_default()),
+                };
+                describe_table(relative, table, known)
+            })
+            .collect::<Result<Vec<Value>, String>>()?,
+    };
+    if tables.is_empty() {
+        return Err("no .vpx inside".to_string());
+    }
+    let sha256 = match (kind, set) {
+        ("folder", _) => None,
+        (_, Some(set)) => Some(set.sha256().map_err(|e| e.to_string())?),
+        _ => Some(hashes::sha256_file(path).map_err(|e| e.to_string())?),
+    };
+    let name = match set {
+        Some(set) => set.name.clone(),
+        None => path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(),
+    };
+    Ok(json!({
+        "name": name,
+        "kind": kind,
+        "parts": set.map(PartSet::file_names),
+        "size": size,
+        "sha256": sha256,
+        "tables": tables,
+        "warnings": warnings,
+        "checked": store::now(),
+        "staged_path": null,
+    }))
+}
+
+/// `dir/name`, or `dir/name (2)`... if that is taken.
+fn free_path(dir: &Path, name: &OsStr) -> PathBuf {
+    let first = dir.join(name);
+    if !first.exists() {
+        return first;
+    }
+    let path = Path::new(name);
+    let stem = path.file_stem().unwrap_or(name).to_string_lossy();
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    (2..).map(|n| dir.join(format!("{} ({}){}", stem, n, extension))).find(|p| !p.exists()).unwrap_or(first)
+}
+
+/// `(from, to)` for moving a download into `dir`: the item itself, or
+/// every part of its set under names that keep them together.
+fn moves(dir: &Path, path: &Path, set: Option<&PartSet>) -> Vec<(PathBuf, PathBuf)> {
+    match set {
+        Some(set) => set.paths().into_iter().zip(set.free_names(dir)).collect(),
+        None => vec![(path.to_path_buf(), free_path(dir, path.file_name().unwrap_or_default()))],
+    }
+}
+
+/// Moves a download into `dir`, returning where its first file went.
+fn move_download(dir: &Path, path: &Path, set: Option<&PartSet>) -> Result<PathBuf, VpxError> {
+    let moves = moves(dir, path, set);
+    for (from, to) in &moves {
+        crate::relocate::move_path(from, to)?;
+    }
+    Ok(moves.into_iter().next().map(|(_, to)| to).unwrap_or_default())
+}
+
+fn manifest_path(staging: &Path) -> PathBuf {
+    staging.join(MANIFEST)
+}
+
+/// The pending imports still in the staging folder.
+fn load_pending(staging: &Path) -> Vec<Value> {
+    let path = manifest_path(staging);
+    let Ok(data) = fs::read(&path) else { return Vec::new() };
+    match serde_json::from_slice::<Value>(&data) {
+        Ok(manifest) if manifest["format"] == FORMAT => manifest["pending"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            // Imported or deleted since.
+            .filter(|item| item["staged_path"].as_str().is_some_and(|p| Path::new(p).exists()))
+            .cloned()
+            .collect(),
+        _ => {
+            log_warn!("Ignoring unreadable '{}'", path.display());
+            Vec::new()
+        }
+    }
+}
+
+fn save_pending(staging: &Path, pending: &[Value]) -> Result<(), VpxError> {
+    let path = manifest_path(staging);
+    let manifest = json!({ "format": FORMAT, "version": VERSION, "pending": pending });
+    let data = serde_json::to_vec_pretty(&manifest)
+        .map_err(|e| VpxError::new(VpxErrorCode::AllocFailed, format!("JSON serialization failed: {}", e)))?;
+    paths::write_atomic(&path, &data).map_err(|e| io_error("write", &path, e))
+}
+
+fn process(downloads: &Path, library: &LibraryConfig, options: &DownloadOptions) -> Result<Value, VpxError> {
+    if !downloads.is_dir() {
+        return Err(VpxError::new(
+            VpxErrorCode::FileNotFound,
+            format!("No downloads folder '{}'", downloads.display()),
+        ));
+    }
+    if !options.dry_run {
+        crate::safe_mode::check("staging downloads")?;
+    }
+    let staging = options.staging_dir.clone().unwrap_or_else(|| downloads.join(STAGING_DIR));
+    let quarantine = options.quarantine_dir.clone().unwrap_or_else(|| downloads.join(QUARANTINE_DIR));
+    let limit = options.max_size_mb.unwrap_or_else(|| config::get().max_file_size_mb).saturating_mul(1024 * 1024);
+    let settle = Duration::from_secs(options.settle_seconds);
+    let known = known_scripts(library);
+
+    let mut candidates: Vec<PathBuf> = fs::read_dir(downloads)
+        .map_err(|e| io_error("read", downloads, e))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| *path != staging && *path != quarantine)
+        .collect();
+    candidates.sort();
+
+    let mut pending = load_pending(&staging);
+    let (mut staged, mut quarantined, mut waiting, mut ignored) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    let mut unsupported = Vec::new();
+    // First parts of the split archives already handled through another part.
+    let mut sets_seen: Vec<PathBuf> = Vec::new();
+    for path in candidates {
+        // Moved along with an earlier part of its set.
+        if !path.exists() {
+            continue;
+        }
+        let name = path.file_name().unwrap_or_default().to_owned();
+        let hidden = name.to_string_lossy().starts_with('.');
+        let set = PartSet::find(&path);
+        if hidden
+            || !(path.is_dir()
+                || set.is_some()
+                || has_extension(&path, &["vpx", "zip", "7z", "rar"])
+                || has_extension(&path, PARTIAL_EXTENSIONS))
+        {
+            ignored.push(json!(name.to_string_lossy()));
+            continue;
+        }
+        let (shown, files) = match &set {
+            Some(set) if sets_seen.iter().any(|first| first == set.first()) => continue,
+            Some(set) => {
+                sets_seen.push(set.first().to_path_buf());
+                (set.name.clone(), set.paths())
+            }
+            None => (name.to_string_lossy().into_owned(), vec![path.clone()]),
+        };
+        let entry = |reason: String| match &set {
+            Some(set) => json!({ "name": shown, "parts": set.file_names(), "reason": reason }),
+            None => json!({ "name": shown, "reason": reason }),
+        };
+        if let Some(reason) = files.iter().find_map(|file| still_downloading(file, settle)) {
+            waiting.push(entry(reason));
+            continue;
+        }
+        if let Some(reason) = set.as_ref().and_then(PartSet::missing) {
+            waiting.push(entry(reason));
+            continue;
+        }
+        let packed = match &set {
+            Some(set) => set.format != Format::Zip,
+            None => sevenzip::is_pack(&path),
+        };
+        if packed && !sevenzip::available() {
+            unsupported.push(entry("needs 7-Zip (7z) to read; install it or extract it here".to_string()));
+            continue;
+        }
+        match check(&path, set.as_ref(), limit, &known) {
+            Ok(mut item) => {
+                if !options.dry_run {
+                    item["staged_path"] = json!(move_download(&staging, &path, set.as_ref())?);
+                }
+                staged.push(item);
+            }
+            Err(reason) => {
+                log_warn!("Quarantining download '{}': {}", shown, reason);
+                let mut target = Value::Null;
+                if !options.dry_run {
+                    let moved = move_download(&quarantine, &path, set.as_ref())?;
+                    let mut note = moved.as_os_str().to_os_string();
+                    note.push(".quarantine.json");
+                    let note = PathBuf::from(note);
+                    let data = json!({ "source": path, "reason": reason, "quarantined": store::now() }).to_string();
+                    paths::write_atomic(&note, data.as_bytes()).map_err(|e| io_error("write", &note, e))?;
+                    target = json!(moved);
+                }
+                let mut item = entry(reason);
+                item["path"] = target;
+                quarantined.push(item);
+            }
+        }
+    }
+    if !options.dry_run && !staged.is_empty() {
+        pending.extend(staged.iter().cloned());
+        save_pending(&staging, &pending)?;
+    }
+    let staged_names: Vec<&Value> = staged.iter().map(|item| &item["name"]).collect();
+    if options.dry_run {
+        pending.extend(staged.iter().cloned());
+    }
+
+    Ok(json!({
+        "dry_run": options.dry_run,
+        "downloads_dir": downloads,
+        "staging_dir": staging,
+        "quarantine_dir": quarantine,
+        "pending": pending,
+        "staged": staged_names,
+        "quarantined": quarantined,
+        "waiting": waiting,
+        "unsupported": unsupported,
+        "ignored": ignored,
+    }))
+}
+
+/// Checks what is new in `downloads_dir` and stages it for import: each
+/// `.vpx`, `.zip`, `.7z` or `.rar` pack or table folder must be within the
+/// size limit, extract cleanly (CRCs, safe entry names) and hold tables
+/// that parse. Those that pass move to the staging folder; those that fail
+/// move to the quarantine folder with a `<name>.quarantine.json` saying
+/// why. Files still downloading (partial names, changed within
+/// `settle_seconds`, open in another program) are left alone. The parts of
+/// a split archive go as one: a set missing parts waits for them, a
+/// complete set is staged whole (its `staged_path` is the first part, which
+/// `vpx_import_table` takes). 7z and RAR packs and sets are reported as
+/// unsupported and not moved when 7-Zip isn't installed.
+///
+/// Returns `{"dry_run", "downloads_dir", "staging_dir", "quarantine_dir",
+/// "pending": [{"name", "kind" ("vpx", "zip", "7z", "rar", "zip_parts",
+/// "7z_parts", "rar_parts", "folder"), "parts", "size", "sha256", "tables": [{"file", "title",
+/// "author", "version", "rom_name", "script_sha256", "duplicate_of"}],
+/// "warnings", "checked", "staged_path"}], "staged": [name...], "quarantined":
+/// [{"name", "parts"?, "reason", "path"}], "waiting": [{"name", "parts"?,
+/// "reason"}], "unsupported": [{"name", "parts"?, "reason"}], "ignored"}`:
+/// `pending` is the review list, every staged download not yet imported,
+/// also kept in the staging folder's `pending.json`. `duplicate_of` is the
+/// index row with the same script (`index_path` in `library_config_json`,
+/// nullable).
+///
+/// `options_json` (nullable): `dry_run`, `staging_dir`, `quarantine_dir`,
+/// `max_size_mb`, `settle_seconds` (30). Returns null on failure. Release
+/// with `vpx_free_string`.
+///
+/// # Safety
+/// All arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_process_download_folder(
+    downloads_dir: *const c_char,
+    library_config_json: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    let result = unsafe { crate::path_from_c(downloads_dir) }
+        .and_then(|downloads| Ok((downloads, unsafe { LibraryConfig::from_c(library_config_json) }?)))
+        .and_then(|(downloads, library)| {
+            let options: DownloadOptions = unsafe { crate::library::options_from_c(options_json, KNOWN_KEYS) }?;
+            let summary = format!("Stage downloads from {}", downloads.display());
+            let run = || crate::catch_panic(&downloads, || process(&downloads, &library, &options));
+            match options.dry_run {
+                true => run(),
+                false => oplog::record("downloads", summary, run),
+            }
+        })
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_process_download_folder", result)
+}
@@ -0,0 +1,67 @@
+//! Result envelope for the JSON-returning calls.
+//!
+//! By default a JSON call returns its document, or null with the reason
+//! only in the log and `vpx_get_last_error_message`. With `result_envelope`
+//! set in the config every one of them returns `{"ok", "error": {"code",
+//! "message"}, "data"}` instead, never null, so the frontend handles
+//! errors in one place and new fields can go next to `data` later. Calls
+//! returning a script or other plain text are not JSON and stay as they
+//! are.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+
+use serde_json::json;
+
+use crate::config;
+use crate::error::{VpxError, VpxErrorCode};
+
+/// The return value of a JSON call named `func`, `result` holding the
+/// document from `crate::into_c_string`: the document itself or null on
+/// failure, or the envelope around either with `result_envelope` on.
+pub(crate) fn finish(func: &str, result: Result<*mut c_char, VpxError>) -> *mut c_char {
+    match &result {
+        Err(e) if e.code == VpxErrorCode::NotModified => log_debug!("{}: {}", func, e),
+        Err(e) => log_error!("{}: {}", func, e),
+        Ok(_) => {}
+    }
+    if !config::get().result_envelope {
+        return result.unwrap_or(std::ptr::null_mut());
+    }
+    let envelope = match result {
+        Ok(ptr) => {
+            // Made by `into_c_string` just now, so ours to take back.
+            crate::allocations::untrack(ptr);
+            let data = unsafe { CString::from_raw(ptr) }.into_string().unwrap_or_default();
+            format!(r#"{{"ok":true,"error":null,"data":{}}}"#, data)
+        }
+        Err(e) => json!({ "ok": false, "error": { "code": e.code as i32, "message": e.message }, "data": null })
+            .to_string(),
+    };
+    crate::into_c_string(envelope).unwrap_or(std::ptr::null_mut())
+}
+
+/// `finish` for the `_with_len` calls, `result` holding the document and
+/// its length from `crate::into_sized_c_string`: also stores the byte
+/// length of what is returned (no NUL, 0 for null) in `out_len`
+/// (nullable).
+///
+/// # Safety
+/// `out_len` must be null or valid for a write.
+pub(crate) unsafe fn finish_with_len(
+    func: &str,
+    result: Result<(*mut c_char, usize), VpxError>,
+    out_len: *mut usize,
+) -> *mut c_char {
+    let (made, len) = result.as_ref().map_or((std::ptr::null_mut(), 0), |&sized| sized);
+    let ptr = finish(func, result.map(|(ptr, _)| ptr));
+    if let Some(out) = unsafe { out_len.as_mut() } {
+        *out = match ptr {
+            ptr if ptr.is_null() => 0,
+            ptr if ptr == made => len,
+            // An envelope, made by `finish` around the document or error.
+            ptr => unsafe { CStr::from_ptr(ptr) }.to_bytes().len(),
+        };
+    }
+    ptr
+}
@@ -0,0 +1,63 @@
+//! Error codes shared by every entry point that reports failures.
+
+use std::fmt;
+
+/// Why a call failed. Returned as a plain `int32_t` over the C ABI.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VpxErrorCode {
+    /// No error.
+    Ok = 0,
+    /// A required pointer argument was null.
+    NullArgument = 1,
+    /// The path is not valid UTF-8.
+    InvalidUtf8Path = 2,
+    /// The file does not exist.
+    FileNotFound = 3,
+    /// The file exists but could not be opened as a VPX (CFB) file.
+    OpenFailed = 4,
+    /// A stream inside the VPX file could not be read or parsed.
+    ParseFailed = 5,
+    /// The result could not be handed back as a C string (e.g. it contains a NUL byte).
+    AllocFailed = 6,
+    /// A panic was caught inside the wrapper.
+    Panic = 7,
+    /// The operation was cancelled before it finished.
+    Cancelled = 8,
+    /// An argument was present but not usable (bad JSON, unknown kind, ...).
+    InvalidArgument = 9,
+    /// A configured limit was hit (e.g. `max_file_size_mb`).
+    LimitExceeded = 10,
+    /// The file still has the content hash the caller already knew; nothing
+    /// was parsed. Not a failure as such.
+    NotModified = 11,
+    /// The call's `timeout_ms` ran out before the read finished.
+    Timeout = 12,
+    /// Another program (VPX playing the table) has the file open, so it was
+    /// not changed.
+    FileInUse = 13,
+    /// The call would write to disk and the wrapper is in `safe_mode`.
+    PermissionDenied = 14,
+}
+
+/// Internal error type: a code for the C side plus a message for the logs.
+#[derive(Debug, Clone)]
+pub(crate) struct VpxError {
+    pub code: VpxErrorCode,
+    pub message: String,
+}
+
+impl VpxError {
+    pub fn new(code: VpxErrorCode, message: impl Into<String>) -> Self {
+        VpxError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for VpxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
@@ -0,0 +1,564 @@
+//! The table events a script handles and what they call.
+//!
+//! VPX raises `Init`, `KeyDown`, `KeyUp`... on the table object and calls
+//! the script's `<Table>_<Event>` subs; PinMAME tables also route each
+//! solenoid to the code named in `SolCallback(n) = "..."`. Following the
+//! calls from `KeyDown` tells whether the table reacts to the plunger,
+//! flipper and nudge keys at all, directly or through the core scripts'
+//! `vpmKeyDown`, before a missing launch gets blamed on the cabinet's key
+//! encoder.
+
+use std::collections::{HashMap, HashSet};
+use std::os::raw::c_char;
+use std::path::Path;
+
+use serde_json::{json, Value};
+use vpin::vpx::gameitem::GameItemEnum;
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::options::CallOptions;
+use crate::vbs::{self, Procedure, Statement, Token};
+
+/// Events VPX raises on the table object.
+const TABLE_EVENTS: &[&str] = &["Init", "Exit", "KeyDown", "KeyUp", "Paused", "UnPaused", "MusicDone", "OptionEvent"];
+
+/// Arrays the core scripts read solenoid handlers from.
+const SOLENOID_ARRAYS: &[&str] = &["SolCallback", "SolModCallback", "SolMCallback"];
+
+/// Words that start a statement without calling anything.
+const KEYWORDS: &str = "and call case const dim do each else elseif end erase exit for function if loop me new next \
+    not on option or private public randomize redim select set stop sub then wend while with";
+
+/// What the key handlers respond to, as names they mention (lowercase).
+const KEYS: &[(&str, &[&str])] = &[
+    ("plunger", &["plungerkey"]),
+    ("left_flipper", &["leftflipperkey"]),
+    ("right_flipper", &["rightflipperkey"]),
+    ("nudge", &["lefttiltkey", "righttiltkey", "centertiltkey", "mechanicaltilt", "nudge"]),
+    ("start", &["startgamekey"]),
+    ("coin", &["addcreditkey", "addcreditkey2"]),
+];
+
+/// The core scripts' key handler, which covers flippers, nudge, start and
+/// coins for ROM tables (but not the plunger).
+const VPM_KEY_DOWN: &str = "vpmkeydown";
+
+struct Graph<'a> {
+    statements: &'a [Statement],
+    procedures: &'a [Procedure],
+    /// Script-level procedures by lowercase name; class methods aren't
+    /// callable by bare name.
+    by_name: HashMap<String, usize>,
+}
+
+/// True when the statement from `tokens` on assigns to its first name
+/// (`x = 1`, `x(2) = 1`) rather than calling it.
+fn is_assignment(tokens: &[Token]) -> bool {
+    let mut rest = &tokens[1..];
+    if rest.first().is_some_and(|t| t.is_symbol("(")) {
+        let mut depth = 0;
+        let close = rest.iter().position(|t| {
+            if t.is_symbol("(") {
+                depth += 1;
+            } else if t.is_symbol(")") {
+                depth -= 1;
+            }
+            depth == 0
+        });
+        rest = close.map_or(&[][..], |close| &rest[close + 1..]);
+    }
+    rest.first().is_some_and(|t| t.is_symbol("=") || t.is_symbol("."))
+}
+
+impl<'a> Graph<'a> {
+    fn new(statements: &'a [Statement], procedures: &'a [Procedure]) -> Self {
+        let by_name = procedures
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.class.is_none())
+            .map(|(i, p)| (p.name.to_ascii_lowercase(), i))
+            .collect();
+        Graph { statements, procedures, by_name }
+    }
+
+    fn body(&self, procedure: usize) -> &'a [Statement] {
+        let body = &self.procedures[procedure].body;
+        &self.statements[body.start.min(self.statements.len())..body.end.min(self.statements.len())]
+    }
+
+    /// The script's procedures `procedure` calls, in order, and the names
+    /// it calls that the script doesn't define (core script and VPX
+    /// functions, mostly).
+    fn calls(&self, procedure: usize) -> (Vec<usize>, Vec<String>) {
+        let own_name = self.procedures[procedure].name.to_ascii_lowercase();
+        let mut calls = Vec::new();
+        let mut external: Vec<String> = Vec::new();
+        for statement in self.body(procedure) {
+            let tokens = &statement.tokens;
+            for (k, token) in tokens.iter().enumerate() {
+                let Token::Ident(name) = token else { continue };
+                let lower = name.to_ascii_lowercase();
+                let member = k > 0 && tokens[k - 1].is_symbol(".");
+                if member || (lower == own_name && is_assignment(&tokens[k..])) {
+                    continue;
+                }
+                if let Some(&callee) = self.by_name.get(&lower) {
+                    if !calls.contains(&callee) {
+                        calls.push(callee);
+                    }
+                    continue;
+                }
+                // Anything else only counts in command position.
+                let command =
+                    k == 0 || tokens[k - 1].is("then") || tokens[k - 1].is("else") || tokens[k - 1].is("call");
+                if command
+                    && !KEYWORDS.split_whitespace().any(|k| k == lower)
+                    && !is_assignment(&tokens[k..])
+                    && !external.iter().any(|e| e.eq_ignore_ascii_case(name))
+                {
+                    external.push(name.clone());
+                }
+            }
+        }
+        (calls, external)
+    }
+
+    /// Every procedure reachable from `start`, itself included.
+    fn reachable(&self, start: &[usize]) -> Vec<usize> {
+        let mut seen: Vec<usize> = start.to_vec();
+        let mut i = 0;
+        while i < seen.len() {
+            for callee in self.calls(seen[i]).0 {
+                if !seen.contains(&callee) {
+                    seen.push(callee);
+                }
+            }
+            i += 1;
+        }
+        seen
+    }
+
+    /// Lowercase names mentioned anywhere in `procedures`.
+    fn names(&self, procedures: &[usize]) -> HashSet<String> {
+        procedures
+            .iter()
+            .flat_map(|&p| self.body(p))
+            .flat_map(|s| &s.tokens)
+            .filter_map(Token::ident)
+            .map(str::to_ascii_lowercase)
+            .collect()
+    }
+
+    /// Adds `calls` and `external_calls` of `procedure` to `entry`.
+    fn add_calls(&self, entry: &mut Value, procedure: usize) {
+        let (calls, external) = self.calls(procedure);
+        entry["calls"] = json!(calls.iter().map(|&c| &self.procedures[c].name).collect::<Vec<_>>());
+        entry["external_calls"] = json!(external);
+    }
+}
+
+/// The table event a procedure handles, as `(object, event)`.
+fn table_event(procedure: &Procedure) -> Option<(&str, &'static str)> {
+    if procedure.class.is_some() || procedure.kind != "sub" {
+        return None;
+    }
+    let (object, event) = procedure.name.rsplit_once('_')?;
+    let event = *TABLE_EVENTS.iter().find(|e| e.eq_ignore_ascii_case(event))?;
+    Some((object, event)).filter(|(object, _)| !object.is_empty())
+}
+
+/// `SolCallback(n) = "Handler"` assignments, as `(array, solenoid, handler, line)`.
+fn solenoid_callbacks(statements: &[Statement]) -> Vec<(String, Value, String, usize)> {
+    statements
+        .iter()
+        .filter_map(|statement| match statement.tokens.as_slice() {
+            [Token::Ident(array), open, rest @ ..]
+                if open.is_symbol("(") && SOLENOID_ARRAYS.iter().any(|a| a.eq_ignore_ascii_case(array)) =>
+            {
+                let close = rest.iter().position(|t| t.is_symbol(")"))?;
+                match &rest[close + 1..] {
+                    [eq, Token::Str(handler), ..] if eq.is_symbol("=") && !handler.trim().is_empty() => Some((
+                        array.clone(),
+                        vbs::literal_value(&rest[..close]),
+                        handler.trim().to_string(),
+                        statement.line,
+                    )),
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn events_report(code: &str, table_name: &str, plungers: usize) -> Value {
+    let statements = vbs::tokenize(code);
+    let outline = vbs::outline(&statements);
+    let graph = Graph::new(&statements, &outline.procedures);
+
+    let mut events = Vec::new();
+    let mut key_down = Vec::new();
+    let mut key_up = Vec::new();
+    for (i, procedure) in outline.procedures.iter().enumerate() {
+        let Some((object, event)) = table_event(procedure) else { continue };
+        match event {
+            "KeyDown" => key_down.push(i),
+            "KeyUp" => key_up.push(i),
+            _ => {}
+        }
+        let mut entry = json!({
+            "name": procedure.name,
+            "object": object,
+            "event": event,
+            "on_table": object.eq_ignore_ascii_case(table_name),
+            "line": procedure.start_line,
+        });
+        graph.add_calls(&mut entry, i);
+        events.push(entry);
+    }
+
+    let callbacks: Vec<Value> = solenoid_callbacks(&statements)
+        .into_iter()
+        .map(|(array, solenoid, handler, line)| {
+            // The handler is code: a sub name, `object.Method` or a call
+            // with leading arguments; the state is appended to it.
+            let first: String = handler.chars().take_while(|c| c.is_ascii_alphanumeric() || *c == '_').collect();
+            let procedure = graph.by_name.get(&first.to_ascii_lowercase()).copied();
+            let mut entry = json!({
+                "array": array,
+                "solenoid": solenoid,
+                "handler": handler,
+                "defined": procedure.is_some(),
+                "line": line,
+            });
+            if let Some(p) = procedure {
+                graph.add_calls(&mut entry, p);
+            }
+            entry
+        })
+        .collect();
+
+    let down = graph.names(&graph.reachable(&key_down));
+    let up = graph.names(&graph.reachable(&key_up));
+    let vpm = down.contains(VPM_KEY_DOWN);
+    let mut keys = json!({
+        "key_down": !key_down.is_empty(),
+        "key_up": !key_up.is_empty(),
+        "uses_vpm_keys": vpm,
+        "plunger_release": up.contains("plungerkey"),
+    });
+    for (key, names) in KEYS {
+        let direct = names.iter().any(|n| down.contains(*n));
+        keys[*key] = json!(direct || (vpm && *key != "plunger"));
+    }
+
+    let mut warnings = Vec::new();
+    if key_down.is_empty() {
+        warnings.push("The script has no KeyDown event; no key will do anything.".to_string());
+    } else {
+        if plungers > 0 && keys["plunger"] != true {
+            warnings.push("The table has a plunger but KeyDown never checks PlungerKey.".to_string());
+        }
+        if plungers > 0 && keys["plunger"] == true && !keys["plunger_release"].as_bool().unwrap_or(false) {
+            warnings.push("KeyUp never checks PlungerKey, so the plunger is never released.".to_string());
+        }
+        if keys["nudge"] != true {
+            warnings.push("KeyDown ignores the nudge keys.".to_string());
+        }
+        if keys["left_flipper"] != true || keys["right_flipper"] != true {
+            warnings.push("KeyDown ignores at least one flipper key.".to_string());
+        }
+    }
+
+    json!({
+        "table": table_name,
+        "procedures": outline.procedures.len(),
+        "plungers": plungers,
+        "events": events,
+        "solenoid_callbacks": callbacks,
+        "keys": keys,
+        "warnings": warnings,
+    })
+}
+
+/// Key constants VPX defines for scripts, with a label and whether they
+/// are among the standard controls every cabinet has.
+const KEY_NAMES: &[(&str, &str, bool)] = &[
+    ("LeftFlipperKey", "Left flipper", true),
+    ("RightFlipperKey", "Right flipper", true),
+    ("StagedLeftFlipperKey", "Left staged flipper", true),
+    ("StagedRightFlipperKey", "Right staged flipper", true),
+    ("LeftTiltKey", "Nudge left", true),
+    ("RightTiltKey", "Nudge right", true),
+    ("CenterTiltKey", "Nudge forward", true),
+    ("MechanicalTilt", "Tilt bob", true),
+    ("PlungerKey", "Plunger", true),
+    ("StartGameKey", "Start", true),
+    ("AddCreditKey", "Coin", true),
+    ("AddCreditKey2", "Coin 2", true),
+    ("ExitGame", "Exit", true),
+    ("LeftMagnaSave", "Left MagnaSave", false),
+    ("RightMagnaSave", "Right MagnaSave", false),
+    ("LockBarKey", "Lockbar (fire) button", false),
+];
+
+/// The key behind a DirectInput scan code, which scripts compare `keycode`
+/// with for keys VPX has no name for.
+fn scan_code_name(code: i64) -> Option<String> {
+    let letter = |row: &str, first: i64| row.chars().nth((code - first) as usize).map(String::from);
+    let name = match code {
+        1 => "Esc",
+        2..=10 => return Some((code - 1).to_string()),
+        11 => "0",
+        12 => "-",
+        13 => "=",
+        14 => "Backspace",
+        15 => "Tab",
+        16..=25 => return letter("QWERTYUIOP", 16),
+        26 => "[",
+        27 => "]",
+        28 => "Enter",
+        29 => "Left Ctrl",
+        30..=38 => return letter("ASDFGHJKL", 30),
+        39 => ";",
+        40 => "'",
+        41 => "`",
+        42 => "Left Shift",
+        43 => "\\",
+        44..=50 => return letter("ZXCVBNM", 44),
+        51 => ",",
+        52 => ".",
+        53 => "/",
+        54 => "Right Shift",
+        55 => "Numpad *",
+        56 => "Left Alt",
+        57 => "Space",
+        58 => "Caps Lock",
+        59..=68 => return Some(format!("F{}", code - 58)),
+        71..=73 | 75..=77 | 79..=82 => {
+            return letter("789-456+1230", 71).map(|key| format!("Numpad {}", key));
+        }
+        74 => "Numpad -",
+        78 => "Numpad +",
+        83 => "Numpad .",
+        87 => "F11",
+        88 => "F12",
+        156 => "Numpad Enter",
+        157 => "Right Ctrl",
+        181 => "Numpad /",
+        184 => "Right Alt",
+        199 => "Home",
+        200 => "Up",
+        201 => "Page Up",
+        203 => "Left",
+        205 => "Right",
+        207 => "End",
+        208 => "Down",
+        209 => "Page Down",
+        210 => "Insert",
+        211 => "Delete",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// One key a handler compares its key code with.
+fn key_entry(token: &Token, event: &str, line: usize, outline: &vbs::Outline) -> Option<Value> {
+    let (key, code, label, standard) = match token {
+        Token::Number(n) => {
+            let code = vbs::literal_value(std::slice::from_ref(token)).as_i64()?;
+            (n.clone(), Some(code), scan_code_name(code), false)
+        }
+        Token::Ident(name) => match KEY_NAMES.iter().find(|(k, _, _)| k.eq_ignore_ascii_case(name)) {
+            Some((key, label, standard)) => (key.to_string(), None, Some(label.to_string()), *standard),
+            // A script constant holding a scan code, or a name the core
+            // scripts define (`keyFront`...).
+            None => {
+                let code = outline.constant(name).and_then(Value::as_i64);
+                (name.clone(), code, code.and_then(scan_code_name), false)
+            }
+        },
+        _ => return None,
+    };
+    Some(json!({ "key": key, "code": code, "label": label, "standard": standard, "event": event, "line": line }))
+}
+
+/// Keys compared with the key code parameter anywhere reachable from the
+/// `roots` handlers: `keycode = X`, `X = keycode` and `Case X` under
+/// `Select Case keycode`.
+fn key_checks(graph: &Graph, outline: &vbs::Outline, roots: &[usize], event: &str, found: &mut Vec<Value>) {
+    for procedure in graph.reachable(roots) {
+        let params: Vec<&str> = graph.procedures[procedure].params.iter().map(String::as_str).collect();
+        let is_param = |token: &Token| token.ident().is_some_and(|n| params.iter().any(|p| p.eq_ignore_ascii_case(n)));
+        // Whether each open `Select Case` is on the key code.
+        let mut selects: Vec<bool> = Vec::new();
+        for statement in graph.body(procedure) {
+            let tokens = &statement.tokens;
+            let mut keys: Vec<&Token> = Vec::new();
+            match tokens.as_slice() {
+                [select, case, subject, ..] if select.is("select") && case.is("case") => {
+                    selects.push(is_param(subject));
+                    continue;
+                }
+                [end, select, ..] if end.is("end") && select.is("select") => {
+                    selects.pop();
+                    continue;
+                }
+                [case, rest @ ..] if case.is("case") => {
+                    if selects.last() == Some(&true) && !rest.first().is_some_and(|t| t.is("else")) {
+                        keys.extend(split_single(rest));
+                    }
+                }
+                _ => {
+                    for (k, window) in tokens.windows(3).enumerate() {
+                        let after = tokens.get(k + 3);
+                        let member = after.is_some_and(|t| t.is_symbol(".") || t.is_symbol("("));
+                        match window {
+                            [a, eq, b] if eq.is_symbol("=") && is_param(a) && !member => keys.push(b),
+                            [b, eq, a]
+                                if eq.is_symbol("=") && is_param(a) && k > 0 && !tokens[k - 1].is_symbol(".") =>
+                            {
+                                keys.push(b)
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            // For one-line `If`s, what the key does.
+            let action = tokens.first().filter(|t| t.is("if")).and_then(|_| {
+                let then = tokens.iter().position(|t| t.is("then"))?;
+                let text = vbs::source_text(&tokens[then + 1..]);
+                (!text.is_empty()).then(|| text.chars().take(ACTION_LEN).collect::<String>())
+            });
+            for key in keys {
+                let Some(mut entry) = key_entry(key, event, statement.line, outline) else { continue };
+                let duplicate = found.iter().any(|f| {
+                    f["event"] == entry["event"]
+                        && f["key"].as_str().map(str::to_ascii_lowercase)
+                            == entry["key"].as_str().map(str::to_ascii_lowercase)
+                });
+                if !duplicate {
+                    entry["action"] = json!(action);
+                    found.push(entry);
+                }
+            }
+        }
+    }
+}
+
+/// Longest `action` reported for a key.
+const ACTION_LEN: usize = 80;
+
+/// The single-token items of a `Case` list.
+fn split_single(tokens: &[Token]) -> Vec<&Token> {
+    vbs::split_list(tokens).into_iter().filter_map(|item| if item.len() == 1 { item.first() } else { None }).collect()
+}
+
+fn keys_report(code: &str) -> Value {
+    let statements = vbs::tokenize(code);
+    let outline = vbs::outline(&statements);
+    let graph = Graph::new(&statements, &outline.procedures);
+    let handlers = |event: &str| -> Vec<usize> {
+        outline
+            .procedures
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| table_event(p).is_some_and(|(_, e)| e == event))
+            .map(|(i, _)| i)
+            .collect()
+    };
+    let key_down = handlers("KeyDown");
+    let mut keys = Vec::new();
+    key_checks(&graph, &outline, &key_down, "KeyDown", &mut keys);
+    key_checks(&graph, &outline, &handlers("KeyUp"), "KeyUp", &mut keys);
+
+    let mut extra: Vec<Value> = Vec::new();
+    for key in keys.iter().filter(|k| k["standard"] == false) {
+        let name = if key["label"].is_string() { key["label"].clone() } else { key["key"].clone() };
+        if !extra.contains(&name) {
+            extra.push(name);
+        }
+    }
+    json!({
+        "uses_vpm_keys": graph.names(&graph.reachable(&key_down)).contains(VPM_KEY_DOWN),
+        "keys": keys,
+        "extra": extra,
+    })
+}
+
+fn read_events(path: &Path, options: &CallOptions) -> Result<Value, VpxError> {
+    crate::catch_panic(path, || {
+        let parse_error = |what: &str, e: std::io::Error| {
+            VpxError::new(VpxErrorCode::ParseFailed, format!("Failed to read {} for '{}': {}", what, path.display(), e))
+        };
+        let mut vpx_file = crate::open_vpx(path, options)?;
+        let gamedata = vpx_file.read_gamedata().map_err(|e| parse_error("gamedata", e))?;
+        let items = vpx_file.read_gameitems().map_err(|e| parse_error("game items", e))?;
+        let plungers = items.iter().filter(|item| matches!(item, GameItemEnum::Plunger(_))).count();
+        Ok(events_report(&gamedata.code.string, &gamedata.name, plungers))
+    })
+}
+
+/// The table events the script of the table at `vpx_file_path` handles
+/// and what they call: `{"table", "procedures", "plungers", "events":
+/// [{"name", "object", "event", "on_table", "line", "calls",
+/// "external_calls"}], "solenoid_callbacks": [{"array", "solenoid",
+/// "handler", "defined", "line", "calls"?, "external_calls"?}], "keys":
+/// {"key_down", "key_up", "uses_vpm_keys", "plunger", "plunger_release",
+/// "left_flipper", "right_flipper", "nudge", "start", "coin"}, "warnings"}`.
+/// `calls` are the script's own subs and functions; `external_calls` the
+/// rest (core script and VPX calls). A key counts as handled when anything
+/// reachable from `KeyDown` checks it, or `vpmKeyDown` does.
+///
+/// `options_json` is as for the `_ex` calls and may be null. Returns null
+/// on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_script_events(
+    vpx_file_path: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    let result = unsafe { CallOptions::from_c(options_json) }
+        .and_then(|options| Ok((unsafe { crate::path_from_c(vpx_file_path) }?, options)))
+        .and_then(|(path, options)| read_events(&path, &options))
+        .and_then(|events| crate::into_c_string(events.to_string()));
+
+    crate::envelope::finish("vpx_get_script_events", result)
+}
+
+fn read_keys(path: &Path, options: &CallOptions) -> Result<Value, VpxError> {
+    crate::catch_panic(path, || {
+        let mut vpx_file = crate::open_vpx(path, options)?;
+        let gamedata = vpx_file.read_gamedata().map_err(|e| {
+            VpxError::new(VpxErrorCode::ParseFailed, format!("Failed to read gamedata for '{}': {}", path.display(), e))
+        })?;
+        Ok(keys_report(&gamedata.code.string))
+    })
+}
+
+/// The keys the script of the table at `vpx_file_path` reacts to in
+/// `KeyDown`/`KeyUp` (and anything they call): `{"uses_vpm_keys", "keys":
+/// [{"key", "code", "label", "standard", "event", "line", "action"}],
+/// "extra"}`. `key` is a VPX key name (`LeftMagnaSave`), a script constant
+/// or a raw scan code; `code` the scan code when the script fixes it;
+/// `label` a readable name (`"Right MagnaSave"`, `"Q"`); `action` the code
+/// of a one-line `If` handling it. `extra` lists the labels of the keys
+/// beyond the standard cabinet controls, for control hints.
+///
+/// `options_json` is as for the `_ex` calls and may be null. Returns null
+/// on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_script_keys(vpx_file_path: *const c_char, options_json: *const c_char) -> *mut c_char {
+    let result = unsafe { CallOptions::from_c(options_json) }
+        .and_then(|options| Ok((unsafe { crate::path_from_c(vpx_file_path) }?, options)))
+        .and_then(|(path, options)| read_keys(&path, &options))
+        .and_then(|keys| crate::into_c_string(keys.to_string()));
+
+    crate::envelope::finish("vpx_get_script_keys", result)
+}
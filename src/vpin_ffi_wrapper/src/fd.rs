@@ -0,0 +1,101 @@
+//! File descriptor entry points (Unix).
+//!
+//! The frontend may want to open tables itself, with `O_NOATIME`, under a
+//! fanotify permission check or from a sandbox portal, and keep all of that
+//! in one place. These variants take the open descriptor instead of a path.
+//! The wrapper reads from its own duplicate, so the caller keeps and closes
+//! the descriptor it passed; the two share a file offset, which the read
+//! moves.
+
+use std::fs::File;
+use std::os::fd::{BorrowedFd, RawFd};
+use std::os::raw::c_char;
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::handle::{OpenTable, VpxHandle};
+use crate::options::CallOptions;
+
+/// A table opened from a duplicate of `fd`.
+///
+/// # Safety
+/// `fd` must be an open descriptor for the duration of the call,
+/// `options_json` null or a valid NUL-terminated string.
+unsafe fn open_fd(fd: RawFd, options_json: *const c_char) -> Result<OpenTable, VpxError> {
+    let options = unsafe { CallOptions::from_c(options_json) }?;
+    if fd < 0 {
+        return Err(VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid file descriptor {}.", fd)));
+    }
+    let name = format!("<fd {}>", fd);
+    let file = unsafe { BorrowedFd::borrow_raw(fd) }
+        .try_clone_to_owned()
+        .map(File::from)
+        .map_err(|e| VpxError::new(VpxErrorCode::OpenFailed, format!("Failed to duplicate {}: {}", name, e)))?;
+    OpenTable::from_file(&name, file, &options)
+}
+
+/// `vpx_get_table_info_as_json_err` on the `.vpx` file open as `fd`.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `fd` must be an open descriptor for the duration of the call,
+/// `options_json` null or a valid NUL-terminated string, `error_code` null
+/// or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_table_info_from_fd(
+    fd: RawFd,
+    options_json: *const c_char,
+    error_code: *mut i32,
+) -> *mut c_char {
+    let result = unsafe { open_fd(fd, options_json) }
+        .and_then(|mut table| table.table_info())
+        .and_then(|table_info| crate::into_c_string(table_info.to_string()));
+    unsafe { crate::set_error_code(error_code, &result) };
+
+    crate::envelope::finish("vpx_get_table_info_from_fd", result)
+}
+
+/// `vpx_get_gamedata_code_err` on the `.vpx` file open as `fd`.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// As for `vpx_get_table_info_from_fd`.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_gamedata_code_from_fd(
+    fd: RawFd,
+    options_json: *const c_char,
+    error_code: *mut i32,
+) -> *mut c_char {
+    let result = unsafe { open_fd(fd, options_json) }.and_then(|mut table| table.code()).and_then(crate::into_c_string);
+    unsafe { crate::set_error_code(error_code, &result) };
+
+    match result {
+        Ok(ptr) => ptr,
+        Err(e) => {
+            log_error!("vpx_get_gamedata_code_from_fd: {}", e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// `vpx_open_err` on the `.vpx` file open as `fd`. The handle holds its own
+/// duplicate until `vpx_close`, so `fd` can be closed right away.
+///
+/// Returns null on failure.
+///
+/// # Safety
+/// As for `vpx_get_table_info_from_fd`.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_open_fd(fd: RawFd, options_json: *const c_char, error_code: *mut i32) -> *mut VpxHandle {
+    let result = unsafe { open_fd(fd, options_json) };
+    unsafe { crate::set_error_code(error_code, &result) };
+
+    match result {
+        Ok(table) => VpxHandle::into_raw(table),
+        Err(e) => {
+            log_error!("vpx_open_fd: {}", e);
+            std::ptr::null_mut()
+        }
+    }
+}
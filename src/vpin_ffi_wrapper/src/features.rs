@@ -0,0 +1,133 @@
+//! Capability discovery.
+//!
+//! The frontend ships separately from this library and may find an older
+//! or newer build next to it. `VPX_FFI_ABI_VERSION` says whether the calls
+//! it knows still have the same shape; the feature flags here say which
+//! ones (and which behaviors) this build has, so it can hide what the
+//! library can't do instead of failing on a missing symbol. Flags are only
+//! ever added: a new capability gets one, and none is renamed or removed.
+
+use std::os::raw::c_char;
+
+use serde_json::json;
+
+/// What every build has.
+const FEATURES: &[&str] = &[
+    "table_info",
+    "gamedata_code",
+    "error_codes",
+    "call_options",
+    "table_info_fields",
+    "conditional_table_info",
+    "summary",
+    "string_lengths",
+    "wide_paths",
+    "byte_paths",
+    "memory_buffers",
+    "buffers",
+    "results",
+    "handles",
+    "script_paging",
+    "script_streaming",
+    "sanitize_nul",
+    "script_charset",
+    "jobs",
+    "job_polling",
+    "batch_scan",
+    "batch_call",
+    "progress",
+    "cancellation",
+    "timeouts",
+    "compression_gzip",
+    "result_envelope",
+    "log_callback",
+    "log_sinks",
+    "last_error",
+    "last_panic",
+    "allocation_tracking",
+    "hash_files",
+    "health",
+    "script_analysis",
+    "tags",
+    "language",
+    "compare",
+    "import",
+    "download_intake",
+    "split_archives",
+    "remove",
+    "move",
+    "file_in_use",
+    "orphans",
+    "playlists",
+    "filters",
+    "stats",
+    "flags",
+    "reviews",
+    "user_tags",
+    "saved_searches",
+    "attract",
+    "recent",
+    "library_bundle",
+    "roots",
+    "scan_metadata",
+    "scan_profiles",
+    "merge",
+    "watchdog",
+    "diagnostics",
+    "scan_journal",
+    "undo",
+    "transactions",
+    "safe_mode",
+    "physics",
+    "lighting",
+    "vr",
+    "textures",
+    "image_list",
+    "sounds",
+    "script_events",
+    "key_bindings",
+    "table_cards",
+    "rom_audit",
+    "b2s_audit",
+    "media_score",
+    "media_provenance",
+    "media_naming",
+    "titles",
+    "wheel",
+    "sort_keys",
+];
+
+/// The flags of this build, in the order above; platform and build option
+/// dependent ones last.
+fn features() -> Vec<&'static str> {
+    let mut features = FEATURES.to_vec();
+    if cfg!(unix) {
+        features.push("fd");
+    }
+    if cfg!(feature = "zstd") {
+        features.push("compression_zstd");
+    }
+    // Found at run time, so this one can come and go with the tool.
+    if crate::sevenzip::available() {
+        features.push("7z_packs");
+    }
+    features
+}
+
+/// What this build of the library can do: `{"abi_version",
+/// "wrapper_version", "features": ["handles", "buffers", "batch_scan",
+/// ...]}`. A frontend checks for the flags of the calls it wants before
+/// using them, and degrades gracefully when one is missing.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+#[no_mangle]
+pub extern "C" fn vpx_get_supported_features() -> *mut c_char {
+    let report = json!({
+        "abi_version": crate::VPX_FFI_ABI_VERSION,
+        "wrapper_version": env!("CARGO_PKG_VERSION"),
+        "features": features(),
+    });
+    let result = crate::into_c_string(report.to_string());
+
+    crate::envelope::finish("vpx_get_supported_features", result)
+}
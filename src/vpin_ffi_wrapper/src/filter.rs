@@ -0,0 +1,304 @@
+//! Smart-filter expressions over the table index.
+//!
+//! ```text
+//! manufacturer="Williams" AND year>=1990 AND has_b2s
+//! (title~"star" OR title~"trek") AND NOT has_pup
+//! ```
+//!
+//! A comparison is `field op value` with `=`, `!=`, `<`, `<=`, `>`, `>=` or
+//! `~` (contains); a bare field is true when it is set. `AND` binds tighter
+//! than `OR`, `NOT` tighter than both, keywords ignore case. Fields are the
+//! index's camelCase names, matched ignoring case and underscores (`has_b2s`
+//! finds `hasB2S`), plus the short names in [`ALIASES`] and the user's own
+//! data from the user store (`favorite`, `rating`, ...). Values compare the
+//! way playlist rules do, so both give the same answers.
+
+use std::cmp::Ordering;
+use std::os::raw::c_char;
+
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::playlist::{as_text, compare, is_present};
+use crate::{index, store};
+
+/// Short names for common index fields.
+const ALIASES: &[(&str, &str)] = &[
+    ("title", "bestTitle"),
+    ("manufacturer", "bestManufacturer"),
+    ("year", "bestYear"),
+    ("version", "bestVersion"),
+    ("rom", "romName"),
+    ("author", "tableAuthor"),
+    ("type", "tableType"),
+    ("players", "vpsPlayers"),
+    ("plays", "playCount"),
+    ("rating", "userRating"),
+    ("note", "userNote"),
+    ("tags", "userTags"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Value(Value),
+    Op(CmpOp),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+#[derive(Debug)]
+pub(crate) enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Set(String),
+    Compare(String, CmpOp, Value),
+}
+
+fn syntax_error(position: usize, message: &str) -> VpxError {
+    VpxError::new(VpxErrorCode::InvalidArgument, format!("Filter error at {}: {}", position, message))
+}
+
+fn tokenize(expression: &str) -> Result<Vec<(usize, Token)>, VpxError> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        match c {
+            _ if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' | ')' => {
+                tokens.push((start, if c == '(' { Token::Open } else { Token::Close }));
+                i += 1;
+            }
+            '"' | '\'' => {
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(syntax_error(start, "unterminated string")),
+                        Some('\\') if i + 1 < chars.len() => {
+                            text.push(chars[i + 1]);
+                            i += 2;
+                        }
+                        Some(&q) if q == c => {
+                            i += 1;
+                            break;
+                        }
+                        Some(&other) => {
+                            text.push(other);
+                            i += 1;
+                        }
+                    }
+                }
+                tokens.push((start, Token::Value(Value::String(text))));
+            }
+            '=' | '!' | '<' | '>' | '~' => {
+                let next = chars.get(i + 1).copied();
+                let (op, len) = match (c, next) {
+                    ('=', Some('=')) => (CmpOp::Eq, 2),
+                    ('=', _) => (CmpOp::Eq, 1),
+                    ('!', Some('=')) => (CmpOp::Ne, 2),
+                    ('<', Some('=')) => (CmpOp::Le, 2),
+                    ('<', _) => (CmpOp::Lt, 1),
+                    ('>', Some('=')) => (CmpOp::Ge, 2),
+                    ('>', _) => (CmpOp::Gt, 1),
+                    ('~', _) => (CmpOp::Contains, 1),
+                    _ => return Err(syntax_error(start, "expected '!='")),
+                };
+                tokens.push((start, Token::Op(op)));
+                i += len;
+            }
+            _ if c.is_alphanumeric() || c == '_' || c == '-' || c == '.' => {
+                while i < chars.len() && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '-' | '.')) {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                let token = match word.to_ascii_uppercase().as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => match word.parse::<f64>() {
+                        Ok(_) => Token::Value(serde_json::from_str(&word).unwrap_or(Value::String(word))),
+                        Err(_) => Token::Ident(word),
+                    },
+                };
+                tokens.push((start, token));
+            }
+            _ => return Err(syntax_error(start, &format!("unexpected '{}'", c))),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map(|(p, _)| *p).unwrap_or(self.end)
+    }
+
+    fn or(&mut self) -> Result<Expr, VpxError> {
+        let mut left = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, VpxError> {
+        let mut left = self.not()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            left = Expr::And(Box::new(left), Box::new(self.not()?));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Expr, VpxError> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, VpxError> {
+        let position = self.position();
+        match self.tokens.get(self.pos).map(|(_, t)| t.clone()) {
+            Some(Token::Open) => {
+                self.pos += 1;
+                let inner = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(syntax_error(self.position(), "expected ')'"));
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            Some(Token::Ident(field)) => {
+                self.pos += 1;
+                let Some(Token::Op(op)) = self.peek().cloned() else { return Ok(Expr::Set(field)) };
+                self.pos += 1;
+                let value = match self.tokens.get(self.pos).map(|(_, t)| t.clone()) {
+                    Some(Token::Value(v)) => v,
+                    // Bare words are text (`manufacturer=Stern`), true/false are booleans.
+                    Some(Token::Ident(word)) => match word.to_ascii_lowercase().as_str() {
+                        "true" => Value::Bool(true),
+                        "false" => Value::Bool(false),
+                        _ => Value::String(word),
+                    },
+                    _ => return Err(syntax_error(self.position(), "expected a value")),
+                };
+                self.pos += 1;
+                Ok(Expr::Compare(field, op, value))
+            }
+            Some(_) => Err(syntax_error(position, "expected a field or '('")),
+            None => Err(syntax_error(position, "unexpected end of filter")),
+        }
+    }
+}
+
+/// Parses a filter expression.
+pub(crate) fn parse(expression: &str) -> Result<Expr, VpxError> {
+    let mut parser = Parser { tokens: tokenize(expression)?, pos: 0, end: expression.chars().count() };
+    let expr = parser.or()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(syntax_error(parser.position(), "expected AND, OR or end of filter"));
+    }
+    Ok(expr)
+}
+
+fn field<'a>(row: &'a Value, name: &str) -> &'a Value {
+    let name = ALIASES.iter().find(|(alias, _)| alias.eq_ignore_ascii_case(name)).map_or(name, |(_, f)| f);
+    if let Some(value) = row.get(name) {
+        return value;
+    }
+    let wanted: String = name.chars().filter(|c| *c != '_').collect();
+    row.as_object()
+        .and_then(|o| o.iter().find(|(key, _)| key.eq_ignore_ascii_case(&wanted)))
+        .map_or(&Value::Null, |(_, value)| value)
+}
+
+/// Whether an index row passes the filter.
+pub(crate) fn matches(row: &Value, expr: &Expr) -> bool {
+    match expr {
+        Expr::And(a, b) => matches(row, a) && matches(row, b),
+        Expr::Or(a, b) => matches(row, a) || matches(row, b),
+        Expr::Not(inner) => !matches(row, inner),
+        Expr::Set(name) => is_present(field(row, name)),
+        Expr::Compare(name, op, value) => {
+            let field = field(row, name);
+            let order = compare(field, value);
+            match op {
+                CmpOp::Eq => order == Ordering::Equal,
+                CmpOp::Ne => order != Ordering::Equal,
+                CmpOp::Lt => is_present(field) && order == Ordering::Less,
+                CmpOp::Le => is_present(field) && order != Ordering::Greater,
+                CmpOp::Gt => is_present(field) && order == Ordering::Greater,
+                CmpOp::Ge => is_present(field) && order != Ordering::Less,
+                CmpOp::Contains => as_text(field).contains(&as_text(value)),
+            }
+        }
+    }
+}
+
+fn filter_index(index_doc: &Value, expression: &str) -> Result<Value, VpxError> {
+    let expr = parse(expression)?;
+    let tables: Vec<&Value> = index::tables(index_doc)
+        .iter()
+        .filter(|row| matches(row, &expr))
+        .map(|row| &row["vpxFile"])
+        .collect();
+    Ok(json!({ "count": tables.len(), "tables": tables }))
+}
+
+/// Filters the frontend's index (`{"tables": [...]}`) with a smart-filter
+/// expression such as `manufacturer="Williams" AND year>=1990 AND has_b2s`.
+///
+/// Returns `{"count", "tables": [vpxFile...]}` in index order, or null on
+/// failure (a syntax error names its position). Release with
+/// `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_filter_index(index_json: *const c_char, expression: *const c_char) -> *mut c_char {
+    let result = unsafe { crate::str_from_c(index_json, "index") }
+        .and_then(index::parse)
+        .and_then(|mut index_doc| {
+            store::annotate_index(&mut index_doc);
+            Ok((index_doc, unsafe { crate::str_from_c(expression, "expression") }?))
+        })
+        .and_then(|(index_doc, expression)| filter_index(&index_doc, expression))
+        .and_then(|filtered| crate::into_c_string(filtered.to_string()));
+
+    crate::envelope::finish("vpx_filter_index", result)
+}
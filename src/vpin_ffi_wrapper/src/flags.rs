@@ -0,0 +1,133 @@
+//! User flags per table, kept in the user store's `flags` section:
+//! `{"favorite", "hidden", "kid_safe"}`. Every view and the CLI read the
+//! same store, and scan metadata carries them, so a table hidden in one
+//! place is hidden everywhere.
+
+use std::os::raw::c_char;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::store;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct TableFlags {
+    pub favorite: bool,
+    pub hidden: bool,
+    pub kid_safe: bool,
+}
+
+/// A change to some flags; the others keep their value.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct FlagChange {
+    favorite: Option<bool>,
+    hidden: Option<bool>,
+    kid_safe: Option<bool>,
+}
+
+const KNOWN_KEYS: &[&str] = &["favorite", "hidden", "kid_safe"];
+
+fn flags_in(store_doc: &Value, vpx_file: &str) -> TableFlags {
+    store_doc["tables"][vpx_file].get("flags").and_then(|flags| TableFlags::deserialize(flags).ok()).unwrap_or_default()
+}
+
+/// A table's flags for scan results: all unset without a user store, or
+/// when it cannot be read (the scan itself does not depend on it).
+pub(crate) fn of(vpx_path: &Path) -> TableFlags {
+    if !store::configured() {
+        return TableFlags::default();
+    }
+    match store::load() {
+        Ok(store_doc) => flags_in(&store_doc, &vpx_path.to_string_lossy()),
+        Err(e) => {
+            log_warn!("Flags of {}: {}", vpx_path.display(), e);
+            TableFlags::default()
+        }
+    }
+}
+
+/// Index fields for a table's flags: `favorite`, `hidden` and `kidSafe`.
+pub(crate) fn row_fields(entry: &Value) -> Vec<(&'static str, Value)> {
+    let flags = entry.get("flags").and_then(|flags| TableFlags::deserialize(flags).ok()).unwrap_or_default();
+    vec![("favorite", json!(flags.favorite)), ("hidden", json!(flags.hidden)), ("kidSafe", json!(flags.kid_safe))]
+}
+
+fn set(vpx_file: &str, change: &FlagChange) -> Result<TableFlags, VpxError> {
+    store::update(|store_doc| {
+        let section = store::section_mut(store_doc, vpx_file, "flags");
+        let mut flags = TableFlags::deserialize(&*section).unwrap_or_default();
+        flags.favorite = change.favorite.unwrap_or(flags.favorite);
+        flags.hidden = change.hidden.unwrap_or(flags.hidden);
+        flags.kid_safe = change.kid_safe.unwrap_or(flags.kid_safe);
+        *section = json!(flags);
+        Ok(flags)
+    })
+}
+
+/// Every table with a flag set, keyed by `.vpx` path.
+fn flagged(store_doc: &Value) -> Map<String, Value> {
+    store::tables(store_doc)
+        .into_iter()
+        .flatten()
+        .filter_map(|(vpx_file, entry)| {
+            let flags = TableFlags::deserialize(entry.get("flags")?).ok()?;
+            (flags != TableFlags::default()).then(|| (vpx_file.clone(), json!(flags)))
+        })
+        .collect()
+}
+
+/// A table's flags, `{"favorite", "hidden", "kid_safe"}`, all false for a
+/// table never flagged.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `vpx_file_path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_table_flags(vpx_file_path: *const c_char) -> *mut c_char {
+    let result = unsafe { crate::str_from_c(vpx_file_path, "path") }
+        .and_then(|path| Ok(flags_in(&store::load()?, path)))
+        .and_then(|flags| crate::into_c_string(json!(flags).to_string()));
+
+    crate::envelope::finish("vpx_get_table_flags", result)
+}
+
+/// Sets the flags given in `flags_json` (`{"favorite": true}`); the others
+/// keep their value. Returns a `VpxErrorCode`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_set_table_flags(vpx_file_path: *const c_char, flags_json: *const c_char) -> i32 {
+    let result = unsafe { crate::str_from_c(vpx_file_path, "path") }.and_then(|path| {
+        if flags_json.is_null() {
+            return Err(VpxError::new(VpxErrorCode::NullArgument, "flags is null"));
+        }
+        let change: FlagChange = unsafe { crate::library::options_from_c(flags_json, KNOWN_KEYS) }?;
+        crate::oplog::record("flags", format!("Set flags of {}", path), || set(path, &change))
+    });
+
+    match result {
+        Ok(_) => VpxErrorCode::Ok as i32,
+        Err(e) => {
+            log_error!("vpx_set_table_flags: {}", e);
+            e.code as i32
+        }
+    }
+}
+
+/// The flags of every table with one set, `{"<vpx path>": {"favorite",
+/// "hidden", "kid_safe"}}`, for views that filter the whole library.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+#[no_mangle]
+pub extern "C" fn vpx_list_table_flags() -> *mut c_char {
+    let result =
+        store::load().and_then(|store_doc| crate::into_c_string(Value::Object(flagged(&store_doc)).to_string()));
+
+    crate::envelope::finish("vpx_list_table_flags", result)
+}
@@ -0,0 +1,438 @@
+//! Open tables.
+//!
+//! Every path-taking query opens the `.vpx` and parses its storage
+//! directory again, so a scan asking for the table info and then the script
+//! pays that twice per table. An `OpenTable` keeps the file open and
+//! remembers what it read; `vpx_open` hands one to the frontend as an
+//! opaque `VpxHandle` for the `vpx_handle_*` queries.
+
+use std::io;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use serde_json::{json, Value};
+use vpin::vpx::VpxFile;
+
+use crate::buffer::VpxBuffer;
+use crate::error::{VpxError, VpxErrorCode};
+use crate::options::CallOptions;
+use crate::registry::{self, Registry};
+use crate::result::VpxResult;
+use crate::source::VpxSource;
+
+/// What `sanitize_nul` puts in place of a NUL byte in a script.
+const NUL_REPLACEMENT: &str = "\u{FFFD}";
+
+/// A `.vpx` file opened once, with what was read from it so far.
+pub(crate) struct OpenTable {
+    path: PathBuf,
+    options: CallOptions,
+    file: VpxFile<VpxSource>,
+    table_info: Option<Value>,
+    code: Option<String>,
+    /// NUL bytes `sanitize_nul` replaced in `code`.
+    nul_replaced: usize,
+}
+
+impl OpenTable {
+    pub(crate) fn open(path: &Path, options: &CallOptions) -> Result<OpenTable, VpxError> {
+        let file = crate::catch_panic(path, || crate::open_vpx(path, options))?;
+        Ok(OpenTable { path: path.to_path_buf(), options: options.clone(), file, table_info: None, code: None, nul_replaced: 0 })
+    }
+
+    /// A table read from a file the caller opened; `name` stands in for the
+    /// path in messages.
+    pub(crate) fn from_file(name: &str, file: std::fs::File, options: &CallOptions) -> Result<OpenTable, VpxError> {
+        let path = PathBuf::from(name);
+        let file = crate::catch_panic(&path, || crate::open_vpx_file(&path, file, options))?;
+        Ok(OpenTable { path, options: options.clone(), file, table_info: None, code: None, nul_replaced: 0 })
+    }
+
+    /// A table read from a copy of `data` instead of a file; `name` stands
+    /// in for the path in messages.
+    pub(crate) fn from_bytes(name: &str, data: Vec<u8>, options: &CallOptions) -> Result<OpenTable, VpxError> {
+        let path = PathBuf::from(name);
+        let file = crate::catch_panic(&path, || {
+            VpxFile::open(VpxSource::Memory(io::Cursor::new(data))).map_err(|e| {
+                VpxError::new(VpxErrorCode::OpenFailed, format!("Failed to open {} as a VPX file: {}", name, e))
+            })
+        })?;
+        Ok(OpenTable { path, options: options.clone(), file, table_info: None, code: None, nul_replaced: 0 })
+    }
+
+    /// The path it was opened from (or the name standing in for one).
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The table info document (`vpx_get_table_info_as_json`).
+    pub(crate) fn table_info(&mut self) -> Result<Value, VpxError> {
+        if let Some(table_info) = &self.table_info {
+            return Ok(self.options.select_fields(table_info.clone()));
+        }
+        let (path, options, file) = (&self.path, &self.options, &mut self.file);
+        crate::cancel::check(path)?;
+        let table_info = crate::catch_panic(path, || {
+            // vpin unwraps some stream lookups, so a missing TableInfo storage
+            // panics instead of erroring; both count as an unreadable stream.
+            let read = crate::panics::catch(|| file.read_tableinfo())
+                .unwrap_or_else(|_| Err(io::Error::other("TableInfo stream missing or corrupt")));
+            match read {
+                Ok(table_info) => Ok(crate::table_info_to_json(table_info)),
+                Err(e) => {
+                    let message = format!("Failed to read table info for '{}': {}", path.display(), e);
+                    if !options.lenient {
+                        return Err(VpxError::new(VpxErrorCode::ParseFailed, message));
+                    }
+                    log_warn!("{}", message);
+                    let mut json_object = crate::table_info_to_json(Default::default());
+                    json_object["warnings"] = json!([message]);
+                    Ok(json_object)
+                }
+            }
+        })
+        .inspect_err(|e| crate::diagnostics::capture(path, crate::diagnostics::TABLE_INFO_STREAMS, e))?;
+        self.table_info = Some(table_info.clone());
+        Ok(self.options.select_fields(table_info))
+    }
+
+    /// The script embedded in the GameData stream.
+    pub(crate) fn code(&mut self) -> Result<String, VpxError> {
+        self.code_ref().map(str::to_string)
+    }
+
+    /// `code` without a copy, for reading it piece by piece.
+    pub(crate) fn code_ref(&mut self) -> Result<&str, VpxError> {
+        if self.code.is_some() {
+            return Ok(self.code.as_deref().unwrap_or_default());
+        }
+        let (path, file, options) = (&self.path, &mut self.file, &self.options);
+        crate::cancel::check(path)?;
+        let code = crate::catch_panic(path, || {
+            let gamedata = file.read_gamedata().map_err(|e| {
+                VpxError::new(
+                    VpxErrorCode::ParseFailed,
+                    format!("Failed to read gamedata for '{}': {}", path.display(), e),
+                )
+            })?;
+            Ok(options.charset.decode(gamedata.code))
+        })
+        .inspect_err(|e| crate::diagnostics::capture(path, crate::diagnostics::GAMEDATA_STREAMS, e))?;
+        let code = match self.options.sanitize_nul && code.contains('\0') {
+            true => {
+                self.nul_replaced = code.matches('\0').count();
+                log_warn!("Replaced {} NUL byte(s) in the script of '{}'", self.nul_replaced, path.display());
+                code.replace('\0', NUL_REPLACEMENT)
+            }
+            false => code,
+        };
+        Ok(self.code.insert(code))
+    }
+
+    /// The script as a `{"code"}` document, with `"nul_replaced"` added
+    /// when `sanitize_nul` had to replace any.
+    pub(crate) fn code_document(&mut self) -> Result<String, VpxError> {
+        let mut document = json!({ "code": self.code_ref()? });
+        if self.nul_replaced > 0 {
+            document["nul_replaced"] = json!(self.nul_replaced);
+        }
+        Ok(document.to_string())
+    }
+}
+
+/// An open table for C callers: only ever used behind a pointer, which is
+/// an id into the handle registry rather than an address.
+pub struct VpxHandle {
+    _opaque: [u8; 0],
+}
+
+/// What the open handles refer to. Queries on one table are serialized by
+/// its mutex; different tables are queried in parallel.
+static HANDLES: Registry<Mutex<OpenTable>> = Registry::new();
+
+impl VpxHandle {
+    /// Hands `table` over to C. Release with `vpx_close`.
+    pub(crate) fn into_raw(table: OpenTable) -> *mut VpxHandle {
+        registry::to_ptr(HANDLES.insert(Mutex::new(table)))
+    }
+}
+
+/// Runs `query` on the handle's table; one call at a time per handle. The
+/// table stays alive until the query returns, even if it is closed
+/// meanwhile.
+pub(crate) fn with_table<T>(
+    handle: *const VpxHandle,
+    query: impl FnOnce(&mut OpenTable) -> Result<T, VpxError>,
+) -> Result<T, VpxError> {
+    if handle.is_null() {
+        return Err(VpxError::new(VpxErrorCode::NullArgument, "handle is null"));
+    }
+    let Some(table) = HANDLES.get(registry::from_ptr(handle)) else {
+        return Err(VpxError::new(VpxErrorCode::InvalidArgument, "handle is not open (closed, or never opened)"));
+    };
+    // A query that panicked left the table as it was before the read.
+    let mut table = table.lock().unwrap_or_else(|e| e.into_inner());
+    query(&mut table)
+}
+
+/// Opens the `.vpx` file at `vpx_file_path` for the `vpx_handle_*` queries,
+/// which then neither reopen nor reparse it, and read each stream at most
+/// once. The file stays open until `vpx_close`.
+///
+/// Returns null on failure.
+///
+/// # Safety
+/// `vpx_file_path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_open(vpx_file_path: *const c_char) -> *mut VpxHandle {
+    unsafe { vpx_open_ex(vpx_file_path, std::ptr::null()) }
+}
+
+/// `vpx_open` with per-call options (`options_json` may be null), which
+/// then apply to every query on the handle.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_open_ex(vpx_file_path: *const c_char, options_json: *const c_char) -> *mut VpxHandle {
+    unsafe { vpx_open_err(vpx_file_path, options_json, std::ptr::null_mut()) }
+}
+
+/// `vpx_open_ex` with the `VpxErrorCode` stored in `error_code` (nullable),
+/// as `vpx_get_table_info_as_json_err` does.
+///
+/// # Safety
+/// `vpx_file_path` and `options_json` must be null or valid NUL-terminated
+/// strings, `error_code` null or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_open_err(
+    vpx_file_path: *const c_char,
+    options_json: *const c_char,
+    error_code: *mut i32,
+) -> *mut VpxHandle {
+    let result = unsafe { CallOptions::from_c(options_json) }
+        .and_then(|options| Ok((unsafe { crate::path_from_c(vpx_file_path) }?, options)))
+        .and_then(|(path, options)| OpenTable::open(&path, &options));
+    unsafe { crate::set_error_code(error_code, &result) };
+
+    match result {
+        Ok(table) => VpxHandle::into_raw(table),
+        Err(e) => {
+            log_error!("vpx_open: {}", e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Closes a table opened with `vpx_open`. Queries still running on it
+/// from other threads finish first; later ones fail. Closing null does
+/// nothing, closing twice logs a warning.
+///
+/// # Safety
+/// `handle` must be null or a pointer from `vpx_open`.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_close(handle: *mut VpxHandle) {
+    if handle.is_null() {
+        return;
+    }
+    if HANDLES.remove(registry::from_ptr(handle)).is_none() {
+        log_warn!("vpx_close: handle is not open (closed twice?)");
+    }
+}
+
+/// How many handles are open, for finding the ones a caller forgot to
+/// close.
+#[no_mangle]
+pub extern "C" fn vpx_get_open_handle_count() -> u64 {
+    HANDLES.len() as u64
+}
+
+/// `vpx_get_table_info_as_json` for an open table.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `handle` must be null or from `vpx_open`; closed ones are rejected.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_handle_get_table_info_as_json(handle: *const VpxHandle) -> *mut c_char {
+    let result =
+        with_table(handle, OpenTable::table_info).and_then(|table_info| crate::into_c_string(table_info.to_string()));
+
+    crate::envelope::finish("vpx_handle_get_table_info_as_json", result)
+}
+
+/// `vpx_get_gamedata_code` for an open table.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `handle` must be null or from `vpx_open`; closed ones are rejected.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_handle_get_gamedata_code(handle: *const VpxHandle) -> *mut c_char {
+    let result = with_table(handle, OpenTable::code).and_then(crate::into_c_string);
+
+    match result {
+        Ok(ptr) => ptr,
+        Err(e) => {
+            log_error!("vpx_handle_get_gamedata_code: {}", e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// `vpx_get_table_info_as_json_with_len` for an open table.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `handle` must be null or from `vpx_open`; closed ones are rejected.
+/// `out_len` must be null or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_handle_get_table_info_as_json_with_len(
+    handle: *const VpxHandle,
+    out_len: *mut usize,
+) -> *mut c_char {
+    let result = with_table(handle, OpenTable::table_info)
+        .and_then(|table_info| crate::into_sized_c_string(table_info.to_string()));
+
+    unsafe { crate::envelope::finish_with_len("vpx_handle_get_table_info_as_json_with_len", result, out_len) }
+}
+
+/// `vpx_get_gamedata_code_with_len` for an open table.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `handle` must be null or from `vpx_open`; closed ones are rejected.
+/// `out_len` must be null or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_handle_get_gamedata_code_with_len(
+    handle: *const VpxHandle,
+    out_len: *mut usize,
+) -> *mut c_char {
+    let result = with_table(handle, OpenTable::code).and_then(crate::into_sized_c_string);
+
+    let (ptr, len) = result.unwrap_or_else(|e| {
+        log_error!("vpx_handle_get_gamedata_code_with_len: {}", e);
+        (std::ptr::null_mut(), 0)
+    });
+    if let Some(out) = unsafe { out_len.as_mut() } {
+        *out = len;
+    }
+    ptr
+}
+
+/// `vpx_get_gamedata_code_as_buffer` for an open table.
+///
+/// `data` is null on failure. Release with `vpx_free_buffer`.
+///
+/// # Safety
+/// `handle` must be null or from `vpx_open`; closed ones are rejected.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_handle_get_gamedata_code_as_buffer(handle: *const VpxHandle) -> VpxBuffer {
+    match with_table(handle, |table| crate::compress::encode(table.code()?.into_bytes(), &table.options)) {
+        Ok(bytes) => VpxBuffer::new(bytes),
+        Err(e) => {
+            log_error!("vpx_handle_get_gamedata_code_as_buffer: {}", e);
+            VpxBuffer::null()
+        }
+    }
+}
+
+/// `vpx_get_table_info_result` for an open table.
+///
+/// Never returns null. Release with `vpx_result_free`.
+///
+/// # Safety
+/// `handle` must be null or from `vpx_open`; closed ones are rejected.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_handle_get_table_info_result(handle: *const VpxHandle) -> *mut VpxResult {
+    let started = Instant::now();
+    let result = with_table(handle, OpenTable::table_info).map(|table_info| table_info.to_string());
+    if let Err(e) = &result {
+        log_debug!("vpx_handle_get_table_info_result: {}", e);
+    }
+    VpxResult::new(result, started).into_raw()
+}
+
+/// `vpx_get_gamedata_code_result` for an open table: `{"code": "<script>"}`.
+///
+/// Never returns null. Release with `vpx_result_free`.
+///
+/// # Safety
+/// `handle` must be null or from `vpx_open`; closed ones are rejected.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_handle_get_gamedata_code_result(handle: *const VpxHandle) -> *mut VpxResult {
+    let started = Instant::now();
+    let result = with_table(handle, OpenTable::code_document);
+    if let Err(e) = &result {
+        log_debug!("vpx_handle_get_gamedata_code_result: {}", e);
+    }
+    VpxResult::new(result, started).into_raw()
+}
+
+/// Length in bytes of the handle's script as UTF-8, stored in `len`, for
+/// reading it with `vpx_handle_get_gamedata_code_chunk`. Returns a
+/// `VpxErrorCode`.
+///
+/// # Safety
+/// `handle` must be null or from `vpx_open` (closed ones are rejected), `len` null or
+/// valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_handle_get_gamedata_code_length(handle: *const VpxHandle, len: *mut u64) -> i32 {
+    let result = with_table(handle, |table| Ok(table.code_ref()?.len() as u64));
+
+    match result {
+        Ok(code_len) => {
+            if let Some(len) = unsafe { len.as_mut() } {
+                *len = code_len;
+            }
+            VpxErrorCode::Ok as i32
+        }
+        Err(e) => {
+            log_error!("vpx_handle_get_gamedata_code_length: {}", e);
+            e.code as i32
+        }
+    }
+}
+
+/// Up to `max_len` bytes of the handle's script from byte `offset`, so a
+/// viewer can page through a multi-megabyte script without one big copy.
+/// A chunk never splits a character: it ends early instead, so each one is
+/// valid UTF-8 on its own and the next starts at `offset + len`. Past the
+/// end the buffer is empty; `max_len` under 4 may not fit a character.
+///
+/// Returns a buffer with a null `data` on failure, when `offset` is inside
+/// a character too. Release with `vpx_free_buffer`.
+///
+/// # Safety
+/// `handle` must be null or from `vpx_open`; closed ones are rejected.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_handle_get_gamedata_code_chunk(
+    handle: *const VpxHandle,
+    offset: u64,
+    max_len: usize,
+) -> VpxBuffer {
+    let result = with_table(handle, |table| {
+        let code = table.code_ref()?;
+        let start = usize::try_from(offset).unwrap_or(usize::MAX).min(code.len());
+        if !code.is_char_boundary(start) {
+            return Err(VpxError::new(
+                VpxErrorCode::InvalidArgument,
+                format!("Offset {} is inside a character.", offset),
+            ));
+        }
+        let end = crate::stream::chunk_end(code, start, max_len);
+        Ok(code.as_bytes()[start..end].to_vec())
+    });
+
+    match result {
+        Ok(chunk) => VpxBuffer::new(chunk),
+        Err(e) => {
+            log_error!("vpx_handle_get_gamedata_code_chunk: {}", e);
+            VpxBuffer::null()
+        }
+    }
+}
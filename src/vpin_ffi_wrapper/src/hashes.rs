@@ -0,0 +1,150 @@
+//! File hashes.
+//!
+//! The frontend hashes tables and media for cache keys, manifests and
+//! duplicate detection. `vpx_hash_files` does a whole list in one call on
+//! `thread_count` threads instead of one call per file.
+
+use std::ffi::{c_void, CString};
+use std::fs::File;
+use std::io;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde_json::{json, Map, Value};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::progress::VpxProgressCallback;
+
+/// Lowercase hex digest of a file, read in chunks.
+fn digest_file<D: Digest + io::Write>(path: &Path) -> io::Result<String> {
+    let mut hasher = D::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Lowercase hex SHA-256 of a file.
+pub(crate) fn sha256_file(path: &Path) -> io::Result<String> {
+    digest_file::<Sha256>(path)
+}
+
+#[derive(Clone, Copy)]
+enum Algo {
+    Sha256,
+    Sha1,
+}
+
+impl Algo {
+    fn parse(name: &str) -> Result<Algo, VpxError> {
+        match name {
+            "sha256" => Ok(Algo::Sha256),
+            "sha1" => Ok(Algo::Sha1),
+            _ => Err(VpxError::new(
+                VpxErrorCode::InvalidArgument,
+                format!("Unknown hash algorithm '{}' (expected \"sha256\" or \"sha1\")", name),
+            )),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Algo::Sha256 => "sha256",
+            Algo::Sha1 => "sha1",
+        }
+    }
+
+    fn hash(self, path: &Path) -> io::Result<String> {
+        match self {
+            Algo::Sha256 => digest_file::<Sha256>(path),
+            Algo::Sha1 => digest_file::<Sha1>(path),
+        }
+    }
+}
+
+/// The progress callback of one call, shared by its threads.
+struct Reporter {
+    callback: VpxProgressCallback,
+    userdata: *mut c_void,
+    done: AtomicU64,
+    total: u64,
+}
+
+// The frontend owns `userdata` and promises it can be used from any thread.
+unsafe impl Sync for Reporter {}
+
+impl Reporter {
+    fn file_done(&self) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some(callback) = self.callback {
+            let stage = CString::new("hash").unwrap_or_default();
+            callback(0, done, self.total, stage.as_ptr(), self.userdata);
+        }
+    }
+}
+
+fn hash_files(paths: &[PathBuf], algo: Algo, reporter: &Reporter) -> Value {
+    let results = crate::parallel::map(paths, |path| {
+        let hash = algo.hash(path);
+        reporter.file_done();
+        hash
+    });
+    let mut hashes = Map::new();
+    let mut failed = Vec::new();
+    for (path, result) in paths.iter().zip(results) {
+        match result {
+            Ok(hash) => {
+                hashes.insert(path.to_string_lossy().into_owned(), Value::String(hash));
+            }
+            Err(e) => {
+                let code = match e.kind() {
+                    io::ErrorKind::NotFound => VpxErrorCode::FileNotFound,
+                    _ => VpxErrorCode::OpenFailed,
+                };
+                failed.push(json!({ "path": path, "error": { "code": code as i32, "message": e.to_string() } }));
+            }
+        }
+    }
+    json!({ "algo": algo.name(), "hashes": hashes, "failed": failed })
+}
+
+/// Hashes many files at once, in parallel on `thread_count` threads.
+/// `paths_json` is `{"paths": ["...", ...]}`, `algo` `"sha256"` (also for
+/// null) or `"sha1"`. `progress_cb` (nullable) hears each file done as
+/// `(0, done, total, "hash", userdata)`, from the hashing threads.
+///
+/// Returns `{"algo", "hashes": {"<path>": "<lowercase hex>"}, "failed":
+/// [{"path", "error": {"code", "message"}}]}`, or null on bad arguments.
+/// Blocks until every file is hashed. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `paths_json` and `algo` must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_hash_files(
+    paths_json: *const c_char,
+    algo: *const c_char,
+    progress_cb: VpxProgressCallback,
+    userdata: *mut c_void,
+) -> *mut c_char {
+    let result = unsafe { crate::str_from_c(paths_json, "paths") }
+        .and_then(|paths| {
+            let value: Value = serde_json::from_str(paths)
+                .map_err(|e| VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid paths JSON: {}", e)))?;
+            match value.get("paths").and_then(Value::as_array) {
+                Some(paths) => Ok(paths.iter().filter_map(Value::as_str).map(PathBuf::from).collect::<Vec<_>>()),
+                None => Err(VpxError::new(VpxErrorCode::InvalidArgument, "Paths JSON has no \"paths\" array.")),
+            }
+        })
+        .and_then(|paths| {
+            let algo = match algo.is_null() {
+                true => Algo::Sha256,
+                false => Algo::parse(unsafe { crate::str_from_c(algo, "algorithm") }?)?,
+            };
+            let reporter =
+                Reporter { callback: progress_cb, userdata, done: AtomicU64::new(0), total: paths.len() as u64 };
+            crate::into_c_string(hash_files(&paths, algo, &reporter).to_string())
+        });
+
+    crate::envelope::finish("vpx_hash_files", result)
+}
@@ -0,0 +1,498 @@
+//! Table health report.
+//!
+//! Runs the checks the frontend used to do piecemeal (ROM, backglass, media,
+//! script, integrity) against one table and folds them into a score and a
+//! red/yellow/green indicator, keeping the per-check details alongside.
+//! The launch pre-flight reuses the subset of checks that can stop VPX from
+//! starting.
+
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use vpin::vpx::VerifyResult;
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::options::CallOptions;
+use crate::{config, paths, script};
+
+/// Where the checks should look. Every key is optional.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct HealthEnv {
+    /// PinMAME folder holding `roms/`. Defaults to `pinmame/` next to the table.
+    pinmame_path: Option<PathBuf>,
+    /// Folder `PlayMusic` paths are relative to. Defaults to `music/` next to the table.
+    music_path: Option<PathBuf>,
+    /// VPX `Scripts` folder (core.vbs and friends).
+    scripts_path: Option<PathBuf>,
+    /// Media to validate, name -> path (relative paths are taken from the table folder).
+    media: BTreeMap<String, String>,
+    /// Recompute the file's MAC signature.
+    verify_mac: bool,
+}
+
+impl Default for HealthEnv {
+    fn default() -> Self {
+        HealthEnv {
+            pinmame_path: None,
+            music_path: None,
+            scripts_path: None,
+            media: BTreeMap::new(),
+            verify_mac: true,
+        }
+    }
+}
+
+const KNOWN_KEYS: &[&str] = &["pinmame_path", "music_path", "scripts_path", "media", "verify_mac"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Status {
+    Skipped,
+    Ok,
+    Warn,
+    Error,
+}
+
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Status::Skipped => "skipped",
+            Status::Ok => "ok",
+            Status::Warn => "warn",
+            Status::Error => "error",
+        }
+    }
+
+    fn penalty(self) -> u32 {
+        match self {
+            Status::Skipped | Status::Ok => 0,
+            Status::Warn => 10,
+            Status::Error => 25,
+        }
+    }
+}
+
+struct Check {
+    status: Status,
+    details: Vec<String>,
+    extra: Map<String, Value>,
+}
+
+impl Check {
+    fn new(status: Status) -> Self {
+        Check { status, details: Vec::new(), extra: Map::new() }
+    }
+
+    fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.details.push(detail.into());
+        self
+    }
+
+    fn with(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.extra.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// Raises the status to at least `status`, recording why.
+    fn flag(&mut self, status: Status, detail: impl Into<String>) {
+        self.status = self.status.max(status);
+        self.details.push(detail.into());
+    }
+
+    fn into_value(self) -> Value {
+        let mut object = self.extra;
+        object.insert("status".into(), self.status.as_str().into());
+        object.insert("details".into(), json!(self.details));
+        Value::Object(object)
+    }
+}
+
+fn parse_env(env_json: Option<&str>) -> Result<HealthEnv, VpxError> {
+    let Some(env_json) = env_json else {
+        return Ok(HealthEnv::default());
+    };
+    let value: Value = serde_json::from_str(env_json).map_err(|e| {
+        VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid environment JSON: {}", e))
+    })?;
+    let Some(object) = value.as_object() else {
+        return Err(VpxError::new(VpxErrorCode::InvalidArgument, "Environment must be a JSON object."));
+    };
+    config::warn_unknown_keys("health environment", object, KNOWN_KEYS);
+    HealthEnv::deserialize(&value)
+        .map_err(|e| VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid environment: {}", e)))
+}
+
+fn check_integrity(path: &Path, env: &HealthEnv, script: &Result<String, VpxError>) -> Check {
+    let mut check = Check::new(Status::Ok);
+    if let Err(e) = crate::read_table_info_value(path, &CallOptions::default()) {
+        check.flag(Status::Error, e.message);
+    }
+    if let Err(e) = script {
+        check.flag(Status::Error, e.message.clone());
+    }
+    if env.verify_mac {
+        if let VerifyResult::Failed(_, reason) = vpin::vpx::verify(path) {
+            check.flag(Status::Error, reason);
+        }
+    }
+    check
+}
+
+fn check_rom(table_dir: &Path, env: &HealthEnv, code: &str) -> Check {
+    let Some(rom_name) = script::rom_name(code) else {
+        return if script::uses_pinmame(code) {
+            Check::new(Status::Warn).detail("Script loads PinMAME but no cGameName was found")
+        } else {
+            Check::new(Status::Skipped).detail("Table does not use a ROM")
+        };
+    };
+    let check = Check::new(Status::Ok).with("rom_name", rom_name.as_str());
+    let pinmame = env.pinmame_path.clone().or_else(|| paths::pinmame_dir(table_dir));
+    let Some(pinmame) = pinmame else {
+        return Check { status: Status::Error, ..check }.detail("No pinmame folder found");
+    };
+    let rom = paths::resolve_case_insensitive(&pinmame, &format!("roms/{}.zip", rom_name));
+    match rom {
+        Some(rom) if paths::has_content(&rom) => check.with("rom_path", rom.to_string_lossy().as_ref()),
+        _ => Check { status: Status::Error, ..check }
+            .detail(format!("ROM '{}.zip' not found in {}", rom_name, pinmame.join("roms").display())),
+    }
+}
+
+fn check_b2s(path: &Path, table_dir: &Path) -> Check {
+    let stem = path.file_stem().and_then(OsStr::to_str).unwrap_or_default();
+    let mut backglasses: Vec<PathBuf> = fs::read_dir(table_dir)
+        .map(|d| d.flatten().map(|e| e.path()).collect())
+        .unwrap_or_default();
+    backglasses.retain(|p| {
+        p.extension()
+            .and_then(OsStr::to_str)
+            .is_some_and(|e| e.eq_ignore_ascii_case("directb2s") || e.eq_ignore_ascii_case("b2s"))
+    });
+    let matching = backglasses.iter().find(|p| {
+        p.file_stem().and_then(OsStr::to_str).is_some_and(|s| s.eq_ignore_ascii_case(stem))
+    });
+    match (matching, backglasses.first()) {
+        (Some(b2s), _) => Check::new(Status::Ok).with("b2s_path", b2s.to_string_lossy().as_ref()),
+        (None, Some(other)) => Check::new(Status::Warn)
+            .with("b2s_path", other.to_string_lossy().as_ref())
+            .detail("Backglass file name does not match the table, B2S will not load it"),
+        (None, None) => Check::new(Status::Warn).detail("No backglass found"),
+    }
+}
+
+fn check_media(table_dir: &Path, env: &HealthEnv) -> Check {
+    if env.media.is_empty() {
+        return Check::new(Status::Skipped).detail("No media configured");
+    }
+    let mut check = Check::new(Status::Ok);
+    let mut missing = Vec::new();
+    for (name, relative) in &env.media {
+        let media_path = table_dir.join(relative);
+        if !paths::has_content(&media_path) {
+            check.flag(Status::Warn, format!("{} missing or empty: {}", name, media_path.display()));
+            missing.push(name.clone());
+        }
+    }
+    check.with("missing", json!(missing))
+}
+
+fn check_script(path: &Path, code: &str) -> Check {
+    let mut check = Check::new(Status::Ok);
+    if code.trim().is_empty() {
+        check.flag(Status::Error, "Table script is empty");
+    }
+    let sidecar = vpin::vpx::vbs_path_for(path);
+    if let Ok(sidecar_code) = fs::read_to_string(&sidecar) {
+        check = check.with("sidecar_path", sidecar.to_string_lossy().as_ref());
+        if sidecar_code != code {
+            check.flag(Status::Warn, "Sidecar .vbs differs from the embedded script and will be used instead");
+        }
+    }
+    check
+}
+
+fn check_scripts(table_dir: &Path, env: &HealthEnv, code: &str) -> Check {
+    let references = script::script_references(code);
+    if references.is_empty() {
+        return Check::new(Status::Skipped).detail("Script loads no external scripts");
+    }
+    let mut check = Check::new(Status::Ok);
+    let mut missing = Vec::new();
+    for reference in &references {
+        let found = std::iter::once(table_dir)
+            .chain(env.scripts_path.as_deref())
+            .any(|dir| paths::resolve_case_insensitive(dir, reference).is_some());
+        if !found {
+            // Without a scripts folder we can't tell a missing file from a
+            // stock one, so only an explicit scripts_path makes this an error.
+            let status = if env.scripts_path.is_some() { Status::Error } else { Status::Warn };
+            check.flag(status, format!("Script '{}' not found", reference));
+            missing.push(reference.clone());
+        }
+    }
+    check.with("referenced", json!(references)).with("missing", json!(missing))
+}
+
+fn check_music(table_dir: &Path, env: &HealthEnv, code: &str) -> Check {
+    let references = script::music_references(code);
+    if references.is_empty() {
+        return Check::new(Status::Skipped).detail("Script plays no music files");
+    }
+    let music_dir = env
+        .music_path
+        .clone()
+        .or_else(|| paths::find_case_insensitive(table_dir, "music"))
+        .unwrap_or_else(|| table_dir.join("music"));
+    let mut check = Check::new(Status::Ok);
+    let mut missing = Vec::new();
+    for reference in &references {
+        if paths::resolve_case_insensitive(&music_dir, reference).is_none() {
+            check.flag(Status::Warn, format!("Music '{}' not found in {}", reference, music_dir.display()));
+            missing.push(reference.clone());
+        }
+    }
+    check.with("referenced", json!(references)).with("missing", json!(missing))
+}
+
+/// Runs every check against the table at `path`.
+///
+/// Only a missing file fails; a table that can't be read still gets a
+/// report, with the integrity check carrying the reason.
+fn health_report(path: &Path, env: &HealthEnv) -> Result<Value, VpxError> {
+    if !path.is_file() {
+        return Err(VpxError::new(
+            VpxErrorCode::FileNotFound,
+            format!("'{}' is not a file", path.display()),
+        ));
+    }
+    let table_dir = paths::table_dir(path);
+    let script = crate::read_gamedata_code(path, &CallOptions::default());
+    let code = script.as_deref().unwrap_or_default();
+
+    let checks = [
+        ("integrity", check_integrity(path, env, &script)),
+        ("rom", check_rom(&table_dir, env, code)),
+        ("b2s", check_b2s(path, &table_dir)),
+        ("media", check_media(&table_dir, env)),
+        ("script", check_script(path, code)),
+        ("scripts", check_scripts(&table_dir, env, code)),
+        ("music", check_music(&table_dir, env, code)),
+    ];
+    let worst = checks.iter().map(|(_, c)| c.status).max().unwrap_or(Status::Skipped);
+    let penalty: u32 = checks.iter().map(|(_, c)| c.status.penalty()).sum();
+    let indicator = match worst {
+        Status::Error => "red",
+        Status::Warn => "yellow",
+        Status::Ok | Status::Skipped => "green",
+    };
+    let checks: Map<String, Value> = checks
+        .into_iter()
+        .map(|(name, check)| (name.to_string(), check.into_value()))
+        .collect();
+
+    Ok(json!({
+        "path": path.to_string_lossy(),
+        "score": 100u32.saturating_sub(penalty),
+        "indicator": indicator,
+        "checks": checks,
+    }))
+}
+
+/// What must be in place to launch the table right now. Errors block the
+/// launch, warnings don't. Skips the MAC check and media, which VPX can live
+/// without and which cost the most.
+fn preflight(path: &Path, env: &HealthEnv) -> Value {
+    let mut blocking = Vec::new();
+    let mut non_blocking = Vec::new();
+    let mut push = |check: &str, status: Status, message: String| {
+        let issue = json!({ "check": check, "message": message });
+        match status {
+            Status::Error => blocking.push(issue),
+            Status::Warn => non_blocking.push(issue),
+            Status::Ok | Status::Skipped => {}
+        }
+    };
+
+    let table_dir = paths::table_dir(path);
+    match crate::read_gamedata_code(path, &CallOptions::default()) {
+        Err(e) => push("file", Status::Error, e.message),
+        Ok(code) => {
+            for (name, check) in [
+                ("rom", check_rom(&table_dir, env, &code)),
+                ("b2s", check_b2s(path, &table_dir)),
+                ("scripts", check_scripts(&table_dir, env, &code)),
+            ] {
+                for detail in check.details {
+                    push(name, check.status, detail);
+                }
+            }
+        }
+    }
+
+    json!({
+        "path": path,
+        "ready": blocking.is_empty(),
+        "blocking": blocking,
+        "non_blocking": non_blocking,
+    })
+}
+
+/// Tables whose `check` came out as warn or error, per check name.
+fn tally_issues(reports: &[(&Path, Value)]) -> Map<String, Value> {
+    let mut issues: BTreeMap<String, (u32, u32, Vec<Value>)> = BTreeMap::new();
+    for (path, report) in reports {
+        let Some(checks) = report["checks"].as_object() else { continue };
+        for (name, check) in checks {
+            let entry = issues.entry(name.clone()).or_default();
+            match check["status"].as_str() {
+                Some("warn") => entry.0 += 1,
+                Some("error") => entry.1 += 1,
+                _ => continue,
+            }
+            entry.2.push(json!({ "path": path, "status": check["status"], "details": check["details"] }));
+        }
+    }
+    issues
+        .into_iter()
+        .map(|(name, (warn, error, tables))| {
+            (name, json!({ "warn": warn, "error": error, "tables": tables }))
+        })
+        .collect()
+}
+
+/// Health of a whole library: every table checked in parallel, then
+/// aggregated into indicator counts and per-check issue lists.
+pub(crate) fn library_health(paths: &[PathBuf], env_json: Option<&str>) -> Result<Value, VpxError> {
+    let env = parse_env(env_json)?;
+    let results = crate::parallel::map(paths, |path| {
+        crate::catch_panic(path, || health_report(path, &env))
+    });
+
+    let mut reports: Vec<(&Path, Value)> = Vec::new();
+    let mut failed = Vec::new();
+    for (path, result) in paths.iter().zip(results) {
+        match result {
+            Ok(report) => reports.push((path, report)),
+            Err(e) => failed.push(json!({ "path": path, "error": { "code": e.code as i32, "message": e.message } })),
+        }
+    }
+
+    let mut indicators = BTreeMap::from([("green", 0u32), ("yellow", 0), ("red", 0)]);
+    let mut score_total = 0u64;
+    let mut tables = Vec::new();
+    for (path, report) in &reports {
+        if let Some(count) = report["indicator"].as_str().and_then(|i| indicators.get_mut(i)) {
+            *count += 1;
+        }
+        score_total += report["score"].as_u64().unwrap_or(0);
+        tables.push(json!({ "path": path, "score": report["score"], "indicator": report["indicator"] }));
+    }
+    let average_score = if reports.is_empty() { 0 } else { score_total / reports.len() as u64 };
+
+    Ok(json!({
+        "total": paths.len(),
+        "checked": reports.len(),
+        "average_score": average_score,
+        "indicators": indicators,
+        "issues": tally_issues(&reports),
+        "failed": failed,
+        "tables": tables,
+    }))
+}
+
+/// Optional C string argument as `Option<&str>`.
+///
+/// # Safety
+/// `s` must be null or a valid NUL-terminated string.
+unsafe fn optional_str<'a>(s: *const c_char, what: &str) -> Result<Option<&'a str>, VpxError> {
+    if s.is_null() {
+        Ok(None)
+    } else {
+        unsafe { crate::str_from_c(s, what) }.map(Some)
+    }
+}
+
+/// Builds a health report for one table: ROM, backglass, media, script,
+/// referenced scripts and music, and file integrity, scored 0-100 with a
+/// `"red"`/`"yellow"`/`"green"` indicator.
+///
+/// `env_config_json` may be null; see the header for its keys. Returns null
+/// on bad arguments. Release with `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_table_health_report(
+    vpx_file_path: *const c_char,
+    env_config_json: *const c_char,
+) -> *mut c_char {
+    let result = unsafe { optional_str(env_config_json, "environment") }
+        .and_then(parse_env)
+        .and_then(|env| Ok((unsafe { crate::path_from_c(vpx_file_path) }?, env)))
+        .and_then(|(path, env)| crate::catch_panic(&path, || health_report(&path, &env)))
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_get_table_health_report", result)
+}
+
+/// Health summary for many tables at once, checked in parallel on the
+/// configured `thread_count` (see `vpx_wrapper_init`). `paths_json` is `{"paths": ["...", ...]}`;
+/// `env_config_json` is as for `vpx_get_table_health_report` and may be null.
+///
+/// Returns `{"total", "checked", "average_score", "indicators", "issues",
+/// "failed", "tables"}`, or null on bad arguments. Blocks until every table
+/// is checked. Release with `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_library_health_report(
+    paths_json: *const c_char,
+    env_config_json: *const c_char,
+) -> *mut c_char {
+    let result = unsafe { crate::str_from_c(paths_json, "paths") }
+        .and_then(|paths| {
+            let value: Value = serde_json::from_str(paths).map_err(|e| {
+                VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid paths JSON: {}", e))
+            })?;
+            match value.get("paths").and_then(Value::as_array) {
+                Some(paths) => Ok(paths.iter().filter_map(Value::as_str).map(PathBuf::from).collect::<Vec<_>>()),
+                None => Err(VpxError::new(VpxErrorCode::InvalidArgument, "Paths JSON has no \"paths\" array.")),
+            }
+        })
+        .and_then(|paths| Ok((paths, unsafe { optional_str(env_config_json, "environment") }?)))
+        .and_then(|(paths, env_json)| library_health(&paths, env_json))
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_get_library_health_report", result)
+}
+
+/// Quick launch-readiness check: file readable, ROM present, backglass
+/// findable, referenced scripts present. `env_config_json` is as for
+/// `vpx_get_table_health_report` (`media` and `verify_mac` are ignored).
+///
+/// Returns `{"path", "ready", "blocking": [...], "non_blocking": [...]}`
+/// with `{"check", "message"}` issues, or null on bad arguments. Release
+/// with `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_preflight_table_launch(
+    vpx_file_path: *const c_char,
+    env_config_json: *const c_char,
+) -> *mut c_char {
+    let result = unsafe { optional_str(env_config_json, "environment") }
+        .and_then(parse_env)
+        .and_then(|env| Ok((unsafe { crate::path_from_c(vpx_file_path) }?, env)))
+        .and_then(|(path, env)| crate::catch_panic(&path, || Ok(preflight(&path, &env))))
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_preflight_table_launch", result)
+}
@@ -0,0 +1,459 @@
+//! Table import pipeline.
+//!
+//! One call takes a table from a `.vpx` file, a folder or a `.zip`, `.7z`
+//! or `.rar` pack to a placed, checked and indexed library entry: extract,
+//! place, detect ROM/B2S/PuP, then index. Media isn't part of it: the
+//! frontend makes it with its screenshot capture once the table is in the
+//! library. Any part of a split archive (`.zip.001`, `.7z.001`,
+//! `.part1.rar`...) imports the whole set. 7z and RAR packs are read with
+//! 7-Zip (see `sevenzip`), unpacked beside the destination first and then
+//! moved into it.
+
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io;
+use std::os::raw::c_char;
+use std::path::{Component, Path, PathBuf};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::library::LibraryConfig;
+use crate::options::CallOptions;
+use crate::{index, locks, multipart, oplog, paths, script, sevenzip};
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct ImportOptions {
+    /// Plan only: report what would be placed, write nothing.
+    dry_run: bool,
+    /// Import into an existing, non-empty table folder.
+    overwrite: bool,
+    /// Folder to create under `tables_path`. Defaults to the pack's top
+    /// folder or the table's file name.
+    folder_name: Option<String>,
+    /// Add the imported tables to the index (needs `index_path`).
+    update_index: bool,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        ImportOptions { dry_run: false, overwrite: false, folder_name: None, update_index: true }
+    }
+}
+
+const KNOWN_KEYS: &[&str] = &["dry_run", "overwrite", "folder_name", "update_index"];
+
+/// Files next to a lone `.vpx` that belong to it.
+const COMPANION_EXTENSIONS: &[&str] = &["directb2s", "b2s", "ini", "vbs", "pov", "txt"];
+
+fn io_error(what: &str, path: &Path, e: io::Error) -> VpxError {
+    VpxError::new(VpxErrorCode::OpenFailed, format!("Failed to {} '{}': {}", what, path.display(), e))
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension().and_then(OsStr::to_str).is_some_and(|e| e.eq_ignore_ascii_case(extension))
+}
+
+/// A `.zip`, `.7z` or `.rar` pack, or a part of a split archive.
+fn is_archive(path: &Path) -> bool {
+    has_extension(path, "zip") || sevenzip::is_pack(path) || multipart::is_part(path)
+}
+
+/// One file of the package, relative to the package root.
+enum Entry {
+    File { relative: PathBuf, source: PathBuf },
+    Zip { relative: PathBuf, index: usize },
+    /// A 7z or RAR member, at `unpacked` in the folder the pack is unpacked to.
+    Packed { relative: PathBuf, unpacked: PathBuf },
+}
+
+impl Entry {
+    fn relative(&self) -> &Path {
+        match self {
+            Entry::File { relative, .. } | Entry::Zip { relative, .. } | Entry::Packed { relative, .. } => relative,
+        }
+    }
+
+    fn relative_mut(&mut self) -> &mut PathBuf {
+        match self {
+            Entry::File { relative, .. } | Entry::Zip { relative, .. } | Entry::Packed { relative, .. } => relative,
+        }
+    }
+}
+
+fn list_entries(src: &Path) -> Result<Vec<Entry>, VpxError> {
+    let mut entries = Vec::new();
+    if src.is_dir() {
+        for source in paths::files_under(src) {
+            if let Ok(relative) = source.strip_prefix(src) {
+                entries.push(Entry::File { relative: relative.to_path_buf(), source: source.clone() });
+            }
+        }
+    } else if has_extension(src, "vpx") {
+        let stem = src.file_stem().and_then(OsStr::to_str).unwrap_or_default();
+        // A loose ROM zip next to the table comes along too.
+        let code = crate::read_gamedata_code(src, &CallOptions::default()).unwrap_or_default();
+        let rom_zip = script::rom_name(&code).map(|rom| format!("{}.zip", rom));
+        let dir = paths::table_dir(src);
+        for sibling in fs::read_dir(&dir).map_err(|e| io_error("read", &dir, e))?.flatten() {
+            let path = sibling.path();
+            let name = sibling.file_name().to_string_lossy().into_owned();
+            let same_stem = path.file_stem().and_then(OsStr::to_str).is_some_and(|s| s.eq_ignore_ascii_case(stem));
+            let is_companion = same_stem && (path == src || COMPANION_EXTENSIONS.iter().any(|e| has_extension(&path, e)));
+            let is_rom = rom_zip.as_ref().is_some_and(|rom| rom.eq_ignore_ascii_case(&name));
+            if path.is_file() && (is_companion || is_rom) {
+                entries.push(Entry::File { relative: PathBuf::from(sibling.file_name()), source: path });
+            }
+        }
+    } else if let Some(pack) = multipart::seven_zip_input(src)? {
+        for member in sevenzip::list(&pack)? {
+            match member.relative {
+                Some(relative) => entries.push(Entry::Packed { relative: relative.clone(), unpacked: relative }),
+                None => log_warn!("import: skipping unsafe archive entry '{}'", member.name),
+            }
+        }
+    } else if is_archive(src) {
+        let mut archive = multipart::open_zip(src)?;
+        for i in 0..archive.len() {
+            let Ok(file) = archive.by_index(i) else { continue };
+            if file.is_dir() {
+                continue;
+            }
+            // enclosed_name() rejects absolute paths and "..".
+            match file.enclosed_name() {
+                Some(relative) => entries.push(Entry::Zip { relative, index: i }),
+                None => log_warn!("import: skipping unsafe archive entry '{}'", file.name()),
+            }
+        }
+    } else {
+        return Err(VpxError::new(
+            VpxErrorCode::InvalidArgument,
+            format!("'{}' is not a .vpx, .zip, .7z, .rar or folder", src.display()),
+        ));
+    }
+    Ok(entries)
+}
+
+/// Drops a top folder shared by every entry, returning its name.
+fn strip_common_root(entries: &mut [Entry]) -> Option<String> {
+    let first = entries.first()?.relative().components().next()?;
+    let Component::Normal(root) = first else { return None };
+    let root = root.to_os_string();
+    let shared = entries.iter().all(|e| {
+        let mut components = e.relative().components();
+        components.next() == Some(Component::Normal(&root)) && components.next().is_some()
+    });
+    if !shared {
+        return None;
+    }
+    for entry in entries.iter_mut() {
+        let stripped = entry.relative().strip_prefix(&root).map(Path::to_path_buf).unwrap_or_default();
+        *entry.relative_mut() = stripped;
+    }
+    Some(root.to_string_lossy().into_owned())
+}
+
+/// Unpacks a 7z or RAR pack into a hidden folder beside `destination`, on
+/// the same drive, so its files can be renamed into place. One `7z x` for
+/// the whole pack instead of one per member, which for a solid pack would
+/// decompress it over and over.
+fn unpack(src: &Path, destination: &Path) -> Result<PathBuf, VpxError> {
+    let pack = multipart::seven_zip_input(src)?.unwrap_or_else(|| src.to_path_buf());
+    let name = destination.file_name().unwrap_or_default().to_string_lossy();
+    let scratch = destination.with_file_name(format!(".{}.unpacking", name));
+    let _ = fs::remove_dir_all(&scratch);
+    fs::create_dir_all(&scratch).map_err(|e| io_error("create", &scratch, e))?;
+    if let Err(e) = sevenzip::extract_all(&pack, &scratch) {
+        let _ = fs::remove_dir_all(&scratch);
+        return Err(e);
+    }
+    Ok(scratch)
+}
+
+fn place(src: &Path, entries: &[Entry], destination: &Path) -> Result<(), VpxError> {
+    let scratch = match entries.iter().any(|e| matches!(e, Entry::Packed { .. })) {
+        true => Some(unpack(src, destination)?),
+        false => None,
+    };
+    let placed = place_entries(src, entries, destination, scratch.as_deref());
+    if let Some(scratch) = &scratch {
+        if let Err(e) = fs::remove_dir_all(scratch) {
+            log_warn!("import: failed to remove '{}': {}", scratch.display(), e);
+        }
+    }
+    placed
+}
+
+fn place_entries(src: &Path, entries: &[Entry], destination: &Path, scratch: Option<&Path>) -> Result<(), VpxError> {
+    let mut archive = match entries.iter().any(|e| matches!(e, Entry::Zip { .. })) {
+        true => Some(multipart::open_zip(src)?),
+        false => None,
+    };
+    for entry in entries {
+        let target = destination.join(entry.relative());
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| io_error("create", parent, e))?;
+        }
+        match (entry, archive.as_mut()) {
+            (Entry::File { source, .. }, _) => {
+                oplog::touch(&target).map_err(|e| io_error("back up", &target, e))?;
+                fs::copy(source, &target).map_err(|e| io_error("copy", source, e))?;
+            }
+            (Entry::Zip { index, .. }, Some(archive)) => {
+                let mut file = archive.by_index(*index).map_err(|e| {
+                    VpxError::new(VpxErrorCode::ParseFailed, format!("Failed to extract '{}': {}", target.display(), e))
+                })?;
+                oplog::touch(&target).map_err(|e| io_error("back up", &target, e))?;
+                let mut out = File::create(&target).map_err(|e| io_error("create", &target, e))?;
+                io::copy(&mut file, &mut out).map_err(|e| io_error("extract", &target, e))?;
+            }
+            (Entry::Zip { relative, .. }, None) => {
+                return Err(VpxError::new(
+                    VpxErrorCode::OpenFailed,
+                    format!("'{}' has no archive open to extract it from", relative.display()),
+                ));
+            }
+            (Entry::Packed { unpacked, .. }, _) => {
+                let Some(scratch) = scratch else {
+                    return Err(VpxError::new(
+                        VpxErrorCode::OpenFailed,
+                        format!("'{}' wasn't unpacked before placing it", unpacked.display()),
+                    ));
+                };
+                let source = scratch.join(unpacked);
+                oplog::touch(&target).map_err(|e| io_error("back up", &target, e))?;
+                fs::rename(&source, &target)
+                    .or_else(|_| fs::copy(&source, &target).map(|_| ()))
+                    .map_err(|e| io_error("move", &source, e))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// What the import found out about one placed table.
+fn detect(vpx_path: &Path, dry_run: bool) -> Value {
+    let table_dir = paths::table_dir(vpx_path);
+    let code = crate::read_gamedata_code(vpx_path, &CallOptions::default()).unwrap_or_default();
+    let rom_name = script::rom_name(&code);
+    let pinmame = paths::pinmame_dir(&table_dir);
+
+    let mut rom_path = rom_name.as_ref().and_then(|rom| {
+        paths::resolve_case_insensitive(pinmame.as_deref()?, &format!("roms/{}.zip", rom))
+    });
+    let mut rom_moved = false;
+    // Packs often ship the ROM zip loose next to the table; move it where PinMAME looks.
+    if let (None, Some(rom)) = (&rom_path, &rom_name) {
+        if let Some(loose) = paths::find_case_insensitive(&table_dir, &format!("{}.zip", rom)) {
+            let target = pinmame.clone().unwrap_or_else(|| table_dir.join("pinmame")).join("roms").join(format!("{}.zip", rom));
+            let moved = dry_run
+                || fs::create_dir_all(target.parent().unwrap_or(&table_dir))
+                    .and_then(|_| fs::rename(&loose, &target))
+                    .inspect(|_| oplog::moved(&loose, &target))
+                    .is_ok();
+            if moved {
+                rom_path = Some(target);
+                rom_moved = true;
+            }
+        }
+    }
+
+    let stem = vpx_path.file_stem().and_then(OsStr::to_str).unwrap_or_default();
+    let b2s = ["directb2s", "b2s"].iter().find_map(|e| paths::find_case_insensitive(&table_dir, &format!("{}.{}", stem, e)));
+    let folder_with_content = |dir: Option<PathBuf>, name: &str| {
+        dir.and_then(|d| paths::find_case_insensitive(&d, name)).is_some_and(|p| paths::has_content(&p))
+    };
+    let info = crate::read_table_info_value(vpx_path, &CallOptions::default()).unwrap_or(Value::Null);
+
+    json!({
+        "vpx_file": vpx_path,
+        "title": info["table_name"].as_str().filter(|t| !t.trim().is_empty()).unwrap_or(stem),
+        "manufacturer": info["properties"]["CompanyName"],
+        "year": info["properties"]["CompanyYear"],
+        "rom_name": rom_name,
+        "rom_path": rom_path,
+        "rom_present": rom_path.is_some() || rom_name.is_none(),
+        "rom_moved": rom_moved,
+        "b2s_path": b2s,
+        "pup": folder_with_content(Some(table_dir.clone()), "pupvideos"),
+        "alt_sound": folder_with_content(pinmame.clone(), "altsound"),
+        "alt_color": folder_with_content(pinmame.clone(), "altcolor"),
+        "ini": paths::find_case_insensitive(&table_dir, &format!("{}.ini", stem)).is_some(),
+        "vbs": paths::find_case_insensitive(&table_dir, &format!("{}.vbs", stem)).is_some(),
+    })
+}
+
+/// Index row for a freshly imported table, in the frontend's field names.
+fn index_row(table: &Value) -> Value {
+    let vpx_file = table["vpx_file"].as_str().unwrap_or_default();
+    json!({
+        "vpxFile": vpx_file,
+        "folder": paths::table_dir(Path::new(vpx_file)),
+        "bestTitle": table["title"],
+        "bestManufacturer": table["manufacturer"].as_str().unwrap_or_default(),
+        "bestYear": table["year"].as_str().unwrap_or_default(),
+        "romName": table["rom_name"].as_str().unwrap_or_default(),
+        "romPath": table["rom_path"].as_str().unwrap_or_default(),
+        "hasB2S": !table["b2s_path"].is_null(),
+        "hasPup": table["pup"],
+        "hasAltSound": table["alt_sound"],
+        "hasAltColor": table["alt_color"],
+        "hasINI": table["ini"],
+        "hasVBS": table["vbs"],
+        "jsonOwner": index::WRAPPER_OWNER,
+        // Older than any real timestamp, so the next scan refreshes the row.
+        "fileLastModified": 0,
+    })
+}
+
+fn step(name: &str, status: &str, details: Value) -> Value {
+    json!({ "step": name, "status": status, "details": details })
+}
+
+fn import(src: &Path, library: &LibraryConfig, options: &ImportOptions) -> Result<Value, VpxError> {
+    let tables_path = library.require_tables_path()?;
+    let mut steps = Vec::new();
+
+    let mut entries = list_entries(src)?;
+    let pack_root = strip_common_root(&mut entries);
+    let vpx_entries: Vec<PathBuf> = entries
+        .iter()
+        .map(|e| e.relative().to_path_buf())
+        .filter(|p| has_extension(p, "vpx"))
+        .collect();
+    let Some(first_vpx) = vpx_entries.first() else {
+        return Err(VpxError::new(VpxErrorCode::InvalidArgument, format!("No .vpx found in '{}'", src.display())));
+    };
+    let folder_name = options
+        .folder_name
+        .clone()
+        .or(pack_root)
+        .or_else(|| first_vpx.file_stem().map(|s| s.to_string_lossy().into_owned()))
+        .filter(|name| Path::new(name).components().all(|c| matches!(c, Component::Normal(_))))
+        .ok_or_else(|| VpxError::new(VpxErrorCode::InvalidArgument, "Invalid table folder name."))?;
+    let destination = tables_path.join(&folder_name);
+    library.ensure_writable(&destination)?;
+    let destination_in_use = paths::has_content(&destination);
+    if destination_in_use && !options.overwrite {
+        return Err(VpxError::new(
+            VpxErrorCode::InvalidArgument,
+            format!("'{}' already exists (pass \"overwrite\": true to import into it)", destination.display()),
+        ));
+    }
+    let files: Vec<PathBuf> = entries.iter().map(|e| e.relative().to_path_buf()).collect();
+    let extract_status = if is_archive(src) { "done" } else { "skipped" };
+
+    if options.dry_run {
+        steps.push(step("extract", if extract_status == "done" { "planned" } else { "skipped" }, json!([])));
+        steps.push(step("place", "planned", json!([format!("{} file(s) into {}", files.len(), destination.display())])));
+    } else {
+        crate::safe_mode::check("importing tables")?;
+        let targets: Vec<PathBuf> = entries.iter().map(|e| destination.join(e.relative())).collect();
+        locks::check_tables_not_in_use(&targets)?;
+        let created = !destination.exists();
+        if created {
+            oplog::created_dir(&destination);
+        }
+        if let Err(e) = place(src, &entries, &destination) {
+            // Don't leave a half-imported table behind.
+            if created {
+                let _ = fs::remove_dir_all(&destination);
+            }
+            return Err(e);
+        }
+        steps.push(step("extract", extract_status, json!([])));
+        steps.push(step("place", "done", json!([format!("{} file(s) into {}", files.len(), destination.display())])));
+    }
+
+    // Detection reads the tables where they are: placed, or still in the
+    // source for a dry run (packs can't be inspected without extracting).
+    let tables: Vec<Value> = vpx_entries
+        .iter()
+        .filter_map(|relative| {
+            if !options.dry_run {
+                Some(detect(&destination.join(relative), false))
+            } else if !is_archive(src) {
+                let source = entries.iter().find_map(|e| match e {
+                    Entry::File { relative: r, source } if r == relative => Some(source.clone()),
+                    _ => None,
+                })?;
+                let mut table = detect(&source, true);
+                table["vpx_file"] = json!(destination.join(relative));
+                Some(table)
+            } else {
+                None
+            }
+        })
+        .collect();
+    let missing_roms: Vec<String> = tables
+        .iter()
+        .filter(|t| t["rom_present"] == false)
+        .map(|t| format!("ROM '{}' not found", t["rom_name"].as_str().unwrap_or_default()))
+        .collect();
+    let detect_status = match (tables.is_empty(), missing_roms.is_empty()) {
+        (true, _) => "skipped",
+        (false, true) => "done",
+        (false, false) => "warn",
+    };
+    steps.push(step("detect", detect_status, json!(missing_roms)));
+
+    match (&library.index_path, options.update_index, options.dry_run) {
+        (Some(index_path), true, false) => {
+            let mut index_doc = index::load(index_path)?;
+            for table in &tables {
+                index::upsert(&mut index_doc, index_row(table));
+            }
+            index::save(index_path, &index_doc)?;
+            steps.push(step("index", "done", json!([format!("{} row(s) in {}", tables.len(), index_path.display())])));
+        }
+        (Some(_), true, true) => steps.push(step("index", "planned", json!([]))),
+        _ => steps.push(step("index", "skipped", json!([]))),
+    }
+
+    Ok(json!({
+        "ok": true,
+        "dry_run": options.dry_run,
+        "source": src,
+        "destination": destination,
+        "overwrote": destination_in_use,
+        "files": files,
+        "tables": tables,
+        "steps": steps,
+    }))
+}
+
+/// Imports a table from a `.vpx` file (with its same-named companions), a
+/// folder or a `.zip`, `.7z` or `.rar` pack into `tables_path`: extract,
+/// place, detect ROM/B2S/PuP (moving a loose ROM zip into `pinmame/roms`),
+/// and add index rows. Media is left to the frontend's capture. 7z and RAR
+/// packs need 7-Zip installed (`FileNotFound` without it). A part of a split
+/// archive brings its whole set; a set with parts missing fails with
+/// `FileNotFound` naming them.
+///
+/// `library_config_json` needs `tables_path` and optionally `index_path`.
+/// `options_json` (nullable): `dry_run`, `overwrite`, `folder_name`,
+/// `update_index`. Returns the report, or null on failure (nothing is left
+/// behind if placing files failed). An overwrite of a table another program
+/// has open fails with `FileInUse`. Release with `vpx_free_string`.
+///
+/// # Safety
+/// All arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_import_table(
+    src_path: *const c_char,
+    library_config_json: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    let result = unsafe { crate::path_from_c(src_path) }
+        .and_then(|src| Ok((src, unsafe { LibraryConfig::from_c(library_config_json) }?)))
+        .and_then(|(src, library)| {
+            let options: ImportOptions = unsafe { crate::library::options_from_c(options_json, KNOWN_KEYS) }?;
+            let summary = format!("Import {}", src.display());
+            oplog::record("import", summary, || crate::catch_panic(&src, || import(&src, &library, &options)))
+        })
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_import_table", result)
+}
@@ -0,0 +1,94 @@
+//! The frontend's table index (`asapcab_index.json`).
+//!
+//! The C++ `AsapIndexManager` owns the format: `{"tables": [...]}` with one
+//! camelCase object per table, keyed by `vpxFile`. Library operations here
+//! edit rows in place and keep every field they don't know about, so the
+//! frontend loads the result as if it had written it. The frontend should
+//! reload its index after any call that changes the file.
+
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::paths;
+
+/// `jsonOwner` of rows added by the wrapper. Unknown to the frontend's merge
+/// priorities, so the next scan replaces them with full metadata.
+pub(crate) const WRAPPER_OWNER: &str = "VPin Wrapper";
+
+/// Parses an index document; anything without a `"tables"` array is rejected.
+pub(crate) fn parse(index_json: &str) -> Result<Value, VpxError> {
+    let index: Value = serde_json::from_str(index_json).map_err(|e| {
+        VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid index JSON: {}", e))
+    })?;
+    if !index["tables"].is_array() {
+        return Err(VpxError::new(VpxErrorCode::InvalidArgument, "Index has no \"tables\" array."));
+    }
+    Ok(index)
+}
+
+/// Loads the index at `path`. A missing file is an empty index, as for the frontend.
+pub(crate) fn load(path: &Path) -> Result<Value, VpxError> {
+    match fs::read_to_string(path) {
+        Ok(text) => parse(&text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(json!({ "tables": [] })),
+        Err(e) => Err(VpxError::new(
+            VpxErrorCode::OpenFailed,
+            format!("Failed to read index '{}': {}", path.display(), e),
+        )),
+    }
+}
+
+/// Writes the index atomically, indented with 4 spaces like the frontend.
+pub(crate) fn save(path: &Path, index: &Value) -> Result<(), VpxError> {
+    let write_error = |e: std::io::Error| {
+        VpxError::new(VpxErrorCode::OpenFailed, format!("Failed to write index '{}': {}", path.display(), e))
+    };
+    let mut text = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+    index
+        .serialize(&mut serde_json::Serializer::with_formatter(&mut text, formatter))
+        .map_err(|e| VpxError::new(VpxErrorCode::AllocFailed, format!("Failed to serialize index: {}", e)))?;
+    paths::write_atomic(path, &text).map_err(write_error)
+}
+
+pub(crate) fn tables(index: &Value) -> &[Value] {
+    index["tables"].as_array().map(Vec::as_slice).unwrap_or_default()
+}
+
+pub(crate) fn tables_mut(index: &mut Value) -> &mut Vec<Value> {
+    if !index["tables"].is_array() {
+        index["tables"] = json!([]);
+    }
+    index["tables"].as_array_mut().expect("tables is an array")
+}
+
+/// Position of the row for `vpx_file`.
+pub(crate) fn position(index: &Value, vpx_file: &str) -> Option<usize> {
+    tables(index).iter().position(|t| t["vpxFile"].as_str() == Some(vpx_file))
+}
+
+/// Merges `row` into the row with the same `vpxFile`, or appends it.
+pub(crate) fn upsert(index: &mut Value, row: Value) {
+    let vpx_file = row["vpxFile"].as_str().unwrap_or_default().to_string();
+    match position(index, &vpx_file) {
+        Some(i) => {
+            let existing = &mut tables_mut(index)[i];
+            if let (Some(existing), Some(row)) = (existing.as_object_mut(), row.as_object()) {
+                for (key, value) in row {
+                    existing.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        None => tables_mut(index).push(row),
+    }
+}
+
+/// Removes and returns the row for `vpx_file`.
+pub(crate) fn remove(index: &mut Value, vpx_file: &str) -> Option<Value> {
+    let i = position(index, vpx_file)?;
+    Some(tables_mut(index).remove(i))
+}
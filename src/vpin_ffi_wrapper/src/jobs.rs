@@ -0,0 +1,535 @@
+//! Background job queue.
+//!
+//! Jobs run on a pool of worker threads owned by the wrapper, started on the
+//! first submit. When a job finishes its completion callback is invoked from
+//! the worker thread with a `VpxResult` the callback takes ownership of.
+//! Jobs submitted without one keep their result in the queue instead, and
+//! a frontend that would rather poll than be called back fetches it with
+//! `vpx_job_take_result`.
+//!
+//! Each priority has its own FIFO and workers always serve the most urgent
+//! one first. Batch jobs work one file at a time and, between files, give
+//! their worker up whenever more urgent work is waiting: the batch goes back
+//! to the head of its queue with its progress and picks up where it left off.
+
+use std::collections::{HashMap, VecDeque};
+use std::ffi::c_void;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard, OnceLock};
+use std::thread;
+use std::time::Instant;
+
+use serde_json::{json, Map, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::options::CallOptions;
+use crate::profiles::{self, Profile};
+use crate::progress;
+use crate::result::VpxResult;
+
+/// What a job does. Passed as `int32_t` to `vpx_submit_job`.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VpxJobKind {
+    /// Same payload as `vpx_get_table_info_result`. Params: `{"path": "..."}`.
+    /// Every kind also accepts an `"options"` object, as for the `_ex` calls.
+    TableInfo = 0,
+    /// Same payload as `vpx_get_gamedata_code_result`. Params: `{"path": "..."}`.
+    GamedataCode = 1,
+    /// Table info for many files. Params: `{"paths": ["...", ...]}`.
+    /// Payload: an array of `{"path", "ok", "profile", "data"}` / `{"path",
+    /// "ok", "profile", "error"}`, each file scanned with its scan profile
+    /// (see `profiles`) and joined by what its other passes found.
+    TableInfoBatch = 2,
+}
+
+impl VpxJobKind {
+    fn from_i32(kind: i32) -> Option<Self> {
+        match kind {
+            0 => Some(VpxJobKind::TableInfo),
+            1 => Some(VpxJobKind::GamedataCode),
+            2 => Some(VpxJobKind::TableInfoBatch),
+            _ => None,
+        }
+    }
+}
+
+/// How urgent a job is. Passed as `int32_t` to `vpx_submit_job_with_priority`.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum VpxJobPriority {
+    /// The user is waiting on it (selected table, wheel scrolling).
+    Interactive = 0,
+    /// Likely needed soon (neighbours of the selected table).
+    Prefetch = 1,
+    /// Library scans and indexing.
+    Background = 2,
+}
+
+const PRIORITY_COUNT: usize = 3;
+
+impl VpxJobPriority {
+    fn from_i32(priority: i32) -> Option<Self> {
+        match priority {
+            0 => Some(VpxJobPriority::Interactive),
+            1 => Some(VpxJobPriority::Prefetch),
+            2 => Some(VpxJobPriority::Background),
+            _ => None,
+        }
+    }
+}
+
+/// State of a job as reported by `vpx_get_job_status`.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VpxJobStatus {
+    /// No job with that id was submitted, or its outcome was already
+    /// handed over (callback called or result taken).
+    Unknown = 0,
+    Queued = 1,
+    Running = 2,
+    Done = 3,
+    Cancelled = 4,
+}
+
+/// Called once per job, on a worker thread. `result` is owned by the callee
+/// and must be released with `vpx_result_free`.
+pub type VpxJobCallback =
+    Option<unsafe extern "C" fn(job_id: u64, result: *mut VpxResult, userdata: *mut c_void)>;
+
+/// Caller's userdata pointer. We never touch it, only hand it back.
+struct UserData(*mut c_void);
+unsafe impl Send for UserData {}
+
+enum JobWork {
+    Single(PathBuf),
+    Batch {
+        paths: Vec<PathBuf>,
+        next: usize,
+        entries: Vec<Value>,
+    },
+}
+
+struct Job {
+    id: u64,
+    kind: VpxJobKind,
+    priority: VpxJobPriority,
+    work: JobWork,
+    options: CallOptions,
+    started: Option<Instant>,
+    callback: VpxJobCallback,
+    userdata: UserData,
+}
+
+/// What a worker got out of running a job for a while.
+enum Step {
+    Finished(Result<String, VpxError>),
+    /// A batch gave its worker up; put it back in the queue.
+    Yielded,
+}
+
+/// One file of a batch: its table info under `data`, plus whatever the
+/// other passes of its scan profile found.
+pub(crate) fn batch_entry(path: &Path, profile: &Profile, result: Result<(Value, Map<String, Value>), VpxError>) -> Value {
+    match result {
+        Ok((data, found)) => {
+            let mut entry = json!({ "path": path, "ok": true, "profile": profile.name, "data": data });
+            if let Some(entry) = entry.as_object_mut() {
+                entry.extend(found);
+            }
+            entry
+        }
+        Err(e) => json!({
+            "path": path,
+            "ok": false,
+            "profile": profile.name,
+            "error": { "code": e.code as i32, "message": e.message },
+        }),
+    }
+}
+
+impl Job {
+    fn run(&mut self, queue: &JobQueue) -> Step {
+        let kind = self.kind;
+        let priority = self.priority;
+        let options = &self.options;
+        let started = self.started;
+        match &mut self.work {
+            JobWork::Single(path) => {
+                let stage = match kind {
+                    VpxJobKind::GamedataCode => "gamedata",
+                    VpxJobKind::TableInfo | VpxJobKind::TableInfoBatch => "table_info",
+                };
+                progress::report(0, 1, stage);
+                let result = match kind {
+                    VpxJobKind::GamedataCode => crate::read_gamedata_code_json(path, options),
+                    VpxJobKind::TableInfo | VpxJobKind::TableInfoBatch => crate::read_table_info_json(path, options),
+                };
+                progress::report(1, 1, stage);
+                Step::Finished(result)
+            }
+            JobWork::Batch { paths, next, entries } => {
+                let total = paths.len() as u64;
+                if *next == 0 {
+                    progress::report(0, total, "table_info");
+                }
+                while *next < paths.len() {
+                    let path = &paths[*next];
+                    let profile = profiles::for_table(path, options);
+                    entries.push(batch_entry(path, profile, profiles::scan(path, options, profile)));
+                    *next += 1;
+                    progress::report(*next as u64, total, "table_info");
+                    if *next < paths.len() && queue.lock().has_pending_above(priority) {
+                        return Step::Yielded;
+                    }
+                }
+                let elapsed = started.map_or(0, |started| started.elapsed().as_secs());
+                crate::journal::record_batch("batch_job", crate::store::now().saturating_sub(elapsed), entries);
+                Step::Finished(Ok(Value::Array(std::mem::take(entries)).to_string()))
+            }
+        }
+    }
+
+    /// Hands `result` to the job's callback and forgets the job, or keeps
+    /// both as `status` for `vpx_job_take_result` if it has none.
+    fn finish(self, queue: &JobQueue, status: VpxJobStatus, result: VpxResult) {
+        let mut state = queue.lock();
+        match self.callback {
+            Some(callback) => {
+                state.status.remove(&self.id);
+                drop(state);
+                unsafe { callback(self.id, result.into_raw(), self.userdata.0) };
+            }
+            None => {
+                state.status.insert(self.id, status);
+                state.results.insert(self.id, result);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct QueueState {
+    next_id: u64,
+    pending: [VecDeque<Job>; PRIORITY_COUNT],
+    /// Jobs not handed over yet.
+    status: HashMap<u64, VpxJobStatus>,
+    /// Results of finished jobs submitted without a callback, until taken.
+    results: HashMap<u64, VpxResult>,
+}
+
+impl QueueState {
+    fn pop_most_urgent(&mut self) -> Option<Job> {
+        self.pending.iter_mut().find_map(VecDeque::pop_front)
+    }
+
+    fn has_pending_above(&self, priority: VpxJobPriority) -> bool {
+        self.pending[..priority as usize].iter().any(|q| !q.is_empty())
+    }
+
+    fn remove_pending(&mut self, job_id: u64) -> Option<Job> {
+        self.pending.iter_mut().find_map(|q| {
+            let index = q.iter().position(|job| job.id == job_id)?;
+            q.remove(index)
+        })
+    }
+}
+
+struct JobQueue {
+    state: Mutex<QueueState>,
+    wakeup: Condvar,
+    /// Workers started, 0 until the first submit manages to start one.
+    workers: AtomicUsize,
+}
+
+impl JobQueue {
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+static QUEUE: OnceLock<JobQueue> = OnceLock::new();
+/// Held while the workers are started, so only one thread does it.
+static STARTING: Mutex<()> = Mutex::new(());
+
+/// Number of workers for a configured `thread_count` (0 = one per core).
+pub(crate) fn resolve_thread_count(thread_count: usize) -> usize {
+    if thread_count > 0 {
+        thread_count
+    } else {
+        thread::available_parallelism().map(|n| n.get()).unwrap_or(2)
+    }
+}
+
+/// Workers actually running, or None if no job was ever submitted.
+pub(crate) fn worker_count() -> Option<usize> {
+    QUEUE.get().map(|queue| queue.workers.load(Ordering::Acquire)).filter(|&workers| workers > 0)
+}
+
+/// The queue, starting its workers on first use. Fails when the system
+/// won't start a single thread; the next submit tries again.
+fn queue() -> Result<&'static JobQueue, VpxError> {
+    let queue = QUEUE.get_or_init(|| JobQueue {
+        state: Mutex::new(QueueState {
+            next_id: 1,
+            ..Default::default()
+        }),
+        wakeup: Condvar::new(),
+        workers: AtomicUsize::new(0),
+    });
+    if queue.workers.load(Ordering::Acquire) > 0 {
+        return Ok(queue);
+    }
+    let _starting = STARTING.lock().unwrap_or_else(|e| e.into_inner());
+    let mut failure = None;
+    if queue.workers.load(Ordering::Acquire) == 0 {
+        for i in 0..resolve_thread_count(crate::config::get().thread_count) {
+            match thread::Builder::new().name(format!("vpx-job-{}", i)).spawn(move || worker_loop(queue)) {
+                Ok(_) => _ = queue.workers.fetch_add(1, Ordering::AcqRel),
+                Err(e) => {
+                    log_warn!("Failed to start job worker {}: {}", i, e);
+                    failure = Some(e);
+                    break;
+                }
+            }
+        }
+    }
+    match (queue.workers.load(Ordering::Acquire), failure) {
+        (0, Some(e)) => Err(VpxError::new(VpxErrorCode::AllocFailed, format!("Failed to start job workers: {}", e))),
+        _ => Ok(queue),
+    }
+}
+
+fn worker_loop(queue: &'static JobQueue) {
+    loop {
+        let mut job = {
+            let mut state = queue.lock();
+            loop {
+                if let Some(job) = state.pop_most_urgent() {
+                    state.status.insert(job.id, VpxJobStatus::Running);
+                    break job;
+                }
+                state = queue.wakeup.wait(state).unwrap_or_else(|e| e.into_inner());
+            }
+        };
+
+        let started = *job.started.get_or_insert_with(Instant::now);
+        match progress::in_job(job.id, || job.run(queue)) {
+            Step::Yielded => {
+                let mut state = queue.lock();
+                state.status.insert(job.id, VpxJobStatus::Queued);
+                state.pending[job.priority as usize].push_front(job);
+                drop(state);
+                queue.wakeup.notify_one();
+            }
+            Step::Finished(result) => {
+                if let Err(e) = &result {
+                    log_debug!("vpx job {}: {}", job.id, e);
+                }
+                let result = VpxResult::new(result, started).compressed(&job.options);
+                job.finish(queue, VpxJobStatus::Done, result);
+            }
+        }
+    }
+}
+
+/// # Safety
+/// `params_json` must be null or a valid NUL-terminated string.
+unsafe fn parse_params(params_json: *const c_char) -> Result<Value, VpxError> {
+    if params_json.is_null() {
+        return Err(VpxError::new(VpxErrorCode::NullArgument, "Job params are null."));
+    }
+    let params = unsafe { crate::str_from_c(params_json, "job params") }?;
+    serde_json::from_str(params).map_err(|e| {
+        VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid job params JSON: {}", e))
+    })
+}
+
+/// The optional `"options"` object of the job params.
+fn options_from_params(params: &Value) -> Result<CallOptions, VpxError> {
+    match params.get("options") {
+        Some(options) => CallOptions::from_value(options),
+        None => Ok(CallOptions::default()),
+    }
+}
+
+fn work_from_params(kind: VpxJobKind, params: &Value) -> Result<JobWork, VpxError> {
+    match kind {
+        VpxJobKind::TableInfo | VpxJobKind::GamedataCode => match params.get("path").and_then(Value::as_str) {
+            Some(path) => Ok(JobWork::Single(PathBuf::from(path))),
+            None => Err(VpxError::new(VpxErrorCode::InvalidArgument, "Job params have no \"path\".")),
+        },
+        VpxJobKind::TableInfoBatch => match params.get("paths").and_then(Value::as_array) {
+            Some(paths) => Ok(JobWork::Batch {
+                paths: paths.iter().filter_map(Value::as_str).map(PathBuf::from).collect(),
+                next: 0,
+                entries: Vec::new(),
+            }),
+            None => Err(VpxError::new(VpxErrorCode::InvalidArgument, "Job params have no \"paths\" array.")),
+        },
+    }
+}
+
+/// Queues a job and returns its id.
+fn submit(
+    kind: VpxJobKind,
+    priority: VpxJobPriority,
+    work: JobWork,
+    options: CallOptions,
+    callback: VpxJobCallback,
+    userdata: *mut c_void,
+) -> Result<u64, VpxError> {
+    let queue = queue()?;
+    let mut state = queue.lock();
+    let id = state.next_id;
+    state.next_id += 1;
+    state.status.insert(id, VpxJobStatus::Queued);
+    state.pending[priority as usize].push_back(Job {
+        id,
+        kind,
+        priority,
+        work,
+        options,
+        started: None,
+        callback,
+        userdata: UserData(userdata),
+    });
+    drop(state);
+    queue.wakeup.notify_one();
+    Ok(id)
+}
+
+/// Queues a job with the given `VpxJobPriority` and returns its id, or 0 if
+/// the job could not be submitted (unknown kind or priority, bad params, no
+/// worker thread could be started).
+/// `completion_cb` may be null, and the result is then kept for
+/// `vpx_job_take_result`.
+///
+/// # Safety
+/// `params_json` must be null or a valid NUL-terminated string. `userdata` is
+/// passed back untouched to `completion_cb`, from a worker thread.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_submit_job_with_priority(
+    kind: i32,
+    params_json: *const c_char,
+    priority: i32,
+    completion_cb: VpxJobCallback,
+    userdata: *mut c_void,
+) -> u64 {
+    let Some(kind) = VpxJobKind::from_i32(kind) else {
+        log_error!("vpx_submit_job: Unknown job kind {}", kind);
+        return 0;
+    };
+    let Some(priority) = VpxJobPriority::from_i32(priority) else {
+        log_error!("vpx_submit_job: Unknown job priority {}", priority);
+        return 0;
+    };
+    let parsed = unsafe { parse_params(params_json) }.and_then(|params| {
+        Ok((work_from_params(kind, &params)?, options_from_params(&params)?))
+    });
+    let (work, options) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            log_error!("vpx_submit_job: {}", e);
+            return 0;
+        }
+    };
+
+    submit(kind, priority, work, options, completion_cb, userdata).unwrap_or_else(|e| {
+        log_error!("vpx_submit_job: {}", e);
+        0
+    })
+}
+
+/// Same as `vpx_submit_job_with_priority` at `Interactive` priority.
+///
+/// # Safety
+/// See `vpx_submit_job_with_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_submit_job(
+    kind: i32,
+    params_json: *const c_char,
+    completion_cb: VpxJobCallback,
+    userdata: *mut c_void,
+) -> u64 {
+    unsafe {
+        vpx_submit_job_with_priority(
+            kind,
+            params_json,
+            VpxJobPriority::Interactive as i32,
+            completion_cb,
+            userdata,
+        )
+    }
+}
+
+/// Cancels a job that is waiting in the queue (including a batch that yielded
+/// between files). Its callback still fires, from the calling thread, with a
+/// `Cancelled` result. Returns false if the job is running, finished or unknown.
+#[no_mangle]
+pub extern "C" fn vpx_cancel_job(job_id: u64) -> bool {
+    let Some(queue) = QUEUE.get() else { return false };
+    let Some(job) = queue.lock().remove_pending(job_id) else {
+        return false;
+    };
+    let cancelled = Err(VpxError::new(VpxErrorCode::Cancelled, "Job was cancelled."));
+    let started = job.started.unwrap_or_else(Instant::now);
+    job.finish(queue, VpxJobStatus::Cancelled, VpxResult::new(cancelled, started));
+    true
+}
+
+/// One of the `VpxJobStatus` values. A job with a callback is `Unknown`
+/// again once the callback is called, one without once its result is taken.
+#[no_mangle]
+pub extern "C" fn vpx_get_job_status(job_id: u64) -> i32 {
+    let status = QUEUE.get().and_then(|queue| queue.lock().status.get(&job_id).copied());
+    status.unwrap_or(VpxJobStatus::Unknown) as i32
+}
+
+/// Queues a table info job for the file at `vpx_file_path` at `Interactive`
+/// priority, without a callback: poll `vpx_get_job_status` and fetch the
+/// outcome with `vpx_job_take_result`. Returns the job id, or 0 for a null
+/// or non-UTF-8 path or when no worker thread could be started.
+///
+/// # Safety
+/// `vpx_file_path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_submit_info_job(vpx_file_path: *const c_char) -> u64 {
+    let submitted = unsafe { crate::path_from_c(vpx_file_path) }.and_then(|path| {
+        submit(
+            VpxJobKind::TableInfo,
+            VpxJobPriority::Interactive,
+            JobWork::Single(path),
+            CallOptions::default(),
+            None,
+            std::ptr::null_mut(),
+        )
+    });
+    match submitted {
+        Ok(id) => id,
+        Err(e) => {
+            log_error!("vpx_submit_info_job: {}", e);
+            0
+        }
+    }
+}
+
+/// The result of a job submitted without a callback, once its status is
+/// `Done` or `Cancelled`: it is handed over once, and only kept until then,
+/// so every such job's result should be taken. Returns null while the job
+/// is still queued or running, for jobs with a callback and for results
+/// already taken. Release with `vpx_result_free`.
+#[no_mangle]
+pub extern "C" fn vpx_job_take_result(job_id: u64) -> *mut VpxResult {
+    let Some(queue) = QUEUE.get() else { return std::ptr::null_mut() };
+    let mut state = queue.lock();
+    match state.results.remove(&job_id) {
+        Some(result) => {
+            state.status.remove(&job_id);
+            result.into_raw()
+        }
+        None => std::ptr::null_mut(),
+    }
+}
@@ -0,0 +1,189 @@
+//! Scan journal: one entry per library scan, with what failed and why, kept
+//! in `scan_journal_path` as JSON lines and rotated to the newest
+//! `scan_journal_limit` runs. Support asks users to export it when the same
+//! tables keep failing to parse.
+//!
+//! Table info batch jobs are journaled by the job queue; the frontend's own
+//! scans report themselves with `vpx_record_scan_run`.
+
+use std::fs;
+use std::os::raw::c_char;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::{config, paths, store};
+
+/// Errors kept per run; the rest are only counted.
+const MAX_ERRORS: usize = 500;
+
+static JOURNAL_LOCK: Mutex<()> = Mutex::new(());
+
+/// One failed file of a run.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ScanError {
+    pub path: String,
+    #[serde(default)]
+    pub code: i32,
+    #[serde(default)]
+    pub error: String,
+}
+
+/// A scan run as reported by the frontend or a batch job.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct ScanRun {
+    /// Who ran it: "frontend", "batch_job", ...
+    pub source: String,
+    /// Unix seconds; 0 on a report means "now".
+    pub started: u64,
+    pub finished: u64,
+    pub files: usize,
+    pub errors: Vec<ScanError>,
+}
+
+const KNOWN_KEYS: &[&str] = &["source", "started", "finished", "files", "errors"];
+
+fn journal_path() -> Option<PathBuf> {
+    let path = config::get().scan_journal_path;
+    (!path.is_empty()).then(|| PathBuf::from(path))
+}
+
+fn io_error(what: &str, path: &std::path::Path, e: impl std::fmt::Display) -> VpxError {
+    VpxError::new(VpxErrorCode::OpenFailed, format!("Failed to {} scan journal '{}': {}", what, path.display(), e))
+}
+
+/// The journaled runs, oldest first; lines that do not parse are skipped.
+fn read_runs(path: &std::path::Path) -> Result<Vec<Value>, VpxError> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(text.lines().filter_map(|line| serde_json::from_str(line).ok()).collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(io_error("read", path, e)),
+    }
+}
+
+fn write_runs(path: &std::path::Path, runs: &[Value]) -> Result<(), VpxError> {
+    let text: String = runs.iter().map(|run| format!("{}\n", run)).collect();
+    paths::write_atomic(path, text.as_bytes()).map_err(|e| io_error("write", path, e))
+}
+
+/// Appends `run` to the journal and drops the oldest runs over the limit.
+/// Does nothing without a journal configured.
+pub(crate) fn record(run: ScanRun) -> Result<(), VpxError> {
+    let Some(path) = journal_path() else { return Ok(()) };
+    let now = store::now();
+    let finished = if run.finished == 0 { now } else { run.finished };
+    let failed = run.errors.len();
+    let errors: Vec<Value> = run
+        .errors
+        .iter()
+        .take(MAX_ERRORS)
+        .map(|e| json!({ "path": e.path, "code": e.code, "error": e.error }))
+        .collect();
+    let entry = json!({
+        "source": if run.source.is_empty() { "unknown" } else { run.source.as_str() },
+        "started": if run.started == 0 { finished } else { run.started },
+        "finished": finished,
+        "files": run.files,
+        "ok": run.files.saturating_sub(failed),
+        "failed": failed,
+        "errors": errors,
+        "errors_truncated": failed > MAX_ERRORS,
+    });
+
+    let _guard = JOURNAL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut runs = read_runs(&path)?;
+    runs.push(entry);
+    let excess = runs.len().saturating_sub(config::get().scan_journal_limit.max(1));
+    runs.drain(..excess);
+    write_runs(&path, &runs)
+}
+
+/// Journals a finished table info batch from its `{"path", "ok", "error"}`
+/// entries as a run of `source`, logging rather than failing the batch if
+/// that goes wrong.
+pub(crate) fn record_batch(source: &str, started: u64, entries: &[Value]) {
+    let errors = entries
+        .iter()
+        .filter(|entry| entry["ok"].as_bool() != Some(true))
+        .map(|entry| ScanError {
+            path: entry["path"].as_str().unwrap_or_default().to_string(),
+            code: entry["error"]["code"].as_i64().unwrap_or(0) as i32,
+            error: entry["error"]["message"].as_str().unwrap_or_default().to_string(),
+        })
+        .collect();
+    let run = ScanRun { source: source.to_string(), started, finished: 0, files: entries.len(), errors };
+    if let Err(e) = record(run) {
+        log_warn!("Scan run not journaled: {}", e);
+    }
+}
+
+/// Adds a scan the frontend ran to the journal: `run_json` is `{"source":
+/// "frontend", "started", "finished" (Unix seconds, 0 or missing for now),
+/// "files", "errors": [{"path", "code", "error"}]}`, `code` a
+/// `VpxErrorCode`. Without `scan_journal_path` configured nothing is kept.
+/// Returns a `VpxErrorCode`.
+///
+/// # Safety
+/// `run_json` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_record_scan_run(run_json: *const c_char) -> i32 {
+    let result = if run_json.is_null() {
+        Err(VpxError::new(VpxErrorCode::NullArgument, "run is null"))
+    } else {
+        unsafe { crate::library::options_from_c::<ScanRun>(run_json, KNOWN_KEYS) }.and_then(record)
+    };
+
+    match result {
+        Ok(_) => VpxErrorCode::Ok as i32,
+        Err(e) => {
+            log_error!("vpx_record_scan_run: {}", e);
+            e.code as i32
+        }
+    }
+}
+
+/// The scan journal, `{"runs": [{"source", "started", "finished", "files",
+/// "ok", "failed", "errors": [{"path", "code", "error"}],
+/// "errors_truncated"}]}` newest first; empty without a journal configured.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+#[no_mangle]
+pub extern "C" fn vpx_get_scan_history() -> *mut c_char {
+    let result = match journal_path() {
+        Some(path) => {
+            let _guard = JOURNAL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            read_runs(&path)
+        }
+        None => Ok(Vec::new()),
+    }
+    .and_then(|mut runs| {
+        runs.reverse();
+        crate::into_c_string(json!({ "runs": runs }).to_string())
+    });
+
+    crate::envelope::finish("vpx_get_scan_history", result)
+}
+
+/// Empties the scan journal. Returns a `VpxErrorCode`.
+#[no_mangle]
+pub extern "C" fn vpx_clear_scan_history() -> i32 {
+    let result = match journal_path() {
+        Some(path) => {
+            let _guard = JOURNAL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            write_runs(&path, &[])
+        }
+        None => Ok(()),
+    };
+
+    match result {
+        Ok(_) => VpxErrorCode::Ok as i32,
+        Err(e) => {
+            log_error!("vpx_clear_scan_history: {}", e);
+            e.code as i32
+        }
+    }
+}
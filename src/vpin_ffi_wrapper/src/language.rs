@@ -0,0 +1,144 @@
+//! Predominant language of table text.
+//!
+//! Table descriptions and rules are short, so this only needs to tell the
+//! usual suspects apart: non-Latin scripts by their Unicode blocks, Latin
+//! ones by counting common function words. Text with too little signal is
+//! reported as undetermined (`null`) rather than guessed.
+
+use std::os::raw::c_char;
+use std::path::Path;
+
+use serde_json::{json, Map, Value};
+
+use crate::error::VpxError;
+use crate::options::CallOptions;
+
+/// Function words that are frequent in one language and rare in the others.
+const STOPWORDS: &[(&str, &[&str])] = &[
+    ("en", &["the", "and", "of", "to", "is", "with", "for", "you", "this", "that", "are", "on", "your", "when", "will", "by", "it", "from"]),
+    ("de", &["der", "die", "und", "das", "ist", "nicht", "mit", "ein", "eine", "auf", "sie", "ich", "wird", "den", "auch", "wenn", "zu", "von"]),
+    ("fr", &["le", "la", "les", "et", "est", "une", "des", "pour", "dans", "vous", "pas", "qui", "sur", "avec", "du", "au", "ce", "sont"]),
+    ("es", &["el", "los", "las", "y", "es", "una", "del", "para", "con", "por", "que", "se", "su", "como", "al", "lo", "en"]),
+    ("it", &["il", "di", "che", "e", "gli", "una", "per", "non", "sono", "della", "con", "del", "si", "nel", "alla", "questo", "anche"]),
+    ("pt", &["o", "os", "as", "e", "um", "uma", "do", "da", "para", "com", "não", "que", "em", "dos", "das", "mais", "você"]),
+    ("nl", &["de", "het", "een", "en", "van", "is", "niet", "dat", "op", "te", "zijn", "met", "voor", "je", "ook", "wordt", "bij"]),
+];
+
+/// Below this many function-word hits the text is undetermined.
+const MIN_HITS: usize = 3;
+
+/// Non-Latin scripts, identified by the share of their characters.
+fn script_language(text: &str) -> Option<(&'static str, f64)> {
+    let mut letters = 0usize;
+    let mut counts = [0usize; 5];
+    for c in text.chars().filter(|c| c.is_alphabetic()) {
+        letters += 1;
+        let slot = match c as u32 {
+            0x3040..=0x30FF => 0,                   // Hiragana, Katakana
+            0xAC00..=0xD7AF | 0x1100..=0x11FF => 1, // Hangul
+            0x4E00..=0x9FFF => 2,                   // CJK ideographs
+            0x0400..=0x04FF => 3,                   // Cyrillic
+            0x0370..=0x03FF => 4,                   // Greek
+            _ => continue,
+        };
+        counts[slot] += 1;
+    }
+    if letters == 0 {
+        return None;
+    }
+    // Japanese mixes kana with ideographs; any real share of kana decides it.
+    let (slot, count) = if counts[0] * 10 >= letters {
+        (0, counts[0] + counts[2])
+    } else {
+        counts.iter().copied().enumerate().max_by_key(|(_, n)| *n)?
+    };
+    let share = count as f64 / letters as f64;
+    if share < 0.3 {
+        return None;
+    }
+    Some((["ja", "ko", "zh", "ru", "el"][slot], share.min(1.0)))
+}
+
+/// Detects the predominant language of `text` as an ISO 639-1 code and a
+/// 0..1 confidence, or None when there isn't enough to go on.
+pub(crate) fn detect(text: &str) -> Option<(&'static str, f64)> {
+    if let Some(found) = script_language(text) {
+        return Some(found);
+    }
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let mut scores: Vec<(&str, usize)> = STOPWORDS
+        .iter()
+        .map(|(lang, list)| (*lang, words.iter().filter(|w| list.contains(&w.as_str())).count()))
+        .collect();
+    scores.sort_by_key(|(_, hits)| std::cmp::Reverse(*hits));
+    let (best, hits) = scores[0];
+    if hits < MIN_HITS {
+        return None;
+    }
+    // Confidence is the margin over the runner-up: shared words like "de"
+    // or "e" count for several languages and shouldn't decide anything.
+    let runner_up = scores[1].1;
+    let confidence = (hits - runner_up) as f64 / hits as f64;
+    Some((best, (confidence * 100.0).round() / 100.0))
+}
+
+fn detection_value(text: &str) -> Value {
+    match detect(text) {
+        Some((language, confidence)) => json!({ "language": language, "confidence": confidence }),
+        None => json!({ "language": null, "confidence": 0.0 }),
+    }
+}
+
+const TEXT_FIELDS: &[&str] = &["table_description", "table_rules", "table_blurb"];
+
+/// Language per text field of a table and for all of them together.
+fn table_language(path: &Path) -> Result<Value, VpxError> {
+    let info = crate::read_table_info_value(path, &CallOptions::default())?;
+    let mut fields = Map::new();
+    let mut all = Vec::new();
+    for field in TEXT_FIELDS {
+        if let Some(text) = info[*field].as_str().filter(|t| !t.trim().is_empty()) {
+            fields.insert(field.to_string(), detection_value(text));
+            all.push(text);
+        }
+    }
+    let mut result = detection_value(&all.join("\n"));
+    result["fields"] = Value::Object(fields);
+    Ok(result)
+}
+
+/// Detects the predominant language of `text`.
+///
+/// Returns `{"language": "de" | null, "confidence": 0.0-1.0}`, or null on
+/// bad arguments. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `text` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_detect_language(text: *const c_char) -> *mut c_char {
+    let result = unsafe { crate::str_from_c(text, "text") }
+        .and_then(|text| crate::into_c_string(detection_value(text).to_string()));
+
+    crate::envelope::finish("vpx_detect_language", result)
+}
+
+/// Detects the language of a table's description, rules and blurb, together
+/// and per field: `{"language", "confidence", "fields": {"table_rules":
+/// {"language", "confidence"}, ...}}`.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `vpx_file_path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_table_language(vpx_file_path: *const c_char) -> *mut c_char {
+    let result = unsafe { crate::path_from_c(vpx_file_path) }
+        .and_then(|path| table_language(&path))
+        .and_then(|language| crate::into_c_string(language.to_string()));
+
+    crate::envelope::finish("vpx_get_table_language", result)
+}
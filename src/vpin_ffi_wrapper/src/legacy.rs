@@ -0,0 +1,49 @@
+//! ABI v0 entry points.
+//!
+//! These are the original, unprefixed symbol names the frontend linked against
+//! before everything moved to the `vpx_` prefix. They forward to the prefixed
+//! functions and are kept so older callers keep linking; new code should not
+//! use them.
+
+use std::os::raw::c_char;
+
+/// Legacy alias of [`crate::vpx_get_table_info_as_json`].
+///
+/// # Safety
+/// Same contract as [`crate::vpx_get_table_info_as_json`].
+#[deprecated(note = "use vpx_get_table_info_as_json")]
+#[no_mangle]
+pub unsafe extern "C" fn get_vpx_table_info_as_json(vpx_file_path: *const c_char) -> *mut c_char {
+    crate::vpx_get_table_info_as_json(vpx_file_path)
+}
+
+/// Legacy alias of [`crate::vpx_get_gamedata_code`].
+///
+/// # Safety
+/// Same contract as [`crate::vpx_get_gamedata_code`].
+#[deprecated(note = "use vpx_get_gamedata_code")]
+#[no_mangle]
+pub unsafe extern "C" fn get_vpx_gamedata_code(vpx_file_path: *const c_char) -> *mut c_char {
+    crate::vpx_get_gamedata_code(vpx_file_path)
+}
+
+/// Legacy alias of [`crate::vpx_free_string`].
+///
+/// # Safety
+/// Same contract as [`crate::vpx_free_string`].
+#[deprecated(note = "use vpx_free_string")]
+#[no_mangle]
+pub unsafe extern "C" fn free_rust_string(s: *mut c_char) {
+    crate::vpx_free_string(s)
+}
+
+/// Alias of [`crate::buffer::vpx_free_buffer`], the buffer counterpart of
+/// `free_rust_string` for callers still on these names.
+///
+/// # Safety
+/// Same contract as [`crate::buffer::vpx_free_buffer`].
+#[deprecated(note = "use vpx_free_buffer")]
+#[no_mangle]
+pub unsafe extern "C" fn free_rust_buffer(buffer: crate::buffer::VpxBuffer) {
+    crate::buffer::vpx_free_buffer(buffer)
+}
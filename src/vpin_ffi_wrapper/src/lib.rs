@@ -1,187 +1,708 @@
 use std::os::raw::c_char;
 use std::ffi::{CString, CStr};
-use std::path::PathBuf;
-use std::panic::catch_unwind;
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 use serde_json::{json, Value};
-use vpin::vpx::open;
+use sha2::{Digest, Sha256};
+use vpin::vpx::VpxFile;
 
-#[no_mangle]
-pub extern "C" fn get_vpx_table_info_as_json(vpx_file_path: *const c_char) -> *mut c_char {
+#[macro_use]
+mod logging;
+
+mod allocations;
+mod attract;
+mod b2s;
+mod batch;
+mod buffer;
+mod bundle;
+mod bytepath;
+mod cancel;
+mod cards;
+mod collation;
+mod compare;
+mod compress;
+mod config;
+mod content;
+mod diagnostics;
+mod downloads;
+mod envelope;
+mod error;
+mod events;
+#[cfg(unix)]
+mod fd;
+mod features;
+mod filter;
+mod flags;
+mod handle;
+mod hashes;
+mod health;
+mod import;
+mod index;
+mod jobs;
+mod journal;
+mod language;
+mod legacy;
+mod library;
+mod lighting;
+mod locks;
+mod media;
+mod memory;
+mod merge;
+mod multipart;
+mod naming;
+mod oplog;
+mod options;
+mod orphans;
+mod parallel;
+mod panics;
+mod paths;
+mod physics;
+mod picks;
+mod playlist;
+mod profiles;
+mod progress;
+mod provenance;
+mod recent;
+mod registry;
+mod relocate;
+mod remove;
+mod result;
+mod review;
+mod roms;
+mod roots;
+mod rules;
+mod safe_mode;
+mod scan;
+mod script;
+mod searches;
+mod sevenzip;
+mod sounds;
+mod source;
+mod stats;
+mod store;
+mod stream;
+mod tagging;
+mod tags;
+mod textures;
+mod titles;
+mod vbs;
+mod vr;
+mod watchdog;
+mod wheel;
+mod wide;
+
+use buffer::VpxBuffer;
+use config::IoStrategy;
+use error::{VpxError, VpxErrorCode};
+use handle::OpenTable;
+use options::CallOptions;
+use result::VpxResult;
+use source::VpxSource;
+
+/// Converts a C path argument into a `PathBuf`.
+///
+/// # Safety
+/// `vpx_file_path` must be null or a valid NUL-terminated string.
+pub(crate) unsafe fn path_from_c(vpx_file_path: *const c_char) -> Result<PathBuf, VpxError> {
     if vpx_file_path.is_null() {
-        eprintln!("get_vpx_table_info_as_json: Input file path is null.");
-        return std::ptr::null_mut();
+        return Err(VpxError::new(VpxErrorCode::NullArgument, "Input file path is null."));
     }
+    match unsafe { CStr::from_ptr(vpx_file_path) }.to_str() {
+        Ok(s) => Ok(PathBuf::from(s)),
+        Err(e) => Err(VpxError::new(
+            VpxErrorCode::InvalidUtf8Path,
+            format!("Invalid UTF-8 in path: {}", e),
+        )),
+    }
+}
 
-    let path_str = unsafe {
-        match CStr::from_ptr(vpx_file_path).to_str() {
-            Ok(s) => s,
-            Err(_e) => {
-                eprintln!("get_vpx_table_info_as_json: Invalid UTF-8 in path: {}", _e);
-                return std::ptr::null_mut();
-            }
+/// Borrows a C string argument as UTF-8; `what` names it in error messages.
+///
+/// # Safety
+/// `s` must be null or a valid NUL-terminated string that outlives `'a`.
+pub(crate) unsafe fn str_from_c<'a>(s: *const c_char, what: &str) -> Result<&'a str, VpxError> {
+    if s.is_null() {
+        return Err(VpxError::new(VpxErrorCode::NullArgument, format!("{} is null.", what)));
+    }
+    unsafe { CStr::from_ptr(s) }.to_str().map_err(|e| {
+        VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid UTF-8 in {}: {}", what, e))
+    })
+}
+
+/// Hands a Rust string over to C. Release with `vpx_free_string`.
+pub(crate) fn into_c_string(s: String) -> Result<*mut c_char, VpxError> {
+    let c_string = CString::new(s).map_err(|e| {
+        VpxError::new(VpxErrorCode::AllocFailed, format!("CString conversion failed: {}", e))
+    })?;
+    let len = c_string.as_bytes_with_nul().len();
+    let ptr = c_string.into_raw();
+    allocations::track(ptr, allocations::Kind::String, len, unsafe { CStr::from_ptr(ptr) }.to_bytes());
+    Ok(ptr)
+}
+
+/// `into_c_string` that also gives the length of `s` in bytes, for the
+/// `_with_len` calls.
+pub(crate) fn into_sized_c_string(s: String) -> Result<(*mut c_char, usize), VpxError> {
+    let len = s.len();
+    into_c_string(s).map(|ptr| (ptr, len))
+}
+
+/// Runs `f`, turning a panic into a `VpxErrorCode::Panic` error carrying
+/// the panic message and where it happened.
+pub(crate) fn catch_panic<T>(path: &Path, f: impl FnOnce() -> Result<T, VpxError>) -> Result<T, VpxError> {
+    panics::catch(f).unwrap_or_else(|report| {
+        panics::remember(&report, &path.to_string_lossy());
+        let location = report.location.map(|location| format!(" at {}", location)).unwrap_or_default();
+        Err(VpxError::new(
+            VpxErrorCode::Panic,
+            format!("Panic for '{}'{}: {}", path.display(), location, report.message),
+        ))
+    })
+}
+
+/// Lowercase hex SHA-256, the same format as the frontend's `hashFromVpx`.
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn open_error(path: &Path, e: io::Error) -> VpxError {
+    let code = if e.kind() == io::ErrorKind::NotFound {
+        VpxErrorCode::FileNotFound
+    } else {
+        VpxErrorCode::OpenFailed
+    };
+    VpxError::new(code, format!("Failed to open '{}': {}", path.display(), e))
+}
+
+/// Opens the .vpx file at `path` following the configured IO strategy and
+/// limits, with per-call overrides from `options`.
+pub(crate) fn open_vpx(path: &Path, options: &CallOptions) -> Result<VpxFile<VpxSource>, VpxError> {
+    cancel::check(path)?;
+    let file = File::open(path).map_err(|e| open_error(path, e))?;
+    open_vpx_file(path, file, options)
+}
+
+/// `open_vpx` on a file that is already open; `path` names it in messages.
+pub(crate) fn open_vpx_file(path: &Path, file: File, options: &CallOptions) -> Result<VpxFile<VpxSource>, VpxError> {
+    let config = config::get();
+    let max_file_size_mb = options.max_file_size_mb(&config);
+    if max_file_size_mb > 0 {
+        let len = file.metadata().map_err(|e| open_error(path, e))?.len();
+        if len > max_file_size_mb * 1024 * 1024 {
+            return Err(VpxError::new(
+                VpxErrorCode::LimitExceeded,
+                format!(
+                    "'{}' is {} bytes, over the {} MB limit",
+                    path.display(),
+                    len,
+                    max_file_size_mb
+                ),
+            ));
         }
+    }
+    let source = match options.io_strategy(&config) {
+        IoStrategy::File => VpxSource::File(file),
+        IoStrategy::Memory => VpxSource::Memory(Cursor::new(read_whole(path, file)?)),
     };
+    let file = VpxFile::open(source).map_err(|e| open_error(path, e))?;
+    cancel::check(path)?;
+    Ok(file)
+}
 
-    let path = PathBuf::from(path_str);
-    // eprintln!("get_vpx_table_info_as_json: Processing file '{}'", path_str);
-
-    let result = catch_unwind(|| {
-        // eprintln!("get_vpx_table_info_as_json: Opening file '{}'", path_str);
-        match vpin::vpx::open(&path) {
-            Ok(mut vpx_file) => {
-                // eprintln!("get_vpx_table_info_as_json: Reading table info for '{}'", path_str);
-                match vpx_file.read_tableinfo() {
-                    Ok(table_info) => {
-                        // eprintln!("get_vpx_table_info_as_json: Table info read succeeded for '{}'", path_str);
-                        let mut json_object = json!({
-                            "table_name": table_info.table_name,
-                            "author_name": table_info.author_name,
-                            "table_blurb": table_info.table_blurb,
-                            "table_rules": table_info.table_rules,
-                            "author_email": table_info.author_email,
-                            "release_date": table_info.release_date,
-                            "table_save_rev": table_info.table_save_rev,
-                            "table_version": table_info.table_version,
-                            "author_website": table_info.author_website,
-                            "table_save_date": table_info.table_save_date,
-                            "table_description": table_info.table_description,
-                        });
-
-                        // eprintln!("get_vpx_table_info_as_json: Building properties for '{}'", path_str);
-                        let mut properties_obj = serde_json::Map::new();
-                        for (key, value) in table_info.properties {
-                            // eprintln!("get_vpx_table_info_as_json: Adding property '{}' = '{}' for '{}'", key, value, path_str);
-                            properties_obj.insert(key, Value::String(value));
-                        }
-                        json_object["properties"] = Value::Object(properties_obj);
-
-                        // eprintln!("get_vpx_table_info_as_json: Serializing JSON for '{}'", path_str);
-                        let json_string = match serde_json::to_string(&json_object) {
-                            Ok(s) => s,
-                            Err(_e) => {
-                                eprintln!("get_vpx_table_info_as_json: JSON serialization failed for '{}': {}", path_str, _e);
-                                return None;
-                            }
-                        };
-
-                        // eprintln!("get_vpx_table_info_as_json: Converting to CString for '{}'", path_str);
-                        match CString::new(json_string) {
-                            Ok(c_string) => {
-                                // eprintln!("get_vpx_table_info_as_json: Success for '{}'", path_str);
-                                Some(c_string.into_raw())
-                            }
-                            Err(_e) => {
-                                eprintln!("get_vpx_table_info_as_json: CString conversion failed for '{}': {}", path_str, _e);
-                                None
-                            }
-                        }
-                    }
-                    Err(_e) => {
-                        eprintln!("get_vpx_table_info_as_json: Failed to read table info for '{}': {}", path_str, _e);
-                        None
-                    }
-                }
-            }
-            Err(_e) => {
-                eprintln!("get_vpx_table_info_as_json: Failed to open '{}': {}", path_str, _e);
-                return None;
-            }
+/// Reads `file` into memory, a chunk at a time so a cancelled call does not
+/// wait for the whole file and a job can report progress.
+fn read_whole(path: &Path, mut file: File) -> Result<Vec<u8>, VpxError> {
+    const CHUNK: usize = 8 * 1024 * 1024;
+    // A descriptor from the frontend may be anywhere in the file.
+    file.rewind().map_err(|e| open_error(path, e))?;
+    let len = file.metadata().map_or(0, |m| m.len());
+    let mut data = Vec::with_capacity(len as usize);
+    loop {
+        cancel::check(path)?;
+        let read = (&mut file).take(CHUNK as u64).read_to_end(&mut data).map_err(|e| open_error(path, e))?;
+        progress::report(data.len() as u64, len.max(data.len() as u64), "read");
+        if read < CHUNK {
+            return Ok(data);
         }
+    }
+}
+
+/// Builds the table info JSON document for the .vpx file at `path`.
+pub(crate) fn read_table_info_value(path: &Path, options: &CallOptions) -> Result<Value, VpxError> {
+    let (owned, options) = (path.to_path_buf(), options.clone());
+    cancel::with_timeout(path, options.timeout_ms, move || OpenTable::open(&owned, &options)?.table_info())
+}
+
+/// The top-level keys of the table info document, besides `warnings`.
+pub(crate) const TABLE_INFO_FIELDS: &[&str] = &[
+    "table_name",
+    "author_name",
+    "table_blurb",
+    "table_rules",
+    "author_email",
+    "release_date",
+    "table_save_rev",
+    "table_version",
+    "author_website",
+    "table_save_date",
+    "table_description",
+    "properties",
+];
+
+pub(crate) fn table_info_to_json(table_info: vpin::vpx::tableinfo::TableInfo) -> Value {
+    let mut json_object = json!({
+        "table_name": table_info.table_name,
+        "author_name": table_info.author_name,
+        "table_blurb": table_info.table_blurb,
+        "table_rules": table_info.table_rules,
+        "author_email": table_info.author_email,
+        "release_date": table_info.release_date,
+        "table_save_rev": table_info.table_save_rev,
+        "table_version": table_info.table_version,
+        "author_website": table_info.author_website,
+        "table_save_date": table_info.table_save_date,
+        "table_description": table_info.table_description,
     });
 
-    match result {
-        Ok(Some(ptr)) => ptr,
-        Ok(None) => {
-            eprintln!("get_vpx_table_info_as_json: Returning null for '{}'", path_str);
-            std::ptr::null_mut()
-        }
-        Err(_e) => {
-            eprintln!("get_vpx_table_info_as_json Panic occurred for '{}'", path_str);
-            std::ptr::null_mut()
-        }
+    let mut properties_obj = serde_json::Map::new();
+    for (key, value) in table_info.properties {
+        properties_obj.insert(key, Value::String(value));
     }
+    json_object["properties"] = Value::Object(properties_obj);
+    json_object
+}
+
+/// Same as `read_table_info_value`, serialized.
+pub(crate) fn read_table_info_json(path: &Path, options: &CallOptions) -> Result<String, VpxError> {
+    let json_object = read_table_info_value(path, options)?;
+    serde_json::to_string(&json_object).map_err(|e| {
+        VpxError::new(
+            VpxErrorCode::AllocFailed,
+            format!("JSON serialization failed for '{}': {}", path.display(), e),
+        )
+    })
+}
+
+/// Reads the script embedded in the GameData stream of the .vpx file at `path`.
+pub(crate) fn read_gamedata_code(path: &Path, options: &CallOptions) -> Result<String, VpxError> {
+    let (owned, options) = (path.to_path_buf(), options.clone());
+    cancel::with_timeout(path, options.timeout_ms, move || OpenTable::open(&owned, &options)?.code())
+}
+
+/// The script of the .vpx file at `path`, as a `{"code": "<script>"}` document
+/// (see `OpenTable::code_document`).
+pub(crate) fn read_gamedata_code_json(path: &Path, options: &CallOptions) -> Result<String, VpxError> {
+    let (owned, options) = (path.to_path_buf(), options.clone());
+    cancel::with_timeout(path, options.timeout_ms, move || OpenTable::open(&owned, &options)?.code_document())
 }
 
+/// Reads the TableInfo stream of a .vpx file and returns it as a JSON string.
+///
+/// Returns null on failure. The result must be released with `vpx_free_string`.
+///
+/// # Safety
+/// `vpx_file_path` must be null or a valid NUL-terminated string.
 #[no_mangle]
-pub extern "C" fn get_vpx_gamedata_code(vpx_file_path: *const c_char) -> *mut c_char {
-    // Safety check for null pointer
-    if vpx_file_path.is_null() {
-        eprintln!("get_vpx_gamedata_code: Input file path is null.");
-        return std::ptr::null_mut();
+pub unsafe extern "C" fn vpx_get_table_info_as_json(vpx_file_path: *const c_char) -> *mut c_char {
+    unsafe { vpx_get_table_info_as_json_ex(vpx_file_path, std::ptr::null()) }
+}
+
+/// `vpx_get_table_info_as_json` with per-call options (`options_json` may be null).
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_table_info_as_json_ex(
+    vpx_file_path: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    unsafe { vpx_get_table_info_as_json_err(vpx_file_path, options_json, std::ptr::null_mut()) }
+}
+
+/// Reads the GameData stream of a .vpx file and returns the embedded script.
+///
+/// Returns null on failure. The result must be released with `vpx_free_string`.
+///
+/// # Safety
+/// `vpx_file_path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_gamedata_code(vpx_file_path: *const c_char) -> *mut c_char {
+    unsafe { vpx_get_gamedata_code_ex(vpx_file_path, std::ptr::null()) }
+}
+
+/// `vpx_get_gamedata_code` with per-call options (`options_json` may be null).
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_gamedata_code_ex(
+    vpx_file_path: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    unsafe { vpx_get_gamedata_code_err(vpx_file_path, options_json, std::ptr::null_mut()) }
+}
+
+/// Stores the outcome of a call in its nullable `error_code` out-parameter.
+///
+/// # Safety
+/// `error_code` must be null or valid for a write.
+pub(crate) unsafe fn set_error_code<T>(error_code: *mut i32, result: &Result<T, VpxError>) {
+    if let Some(out) = unsafe { error_code.as_mut() } {
+        *out = match result {
+            Ok(_) => VpxErrorCode::Ok as i32,
+            Err(e) => e.code as i32,
+        };
     }
+}
 
-    // Convert C string to Rust string
-    let path_str = unsafe {
-        match CStr::from_ptr(vpx_file_path).to_str() {
-            Ok(s) => s,
-            Err(e) => {
-                eprintln!("get_vpx_gamedata_code: Invalid UTF-8 in path: {}", e);
-                return std::ptr::null_mut();
-            }
-        }
-    };
+/// `vpx_get_table_info_as_json_ex` that also says why it failed: the
+/// `VpxErrorCode` is stored in `error_code` (nullable), `Ok` on success.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `vpx_file_path` and `options_json` must be null or valid NUL-terminated
+/// strings, `error_code` null or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_table_info_as_json_err(
+    vpx_file_path: *const c_char,
+    options_json: *const c_char,
+    error_code: *mut i32,
+) -> *mut c_char {
+    let result = unsafe { CallOptions::from_c(options_json) }
+        .and_then(|options| Ok((unsafe { path_from_c(vpx_file_path) }?, options)))
+        .and_then(|(path, options)| read_table_info_json(&path, &options))
+        .and_then(into_c_string);
+    unsafe { set_error_code(error_code, &result) };
+
+    crate::envelope::finish("vpx_get_table_info_as_json", result)
+}
+
+/// `vpx_get_table_info_as_json_err` that also stores the length of the
+/// returned string in bytes, without the NUL, in `out_len` (nullable; 0 on
+/// failure), so the frontend can adopt a large document without a
+/// `strlen`. With `result_envelope` on it is the envelope's length.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `vpx_file_path` and `options_json` must be null or valid NUL-terminated
+/// strings, `out_len` and `error_code` null or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_table_info_as_json_with_len(
+    vpx_file_path: *const c_char,
+    options_json: *const c_char,
+    out_len: *mut usize,
+    error_code: *mut i32,
+) -> *mut c_char {
+    let result = unsafe { CallOptions::from_c(options_json) }
+        .and_then(|options| Ok((unsafe { path_from_c(vpx_file_path) }?, options)))
+        .and_then(|(path, options)| read_table_info_json(&path, &options))
+        .and_then(into_sized_c_string);
+    unsafe { set_error_code(error_code, &result) };
 
-    let path = PathBuf::from(path_str);
-
-    // Use catch_unwind to handle potential panics
-    let result = catch_unwind(|| {
-        // Open the VPX file
-        match open(&path) {
-            Ok(mut vpx_file) => {
-                // Read only the GameData stream
-                match vpx_file.read_gamedata() {
-                    Ok(gamedata) => {
-                        let code = gamedata.code.string;
-                        match CString::new(code) {
-                            Ok(c_string) => Some(c_string.into_raw()),
-                            Err(e) => {
-                                eprintln!(
-                                    "get_vpx_gamedata_code: CString conversion failed for '{}': {}",
-                                    path_str, e
-                                );
-                                None
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!(
-                            "get_vpx_gamedata_code: Failed to read gamedata for '{}': {}",
-                            path_str, e
-                        );
-                        None
-                    }
+    unsafe { crate::envelope::finish_with_len("vpx_get_table_info_as_json_with_len", result, out_len) }
+}
+
+/// Table info, script details and file stats of the table at `path` from a
+/// single open: `{"table_info", "rom", "script_sha256", "size",
+/// "modified"}`. With `lenient` an unreadable script leaves `rom` and
+/// `script_sha256` null and adds to `table_info.warnings`.
+fn read_summary(path: &Path, options: &CallOptions) -> Result<Value, VpxError> {
+    let metadata = std::fs::metadata(path).map_err(|e| open_error(path, e))?;
+    let modified = metadata.modified().ok().and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok());
+    let (owned, options) = (path.to_path_buf(), options.clone());
+    let (table_info, code) = cancel::with_timeout(path, options.timeout_ms, move || {
+        let mut table = OpenTable::open(&owned, &options)?;
+        let mut table_info = table.table_info()?;
+        match table.code() {
+            Ok(code) => Ok((table_info, Some(code))),
+            Err(e) if options.lenient => {
+                log_warn!("{}", e);
+                match table_info["warnings"].as_array_mut() {
+                    Some(warnings) => warnings.push(json!(e.message)),
+                    None => table_info["warnings"] = json!([e.message]),
                 }
+                Ok((table_info, None))
             }
-            Err(e) => {
-                eprintln!(
-                    "get_vpx_gamedata_code: Failed to open '{}': {}",
-                    path_str, e
-                );
-                None
+            Err(e) => Err(e),
+        }
+    })?;
+    Ok(json!({
+        "table_info": table_info,
+        "rom": code.as_deref().and_then(script::rom_name),
+        "script_sha256": code.as_deref().map(|code| sha256_hex(code.as_bytes())),
+        "size": metadata.len(),
+        "modified": modified.map(|d| d.as_secs()),
+    }))
+}
+
+/// Everything a scan wants to know about one table in one open and parse:
+/// `{"table_info": {...as vpx_get_table_info_as_json}, "rom" (the script's
+/// `cGameName`, or null), "script_sha256" (the frontend's `hashFromVpx`),
+/// "size", "modified" (Unix seconds)}`. Per-call options as for the `_ex`
+/// calls; `error_code` (nullable) as for `vpx_get_table_info_as_json_err`.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `vpx_file_path` and `options_json` must be null or valid NUL-terminated
+/// strings, `error_code` null or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_summary_as_json(
+    vpx_file_path: *const c_char,
+    options_json: *const c_char,
+    error_code: *mut i32,
+) -> *mut c_char {
+    let result = unsafe { CallOptions::from_c(options_json) }
+        .and_then(|options| Ok((unsafe { path_from_c(vpx_file_path) }?, options)))
+        .and_then(|(path, options)| read_summary(&path, &options))
+        .and_then(|summary| into_c_string(summary.to_string()));
+    unsafe { set_error_code(error_code, &result) };
+
+    crate::envelope::finish("vpx_get_summary_as_json", result)
+}
+
+/// `vpx_get_table_info_as_json_err` for a table the frontend has seen
+/// before: `known_sha256` (nullable) is the whole-file SHA-256 it got last
+/// time. If the file still hashes to it the table is not parsed and the
+/// call returns null with `NotModified`; otherwise `{"sha256", "table_info"}`
+/// with the hash to keep for next time.
+///
+/// Returns null on failure or when unchanged. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `vpx_file_path`, `known_sha256` and `options_json` must be null or valid
+/// NUL-terminated strings, `error_code` null or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_table_info_if_changed(
+    vpx_file_path: *const c_char,
+    known_sha256: *const c_char,
+    options_json: *const c_char,
+    error_code: *mut i32,
+) -> *mut c_char {
+    let result = unsafe { CallOptions::from_c(options_json) }
+        .and_then(|options| Ok((unsafe { path_from_c(vpx_file_path) }?, options)))
+        .and_then(|(path, options)| {
+            let known = match known_sha256.is_null() {
+                true => None,
+                false => Some(unsafe { str_from_c(known_sha256, "known hash") }?),
+            };
+            let sha256 = hashes::sha256_file(&path).map_err(|e| open_error(&path, e))?;
+            if known.is_some_and(|known| known.trim().eq_ignore_ascii_case(&sha256)) {
+                return Err(VpxError::new(
+                    VpxErrorCode::NotModified,
+                    format!("'{}' is unchanged ({})", path.display(), sha256),
+                ));
             }
+            let table_info = read_table_info_value(&path, &options)?;
+            into_c_string(json!({ "sha256": sha256, "table_info": table_info }).to_string())
+        });
+    unsafe { set_error_code(error_code, &result) };
+
+    crate::envelope::finish("vpx_get_table_info_if_changed", result)
+}
+
+/// `vpx_get_gamedata_code_ex` with the `VpxErrorCode` stored in
+/// `error_code` (nullable), as `vpx_get_table_info_as_json_err` does.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `vpx_file_path` and `options_json` must be null or valid NUL-terminated
+/// strings, `error_code` null or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_gamedata_code_err(
+    vpx_file_path: *const c_char,
+    options_json: *const c_char,
+    error_code: *mut i32,
+) -> *mut c_char {
+    let result = unsafe { CallOptions::from_c(options_json) }
+        .and_then(|options| Ok((unsafe { path_from_c(vpx_file_path) }?, options)))
+        .and_then(|(path, options)| read_gamedata_code(&path, &options))
+        .and_then(into_c_string);
+    unsafe { set_error_code(error_code, &result) };
+
+    match result {
+        Ok(ptr) => ptr,
+        Err(e) => {
+            log_error!("vpx_get_gamedata_code: {}", e);
+            std::ptr::null_mut()
         }
+    }
+}
+
+/// `vpx_get_gamedata_code_err` that also stores the length of the script
+/// in bytes, without the NUL, in `out_len` (nullable; 0 on failure).
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `vpx_file_path` and `options_json` must be null or valid NUL-terminated
+/// strings, `out_len` and `error_code` null or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_gamedata_code_with_len(
+    vpx_file_path: *const c_char,
+    options_json: *const c_char,
+    out_len: *mut usize,
+    error_code: *mut i32,
+) -> *mut c_char {
+    let result = unsafe { CallOptions::from_c(options_json) }
+        .and_then(|options| Ok((unsafe { path_from_c(vpx_file_path) }?, options)))
+        .and_then(|(path, options)| read_gamedata_code(&path, &options))
+        .and_then(into_sized_c_string);
+    unsafe { set_error_code(error_code, &result) };
+
+    let (ptr, len) = result.unwrap_or_else(|e| {
+        log_error!("vpx_get_gamedata_code_with_len: {}", e);
+        (std::ptr::null_mut(), 0)
     });
+    if let Some(out) = unsafe { out_len.as_mut() } {
+        *out = len;
+    }
+    ptr
+}
+
+/// `vpx_get_table_info_as_json_ex` returning the JSON as a `VpxBuffer`
+/// (UTF-8, no trailing NUL).
+///
+/// `data` is null on failure. Release with `vpx_free_buffer`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_table_info_as_buffer(
+    vpx_file_path: *const c_char,
+    options_json: *const c_char,
+) -> VpxBuffer {
+    let result = unsafe { CallOptions::from_c(options_json) }
+        .and_then(|options| Ok((unsafe { path_from_c(vpx_file_path) }?, options)))
+        .and_then(|(path, options)| {
+            let table_info = read_table_info_value(&path, &options)?;
+            compress::encode(table_info.to_string().into_bytes(), &options)
+        });
 
-    // Handle the result
     match result {
-        Ok(Some(ptr)) => ptr,
-        Ok(None) => {
-            eprintln!("get_vpx_gamedata_code: Returning null for '{}'", path_str);
-            std::ptr::null_mut()
+        Ok(bytes) => VpxBuffer::new(bytes),
+        Err(e) => {
+            log_error!("vpx_get_table_info_as_buffer: {}", e);
+            VpxBuffer::null()
         }
-        Err(_) => {
-            eprintln!("get_vpx_gamedata_code: Panic occurred for '{}'", path_str);
-            std::ptr::null_mut()
+    }
+}
+
+/// `vpx_get_gamedata_code_ex` returning the script as a `VpxBuffer`
+/// (UTF-8, no trailing NUL), NUL bytes and all.
+///
+/// `data` is null on failure. Release with `vpx_free_buffer`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_gamedata_code_as_buffer(
+    vpx_file_path: *const c_char,
+    options_json: *const c_char,
+) -> VpxBuffer {
+    let result = unsafe { CallOptions::from_c(options_json) }
+        .and_then(|options| Ok((unsafe { path_from_c(vpx_file_path) }?, options)))
+        .and_then(|(path, options)| compress::encode(read_gamedata_code(&path, &options)?.into_bytes(), &options));
+
+    match result {
+        Ok(bytes) => VpxBuffer::new(bytes),
+        Err(e) => {
+            log_error!("vpx_get_gamedata_code_as_buffer: {}", e);
+            VpxBuffer::null()
         }
     }
 }
 
+/// Same as `vpx_get_table_info_as_json`, but wrapped in a `VpxResult`.
+///
+/// Never returns null. Release with `vpx_result_free`.
+///
+/// # Safety
+/// `vpx_file_path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_table_info_result(vpx_file_path: *const c_char) -> *mut VpxResult {
+    unsafe { vpx_get_table_info_result_ex(vpx_file_path, std::ptr::null()) }
+}
+
+/// `vpx_get_table_info_result` with per-call options (`options_json` may be null).
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_table_info_result_ex(
+    vpx_file_path: *const c_char,
+    options_json: *const c_char,
+) -> *mut VpxResult {
+    let started = Instant::now();
+    let result = unsafe { CallOptions::from_c(options_json) }
+        .and_then(|options| Ok((unsafe { path_from_c(vpx_file_path) }?, options)))
+        .and_then(|(path, options)| read_table_info_json(&path, &options));
+    if let Err(e) = &result {
+        log_debug!("vpx_get_table_info_result: {}", e);
+    }
+    VpxResult::new(result, started).into_raw()
+}
+
+/// Same as `vpx_get_gamedata_code`, wrapped in a `VpxResult` whose JSON
+/// payload is `{"code": "<script>"}`.
+///
+/// Never returns null. Release with `vpx_result_free`.
+///
+/// # Safety
+/// `vpx_file_path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_gamedata_code_result(vpx_file_path: *const c_char) -> *mut VpxResult {
+    unsafe { vpx_get_gamedata_code_result_ex(vpx_file_path, std::ptr::null()) }
+}
+
+/// `vpx_get_gamedata_code_result` with per-call options (`options_json` may be null).
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
 #[no_mangle]
-pub extern "C" fn free_rust_string(s: *mut c_char) {
+pub unsafe extern "C" fn vpx_get_gamedata_code_result_ex(
+    vpx_file_path: *const c_char,
+    options_json: *const c_char,
+) -> *mut VpxResult {
+    let started = Instant::now();
+    let result = unsafe { CallOptions::from_c(options_json) }
+        .and_then(|options| Ok((unsafe { path_from_c(vpx_file_path) }?, options)))
+        .and_then(|(path, options)| read_gamedata_code_json(&path, &options));
+    if let Err(e) = &result {
+        log_debug!("vpx_get_gamedata_code_result: {}", e);
+    }
+    VpxResult::new(result, started).into_raw()
+}
+
+/// Releases a string returned by any of the `vpx_*` functions.
+///
+/// # Safety
+/// `s` must be null or a pointer previously returned by this library, not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_free_string(s: *mut c_char) {
     if s.is_null() {
         return;
     }
+    allocations::untrack(s);
     unsafe {
         _ = CString::from_raw(s);
     }
-}
\ No newline at end of file
+}
+
+/// Version of the C ABI in include/vpin_wrapper.h: bumped whenever an
+/// exported signature, struct layout or enum value changes or a function is
+/// removed, not for additions.
+pub const VPX_FFI_ABI_VERSION: u32 = 1;
+
+/// The `VPX_FFI_ABI_VERSION` this library was built with, for the frontend
+/// to compare against the one in the header it was compiled with before
+/// calling anything else.
+#[no_mangle]
+pub extern "C" fn vpx_get_ffi_abi_version() -> u32 {
+    VPX_FFI_ABI_VERSION
+}
@@ -0,0 +1,206 @@
+//! Library layout shared by the import/remove/move operations.
+//!
+//! Passed as `library_config_json`, mirroring the frontend settings that
+//! matter to file operations.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_json::Value;
+use sha1::{Digest, Sha1};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::{config, paths};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct LibraryConfig {
+    /// Root folder holding one folder per table (`VPXTablesPath`).
+    pub tables_path: Option<PathBuf>,
+    /// The frontend's `asapcab_index.json` (`indexPath`). Without it the
+    /// index is left alone.
+    pub index_path: Option<PathBuf>,
+    /// The frontend's thumbnail cache (`previewCacheDir`), whose entries
+    /// are named after a SHA-1 of the media path.
+    pub preview_cache_dir: Option<PathBuf>,
+    /// Table roots for a library split across drives, highest precedence
+    /// first. Empty means `tables_path` alone.
+    pub roots: Vec<LibraryRoot>,
+}
+
+/// One folder of tables in a multi-root library.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct LibraryRoot {
+    pub path: PathBuf,
+    /// Disabled roots are skipped, as if unplugged.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Display name ("SSD", "USB drive"); defaults to the path. Also names
+    /// the root in virtual index paths (`root://<label>/...`).
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Tables on a share the cabinet must not write to. Table files there
+    /// are never moved or deleted; sidecars go to `overlay`.
+    #[serde(default)]
+    pub read_only: bool,
+    /// Local folder mirroring a read-only root's layout, holding the files
+    /// written for its tables (overrides, ini...).
+    #[serde(default)]
+    pub overlay: Option<PathBuf>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+const KNOWN_KEYS: &[&str] = &["tables_path", "index_path", "preview_cache_dir", "roots"];
+
+impl LibraryConfig {
+    /// Parses `library_config_json`; null means all defaults.
+    ///
+    /// # Safety
+    /// `library_config_json` must be null or a valid NUL-terminated string.
+    pub unsafe fn from_c(library_config_json: *const c_char) -> Result<Self, VpxError> {
+        if library_config_json.is_null() {
+            return Ok(LibraryConfig::default());
+        }
+        let text = unsafe { crate::str_from_c(library_config_json, "library config") }?;
+        let value: Value = serde_json::from_str(text).map_err(|e| {
+            VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid library config JSON: {}", e))
+        })?;
+        let Some(object) = value.as_object() else {
+            return Err(VpxError::new(VpxErrorCode::InvalidArgument, "Library config must be a JSON object."));
+        };
+        config::warn_unknown_keys("library config", object, KNOWN_KEYS);
+        LibraryConfig::deserialize(&value).map_err(|e| {
+            VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid library config: {}", e))
+        })
+    }
+
+    /// `tables_path`, or else the first enabled root: where new tables go.
+    pub fn require_tables_path(&self) -> Result<&PathBuf, VpxError> {
+        self.tables_path
+            .as_ref()
+            .or_else(|| self.roots.iter().find(|r| r.enabled).map(|r| &r.path))
+            .ok_or_else(|| VpxError::new(VpxErrorCode::InvalidArgument, "Library config has no \"tables_path\"."))
+    }
+
+    /// The table roots in precedence order: `roots`, or `tables_path` alone.
+    pub fn table_roots(&self) -> Vec<LibraryRoot> {
+        match (&self.tables_path, self.roots.is_empty()) {
+            (Some(path), true) => {
+                vec![LibraryRoot { path: path.clone(), enabled: true, label: None, read_only: false, overlay: None }]
+            }
+            _ => self.roots.clone(),
+        }
+    }
+
+    /// The root `path` lives under, with `path` relative to it.
+    pub fn root_of(&self, path: &Path) -> Option<(LibraryRoot, PathBuf)> {
+        self.table_roots()
+            .into_iter()
+            .find_map(|root| Some((path.strip_prefix(&root.path).ok()?.to_path_buf(), root)))
+            .map(|(relative, root)| (root, relative))
+    }
+
+    /// Whether the folder holding `vpx_path` is the table's own, to move or
+    /// delete as a whole: below (not at) a configured root, and with no
+    /// other `.vpx` anywhere under it. Folders outside every root, such as
+    /// a downloads folder, never are.
+    pub fn owns_folder(&self, vpx_path: &Path) -> bool {
+        let table_dir = paths::table_dir(vpx_path);
+        let Ok(dir) = fs::canonicalize(&table_dir) else { return false };
+        let below_root = self
+            .table_roots()
+            .iter()
+            .filter_map(|root| fs::canonicalize(&root.path).ok())
+            .any(|root| dir != root && dir.starts_with(&root));
+        let is_other_table = |p: &PathBuf| {
+            p.as_path() != vpx_path
+                && p.extension().and_then(OsStr::to_str).is_some_and(|e| e.eq_ignore_ascii_case("vpx"))
+        };
+        below_root && !paths::files_under(&table_dir).iter().any(is_other_table)
+    }
+
+    /// Fails for a path on a read-only root: table files there stay put.
+    pub fn ensure_writable(&self, path: &Path) -> Result<(), VpxError> {
+        match self.root_of(path) {
+            Some((root, _)) if root.read_only => Err(VpxError::new(
+                VpxErrorCode::InvalidArgument,
+                format!("'{}' is on the read-only root '{}'", path.display(), root.path.display()),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Where to write a file that belongs at `path`: the same place in the
+    /// overlay for a read-only root, `path` itself otherwise.
+    pub fn writable_path(&self, path: &Path) -> Result<PathBuf, VpxError> {
+        match self.root_of(path) {
+            Some((root, relative)) if root.read_only => match &root.overlay {
+                Some(overlay) => Ok(overlay.join(relative)),
+                None => self.ensure_writable(path).map(|_| path.to_path_buf()),
+            },
+            _ => Ok(path.to_path_buf()),
+        }
+    }
+
+    /// Where to read a file that belongs at `path`: its overlay copy when
+    /// there is one.
+    pub fn readable_path(&self, path: &Path) -> PathBuf {
+        match self.writable_path(path) {
+            Ok(local) if local != path && local.exists() => local,
+            _ => path.to_path_buf(),
+        }
+    }
+}
+
+/// Start of the cached thumbnail names for `media`: the hex SHA-1 of the
+/// path string, as the frontend's `MediaPreview` computes it.
+pub(crate) fn thumbnail_prefix(media: &Path) -> String {
+    let digest = Sha1::digest(media.to_string_lossy().as_bytes());
+    format!("{}_", digest.iter().map(|b| format!("{:02x}", b)).collect::<String>())
+}
+
+/// Thumbnails the frontend cached for `media` (`<sha1 of path>_<height>.webp`).
+pub(crate) fn cached_thumbnails(cache_dir: &Path, media: &[PathBuf]) -> Vec<PathBuf> {
+    let prefixes: Vec<String> = media.iter().map(|p| thumbnail_prefix(p)).collect();
+    let mut found: Vec<PathBuf> = fs::read_dir(cache_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .and_then(OsStr::to_str)
+                .is_some_and(|n| prefixes.iter().any(|prefix| n.starts_with(prefix.as_str())))
+        })
+        .collect();
+    found.sort();
+    found
+}
+
+/// Parses an optional JSON options object into `T`, warning on unknown keys.
+///
+/// # Safety
+/// `options_json` must be null or a valid NUL-terminated string.
+pub(crate) unsafe fn options_from_c<T>(options_json: *const c_char, known: &[&str]) -> Result<T, VpxError>
+where
+    T: Default + for<'de> Deserialize<'de>,
+{
+    if options_json.is_null() {
+        return Ok(T::default());
+    }
+    let text = unsafe { crate::str_from_c(options_json, "options") }?;
+    let value: Value = serde_json::from_str(text).map_err(|e| {
+        VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid options JSON: {}", e))
+    })?;
+    let Some(object) = value.as_object() else {
+        return Err(VpxError::new(VpxErrorCode::InvalidArgument, "Options must be a JSON object."));
+    };
+    config::warn_unknown_keys("options", object, known);
+    T::deserialize(&value).map_err(|e| VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid options: {}", e)))
+}
@@ -0,0 +1,89 @@
+//! Lighting settings that decide how bright a table looks: the day/night
+//! slider default, the color grade (LUT), tonemapping and exposure.
+//!
+//! Cabinet displays are rarely as bright as the author's monitor, so dark
+//! tables are the ones users end up tweaking. The hint here only looks at
+//! the table's own values; VPX uses the player's global day/night setting
+//! instead unless the table overrides it.
+
+use std::os::raw::c_char;
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::options::CallOptions;
+use crate::physics::round;
+use crate::script;
+
+/// Day/night times exposure below this reads as a dark table.
+const DARK_BELOW: f32 = 0.35;
+/// And from this up as a bright one.
+const BRIGHT_FROM: f32 = 0.8;
+
+fn brightness_hint(level: f32) -> &'static str {
+    match level {
+        l if l < DARK_BELOW => "dark",
+        l if l >= BRIGHT_FROM => "bright",
+        _ => "normal",
+    }
+}
+
+fn read_lighting(path: &Path, options: &CallOptions) -> Result<Value, VpxError> {
+    crate::catch_panic(path, || {
+        let mut vpx_file = crate::open_vpx(path, options)?;
+        let gamedata = vpx_file.read_gamedata().map_err(|e| {
+            VpxError::new(
+                VpxErrorCode::ParseFailed,
+                format!("Failed to read gamedata for '{}': {}", path.display(), e),
+            )
+        })?;
+        let code = &gamedata.code.string;
+        let day_night = gamedata.global_emission_scale;
+        let exposure = gamedata.exposure.unwrap_or(1.0);
+        let lut = Some(gamedata.image_color_grade.clone()).filter(|l| !l.is_empty());
+        Ok(json!({
+            "day_night": round(day_night as f64),
+            "day_night_percent": (day_night * 100.0).round(),
+            "overrides_global_day_night": gamedata.overwrite_global_day_night.unwrap_or(false),
+            "exposure": round(exposure as f64),
+            "tone_mapper": gamedata.tone_mapper.unwrap_or(vpin::vpx::gamedata::ToneMapper::Reinhard),
+            "bloom_strength": round(gamedata.bloom_strength as f64),
+            "light_emission_scale": round(gamedata.light_emission_scale as f64),
+            "env_emission_scale": round(gamedata.env_emission_scale as f64),
+            "ao_scale": round(gamedata.ao_scale as f64),
+            "lut": lut,
+            "script_luts": script::lut_references(code),
+            "script_switches_lut": script::switches_lut(code),
+            "script_reads_day_night": script::reads_night_day(code),
+            "brightness_hint": brightness_hint(day_night * exposure),
+        }))
+    })
+}
+
+/// The lighting settings of the table at `vpx_file_path`: `day_night` (the
+/// slider default, 0-1, also as `day_night_percent`) and whether the table
+/// `overrides_global_day_night`, `exposure`, `tone_mapper`,
+/// `bloom_strength`, the emission and AO scales, the color grade image
+/// (`lut`, null for none), LUT images the script switches between
+/// (`script_luts`, `script_switches_lut`), whether the script follows the
+/// player's day/night setting (`script_reads_day_night`), and a
+/// `brightness_hint` of `"dark"`, `"normal"` or `"bright"`.
+///
+/// `options_json` is as for the `_ex` calls and may be null. Returns null
+/// on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_lighting_info(
+    vpx_file_path: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    let result = unsafe { CallOptions::from_c(options_json) }
+        .and_then(|options| Ok((unsafe { crate::path_from_c(vpx_file_path) }?, options)))
+        .and_then(|(path, options)| read_lighting(&path, &options))
+        .and_then(|lighting| crate::into_c_string(lighting.to_string()));
+
+    crate::envelope::finish("vpx_get_lighting_info", result)
+}
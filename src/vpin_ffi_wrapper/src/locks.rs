@@ -0,0 +1,90 @@
+//! Files in use.
+//!
+//! Moving, removing or overwriting a table while VPX is playing it either
+//! fails half-way (Windows) or leaves VPX saving into a file that is no
+//! longer the table (Linux). Calls that change table files check first and
+//! fail with `FileInUse`, naming the program holding the file when the
+//! system tells: on Linux from `/proc/<pid>/fd`, on Windows only that some
+//! program has it open, from a sharing violation. Elsewhere nothing is
+//! checked. The wrapper's own open handles don't count.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{VpxError, VpxErrorCode};
+
+/// A program that has a file open.
+struct Holder {
+    pid: Option<u32>,
+    name: Option<String>,
+}
+
+impl Holder {
+    fn describe(&self) -> String {
+        match (&self.name, self.pid) {
+            (Some(name), Some(pid)) => format!("{} (pid {})", name, pid),
+            (None, Some(pid)) => format!("pid {}", pid),
+            _ => "another program".to_string(),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn holders(path: &Path) -> Vec<Holder> {
+    let Ok(target) = fs::canonicalize(path) else { return Vec::new() };
+    let own = std::process::id();
+    let mut found = Vec::new();
+    for entry in fs::read_dir("/proc").into_iter().flatten().flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) else { continue };
+        if pid == own {
+            continue;
+        }
+        // Other users' processes can't be looked into, and don't play our tables.
+        let Ok(fds) = fs::read_dir(entry.path().join("fd")) else { continue };
+        if fds.flatten().any(|fd| fs::read_link(fd.path()).is_ok_and(|link| link == target)) {
+            let name = fs::read_to_string(entry.path().join("comm")).ok().map(|comm| comm.trim().to_string());
+            found.push(Holder { pid: Some(pid), name });
+        }
+    }
+    found
+}
+
+#[cfg(windows)]
+fn holders(path: &Path) -> Vec<Holder> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    // Opening without sharing fails while anyone else has the file open.
+    match fs::OpenOptions::new().read(true).share_mode(0).open(path) {
+        Err(e) if e.raw_os_error() == Some(ERROR_SHARING_VIOLATION) => vec![Holder { pid: None, name: None }],
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn holders(_path: &Path) -> Vec<Holder> {
+    Vec::new()
+}
+
+/// Fails with `FileInUse` if another program has `path` open.
+pub(crate) fn check_not_in_use(path: &Path) -> Result<(), VpxError> {
+    if !path.is_file() {
+        return Ok(());
+    }
+    let holders = holders(path);
+    if holders.is_empty() {
+        return Ok(());
+    }
+    let names: Vec<String> = holders.iter().map(Holder::describe).collect();
+    Err(VpxError::new(VpxErrorCode::FileInUse, format!("'{}' is in use by {}", path.display(), names.join(", "))))
+}
+
+/// `check_not_in_use` for each table (`.vpx`) among `paths`, before a call
+/// that overwrites or restores them writes anything.
+pub(crate) fn check_tables_not_in_use(paths: &[PathBuf]) -> Result<(), VpxError> {
+    paths
+        .iter()
+        .filter(|path| path.extension().and_then(OsStr::to_str).is_some_and(|e| e.eq_ignore_ascii_case("vpx")))
+        .try_for_each(|path| check_not_in_use(path))
+}
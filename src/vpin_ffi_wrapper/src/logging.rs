@@ -0,0 +1,302 @@
+//! Internal logging.
+//!
+//! Everything the wrapper has to say goes through `log()` (or the `log_*!`
+//! macros), filtered by the `log_level` from the wrapper config, and ends up
+//! at the callback from `vpx_register_log_callback`, or without one at the
+//! sink chosen with `vpx_set_log_sink`: stderr, a file rotated as it grows,
+//! or a ring of recent messages kept for `vpx_get_recent_logs_as_json`,
+//! for a frontend running on a cabinet with no terminal to read. The last
+//! error logged on each thread is also kept, whatever the level, for
+//! `vpx_get_last_error_message`.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::ffi::{c_void, CString};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Mutex;
+
+use serde_json::json;
+
+use crate::error::{VpxError, VpxErrorCode};
+
+/// Message severity, most severe first.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum VpxLogLevel {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+}
+
+impl VpxLogLevel {
+    fn as_str(self) -> &'static str {
+        match self {
+            VpxLogLevel::Error => "ERROR",
+            VpxLogLevel::Warn => "WARN",
+            VpxLogLevel::Info => "INFO",
+            VpxLogLevel::Debug => "DEBUG",
+        }
+    }
+}
+
+/// Most verbose level that still gets through; 0 silences everything.
+static MAX_LEVEL: AtomicI32 = AtomicI32::new(VpxLogLevel::Warn as i32);
+
+pub(crate) fn set_max_level(level: i32) {
+    MAX_LEVEL.store(level, Ordering::Relaxed);
+}
+
+pub(crate) fn enabled(level: VpxLogLevel) -> bool {
+    level as i32 <= MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Receives every message that gets through, with its `VpxLogLevel`, from
+/// whichever thread logged it. `message` is only valid during the call.
+pub type VpxLogCallback = Option<extern "C" fn(level: i32, message: *const c_char, userdata: *mut c_void)>;
+
+#[derive(Clone, Copy)]
+struct LogSink {
+    callback: VpxLogCallback,
+    userdata: *mut c_void,
+}
+
+// The frontend owns `userdata` and promises it can be used from any thread.
+unsafe impl Send for LogSink {}
+
+static SINK: Mutex<Option<LogSink>> = Mutex::new(None);
+
+/// A log file is rotated to `<path>.1` once it grows past this.
+const LOG_FILE_MAX_BYTES: u64 = 1024 * 1024;
+/// Messages the memory sink keeps.
+const RECENT_CAPACITY: usize = 1000;
+
+/// Where messages go without a callback.
+enum Output {
+    Stderr,
+    File { path: PathBuf, file: File, len: u64 },
+    Memory { next_seq: u64, recent: VecDeque<Recent> },
+}
+
+struct Recent {
+    seq: u64,
+    time: u64,
+    level: VpxLogLevel,
+    message: String,
+}
+
+static OUTPUT: Mutex<Output> = Mutex::new(Output::Stderr);
+
+fn output() -> std::sync::MutexGuard<'static, Output> {
+    OUTPUT.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn open_log_file(path: &Path) -> std::io::Result<File> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+impl Output {
+    fn write(&mut self, level: VpxLogLevel, message: &str) {
+        match self {
+            Output::Stderr => eprintln!("[vpin_ffi_wrapper] {}: {}", level.as_str(), message),
+            Output::File { path, file, len } => {
+                if *len >= LOG_FILE_MAX_BYTES {
+                    let mut rotated = path.as_os_str().to_os_string();
+                    rotated.push(".1");
+                    // Failing to rotate just means the file keeps growing.
+                    if fs::rename(&*path, rotated).is_ok() {
+                        if let Ok(reopened) = open_log_file(path) {
+                            (*file, *len) = (reopened, 0);
+                        }
+                    }
+                }
+                let line = format!("{} {}: {}\n", crate::store::now(), level.as_str(), message);
+                if file.write_all(line.as_bytes()).is_ok() {
+                    *len += line.len() as u64;
+                }
+            }
+            Output::Memory { next_seq, recent } => {
+                if recent.len() == RECENT_CAPACITY {
+                    recent.pop_front();
+                }
+                recent.push_back(Recent {
+                    seq: *next_seq,
+                    time: crate::store::now(),
+                    level,
+                    message: message.to_string(),
+                });
+                *next_seq += 1;
+            }
+        }
+    }
+
+    fn mode(&self) -> &'static str {
+        match self {
+            Output::Stderr => "stderr",
+            Output::File { .. } => "file",
+            Output::Memory { .. } => "memory",
+        }
+    }
+}
+
+thread_local! {
+    /// The last error logged on this thread.
+    static LAST_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+pub(crate) fn log(level: VpxLogLevel, message: &str) {
+    if level == VpxLogLevel::Error {
+        LAST_ERROR.with(|last| *last.borrow_mut() = Some(message.to_string()));
+    }
+    if !enabled(level) {
+        return;
+    }
+    // Called outside the lock, so the callback may itself call into the wrapper.
+    let sink = *SINK.lock().unwrap_or_else(|e| e.into_inner());
+    match sink.and_then(|sink| Some((sink.callback?, sink.userdata))) {
+        Some((callback, userdata)) => {
+            let message = CString::new(message.replace('\0', "\u{FFFD}")).unwrap_or_default();
+            callback(level as i32, message.as_ptr(), userdata);
+        }
+        None => output().write(level, message),
+    }
+}
+
+macro_rules! log_error {
+    ($($arg:tt)*) => { $crate::logging::log($crate::logging::VpxLogLevel::Error, &format!($($arg)*)) };
+}
+
+macro_rules! log_warn {
+    ($($arg:tt)*) => { $crate::logging::log($crate::logging::VpxLogLevel::Warn, &format!($($arg)*)) };
+}
+
+macro_rules! log_info {
+    ($($arg:tt)*) => { $crate::logging::log($crate::logging::VpxLogLevel::Info, &format!($($arg)*)) };
+}
+
+macro_rules! log_debug {
+    ($($arg:tt)*) => { $crate::logging::log($crate::logging::VpxLogLevel::Debug, &format!($($arg)*)) };
+}
+
+/// Sends the wrapper's log messages to `callback` (with `userdata` passed
+/// back untouched) instead of stderr, still filtered by the configured
+/// `log_level`. A null `callback` goes back to stderr. The callback can be
+/// called from any thread, also while being replaced, so `userdata` has to
+/// outlive the registration.
+#[no_mangle]
+pub extern "C" fn vpx_register_log_callback(callback: VpxLogCallback, userdata: *mut c_void) {
+    *SINK.lock().unwrap_or_else(|e| e.into_inner()) = callback.is_some().then_some(LogSink { callback, userdata });
+}
+
+fn set_sink(mode: &str, path: Option<PathBuf>) -> Result<Output, VpxError> {
+    match mode {
+        "stderr" => Ok(Output::Stderr),
+        "memory" => Ok(Output::Memory { next_seq: 1, recent: VecDeque::new() }),
+        "file" => {
+            let Some(path) = path else {
+                return Err(VpxError::new(VpxErrorCode::NullArgument, "The file log sink needs a path."));
+            };
+            let file = open_log_file(&path).map_err(|e| {
+                VpxError::new(VpxErrorCode::OpenFailed, format!("Failed to open log file '{}': {}", path.display(), e))
+            })?;
+            let len = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+            Ok(Output::File { path, file, len })
+        }
+        _ => Err(VpxError::new(
+            VpxErrorCode::InvalidArgument,
+            format!("Unknown log sink '{}' (expected \"stderr\", \"file\" or \"memory\")", mode),
+        )),
+    }
+}
+
+/// Chooses where log messages go while no callback is registered: `mode`
+/// `"stderr"` (the default), `"file"`, appending to `path` and keeping
+/// one older file as `<path>.1` once it passes 1 MiB, or `"memory"`, the
+/// last 1000 messages for `vpx_get_recent_logs_as_json`. `path` is only
+/// read for `"file"` and may be null otherwise. Returns a `VpxErrorCode`;
+/// on error the previous sink is kept. Switching drops what the memory
+/// sink held.
+///
+/// # Safety
+/// `mode` and `path` must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_set_log_sink(mode: *const c_char, path: *const c_char) -> i32 {
+    let sink = unsafe { crate::str_from_c(mode, "log sink mode") }.and_then(|mode| {
+        let path = match path.is_null() {
+            true => None,
+            false => Some(unsafe { crate::path_from_c(path) }?),
+        };
+        set_sink(mode, path)
+    });
+    match sink {
+        Ok(sink) => {
+            *output() = sink;
+            VpxErrorCode::Ok as i32
+        }
+        Err(e) => {
+            log_error!("vpx_set_log_sink: {}", e);
+            e.code as i32
+        }
+    }
+}
+
+/// What the memory sink holds, oldest first: `{"mode", "logs": [{"seq",
+/// "time" (Unix seconds), "level" ("ERROR", "WARN"...), "message"}]}`.
+/// `logs` is empty unless the sink is `"memory"`.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+#[no_mangle]
+pub extern "C" fn vpx_get_recent_logs_as_json() -> *mut c_char {
+    let logs = {
+        let output = output();
+        let logs: Vec<_> = match &*output {
+            Output::Memory { recent, .. } => recent
+                .iter()
+                .map(|entry| {
+                    json!({
+                        "seq": entry.seq,
+                        "time": entry.time,
+                        "level": entry.level.as_str(),
+                        "message": entry.message,
+                    })
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        json!({ "mode": output.mode(), "logs": logs })
+    };
+    let result = crate::into_c_string(logs.to_string());
+
+    crate::envelope::finish("vpx_get_recent_logs_as_json", result)
+}
+
+/// The last error logged on the calling thread, such as `"vpx_open: File
+/// not found: ..."`, for a call that just returned null or an error code.
+/// Successful calls do not clear it; see `vpx_clear_last_error`.
+///
+/// Returns null when there has been no error. Release with
+/// `vpx_free_string` (or `free_rust_string`).
+#[no_mangle]
+pub extern "C" fn vpx_get_last_error_message() -> *mut c_char {
+    let Some(message) = LAST_ERROR.with(|last| last.borrow().clone()) else {
+        return std::ptr::null_mut();
+    };
+    // Errors are formatted from strings that came from C or from vpin; one
+    // with a NUL byte in it is cut short rather than lost.
+    let message = message.split('\0').next().unwrap_or_default().to_string();
+    crate::into_c_string(message).unwrap_or(std::ptr::null_mut())
+}
+
+/// Forgets the calling thread's last error, so the next
+/// `vpx_get_last_error_message` only reports what failed after this.
+#[no_mangle]
+pub extern "C" fn vpx_clear_last_error() {
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+}
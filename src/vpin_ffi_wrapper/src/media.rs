@@ -0,0 +1,195 @@
+//! Media completeness score.
+//!
+//! Rates how much of the media the frontend can show a table actually has,
+//! from 0 (nothing) to 100 (every screen and the audio), so a "needs media
+//! work" view can sort on it. Presence comes from the index row's `has*`
+//! flags, which the file scanner sets and which already tell the default
+//! wheel from a real one; rows without a flag fall back to the media path
+//! existing on disk.
+
+use std::collections::BTreeMap;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::{index, library, oplog, paths};
+
+/// Index field the score is stored in.
+const SCORE_FIELD: &str = "mediaScore";
+
+/// A scored category: name, default weight and its media as (flag, path
+/// field).
+type Category = (&'static str, u32, &'static [(&'static str, &'static str)]);
+
+/// Default weights sum to 100; a category with a still and a video gives
+/// half its weight to each.
+const CATEGORIES: &[Category] = &[
+    ("wheel", 25, &[("hasWheelImage", "wheelImage")]),
+    ("playfield", 25, &[("hasPlayfieldImage", "playfieldImage"), ("hasPlayfieldVideo", "playfieldVideo")]),
+    ("backglass", 20, &[("hasBackglassImage", "backglassImage"), ("hasBackglassVideo", "backglassVideo")]),
+    ("dmd", 15, &[("hasDmdImage", "dmdImage"), ("hasDmdVideo", "dmdVideo")]),
+    ("audio", 15, &[("hasTableMusic", "music"), ("hasLaunchAudio", "launchAudio")]),
+];
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct MediaOptions {
+    /// Weight per category; 0 leaves a category out.
+    weights: BTreeMap<String, u32>,
+    /// Any one medium completes a category (a still is enough for a
+    /// cabinet without videos, music or launch audio for the audio).
+    any_medium: bool,
+    /// Report only, leave the index file alone.
+    dry_run: bool,
+}
+
+const KNOWN_KEYS: &[&str] = &["weights", "any_medium", "dry_run"];
+
+fn weight(options: &MediaOptions, category: &str, default: u32) -> u32 {
+    options.weights.get(category).copied().unwrap_or(default)
+}
+
+fn validate(options: &MediaOptions) -> Result<(), VpxError> {
+    if let Some(unknown) = options.weights.keys().find(|k| !CATEGORIES.iter().any(|(name, _, _)| name == k)) {
+        return Err(VpxError::new(VpxErrorCode::InvalidArgument, format!("Unknown media category '{}'", unknown)));
+    }
+    if CATEGORIES.iter().all(|(name, default, _)| weight(options, name, *default) == 0) {
+        return Err(VpxError::new(VpxErrorCode::InvalidArgument, "Every media category has weight 0."));
+    }
+    Ok(())
+}
+
+/// Whether the row has a medium: its flag, else its path on disk
+/// (relative paths from the row's folder).
+fn has_medium(row: &Value, flag: &str, field: &str) -> bool {
+    if let Some(flag) = row[flag].as_bool() {
+        return flag;
+    }
+    let Some(path) = row[field].as_str().filter(|p| !p.is_empty()) else { return false };
+    let path = match (Path::new(path).is_relative(), row["folder"].as_str()) {
+        (true, Some(folder)) => Path::new(folder).join(path),
+        _ => PathBuf::from(path),
+    };
+    paths::has_content(&path)
+}
+
+/// The score of one index row, with the media it lacks and the points per
+/// category.
+fn score_row(row: &Value, options: &MediaOptions) -> Value {
+    let (mut earned, mut total) = (0.0, 0.0);
+    let mut missing = Vec::new();
+    let mut categories = serde_json::Map::new();
+    for (category, default, media) in CATEGORIES {
+        let weight = weight(options, category, *default) as f64;
+        if weight == 0.0 {
+            continue;
+        }
+        let present: Vec<bool> = media.iter().map(|(flag, field)| has_medium(row, flag, field)).collect();
+        let fraction = match options.any_medium {
+            true if present.contains(&true) => 1.0,
+            true => 0.0,
+            false => present.iter().filter(|p| **p).count() as f64 / present.len() as f64,
+        };
+        if !options.any_medium || fraction == 0.0 {
+            missing.extend(media.iter().zip(&present).filter(|(_, p)| !**p).map(|((_, field), _)| *field));
+        }
+        earned += weight * fraction;
+        total += weight;
+        categories.insert(category.to_string(), json!((weight * fraction).round() as u32));
+    }
+    json!({
+        "score": (100.0 * earned / total).round() as u32,
+        "missing": missing,
+        "categories": categories,
+    })
+}
+
+fn score_index(index_path: &Path, options: &MediaOptions) -> Result<Value, VpxError> {
+    let mut index_doc = index::load(index_path)?;
+    let mut results: Vec<Value> = Vec::new();
+    for row in index::tables_mut(&mut index_doc) {
+        let mut scored = score_row(row, options);
+        row[SCORE_FIELD] = scored["score"].clone();
+        scored["vpx_file"] = row["vpxFile"].clone();
+        scored["title"] = row["bestTitle"].clone();
+        results.push(scored);
+    }
+    if !options.dry_run && !results.is_empty() {
+        index::save(index_path, &index_doc)?;
+    }
+
+    // Most work first.
+    results.sort_by_key(|r| r["score"].as_u64());
+    let average = match results.len() {
+        0 => 0.0,
+        n => crate::physics::round(results.iter().filter_map(|r| r["score"].as_f64()).sum::<f64>() / n as f64),
+    };
+    let complete = results.iter().filter(|r| r["score"].as_u64() == Some(100)).count();
+    Ok(json!({
+        "tables": results.len(),
+        "average_score": average,
+        "complete": complete,
+        "written": !options.dry_run && !results.is_empty(),
+        "results": results,
+    }))
+}
+
+/// Scores one table's media from its index row (`row_json`, the frontend's
+/// camelCase fields; the `has*` flags or else the media paths).
+///
+/// `options_json` (nullable): `weights` per category (`wheel` 25,
+/// `playfield` 25, `backglass` 20, `dmd` 15, `audio` 15; 0 leaves one out),
+/// `any_medium` (a still or a video alone completes a category, music or
+/// launch audio the audio; default false, each is half).
+///
+/// Returns `{"score": 0-100, "missing": [path fields], "categories":
+/// {name: points}}`, or null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_score_table_media(row_json: *const c_char, options_json: *const c_char) -> *mut c_char {
+    let result = unsafe { crate::str_from_c(row_json, "row") }
+        .and_then(|text| {
+            serde_json::from_str::<Value>(text)
+                .map_err(|e| VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid row JSON: {}", e)))
+        })
+        .and_then(|row| {
+            let options: MediaOptions = unsafe { library::options_from_c(options_json, KNOWN_KEYS) }?;
+            validate(&options)?;
+            crate::into_c_string(score_row(&row, &options).to_string())
+        });
+
+    crate::envelope::finish("vpx_score_table_media", result)
+}
+
+/// Scores every table of the index at `index_path` as
+/// `vpx_score_table_media` does and stores the score in each row's
+/// `mediaScore`. `options_json` (nullable) takes the same keys plus
+/// `dry_run` (default false) to leave the file alone.
+///
+/// Returns `{"tables", "average_score", "complete", "written", "results":
+/// [{"vpx_file", "title", "score", "missing", "categories"}]}` with the
+/// lowest scores first, or null on failure. The frontend should reload its
+/// index afterwards. Release with `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_score_media(index_path: *const c_char, options_json: *const c_char) -> *mut c_char {
+    let result = unsafe { crate::path_from_c(index_path) }
+        .and_then(|path| {
+            let options: MediaOptions = unsafe { library::options_from_c(options_json, KNOWN_KEYS) }?;
+            validate(&options)?;
+            Ok((path, options))
+        })
+        .and_then(|(path, options)| {
+            oplog::record("media", format!("Score media in {}", path.display()), || score_index(&path, &options))
+        })
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_score_media", result)
+}
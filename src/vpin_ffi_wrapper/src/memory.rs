@@ -0,0 +1,118 @@
+//! In-memory buffer entry points.
+//!
+//! A table coming out of an archive or off the network is already in the
+//! frontend's memory; writing it to a temporary file just so it can be
+//! opened again costs a round trip through the disk. These variants parse a
+//! caller-provided buffer instead of a path, with the same options and
+//! results as the path-taking calls.
+
+use std::os::raw::c_char;
+
+use crate::config;
+use crate::error::{VpxError, VpxErrorCode};
+use crate::handle::{OpenTable, VpxHandle};
+use crate::options::CallOptions;
+
+/// How buffers are named in messages, where a path would be.
+const BUFFER_NAME: &str = "<memory buffer>";
+
+/// A table opened from a copy of the `len` bytes at `data`, held to the
+/// same `max_file_size_mb` limit as a file.
+///
+/// # Safety
+/// `data` must be null or valid for reads of `len` bytes, `options_json`
+/// null or a valid NUL-terminated string.
+unsafe fn open_buffer(data: *const u8, len: usize, options_json: *const c_char) -> Result<OpenTable, VpxError> {
+    let options = unsafe { CallOptions::from_c(options_json) }?;
+    if data.is_null() {
+        return Err(VpxError::new(VpxErrorCode::NullArgument, "Input buffer is null."));
+    }
+    let max_file_size_mb = options.max_file_size_mb(&config::get());
+    if max_file_size_mb > 0 && len as u64 > max_file_size_mb * 1024 * 1024 {
+        return Err(VpxError::new(
+            VpxErrorCode::LimitExceeded,
+            format!("Buffer is {} bytes, over the {} MB limit", len, max_file_size_mb),
+        ));
+    }
+    let bytes = unsafe { std::slice::from_raw_parts(data, len) }.to_vec();
+    OpenTable::from_bytes(BUFFER_NAME, bytes, &options)
+}
+
+/// `vpx_get_table_info_as_json_err` on the `len` bytes of a `.vpx` file at
+/// `data` instead of a path. The buffer is copied and can be released as
+/// soon as the call returns.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `data` must be null or valid for reads of `len` bytes, `options_json`
+/// null or a valid NUL-terminated string, `error_code` null or valid for a
+/// write.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_table_info_from_buffer(
+    data: *const u8,
+    len: usize,
+    options_json: *const c_char,
+    error_code: *mut i32,
+) -> *mut c_char {
+    let result = unsafe { open_buffer(data, len, options_json) }
+        .and_then(|mut table| table.table_info())
+        .and_then(|table_info| crate::into_c_string(table_info.to_string()));
+    unsafe { crate::set_error_code(error_code, &result) };
+
+    crate::envelope::finish("vpx_get_table_info_from_buffer", result)
+}
+
+/// `vpx_get_gamedata_code_err` on an in-memory `.vpx` file, as for
+/// `vpx_get_table_info_from_buffer`.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// As for `vpx_get_table_info_from_buffer`.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_gamedata_code_from_buffer(
+    data: *const u8,
+    len: usize,
+    options_json: *const c_char,
+    error_code: *mut i32,
+) -> *mut c_char {
+    let result = unsafe { open_buffer(data, len, options_json) }
+        .and_then(|mut table| table.code())
+        .and_then(crate::into_c_string);
+    unsafe { crate::set_error_code(error_code, &result) };
+
+    match result {
+        Ok(ptr) => ptr,
+        Err(e) => {
+            log_error!("vpx_get_gamedata_code_from_buffer: {}", e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// `vpx_open_err` on an in-memory `.vpx` file: the handle keeps its own
+/// copy of the buffer until `vpx_close`.
+///
+/// Returns null on failure.
+///
+/// # Safety
+/// As for `vpx_get_table_info_from_buffer`.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_open_buffer(
+    data: *const u8,
+    len: usize,
+    options_json: *const c_char,
+    error_code: *mut i32,
+) -> *mut VpxHandle {
+    let result = unsafe { open_buffer(data, len, options_json) };
+    unsafe { crate::set_error_code(error_code, &result) };
+
+    match result {
+        Ok(table) => VpxHandle::into_raw(table),
+        Err(e) => {
+            log_error!("vpx_open_buffer: {}", e);
+            std::ptr::null_mut()
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Merging a table's metadata from several sources.
+//!
+//! Each source gives a set of fields and, optionally, when it last changed:
+//!
+//! ```json
+//! [{ "source": "vpx", "modified": 1700000000, "fields": { "title": "TZ", "year": "1993" } },
+//!  { "source": "user", "fields": { "title": "Twilight Zone" } }]
+//! ```
+//!
+//! Precedence is the scan's: `filename` < `vpx` < `script` < `vps` <
+//! `sidecar` < `user`; unknown sources rank below all of them, in the order
+//! given. Empty values don't count. A field is in conflict when sources
+//! disagree on it (ignoring case and surrounding space). Policies:
+//!
+//! - `prefer-user`: the highest-precedence value wins.
+//! - `prefer-newest`: the most recently modified source wins, precedence
+//!   breaking ties; sources without `modified` are oldest.
+//! - `prompt-list`: conflicting fields are left out of the merged record
+//!   for the user to pick from the conflict list.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::os::raw::c_char;
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::playlist::compare;
+use crate::scan::{present, SOURCES};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Policy {
+    PreferUser,
+    PreferNewest,
+    PromptList,
+}
+
+impl Policy {
+    fn parse(name: &str) -> Result<Policy, VpxError> {
+        match name {
+            "prefer-user" => Ok(Policy::PreferUser),
+            "prefer-newest" => Ok(Policy::PreferNewest),
+            "prompt-list" => Ok(Policy::PromptList),
+            other => Err(VpxError::new(
+                VpxErrorCode::InvalidArgument,
+                format!("Unknown merge policy '{}' (prefer-user, prefer-newest, prompt-list)", other),
+            )),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Policy::PreferUser => "prefer-user",
+            Policy::PreferNewest => "prefer-newest",
+            Policy::PromptList => "prompt-list",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Source {
+    source: String,
+    #[serde(default)]
+    modified: u64,
+    #[serde(default)]
+    fields: Map<String, Value>,
+}
+
+struct Candidate<'a> {
+    source: &'a Source,
+    /// Position in the precedence order, higher wins.
+    rank: usize,
+    value: Value,
+}
+
+fn rank(sources: &[Source], i: usize) -> usize {
+    match SOURCES.iter().position(|s| *s == sources[i].source) {
+        Some(known) => sources.len() + known,
+        None => i,
+    }
+}
+
+fn merge(sources: &[Source], policy: Policy) -> Value {
+    let mut by_field: BTreeMap<&str, Vec<Candidate>> = BTreeMap::new();
+    for (i, source) in sources.iter().enumerate() {
+        for (field, value) in &source.fields {
+            if let Some(value) = present(value) {
+                by_field.entry(field).or_default().push(Candidate { source, rank: rank(sources, i), value });
+            }
+        }
+    }
+
+    let mut merged = Map::new();
+    let mut chosen_from = Map::new();
+    let mut conflicts = Vec::new();
+    for (field, mut candidates) in by_field {
+        candidates.sort_by(|a, b| match policy {
+            Policy::PreferNewest => a.source.modified.cmp(&b.source.modified).then(a.rank.cmp(&b.rank)),
+            _ => a.rank.cmp(&b.rank),
+        });
+        let winner = candidates.last().expect("fields have at least one candidate");
+        let conflict = candidates.iter().any(|c| compare(&c.value, &winner.value) != Ordering::Equal);
+        let pick = !(conflict && policy == Policy::PromptList);
+        if pick {
+            merged.insert(field.to_string(), winner.value.clone());
+            chosen_from.insert(field.to_string(), json!(winner.source.source));
+        }
+        if conflict {
+            let listed: Vec<Value> = candidates
+                .iter()
+                .rev()
+                .map(|c| json!({ "source": c.source.source, "value": c.value, "modified": c.source.modified }))
+                .collect();
+            conflicts.push(json!({
+                "field": field,
+                "chosen": if pick { json!(winner.source.source) } else { Value::Null },
+                "candidates": listed,
+            }));
+        }
+    }
+
+    json!({
+        "policy": policy.as_str(),
+        "merged": merged,
+        "sources": chosen_from,
+        "conflicts": conflicts,
+    })
+}
+
+/// Merges a table's metadata from several sources (`sources_json`, an
+/// array of `{"source", "modified", "fields"}`) under `policy`:
+/// `"prefer-user"` (null), `"prefer-newest"` or `"prompt-list"`.
+///
+/// Returns `{"policy", "merged", "sources": {field: source}, "conflicts":
+/// [{"field", "chosen", "candidates": [{"source", "value", "modified"}]}]}`
+/// with candidates best first, or null on failure. Release with
+/// `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_merge_table_metadata(sources_json: *const c_char, policy: *const c_char) -> *mut c_char {
+    let policy = match policy.is_null() {
+        true => Ok(Policy::PreferUser),
+        false => unsafe { crate::str_from_c(policy, "policy") }.and_then(Policy::parse),
+    };
+    let result = policy
+        .and_then(|policy| Ok((policy, unsafe { crate::str_from_c(sources_json, "sources") }?)))
+        .and_then(|(policy, text)| {
+            let sources: Vec<Source> = serde_json::from_str(text).map_err(|e| {
+                VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid sources JSON: {}", e))
+            })?;
+            crate::into_c_string(merge(&sources, policy).to_string())
+        });
+
+    crate::envelope::finish("vpx_merge_table_metadata", result)
+}
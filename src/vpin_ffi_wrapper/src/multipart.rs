@@ -0,0 +1,331 @@
+//! Multi-part archives.
+//!
+//! Big table packs are often posted split: `Pack.zip.001`, `Pack.7z.001`,
+//! `Pack.part1.rar`. The parts only make sense together, so intake and
+//! import treat a set as one download, find the set from any of its parts
+//! and say which parts are missing instead of failing on a truncated
+//! archive. A split `.zip` is the zip cut into pieces and reads like one
+//! once they are joined back; 7z and RAR sets are read through 7-Zip from
+//! their first part, which finds the rest. Gaps in the numbering show for
+//! every format; missing last
+//! parts only for zip (no directory at the end) and 7z (shorter than its
+//! header says), since a RAR set's end can't be told without reading it.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::{VpxError, VpxErrorCode};
+
+/// What starts every 7z archive.
+const SEVEN_ZIP_SIGNATURE: &[u8] = b"7z\xBC\xAF\x27\x1C";
+/// The 7z signature header: signature, version, CRC, next header offset and size.
+const SEVEN_ZIP_HEADER_LEN: u64 = 32;
+/// What starts a zip's end of central directory record.
+const ZIP_END_SIGNATURE: &[u8] = b"PK\x05\x06";
+/// How far from the end that record can be: its own size plus the longest comment.
+const ZIP_END_SEARCH: u64 = 22 + 0xFFFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    Zip,
+    SevenZip,
+    Rar,
+}
+
+impl Format {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Format::Zip => "zip",
+            Format::SevenZip => "7z",
+            Format::Rar => "rar",
+        }
+    }
+}
+
+/// One part's place in its set, from the file name alone.
+struct Part {
+    format: Format,
+    /// The name before the part suffix (`Pack` of `Pack.7z.001`).
+    stem: String,
+    /// `.7z.001`, `.part1.rar`.
+    suffix: String,
+    number: u32,
+    /// `.001` style rather than `.part1.rar`.
+    numbered: bool,
+}
+
+fn digits(s: &str) -> Option<u32> {
+    match !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+        true => s.parse().ok(),
+        false => None,
+    }
+}
+
+/// `Pack.zip.001` / `Pack.7z.001` / `Pack.rar.001`, or `Pack.part1.rar`.
+fn parse(path: &Path) -> Option<Part> {
+    let name = path.file_name()?.to_str()?;
+    let (head, tail) = name.rsplit_once('.')?;
+    if let Some(number) = digits(tail) {
+        let (stem, ext) = head.rsplit_once('.')?;
+        let format = match ext.to_ascii_lowercase().as_str() {
+            "zip" => Format::Zip,
+            "7z" => Format::SevenZip,
+            "rar" => Format::Rar,
+            _ => return None,
+        };
+        let suffix = name[stem.len()..].to_string();
+        return (!stem.is_empty()).then(|| Part { format, stem: stem.to_string(), suffix, number, numbered: true });
+    }
+    if !tail.eq_ignore_ascii_case("rar") {
+        return None;
+    }
+    let (stem, part) = head.rsplit_once('.')?;
+    let number = part.get(..4).filter(|p| p.eq_ignore_ascii_case("part")).and_then(|_| digits(&part[4..]))?;
+    let suffix = name[stem.len()..].to_string();
+    (!stem.is_empty()).then(|| Part { format: Format::Rar, stem: stem.to_string(), suffix, number, numbered: false })
+}
+
+/// Whether `path` is named like a part of a multi-part archive.
+pub(crate) fn is_part(path: &Path) -> bool {
+    parse(path).is_some()
+}
+
+/// The parts of one split archive found next to each other.
+pub(crate) struct PartSet {
+    pub format: Format,
+    /// `Pack.7z`: what the set is called as a whole.
+    pub name: String,
+    stem: String,
+    /// `(number, path, suffix)`, by number.
+    parts: Vec<(u32, PathBuf, String)>,
+}
+
+impl PartSet {
+    /// The set `path` belongs to, with every part beside it; None when
+    /// `path` isn't named like a part.
+    pub fn find(path: &Path) -> Option<PartSet> {
+        let part = parse(path)?;
+        let dir = match path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            Some(dir) => dir.to_path_buf(),
+            None => PathBuf::from("."),
+        };
+        let same_set = |other: &Part| {
+            other.format == part.format
+                && other.numbered == part.numbered
+                && other.stem.eq_ignore_ascii_case(&part.stem)
+        };
+        let mut parts: Vec<(u32, PathBuf, String)> = fs::read_dir(&dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|sibling| sibling.is_file())
+            .filter_map(|sibling| parse(&sibling).filter(same_set).map(|other| (other.number, sibling, other.suffix)))
+            .collect();
+        if parts.is_empty() {
+            parts.push((part.number, path.to_path_buf(), part.suffix.clone()));
+        }
+        parts.sort_by_key(|(number, _, _)| *number);
+        let name = format!("{}.{}", part.stem, part.format.as_str());
+        Some(PartSet { format: part.format, name, stem: part.stem, parts })
+    }
+
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.parts.iter().map(|(_, path, _)| path.clone()).collect()
+    }
+
+    /// The part file names, by number.
+    pub fn file_names(&self) -> Vec<String> {
+        self.parts
+            .iter()
+            .map(|(_, path, _)| path.file_name().unwrap_or_default().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    /// The first part as found on disk.
+    pub fn first(&self) -> &Path {
+        &self.parts[0].1
+    }
+
+    pub fn size(&self) -> u64 {
+        self.parts.iter().filter_map(|(_, path, _)| fs::metadata(path).ok()).map(|m| m.len()).sum()
+    }
+
+    /// Part numbers missing before the highest one found (numbering starts at 1).
+    fn gaps(&self) -> Vec<u32> {
+        let last = self.parts.last().map_or(0, |(number, _, _)| *number);
+        (1..last).filter(|n| !self.parts.iter().any(|(number, _, _)| number == n)).collect()
+    }
+
+    /// Why the set can't be read as a whole yet, if parts are missing.
+    pub fn missing(&self) -> Option<String> {
+        let gaps = self.gaps();
+        if !gaps.is_empty() {
+            let list: Vec<String> = gaps.iter().map(u32::to_string).collect();
+            return Some(format!("'{}' is missing part(s) {}", self.name, list.join(", ")));
+        }
+        let last = self.parts.last().map_or(0, |(number, _, _)| *number);
+        match self.format {
+            Format::Zip => {
+                return (!self.has_zip_end()).then(|| {
+                    format!("'{}' is missing its last part(s): no zip directory after part {}", self.name, last)
+                })
+            }
+            Format::Rar => return None,
+            Format::SevenZip => {}
+        }
+        // The signature header says where the archive ends.
+        let mut header = [0u8; SEVEN_ZIP_HEADER_LEN as usize];
+        let read = File::open(self.first()).and_then(|mut file| file.read_exact(&mut header));
+        if read.is_err() || !header.starts_with(SEVEN_ZIP_SIGNATURE) {
+            return Some(format!("'{}' part {} doesn't start a 7z archive", self.name, self.parts[0].0));
+        }
+        let field = |at: usize| u64::from_le_bytes(header[at..at + 8].try_into().unwrap_or_default());
+        let expected = SEVEN_ZIP_HEADER_LEN.saturating_add(field(12)).saturating_add(field(20));
+        let size = self.size();
+        (size < expected).then(|| {
+            format!("'{}' is missing its last part(s): {} of {} bytes after part {}", self.name, size, expected, last)
+        })
+    }
+
+    /// Whether the joined parts end with a zip directory.
+    fn has_zip_end(&self) -> bool {
+        let tail = || -> io::Result<Vec<u8>> {
+            let mut reader = self.reader()?;
+            reader.seek(SeekFrom::Start(reader.len.saturating_sub(ZIP_END_SEARCH)))?;
+            let mut tail = Vec::new();
+            reader.read_to_end(&mut tail)?;
+            Ok(tail)
+        };
+        tail().is_ok_and(|tail| tail.windows(ZIP_END_SIGNATURE.len()).any(|w| w == ZIP_END_SIGNATURE))
+    }
+
+    /// The file names the parts get moved to in `dir`, with ` (2)`... after
+    /// the stem when a name there is taken, so the set stays a set.
+    pub fn free_names(&self, dir: &Path) -> Vec<PathBuf> {
+        let names = |copy: Option<u32>| -> Vec<PathBuf> {
+            self.parts
+                .iter()
+                .map(|(_, _, suffix)| match copy {
+                    None => dir.join(format!("{}{}", self.stem, suffix)),
+                    Some(n) => dir.join(format!("{} ({}){}", self.stem, n, suffix)),
+                })
+                .collect()
+        };
+        std::iter::once(None)
+            .chain((2..).map(Some))
+            .map(names)
+            .find(|targets| targets.iter().all(|target| !target.exists()))
+            .unwrap_or_else(|| names(None))
+    }
+
+    /// The parts read back to back as one file.
+    pub fn reader(&self) -> io::Result<Joined> {
+        Joined::open(&self.paths())
+    }
+
+    /// Lowercase hex SHA-256 of the joined parts.
+    pub fn sha256(&self) -> io::Result<String> {
+        let mut hasher = Sha256::new();
+        io::copy(&mut self.reader()?, &mut hasher)?;
+        Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+    }
+}
+
+/// Files read one after another as if they were one.
+pub(crate) struct Joined {
+    files: Vec<(File, u64)>,
+    len: u64,
+    pos: u64,
+}
+
+impl Joined {
+    pub fn open(paths: &[PathBuf]) -> io::Result<Joined> {
+        let files = paths
+            .iter()
+            .map(|path| {
+                let file = File::open(path)?;
+                let len = file.metadata()?.len();
+                Ok((file, len))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        let len = files.iter().map(|(_, len)| len).sum();
+        Ok(Joined { files, len, pos: 0 })
+    }
+}
+
+impl Read for Joined {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut start = 0;
+        for (file, len) in &mut self.files {
+            if self.pos < start + *len {
+                let offset = self.pos - start;
+                file.seek(SeekFrom::Start(offset))?;
+                let want = buf.len().min((*len - offset).try_into().unwrap_or(usize::MAX));
+                let read = file.read(&mut buf[..want])?;
+                self.pos += read as u64;
+                return Ok(read);
+            }
+            start += *len;
+        }
+        Ok(0)
+    }
+}
+
+impl Seek for Joined {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+        let target =
+            target.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "seek before the start of the parts"))?;
+        self.pos = target;
+        Ok(target)
+    }
+}
+
+/// What 7-Zip opens for the 7z or RAR pack at `src`: the pack itself, or
+/// the first part of its set. None for anything else. Fails with the
+/// missing parts for an incomplete set.
+pub(crate) fn seven_zip_input(src: &Path) -> Result<Option<PathBuf>, VpxError> {
+    match PartSet::find(src) {
+        Some(set) if set.format != Format::Zip => match set.missing() {
+            Some(missing) => Err(VpxError::new(VpxErrorCode::FileNotFound, missing)),
+            None => Ok(Some(set.first().to_path_buf())),
+        },
+        Some(_) => Ok(None),
+        None => Ok(crate::sevenzip::is_pack(src).then(|| src.to_path_buf())),
+    }
+}
+
+/// Opens the zip at `src`, joining its parts first when it is one of a
+/// split `.zip`. Fails with the missing parts for an incomplete set, and
+/// with `InvalidArgument` for 7z and RAR ones (`seven_zip_input`).
+pub(crate) fn open_zip(src: &Path) -> Result<zip::ZipArchive<Joined>, VpxError> {
+    let open_error =
+        |e: io::Error| VpxError::new(VpxErrorCode::OpenFailed, format!("Failed to open '{}': {}", src.display(), e));
+    let set = PartSet::find(src);
+    let reader = match &set {
+        None => Joined::open(&[src.to_path_buf()]).map_err(open_error)?,
+        Some(set) => {
+            if let Some(missing) = set.missing() {
+                return Err(VpxError::new(VpxErrorCode::FileNotFound, missing));
+            }
+            if set.format != Format::Zip {
+                return Err(VpxError::new(
+                    VpxErrorCode::InvalidArgument,
+                    format!("'{}' is a {} pack, not a zip", set.name, set.format.as_str()),
+                ));
+            }
+            set.reader().map_err(open_error)?
+        }
+    };
+    zip::ZipArchive::new(reader).map_err(|e| {
+        VpxError::new(VpxErrorCode::ParseFailed, format!("Failed to read archive '{}': {}", src.display(), e))
+    })
+}
@@ -0,0 +1,457 @@
+//! Moving media between frontend naming conventions.
+//!
+//! HyperPin, PinballX and PinUP Popper keep one folder per kind of media
+//! with files named after the table (`Wheel Images/Attack from Mars (Bally
+//! 1995).png`); this frontend keeps fixed names inside each table's folder
+//! (`Attack from Mars (Bally 1995)/images/wheel.png`). Users coming from
+//! those frontends bring thousands of files in the wrong layout. A flat
+//! file belongs to the table whose `.vpx` or folder carries its name.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fs;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::{library, oplog, paths, provenance, relocate};
+
+/// Kinds of media, as (kind, per-table default from the frontend settings).
+const KINDS: &[(&str, &str)] = &[
+    ("wheel", "images/wheel.png"),
+    ("playfield_image", "images/table.png"),
+    ("playfield_video", "video/table.mp4"),
+    ("backglass_image", "images/backglass.png"),
+    ("backglass_video", "video/backglass.mp4"),
+    ("dmd_image", "images/dmd.png"),
+    ("dmd_video", "video/dmd.mp4"),
+    ("topper_image", "images/topper.png"),
+    ("topper_video", "images/topper.mp4"),
+    ("music", "audio/music.mp3"),
+    ("launch_audio", "audio/launch.mp3"),
+];
+
+/// Folder per kind in the flat conventions. PinUP keeps stills and videos
+/// of a screen together; the extension tells them apart.
+const FLAT_CONVENTIONS: &[(&str, &[(&str, &str)])] = &[
+    (
+        "hyperpin",
+        &[
+            ("wheel", "Wheel Images"),
+            ("playfield_image", "Table Images"),
+            ("playfield_video", "Table Videos"),
+            ("backglass_image", "Backglass Images"),
+            ("backglass_video", "Backglass Videos"),
+        ],
+    ),
+    (
+        "pinballx",
+        &[
+            ("wheel", "Wheel Images"),
+            ("playfield_image", "Table Images"),
+            ("playfield_video", "Table Videos"),
+            ("backglass_image", "Backglass Images"),
+            ("backglass_video", "Backglass Videos"),
+            ("dmd_image", "DMD Images"),
+            ("dmd_video", "DMD Videos"),
+            ("topper_image", "Topper Images"),
+            ("topper_video", "Topper Videos"),
+            ("music", "Table Audio"),
+            ("launch_audio", "Launch Audio"),
+        ],
+    ),
+    (
+        "pinup",
+        &[
+            ("wheel", "Wheel"),
+            ("playfield_image", "Playfield"),
+            ("playfield_video", "Playfield"),
+            ("backglass_image", "BackGlass"),
+            ("backglass_video", "BackGlass"),
+            ("dmd_image", "DMD"),
+            ("dmd_video", "DMD"),
+            ("topper_image", "Topper"),
+            ("topper_video", "Topper"),
+            ("music", "Audio"),
+            ("launch_audio", "AudioLaunch"),
+        ],
+    ),
+];
+
+/// The per-table convention's name.
+const PER_TABLE: &str = "per_table";
+
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp", "gif", "bmp"];
+const VIDEO_EXTENSIONS: &[&str] = &["mp4", "f4v", "flv", "avi", "mkv", "webm", "mov", "m4v"];
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "ogg", "wav", "flac", "m4a"];
+/// Stills the wrapper can re-encode when the target name wants another format.
+const CONVERTIBLE: &[&str] = &["png", "jpg", "jpeg", "webp"];
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct MigrateOptions {
+    /// Plan only: report the renames, change nothing.
+    dry_run: bool,
+    /// Copy the files instead of moving them.
+    copy: bool,
+    /// Replace files already at the target.
+    overwrite: bool,
+    /// Per-table file per kind, as in the frontend's `[CustomMedia]`
+    /// settings; kinds left out keep the defaults.
+    layout: HashMap<String, String>,
+}
+
+impl Default for MigrateOptions {
+    fn default() -> Self {
+        MigrateOptions { dry_run: true, copy: false, overwrite: false, layout: HashMap::new() }
+    }
+}
+
+const KNOWN_KEYS: &[&str] = &["dry_run", "copy", "overwrite", "layout"];
+
+/// Where the media live: the flat conventions' media folder (the one
+/// holding `Wheel Images` or `Wheel`) and the tables folder.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct MediaDirs {
+    media_path: Option<PathBuf>,
+    tables_path: Option<PathBuf>,
+}
+
+enum Convention {
+    Flat(&'static [(&'static str, &'static str)]),
+    PerTable,
+}
+
+fn convention(name: &str) -> Result<Convention, VpxError> {
+    if name == PER_TABLE {
+        return Ok(Convention::PerTable);
+    }
+    match FLAT_CONVENTIONS.iter().find(|(n, _)| *n == name) {
+        Some((_, folders)) => Ok(Convention::Flat(folders)),
+        None => Err(VpxError::new(
+            VpxErrorCode::InvalidArgument,
+            format!("Unknown naming convention '{}' (hyperpin, pinballx, pinup or per_table)", name),
+        )),
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension().and_then(OsStr::to_str).unwrap_or_default().to_ascii_lowercase()
+}
+
+fn stem(path: &Path) -> String {
+    path.file_stem().and_then(OsStr::to_str).unwrap_or_default().to_string()
+}
+
+/// Extensions a file of `kind` can have.
+fn extensions_of(kind: &str) -> &'static [&'static str] {
+    match kind {
+        "music" | "launch_audio" => AUDIO_EXTENSIONS,
+        k if k.ends_with("_video") => VIDEO_EXTENSIONS,
+        _ => IMAGE_EXTENSIONS,
+    }
+}
+
+/// One medium found in the source layout.
+struct Medium {
+    kind: &'static str,
+    /// The table name it goes by: the file stem (flat) or the `.vpx` stem.
+    name: String,
+    path: PathBuf,
+}
+
+struct Plan {
+    kind: &'static str,
+    from: PathBuf,
+    to: Option<PathBuf>,
+    action: &'static str,
+    reason: Option<String>,
+}
+
+/// The tables under `tables_path`, as (`.vpx` stem, folder).
+fn tables(tables_path: &Path) -> Vec<(String, PathBuf)> {
+    let mut tables: Vec<(String, PathBuf)> = paths::files_under(tables_path)
+        .into_iter()
+        .filter(|f| extension(f) == "vpx")
+        .map(|f| (stem(&f), paths::table_dir(&f)))
+        .collect();
+    tables.sort();
+    tables
+}
+
+fn layout<'a>(options: &'a MigrateOptions, kind: &str) -> &'a str {
+    match options.layout.get(kind) {
+        Some(relative) => relative,
+        None => KINDS.iter().find(|(k, _)| *k == kind).map(|(_, default)| *default).unwrap_or_default(),
+    }
+}
+
+fn collect(
+    from: &Convention,
+    dirs: &MediaDirs,
+    tables: &[(String, PathBuf)],
+    options: &MigrateOptions,
+) -> Result<(Vec<Medium>, Vec<PathBuf>), VpxError> {
+    let mut media = Vec::new();
+    let mut unmatched = Vec::new();
+    match from {
+        Convention::Flat(folders) => {
+            let root = required(&dirs.media_path, "media_path")?;
+            let mut seen: HashSet<PathBuf> = HashSet::new();
+            for (kind, folder) in *folders {
+                let Some(dir) = paths::find_case_insensitive(root, folder).filter(|d| d.is_dir()) else { continue };
+                let mut files: Vec<PathBuf> = fs::read_dir(&dir)
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .map(|e| e.path())
+                    .filter(|p| p.is_file())
+                    .collect();
+                files.sort();
+                for file in files {
+                    if extensions_of(kind).contains(&extension(&file).as_str()) {
+                        seen.insert(file.clone());
+                        media.push(Medium { kind, name: stem(&file), path: file });
+                    }
+                }
+            }
+            // Files no kind claimed (a `.txt` in `Wheel Images`).
+            for (_, folder) in *folders {
+                let Some(dir) = paths::find_case_insensitive(root, folder) else { continue };
+                for file in fs::read_dir(&dir).into_iter().flatten().flatten().map(|e| e.path()) {
+                    if file.is_file() && !seen.contains(&file) && !unmatched.contains(&file) {
+                        unmatched.push(file);
+                    }
+                }
+            }
+        }
+        Convention::PerTable => {
+            for (name, folder) in tables {
+                for (kind, _) in KINDS {
+                    if let Some(file) = paths::resolve_case_insensitive(folder, layout(options, kind)) {
+                        if file.is_file() {
+                            media.push(Medium { kind, name: name.clone(), path: file });
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok((media, unmatched))
+}
+
+fn required<'a>(dir: &'a Option<PathBuf>, key: &str) -> Result<&'a Path, VpxError> {
+    match dir {
+        Some(dir) if dir.is_dir() => Ok(dir),
+        Some(dir) => Err(VpxError::new(VpxErrorCode::OpenFailed, format!("'{}' is not a folder", dir.display()))),
+        None => Err(VpxError::new(VpxErrorCode::InvalidArgument, format!("Media dirs JSON has no \"{}\".", key))),
+    }
+}
+
+/// Where `medium` goes in the target layout, and whether it must be
+/// re-encoded to get there.
+fn target(
+    medium: &Medium,
+    to: &Convention,
+    dirs: &MediaDirs,
+    by_name: &HashMap<String, &PathBuf>,
+    options: &MigrateOptions,
+) -> Result<(PathBuf, bool), String> {
+    match to {
+        Convention::Flat(folders) => {
+            let Some((_, folder)) = folders.iter().find(|(k, _)| *k == medium.kind) else {
+                return Err("The target convention has no folder for this kind of media".to_string());
+            };
+            let root = dirs.media_path.as_deref().expect("checked by plan");
+            let dir = paths::find_case_insensitive(root, folder).unwrap_or_else(|| root.join(folder));
+            Ok((dir.join(format!("{}.{}", medium.name, extension(&medium.path))), false))
+        }
+        Convention::PerTable => {
+            let Some(folder) = by_name.get(&medium.name.to_lowercase()) else {
+                return Err(format!("No table named '{}'", medium.name));
+            };
+            let relative = layout(options, medium.kind);
+            let to = folder.join(relative);
+            let (from_ext, to_ext) = (extension(&medium.path), extension(&to));
+            let same = from_ext == to_ext || (["jpg", "jpeg"].contains(&from_ext.as_str()) && to_ext.starts_with("jp"));
+            match same {
+                true => Ok((to, false)),
+                false if CONVERTIBLE.contains(&from_ext.as_str()) && CONVERTIBLE.contains(&to_ext.as_str()) => {
+                    Ok((to, true))
+                }
+                false => {
+                    Err(format!("The frontend loads '{}', this file is .{} and can't be converted", relative, from_ext))
+                }
+            }
+        }
+    }
+}
+
+fn plan(from: &Convention, to: &Convention, dirs: &MediaDirs, options: &MigrateOptions) -> Result<Value, VpxError> {
+    let tables = match (from, to) {
+        (Convention::Flat(_), Convention::Flat(_)) => Vec::new(),
+        _ => tables(required(&dirs.tables_path, "tables_path")?),
+    };
+    // Flat files go by the `.vpx` name or the table folder's name.
+    let mut by_name: HashMap<String, &PathBuf> = HashMap::new();
+    for (name, folder) in &tables {
+        by_name.entry(name.to_lowercase()).or_insert(folder);
+        by_name.entry(stem(folder).to_lowercase()).or_insert(folder);
+    }
+    if matches!(to, Convention::Flat(_)) && dirs.media_path.is_none() {
+        return Err(VpxError::new(VpxErrorCode::InvalidArgument, "Media dirs JSON has no \"media_path\"."));
+    }
+    let (media, mut unmatched) = collect(from, dirs, &tables, options)?;
+
+    let mut targets: HashSet<PathBuf> = HashSet::new();
+    let mut steps = Vec::new();
+    for medium in media {
+        let step = match target(&medium, to, dirs, &by_name, options) {
+            Err(reason) => {
+                if matches!(to, Convention::PerTable) && !by_name.contains_key(&medium.name.to_lowercase()) {
+                    unmatched.push(medium.path);
+                    continue;
+                }
+                Plan { kind: medium.kind, from: medium.path, to: None, action: "skip", reason: Some(reason) }
+            }
+            Ok((to, _)) if to == medium.path => continue,
+            Ok((to, _)) if !targets.insert(to.clone()) => Plan {
+                kind: medium.kind,
+                from: medium.path,
+                to: Some(to),
+                action: "skip",
+                reason: Some("Another file already goes there".to_string()),
+            },
+            Ok((to, _)) if to.exists() && !options.overwrite => Plan {
+                kind: medium.kind,
+                from: medium.path,
+                to: Some(to),
+                action: "skip",
+                reason: Some("Target exists".to_string()),
+            },
+            Ok((to, convert)) => {
+                let action = match (convert, options.copy) {
+                    (true, _) => "convert",
+                    (false, true) => "copy",
+                    (false, false) => "move",
+                };
+                Plan { kind: medium.kind, from: medium.path, to: Some(to), action, reason: None }
+            }
+        };
+        steps.push(step);
+    }
+
+    let mut failed = 0;
+    if !options.dry_run {
+        crate::safe_mode::check("migrating media")?;
+        for step in steps.iter_mut().filter(|s| s.action != "skip") {
+            let to = step.to.as_deref().expect("planned steps have a target");
+            if let Err(e) = apply(step, to, options) {
+                failed += 1;
+                step.reason = Some(e.to_string());
+                step.action = "failed";
+            }
+        }
+    }
+
+    let count = |action: &str| steps.iter().filter(|s| s.action == action).count();
+    let planned = steps.len() - count("skip") - count("failed");
+    let listed: Vec<Value> = steps
+        .iter()
+        .map(|s| json!({ "kind": s.kind, "from": s.from, "to": s.to, "action": s.action, "reason": s.reason }))
+        .collect();
+    Ok(json!({
+        "dry_run": options.dry_run,
+        "planned": planned,
+        "skipped": count("skip"),
+        "failed": failed,
+        "steps": listed,
+        "unmatched": unmatched,
+    }))
+}
+
+fn apply(step: &Plan, to: &Path, options: &MigrateOptions) -> Result<(), VpxError> {
+    let io_error = |e: std::io::Error| {
+        VpxError::new(VpxErrorCode::OpenFailed, format!("'{}' -> '{}': {}", step.from.display(), to.display(), e))
+    };
+    if step.action == "move" {
+        relocate::move_path(&step.from, to)?;
+        provenance::follow(&step.from, to, false);
+        return Ok(());
+    }
+    let data = match step.action {
+        "convert" => {
+            let format = image::ImageFormat::from_path(to)
+                .map_err(|e| VpxError::new(VpxErrorCode::InvalidArgument, e.to_string()))?;
+            let decoded = image::open(&step.from).map_err(|e| {
+                VpxError::new(VpxErrorCode::InvalidArgument, format!("'{}': {}", step.from.display(), e))
+            })?;
+            let mut encoded = std::io::Cursor::new(Vec::new());
+            decoded
+                .write_to(&mut encoded, format)
+                .map_err(|e| VpxError::new(VpxErrorCode::AllocFailed, format!("'{}': {}", to.display(), e)))?;
+            encoded.into_inner()
+        }
+        _ => fs::read(&step.from).map_err(io_error)?,
+    };
+    paths::write_atomic(to, &data).map_err(io_error)?;
+    let keep = step.action != "convert" || options.copy;
+    if !keep {
+        oplog::discard(&step.from).map_err(io_error)?;
+    }
+    provenance::follow(&step.from, to, keep);
+    Ok(())
+}
+
+/// Plans, and with `"dry_run": false` carries out, moving media files from
+/// one naming convention to another: `"hyperpin"`, `"pinballx"`, `"pinup"`
+/// (a folder per kind of media, files named after the table) or
+/// `"per_table"` (fixed names inside each table's folder, as this frontend
+/// loads them).
+///
+/// `media_dirs_json` is `{"media_path", "tables_path"}`: the flat
+/// conventions' media folder (holding `Wheel Images`, `Wheel`...) and the
+/// tables folder, needed when either side is `per_table`. `options_json`
+/// (nullable): `dry_run` (default true), `copy`, `overwrite`, `layout`
+/// (kind -> per-table path, like the frontend's `[CustomMedia]` settings).
+/// Stills needing another format are re-encoded (png, jpeg, webp); other
+/// mismatches are skipped.
+///
+/// Returns `{"dry_run", "planned", "skipped", "failed", "steps": [{"kind",
+/// "from", "to", "action": "move" | "copy" | "convert" | "skip" | "failed",
+/// "reason"}], "unmatched": [paths]}`, or null on failure. Release with
+/// `vpx_free_string`.
+///
+/// # Safety
+/// All arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_migrate_media_naming(
+    media_dirs_json: *const c_char,
+    from_convention: *const c_char,
+    to_convention: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    let result = unsafe { crate::str_from_c(media_dirs_json, "media dirs") }
+        .and_then(|text| {
+            serde_json::from_str::<MediaDirs>(text)
+                .map_err(|e| VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid media dirs JSON: {}", e)))
+        })
+        .and_then(|dirs| {
+            let from_name = unsafe { crate::str_from_c(from_convention, "from convention") }?;
+            let to_name = unsafe { crate::str_from_c(to_convention, "to convention") }?;
+            let (from, to) = (convention(from_name)?, convention(to_name)?);
+            let options: MigrateOptions = unsafe { library::options_from_c(options_json, KNOWN_KEYS) }?;
+            if let Some(unknown) = options.layout.keys().find(|k| !KINDS.iter().any(|(kind, _)| kind == k)) {
+                return Err(VpxError::new(VpxErrorCode::InvalidArgument, format!("Unknown media kind '{}'", unknown)));
+            }
+            let summary = format!("Migrate media from {} to {} naming", from_name, to_name);
+            oplog::record("media_naming", summary, || plan(&from, &to, &dirs, &options))
+        })
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_migrate_media_naming", result)
+}
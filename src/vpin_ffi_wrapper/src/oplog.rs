@@ -0,0 +1,671 @@
+//! Undo log for the write APIs.
+//!
+//! When `operation_log_dir` is configured, each call that changes the
+//! library on the user's behalf (import, remove, move, bundle import,
+//! download intake, media renames and scores, texture and sound rewrites,
+//! playlist writes, and the stats, flags, review, tag and saved search
+//! edits) is one operation in `<dir>/operations.jsonl`, an append-only log.
+//! Writes the frontend makes on its own (play sessions from
+//! `vpx_record_play`, picks) and exports to files outside the library
+//! (cards, bundles) are not logged.
+//!
+//! While a call runs, every file it is about to overwrite is first copied
+//! to `<dir>/files/<id>/`, files it removes are moved there instead of
+//! being deleted, and moves are noted. Undo puts all of that back in
+//! reverse order (keeping copies of what it replaces, so redo can reapply
+//! it), unless a file was changed since, logged or not; undo and redo are
+//! appended to the log as events of their own.
+//!
+//! Only the last `operation_log_limit` operations keep their backups. A new
+//! operation after an undo drops what could have been redone.
+//!
+//! A transaction groups several calls the same way, log or not, so a batch
+//! (an import followed by its sidecar and media writes) can be rolled back
+//! as a whole when a step fails.
+
+use std::cell::RefCell;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{self, AtomicU64};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::config;
+use crate::error::{VpxError, VpxErrorCode};
+use crate::store;
+
+const LOG_FILE: &str = "operations.jsonl";
+const FILES_DIR: &str = "files";
+
+/// Held while the log is read or appended. Holds the ids of the operations
+/// still recording, whose backups `prune` must leave alone.
+static LOG_LOCK: Mutex<Vec<u64>> = Mutex::new(Vec::new());
+static SCRATCH: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Change {
+    /// A file about to be written: its old content (`before`, absent if it
+    /// didn't exist). `left` is the SHA-256 of the file as the operation
+    /// left it ("" if it left none), so undo can tell it changed since;
+    /// older logs have none. `after` is filled in by undo for redo.
+    File {
+        path: PathBuf,
+        before: Option<PathBuf>,
+        #[serde(default)]
+        left: Option<String>,
+        #[serde(default)]
+        after: Option<PathBuf>,
+    },
+    /// A file or folder moved (removals are moves into the backups).
+    Move { from: PathBuf, to: PathBuf },
+    /// A folder created for the operation, removed again by undo if empty.
+    CreatedDir { path: PathBuf },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Operation {
+    id: u64,
+    time: u64,
+    kind: String,
+    summary: String,
+    changes: Vec<Change>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event {
+    Operation(Operation),
+    /// `changes` as undo left them, with `after` copies.
+    Undo { id: u64, time: u64, changes: Vec<Change> },
+    Redo { id: u64, time: u64 },
+}
+
+/// The operation being recorded on this thread.
+struct Recording {
+    operation: Operation,
+    dir: PathBuf,
+    /// False for a transaction's scratch folder, dropped when it ends.
+    logged: bool,
+    transaction: bool,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Recording>> = const { RefCell::new(None) };
+}
+
+fn log_dir() -> Option<PathBuf> {
+    let dir = config::get().operation_log_dir;
+    (!dir.is_empty()).then(|| PathBuf::from(dir))
+}
+
+fn io_error(what: &str, path: &Path, e: io::Error) -> VpxError {
+    VpxError::new(VpxErrorCode::OpenFailed, format!("Failed to {} '{}': {}", what, path.display(), e))
+}
+
+fn read_events(dir: &Path) -> Result<Vec<Event>, VpxError> {
+    let path = dir.join(LOG_FILE);
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(io_error("read", &path, e)),
+    };
+    // A line cut short by a crash is skipped rather than losing the log.
+    Ok(text.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+}
+
+fn append(dir: &Path, event: &Event) -> Result<(), VpxError> {
+    let path = dir.join(LOG_FILE);
+    let line = serde_json::to_string(event)
+        .map_err(|e| VpxError::new(VpxErrorCode::AllocFailed, format!("Failed to serialize operation: {}", e)))?;
+    fs::create_dir_all(dir).map_err(|e| io_error("create", dir, e))?;
+    let mut file = OpenOptions::new().create(true).append(true).open(&path).map_err(|e| io_error("open", &path, e))?;
+    writeln!(file, "{}", line).map_err(|e| io_error("write", &path, e))
+}
+
+/// Undo and redo stacks replayed from the log: the operations (latest
+/// state of their changes) that can be undone, then those that can be redone.
+fn stacks(events: Vec<Event>) -> (Vec<Operation>, Vec<Operation>) {
+    let (mut done, mut undone): (Vec<Operation>, Vec<Operation>) = (Vec::new(), Vec::new());
+    for event in events {
+        match event {
+            Event::Operation(operation) => {
+                done.push(operation);
+                undone.clear();
+            }
+            Event::Undo { id, changes, .. } => {
+                if done.last().is_some_and(|o| o.id == id) {
+                    if let Some(mut operation) = done.pop() {
+                        operation.changes = changes;
+                        undone.push(operation);
+                    }
+                }
+            }
+            Event::Redo { id, .. } => {
+                if undone.last().is_some_and(|o| o.id == id) {
+                    done.extend(undone.pop());
+                }
+            }
+        }
+    }
+    (done, undone)
+}
+
+/// `rename`, or copy and delete across drives.
+fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(_) if from.is_file() => fs::copy(from, to).and_then(|_| fs::remove_file(from)),
+        Err(e) => Err(e),
+    }
+}
+
+/// A fresh backup path for the operation being recorded.
+fn backup_path(recording: &Recording) -> PathBuf {
+    recording
+        .dir
+        .join(FILES_DIR)
+        .join(recording.operation.id.to_string())
+        .join(recording.operation.changes.len().to_string())
+}
+
+/// A fresh id, reserved by creating its backup folder and marked as
+/// recording until `release`, so concurrent operations never share one.
+fn reserve_id(dir: &Path) -> Result<u64, VpxError> {
+    let mut recording = LOG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let events = read_events(dir)?;
+    let last = events
+        .iter()
+        .filter_map(|e| match e {
+            Event::Operation(o) => Some(o.id),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+    // Ids stay unique even when a failed operation left no entry.
+    let used = fs::read_dir(dir.join(FILES_DIR))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|e| e.file_name().to_str()?.parse::<u64>().ok())
+        .max()
+        .unwrap_or(0);
+    let id = last.max(used).max(recording.iter().copied().max().unwrap_or(0)) + 1;
+    let backups = dir.join(FILES_DIR).join(id.to_string());
+    fs::create_dir_all(&backups).map_err(|e| io_error("create", &backups, e))?;
+    recording.push(id);
+    Ok(id)
+}
+
+/// Marks `id` as no longer recording.
+fn release(recording: &mut Vec<u64>, id: u64) {
+    if let Some(i) = recording.iter().position(|&r| r == id) {
+        recording.swap_remove(i);
+    }
+}
+
+/// Starts recording on this thread, into the log or, for a transaction
+/// without one, a scratch folder.
+fn start(kind: &str, summary: String, transaction: bool) -> Result<(), VpxError> {
+    let (dir, logged) = match log_dir() {
+        Some(dir) => (dir, true),
+        None => {
+            let n = SCRATCH.fetch_add(1, atomic::Ordering::Relaxed);
+            (std::env::temp_dir().join(format!("vpin_transaction_{}_{}", std::process::id(), n)), false)
+        }
+    };
+    let id = match logged {
+        true => reserve_id(&dir)?,
+        // The scratch folder is the transaction's own.
+        false => 0,
+    };
+    let operation = Operation { id, time: store::now(), kind: kind.to_string(), summary, changes: Vec::new() };
+    CURRENT.with(|c| *c.borrow_mut() = Some(Recording { operation, dir, logged, transaction }));
+    Ok(())
+}
+
+/// Stops recording and logs the operation, if it changed anything.
+fn finish(failure: Option<&VpxError>) {
+    let Some(recording) = CURRENT.with(|c| c.borrow_mut().take()) else { return };
+    let Recording { mut operation, dir, logged, .. } = recording;
+    if !logged {
+        let _ = fs::remove_dir_all(&dir);
+        return;
+    }
+    let mut recording = LOG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    release(&mut recording, operation.id);
+    if operation.changes.is_empty() {
+        let _ = fs::remove_dir_all(dir.join(FILES_DIR).join(operation.id.to_string()));
+        return;
+    }
+    // A failed call may still have changed (or moved away) files; log it
+    // so they can be put back.
+    if let Some(e) = failure {
+        operation.summary = format!("{} (failed: {})", operation.summary, e);
+    }
+    for change in &mut operation.changes {
+        if let Change::File { path, left, .. } = change {
+            *left = Some(content_hash(path));
+        }
+    }
+    if let Err(e) = append(&dir, &Event::Operation(operation)) {
+        log_warn!("operation log: {}", e);
+    }
+    prune(&dir, &recording);
+}
+
+/// Runs `f` as one undoable operation when the log is configured. Calls
+/// inside another operation or a transaction belong to it. Nothing is
+/// logged if `f` changed nothing.
+pub(crate) fn record<T>(kind: &str, summary: String, f: impl FnOnce() -> Result<T, VpxError>) -> Result<T, VpxError> {
+    let nested = CURRENT.with(|c| c.borrow().is_some());
+    if nested || log_dir().is_none() {
+        return f();
+    }
+    start(kind, summary, false)?;
+    let result = f();
+    finish(result.as_ref().err());
+    result
+}
+
+/// Deletes the backups of operations too old to undo. Those of operations
+/// still recording (`recording`, or newer than the log in another process)
+/// are kept.
+fn prune(dir: &Path, recording: &[u64]) {
+    let limit = config::get().operation_log_limit;
+    let (done, undone) = stacks(read_events(dir).unwrap_or_default());
+    let last = done.iter().chain(&undone).map(|o| o.id).max().unwrap_or(0);
+    let keep: Vec<u64> = done.iter().rev().take(limit).chain(&undone).map(|o| o.id).chain(recording.iter().copied()).collect();
+    for entry in fs::read_dir(dir.join(FILES_DIR)).into_iter().flatten().flatten() {
+        let Some(id) = entry.file_name().to_str().and_then(|name| name.parse::<u64>().ok()) else { continue };
+        if id <= last && !keep.contains(&id) {
+            let _ = fs::remove_dir_all(entry.path());
+        }
+    }
+}
+
+/// Backs up `path` before it is overwritten, once per operation.
+pub(crate) fn touch(path: &Path) -> io::Result<()> {
+    CURRENT.with(|c| {
+        let mut current = c.borrow_mut();
+        let Some(recording) = current.as_mut() else { return Ok(()) };
+        let seen = recording.operation.changes.iter().any(|change| match change {
+            Change::File { path: p, .. } => p == path,
+            _ => false,
+        });
+        if seen {
+            return Ok(());
+        }
+        let before = match path.is_file() {
+            true => {
+                let backup = backup_path(recording);
+                fs::create_dir_all(backup.parent().unwrap_or(&recording.dir))?;
+                fs::copy(path, &backup)?;
+                Some(backup)
+            }
+            false => None,
+        };
+        recording.operation.changes.push(Change::File { path: path.to_path_buf(), before, left: None, after: None });
+        Ok(())
+    })
+}
+
+/// Notes a move the operation made.
+pub(crate) fn moved(from: &Path, to: &Path) {
+    CURRENT.with(|c| {
+        if let Some(recording) = c.borrow_mut().as_mut() {
+            recording.operation.changes.push(Change::Move { from: from.to_path_buf(), to: to.to_path_buf() });
+        }
+    })
+}
+
+/// Notes a folder the operation created.
+pub(crate) fn created_dir(path: &Path) {
+    CURRENT.with(|c| {
+        if let Some(recording) = c.borrow_mut().as_mut() {
+            recording.operation.changes.push(Change::CreatedDir { path: path.to_path_buf() });
+        }
+    })
+}
+
+/// Removes a file or folder; while recording it is moved to the backups.
+pub(crate) fn discard(path: &Path) -> io::Result<()> {
+    let backup = CURRENT.with(|c| c.borrow().as_ref().map(backup_path));
+    match backup {
+        Some(backup) => {
+            move_path(path, &backup)?;
+            moved(path, &backup);
+            Ok(())
+        }
+        None if path.is_dir() => fs::remove_dir_all(path),
+        None => fs::remove_file(path),
+    }
+}
+
+fn conflict(path: &Path) -> VpxError {
+    VpxError::new(
+        VpxErrorCode::InvalidArgument,
+        format!("'{}' is in the way; it changed after the operation", path.display()),
+    )
+}
+
+/// SHA-256 of `path`, "" when there is no such file.
+fn content_hash(path: &Path) -> String {
+    crate::hashes::sha256_file(path).unwrap_or_default()
+}
+
+/// Copies `from` over `path`, or deletes `path` when there is nothing to restore.
+fn restore(path: &Path, from: Option<&Path>) -> Result<(), VpxError> {
+    match from {
+        Some(from) => fs::copy(from, path).map(|_| ()).map_err(|e| io_error("restore", path, e)),
+        None if path.exists() => fs::remove_file(path).map_err(|e| io_error("remove", path, e)),
+        None => Ok(()),
+    }
+}
+
+/// The files undo or redo writes over or moves.
+fn targets(operation: &Operation, undoing: bool) -> Vec<PathBuf> {
+    operation
+        .changes
+        .iter()
+        .filter_map(|change| match change {
+            Change::File { path, .. } => Some(path.clone()),
+            Change::Move { from, to } => Some(if undoing { to.clone() } else { from.clone() }),
+            Change::CreatedDir { .. } => None,
+        })
+        .collect()
+}
+
+fn undo(dir: &Path, operation: &mut Operation) -> Result<(), VpxError> {
+    crate::locks::check_tables_not_in_use(&targets(operation, true))?;
+    // A file written again since (a later playlist or user store edit) is
+    // in the way just like a moved one: restoring it would lose that edit.
+    for change in &operation.changes {
+        if let Change::File { path, left: Some(left), .. } = change {
+            if content_hash(path) != *left {
+                return Err(conflict(path));
+            }
+        }
+    }
+    let redo_dir = dir.join(FILES_DIR).join(operation.id.to_string()).join("redo");
+    for n in (0..operation.changes.len()).rev() {
+        if let Err(e) = undo_change(&redo_dir, n, &mut operation.changes[n]) {
+            // Reapply what was already put back, so the operation is still
+            // done as the log says and can be undone once the way is clear.
+            if let Err(again) = reapply(&operation.changes[n + 1..]) {
+                log_error!("operation log: undo of {} stopped halfway: {}", operation.id, again);
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+/// Undoes change `n`, copying what it replaces into `redo_dir`.
+fn undo_change(redo_dir: &Path, n: usize, change: &mut Change) -> Result<(), VpxError> {
+    match change {
+        Change::File { path, before, after, .. } => {
+            *after = match path.is_file() {
+                true => {
+                    let copy = redo_dir.join(n.to_string());
+                    fs::create_dir_all(redo_dir).map_err(|e| io_error("create", redo_dir, e))?;
+                    fs::copy(&*path, &copy).map_err(|e| io_error("back up", path, e))?;
+                    Some(copy)
+                }
+                false => None,
+            };
+            restore(path, before.as_deref())?;
+        }
+        Change::Move { from, to } => {
+            if from.exists() {
+                return Err(conflict(from));
+            }
+            move_path(to, from).map_err(|e| io_error("move back", to, e))?;
+        }
+        Change::CreatedDir { path } => {
+            // Only empty subfolders may be left once the files are undone.
+            if crate::paths::files_under(path).is_empty() {
+                let _ = fs::remove_dir_all(&*path);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn redo(operation: &Operation) -> Result<(), VpxError> {
+    crate::locks::check_tables_not_in_use(&targets(operation, false))?;
+    reapply(&operation.changes)
+}
+
+/// Makes undone `changes` again, in order.
+fn reapply(changes: &[Change]) -> Result<(), VpxError> {
+    for change in changes {
+        match change {
+            Change::File { path, after, .. } => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).map_err(|e| io_error("create", parent, e))?;
+                }
+                restore(path, after.as_deref())?;
+            }
+            Change::Move { from, to } => {
+                if to.exists() {
+                    return Err(conflict(to));
+                }
+                move_path(from, to).map_err(|e| io_error("move", from, e))?;
+            }
+            Change::CreatedDir { path } => fs::create_dir_all(path).map_err(|e| io_error("create", path, e))?,
+        }
+    }
+    Ok(())
+}
+
+fn require_dir() -> Result<PathBuf, VpxError> {
+    log_dir().ok_or_else(|| {
+        VpxError::new(
+            VpxErrorCode::InvalidArgument,
+            "No operation log configured (set \"operation_log_dir\" with vpx_wrapper_init).",
+        )
+    })
+}
+
+fn summary(operation: &Operation, state: &str) -> Value {
+    let files: Vec<&PathBuf> = operation
+        .changes
+        .iter()
+        .filter_map(|c| match c {
+            Change::File { path, .. } => Some(path),
+            Change::Move { from, .. } => Some(from),
+            Change::CreatedDir { .. } => None,
+        })
+        .collect();
+    json!({
+        "id": operation.id,
+        "time": operation.time,
+        "kind": operation.kind,
+        "summary": operation.summary,
+        "state": state,
+        "files": files,
+    })
+}
+
+fn undo_last() -> Result<Value, VpxError> {
+    crate::safe_mode::check("undo")?;
+    let dir = require_dir()?;
+    let _guard = LOG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (done, _) = stacks(read_events(&dir)?);
+    let limit = config::get().operation_log_limit;
+    let Some(mut operation) = done.into_iter().rev().take(limit).next() else {
+        return Err(VpxError::new(VpxErrorCode::InvalidArgument, "Nothing to undo."));
+    };
+    undo(&dir, &mut operation)?;
+    append(&dir, &Event::Undo { id: operation.id, time: store::now(), changes: operation.changes.clone() })?;
+    Ok(summary(&operation, "undone"))
+}
+
+fn redo_last() -> Result<Value, VpxError> {
+    crate::safe_mode::check("redo")?;
+    let dir = require_dir()?;
+    let _guard = LOG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (_, undone) = stacks(read_events(&dir)?);
+    let Some(operation) = undone.last() else {
+        return Err(VpxError::new(VpxErrorCode::InvalidArgument, "Nothing to redo."));
+    };
+    redo(operation)?;
+    append(&dir, &Event::Redo { id: operation.id, time: store::now() })?;
+    Ok(summary(operation, "done"))
+}
+
+fn history(limit: usize) -> Result<Value, VpxError> {
+    let dir = require_dir()?;
+    let (done, undone) = {
+        let _guard = LOG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        stacks(read_events(&dir)?)
+    };
+    let undo_limit = config::get().operation_log_limit;
+    let operations: Vec<Value> = undone
+        .iter()
+        .map(|o| summary(o, "undone"))
+        .chain(done.iter().rev().enumerate().map(|(i, o)| summary(o, if i < undo_limit { "done" } else { "expired" })))
+        .take(if limit == 0 { usize::MAX } else { limit })
+        .collect();
+    Ok(json!({
+        "can_undo": !done.is_empty() && undo_limit > 0,
+        "can_redo": !undone.is_empty(),
+        "operations": operations,
+    }))
+}
+
+fn report(name: &str, result: Result<Value, VpxError>) -> *mut c_char {
+    crate::envelope::finish(name, result.and_then(|value| crate::into_c_string(value.to_string())))
+}
+
+/// Undoes the most recent operation still done: `{"id", "time", "kind",
+/// "summary", "state": "undone", "files"}`. Changes nothing, with an
+/// error, if a file it would put back was written again since (say a later
+/// stats edit in the user store) or one it would move back is in the way,
+/// nor while a table it touches is open in another program (`FileInUse`).
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+#[no_mangle]
+pub extern "C" fn vpx_undo_last_operation() -> *mut c_char {
+    report("vpx_undo_last_operation", undo_last())
+}
+
+/// Reapplies the most recently undone operation, if nothing was done since.
+/// Returns its summary as for `vpx_undo_last_operation`, or null; fails
+/// the same way when a table is in use.
+#[no_mangle]
+pub extern "C" fn vpx_redo_operation() -> *mut c_char {
+    report("vpx_redo_operation", redo_last())
+}
+
+/// The operation log, newest first and at most `limit` entries (0 = all):
+/// `{"can_undo", "can_redo", "operations": [{"id", "time", "kind",
+/// "summary", "state": "done"|"undone"|"expired", "files"}]}`.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+#[no_mangle]
+pub extern "C" fn vpx_get_operation_history(limit: u32) -> *mut c_char {
+    report("vpx_get_operation_history", history(limit as usize))
+}
+
+fn no_transaction() -> VpxError {
+    VpxError::new(VpxErrorCode::InvalidArgument, "No transaction is open on this thread.")
+}
+
+fn begin_transaction(label: &str) -> Result<(), VpxError> {
+    if CURRENT.with(|c| c.borrow().is_some()) {
+        return Err(VpxError::new(VpxErrorCode::InvalidArgument, "A transaction is already open on this thread."));
+    }
+    start("transaction", label.to_string(), true)
+}
+
+fn end_transaction(roll_back: bool) -> Result<(), VpxError> {
+    if !CURRENT.with(|c| c.borrow().as_ref().is_some_and(|r| r.transaction)) {
+        return Err(no_transaction());
+    }
+    if !roll_back {
+        finish(None);
+        return Ok(());
+    }
+    let Some(mut recording) = CURRENT.with(|c| c.borrow_mut().take()) else { return Err(no_transaction()) };
+    let backups = recording.dir.join(FILES_DIR).join(recording.operation.id.to_string());
+    match undo(&recording.dir, &mut recording.operation) {
+        Ok(()) => {
+            let mut ids = LOG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            if recording.logged {
+                release(&mut ids, recording.operation.id);
+            }
+            let _ = fs::remove_dir_all(if recording.logged { &backups } else { &recording.dir });
+            Ok(())
+        }
+        // Nothing was put back. With the log the transaction stays in it, to
+        // be undone once the way is clear; without, its backups are kept.
+        Err(e) if recording.logged => {
+            let id = recording.operation.id;
+            CURRENT.with(|c| *c.borrow_mut() = Some(recording));
+            finish(Some(&VpxError::new(e.code, format!("rollback: {}", e))));
+            Err(VpxError::new(e.code, format!("{} (logged as operation {})", e, id)))
+        }
+        Err(e) => Err(VpxError::new(e.code, format!("{} (backups kept in '{}')", e, backups.display()))),
+    }
+}
+
+/// Opens a transaction on the calling thread: the write calls made until
+/// `vpx_commit_transaction` or `vpx_rollback_transaction` share one set of
+/// backups, so a batch that fails midway can be put back as a whole. With
+/// the operation log configured, a committed transaction is one operation
+/// there, named `label` (nullable). Only one transaction can be open per
+/// thread. Returns a `VpxErrorCode`.
+///
+/// # Safety
+/// `label` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_begin_transaction(label: *const c_char) -> i32 {
+    let label = match label.is_null() {
+        true => Ok("transaction"),
+        false => unsafe { crate::str_from_c(label, "label") },
+    };
+    match label.and_then(begin_transaction) {
+        Ok(()) => VpxErrorCode::Ok as i32,
+        Err(e) => {
+            log_error!("vpx_begin_transaction: {}", e);
+            e.code as i32
+        }
+    }
+}
+
+/// Keeps the changes made in the open transaction and closes it. Returns a
+/// `VpxErrorCode`.
+#[no_mangle]
+pub extern "C" fn vpx_commit_transaction() -> i32 {
+    match end_transaction(false) {
+        Ok(()) => VpxErrorCode::Ok as i32,
+        Err(e) => {
+            log_error!("vpx_commit_transaction: {}", e);
+            e.code as i32
+        }
+    }
+}
+
+/// Puts back everything the open transaction changed, in reverse order,
+/// and closes it. If a file is in the way nothing is put back: with the
+/// operation log the transaction is logged so it can be undone later,
+/// without one its backups are kept. Returns a `VpxErrorCode`.
+#[no_mangle]
+pub extern "C" fn vpx_rollback_transaction() -> i32 {
+    match end_transaction(true) {
+        Ok(()) => VpxErrorCode::Ok as i32,
+        Err(e) => {
+            log_error!("vpx_rollback_transaction: {}", e);
+            e.code as i32
+        }
+    }
+}
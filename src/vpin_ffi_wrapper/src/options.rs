@@ -0,0 +1,168 @@
+//! Per-call options.
+//!
+//! The `_ex` entry points take an `options_json` argument (nullable) that
+//! tunes a single call without touching the wrapper config. Keys are all
+//! optional and unknown ones are ignored with a warning, same as the config.
+
+use std::os::raw::c_char;
+
+use serde::Deserialize;
+use serde_json::Value;
+use vpin::vpx::model::{StringEncoding, StringWithEncoding};
+
+use crate::compress::Compression;
+use crate::config::{self, IoStrategy};
+use crate::error::{VpxError, VpxErrorCode};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct CallOptions {
+    /// Return what could be read instead of failing. For table info an
+    /// unreadable TableInfo stream gives empty fields plus a "warnings" entry.
+    pub lenient: bool,
+    /// Overrides the configured `io_strategy` for this call.
+    pub io_strategy: Option<IoStrategy>,
+    /// Overrides the configured `max_file_size_mb` for this call.
+    pub max_file_size_mb: Option<u64>,
+    /// Compresses what the buffer calls and jobs return (see `compress`).
+    pub compress: Compression,
+    /// Payloads smaller than this are stored uncompressed; default 64 KiB.
+    pub compress_min_bytes: Option<usize>,
+    /// Gives up on a path-taking parse after this many milliseconds with
+    /// `Timeout`; 0 or missing waits for as long as it takes.
+    pub timeout_ms: Option<u64>,
+    /// Keeps only these top-level table info keys (and `warnings`), for list
+    /// views that don't render the rules and description blobs.
+    pub fields: Option<Vec<String>>,
+    /// Scan profile for this call, over the configured ones (see `profiles`).
+    pub profile: Option<String>,
+    /// Replaces NUL bytes in the script with U+FFFD instead of failing the
+    /// calls that return it as a C string.
+    pub sanitize_nul: bool,
+    /// How the script's bytes are decoded, for the calls that return it.
+    pub charset: Charset,
+}
+
+/// Script text encodings. VPX itself takes a script as UTF-8 when it is
+/// valid UTF-8 and as Latin-1 otherwise, which garbles the curly quotes,
+/// dashes and euro signs of scripts saved in a Windows ANSI code page.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub(crate) enum Charset {
+    /// As VPX decodes it.
+    #[default]
+    #[serde(rename = "auto")]
+    Auto,
+    /// Always UTF-8, invalid sequences replaced with U+FFFD.
+    #[serde(rename = "utf-8", alias = "utf8")]
+    Utf8,
+    /// Windows-1252, the code page of most ANSI scripts.
+    #[serde(rename = "cp1252", alias = "windows-1252")]
+    Cp1252,
+}
+
+/// Windows-1252 characters for 0x80..=0x9F, where it differs from Latin-1.
+/// The five bytes it leaves undefined stay the C1 controls Latin-1 has.
+const CP1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{8D}', '\u{017D}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{9D}', '\u{017E}', '\u{0178}',
+];
+
+/// The Windows-1252 byte for `c`, if it has one.
+pub(crate) fn cp1252_byte(c: char) -> Option<u8> {
+    match u32::from(c) {
+        0x80..=0x9F => None,
+        code @ 0..=0xFF => Some(code as u8),
+        _ => CP1252_HIGH.iter().position(|&high| high == c).map(|i| 0x80 + i as u8),
+    }
+}
+
+impl Charset {
+    /// `text` decoded from the bytes the table stores.
+    pub fn decode(self, text: StringWithEncoding) -> String {
+        if self == Charset::Auto {
+            return text.string;
+        }
+        let bytes: Vec<u8> = match text.encoding {
+            StringEncoding::Utf8 => text.string.into_bytes(),
+            // Latin-1 maps each byte to the char of the same value.
+            StringEncoding::Latin1 => text.string.chars().map(|c| c as u8).collect(),
+        };
+        match self {
+            Charset::Auto | Charset::Utf8 => String::from_utf8_lossy(&bytes).into_owned(),
+            Charset::Cp1252 => bytes
+                .iter()
+                .map(|&b| match b {
+                    0x80..=0x9F => CP1252_HIGH[usize::from(b - 0x80)],
+                    _ => char::from(b),
+                })
+                .collect(),
+        }
+    }
+}
+
+const KNOWN_KEYS: &[&str] = &[
+    "lenient",
+    "io_strategy",
+    "max_file_size_mb",
+    "compress",
+    "compress_min_bytes",
+    "timeout_ms",
+    "fields",
+    "profile",
+    "sanitize_nul",
+    "charset",
+];
+
+impl CallOptions {
+    pub fn from_value(value: &Value) -> Result<Self, VpxError> {
+        let Some(object) = value.as_object() else {
+            return Err(VpxError::new(VpxErrorCode::InvalidArgument, "Options must be a JSON object."));
+        };
+        config::warn_unknown_keys("options", object, KNOWN_KEYS);
+        let options = CallOptions::deserialize(value)
+            .map_err(|e| VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid options: {}", e)))?;
+        options.compress.check_available()?;
+        for field in options.fields.iter().flatten().filter(|f| !crate::TABLE_INFO_FIELDS.contains(&f.as_str())) {
+            log_warn!("Unknown table info field '{}' in \"fields\"", field);
+        }
+        if let Some(profile) = &options.profile {
+            crate::profiles::find(profile)?;
+        }
+        Ok(options)
+    }
+
+    /// Parses `options_json`; null means all defaults.
+    ///
+    /// # Safety
+    /// `options_json` must be null or a valid NUL-terminated string.
+    pub unsafe fn from_c(options_json: *const c_char) -> Result<Self, VpxError> {
+        if options_json.is_null() {
+            return Ok(CallOptions::default());
+        }
+        let options = unsafe { crate::str_from_c(options_json, "options") }?;
+        let value: Value = serde_json::from_str(options)
+            .map_err(|e| VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid options JSON: {}", e)))?;
+        CallOptions::from_value(&value)
+    }
+
+    pub fn io_strategy(&self, config: &config::Config) -> IoStrategy {
+        self.io_strategy.unwrap_or(config.io_strategy)
+    }
+
+    pub fn max_file_size_mb(&self, config: &config::Config) -> u64 {
+        self.max_file_size_mb.unwrap_or(config.max_file_size_mb)
+    }
+
+    /// `table_info` cut down to the requested `fields`, or whole without.
+    pub fn select_fields(&self, table_info: Value) -> Value {
+        match (&self.fields, table_info) {
+            (Some(fields), Value::Object(mut object)) => {
+                object.retain(|key, _| key == "warnings" || fields.iter().any(|field| field == key));
+                Value::Object(object)
+            }
+            (_, table_info) => table_info,
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Leftovers in the library that no table uses any more.
+//!
+//! Deleting or renaming tables by hand leaves behind backglasses, ini files,
+//! NVRAM and whole folders of media. Everything here is matched by name, the
+//! way VPX and PinMAME pick the files up, so an orphan is a file nothing
+//! would ever load.
+
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsStr;
+use std::fs;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::options::CallOptions;
+use crate::{paths, script};
+
+/// Files VPX or B2S load by the table's name.
+const SIDECAR_EXTENSIONS: &[(&str, &str)] =
+    &[("directb2s", "b2s"), ("b2s", "b2s"), ("ini", "ini"), ("pov", "pov")];
+
+fn extension(path: &Path) -> String {
+    path.extension().and_then(OsStr::to_str).unwrap_or_default().to_ascii_lowercase()
+}
+
+fn stem(path: &Path) -> String {
+    path.file_stem().and_then(OsStr::to_str).unwrap_or_default().to_lowercase()
+}
+
+fn size_of(path: &Path) -> u64 {
+    if path.is_dir() {
+        paths::files_under(path).iter().map(|f| size_of(f)).sum()
+    } else {
+        fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+    }
+}
+
+struct Orphan {
+    path: PathBuf,
+    kind: &'static str,
+    reason: String,
+}
+
+fn find_orphans(tables_dir: &Path, media_dirs: &[PathBuf]) -> Result<Value, VpxError> {
+    if !tables_dir.is_dir() {
+        return Err(VpxError::new(VpxErrorCode::OpenFailed, format!("'{}' is not a folder", tables_dir.display())));
+    }
+    let files = paths::files_under(tables_dir);
+    let tables: Vec<PathBuf> = files.iter().filter(|f| extension(f) == "vpx").cloned().collect();
+    let roms = crate::parallel::map(&tables, |table| {
+        let code = crate::read_gamedata_code(table, &CallOptions::default()).unwrap_or_default();
+        script::rom_name(&code).map(|rom| rom.to_lowercase())
+    });
+
+    // Table folders, and every folder above one up to the library root.
+    let mut table_dirs: BTreeMap<PathBuf, (HashSet<String>, HashSet<String>)> = BTreeMap::new();
+    let mut used_dirs: HashSet<PathBuf> = HashSet::new();
+    for (table, rom) in tables.iter().zip(&roms) {
+        let dir = paths::table_dir(table);
+        let (stems, dir_roms) = table_dirs.entry(dir.clone()).or_default();
+        stems.insert(stem(table));
+        dir_roms.extend(rom.clone());
+        for ancestor in dir.ancestors().take_while(|a| a.starts_with(tables_dir)) {
+            used_dirs.insert(ancestor.to_path_buf());
+        }
+    }
+
+    let mut orphans = Vec::new();
+    // Folders directly under the library root with no table anywhere inside.
+    let mut top_dirs: Vec<PathBuf> = fs::read_dir(tables_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_dir() && !used_dirs.contains(p) && paths::has_content(p))
+        .collect();
+    top_dirs.sort();
+    for dir in top_dirs {
+        orphans.push(Orphan { path: dir, kind: "folder", reason: "No table in this folder".to_string() });
+    }
+
+    for (dir, (stems, dir_roms)) in &table_dirs {
+        for file in fs::read_dir(dir).into_iter().flatten().flatten().map(|e| e.path()) {
+            let Some((_, kind)) = SIDECAR_EXTENSIONS.iter().find(|(e, _)| *e == extension(&file)) else { continue };
+            if file.is_file() && !stems.contains(&stem(&file)) {
+                orphans.push(Orphan { path: file, kind, reason: "No table with this name".to_string() });
+            }
+        }
+        let Some(nvram) = paths::pinmame_dir(dir).and_then(|p| paths::find_case_insensitive(&p, "nvram")) else {
+            continue;
+        };
+        for file in fs::read_dir(&nvram).into_iter().flatten().flatten().map(|e| e.path()) {
+            if extension(&file) == "nv" && !dir_roms.contains(&stem(&file)) {
+                orphans.push(Orphan { path: file, kind: "nvram", reason: "No table here uses this ROM".to_string() });
+            }
+        }
+    }
+
+    // Frontend-style media folders, with one file per table name
+    // (`Wheel Images/Attack from Mars (Bally 1995).png`).
+    let names: HashSet<String> = tables
+        .iter()
+        .flat_map(|t| {
+            let folder = paths::table_dir(t);
+            [stem(t), folder.file_name().and_then(OsStr::to_str).unwrap_or_default().to_lowercase()]
+        })
+        .collect();
+    for media_dir in media_dirs {
+        for file in paths::files_under(media_dir) {
+            if !names.contains(&stem(&file)) {
+                orphans.push(Orphan { path: file, kind: "media", reason: "No table with this name".to_string() });
+            }
+        }
+    }
+
+    let mut by_kind: BTreeMap<&str, (u64, u64)> = BTreeMap::new();
+    let listed: Vec<Value> = orphans
+        .iter()
+        .map(|o| {
+            let size = size_of(&o.path);
+            let entry = by_kind.entry(o.kind).or_default();
+            entry.0 += 1;
+            entry.1 += size;
+            json!({ "path": o.path, "kind": o.kind, "reason": o.reason, "size": size })
+        })
+        .collect();
+    let reclaimable: u64 = by_kind.values().map(|(_, bytes)| bytes).sum();
+    let by_kind: BTreeMap<&str, Value> = by_kind
+        .into_iter()
+        .map(|(kind, (count, bytes))| (kind, json!({ "count": count, "bytes": bytes })))
+        .collect();
+
+    Ok(json!({
+        "tables": tables.len(),
+        "orphans": listed,
+        "by_kind": by_kind,
+        "reclaimable_bytes": reclaimable,
+    }))
+}
+
+/// Lists files under `tables_dir` that no table would load: backglasses,
+/// ini and pov files not named after a table in their folder, NVRAM for
+/// ROMs no table there uses, and folders without any table. With
+/// `media_dirs_json` (`{"paths": [...]}`, nullable), files in those media
+/// folders not named after any table or table folder are listed too.
+///
+/// Nothing is deleted. Returns `{"tables", "orphans": [{"path", "kind",
+/// "reason", "size"}], "by_kind", "reclaimable_bytes"}`, or null on failure.
+/// Release with `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_find_orphaned_assets(
+    tables_dir: *const c_char,
+    media_dirs_json: *const c_char,
+) -> *mut c_char {
+    let media_dirs = || -> Result<Vec<PathBuf>, VpxError> {
+        if media_dirs_json.is_null() {
+            return Ok(Vec::new());
+        }
+        let text = unsafe { crate::str_from_c(media_dirs_json, "media dirs") }?;
+        let value: Value = serde_json::from_str(text).map_err(|e| {
+            VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid media dirs JSON: {}", e))
+        })?;
+        match value.get("paths").and_then(Value::as_array) {
+            Some(paths) => Ok(paths.iter().filter_map(Value::as_str).map(PathBuf::from).collect()),
+            None => Err(VpxError::new(VpxErrorCode::InvalidArgument, "Media dirs JSON has no \"paths\" array.")),
+        }
+    };
+    let result = unsafe { crate::path_from_c(tables_dir) }
+        .and_then(|dir| Ok((dir, media_dirs()?)))
+        .and_then(|(dir, media_dirs)| find_orphans(&dir, &media_dirs))
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_find_orphaned_assets", result)
+}
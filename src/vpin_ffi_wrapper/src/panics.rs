@@ -0,0 +1,113 @@
+//! Panic capture.
+//!
+//! `catch_unwind` only hands back the payload, so a crash on an exotic
+//! table used to reach the frontend as a bare "Panic occurred". A panic
+//! hook, installed on first use, records the message, where it happened and
+//! a backtrace (when `RUST_BACKTRACE` is set) for the `catch` that is about
+//! to catch it; the message and location go into the `Panic` error, the
+//! whole report is kept for `vpx_get_last_panic`. Panics outside the
+//! wrapper's own calls go to whatever hook was there before.
+
+use std::any::Any;
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::cell::{Cell, RefCell};
+use std::os::raw::c_char;
+use std::panic::{self, catch_unwind, AssertUnwindSafe};
+use std::sync::{Mutex, Once};
+
+use serde_json::{json, Value};
+
+/// What the hook saw of one panic.
+#[derive(Clone)]
+pub(crate) struct PanicReport {
+    pub message: String,
+    pub location: Option<String>,
+    pub backtrace: Option<String>,
+}
+
+thread_local! {
+    /// How many `catch` calls are running on this thread.
+    static CATCHING: Cell<u32> = const { Cell::new(0) };
+    /// Left by the hook for the innermost `catch`.
+    static PENDING: RefCell<Option<PanicReport>> = const { RefCell::new(None) };
+}
+
+/// The last panic caught on any thread, with the table it happened on.
+static LAST: Mutex<Option<(PanicReport, String)>> = Mutex::new(None);
+
+fn payload_message(payload: &(dyn Any + Send)) -> String {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => payload.downcast_ref::<String>().cloned().unwrap_or_else(|| "non-string panic payload".to_string()),
+    }
+}
+
+fn install() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if CATCHING.with(Cell::get) == 0 {
+                return previous(info);
+            }
+            let backtrace = Backtrace::capture();
+            let report = PanicReport {
+                message: payload_message(info.payload()),
+                location: info.location().map(|location| location.to_string()),
+                backtrace: (backtrace.status() == BacktraceStatus::Captured).then(|| backtrace.to_string()),
+            };
+            PENDING.with(|pending| *pending.borrow_mut() = Some(report));
+        }));
+    });
+}
+
+/// Runs `f`, returning what the hook recorded instead if it panics.
+pub(crate) fn catch<T>(f: impl FnOnce() -> T) -> Result<T, PanicReport> {
+    struct Depth;
+    impl Drop for Depth {
+        fn drop(&mut self) {
+            CATCHING.with(|catching| catching.set(catching.get() - 1));
+        }
+    }
+    install();
+    CATCHING.with(|catching| catching.set(catching.get() + 1));
+    let _depth = Depth;
+    catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        PENDING.with(|pending| pending.borrow_mut().take()).unwrap_or_else(|| PanicReport {
+            message: payload_message(payload.as_ref()),
+            location: None,
+            backtrace: None,
+        })
+    })
+}
+
+/// Keeps `report` as the last panic, caught while working on `path`.
+pub(crate) fn remember(report: &PanicReport, path: &str) {
+    if let Some(backtrace) = &report.backtrace {
+        log_debug!("Panic backtrace:\n{}", backtrace);
+    }
+    *LAST.lock().unwrap_or_else(|e| e.into_inner()) = Some((report.clone(), path.to_string()));
+}
+
+/// The last panic the wrapper caught, on any thread: `{"panic": {"path",
+/// "message", "location", "backtrace"}}`, with `location` and `backtrace`
+/// null when unknown (backtraces need `RUST_BACKTRACE=1` in the
+/// environment), or `{"panic": null}` if there has been none.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+#[no_mangle]
+pub extern "C" fn vpx_get_last_panic() -> *mut c_char {
+    let last = LAST.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let panic = match last {
+        Some((report, path)) => json!({
+            "path": path,
+            "message": report.message,
+            "location": report.location,
+            "backtrace": report.backtrace,
+        }),
+        None => Value::Null,
+    };
+    let result = crate::into_c_string(json!({ "panic": panic }).to_string());
+
+    crate::envelope::finish("vpx_get_last_panic", result)
+}
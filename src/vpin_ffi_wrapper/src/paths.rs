@@ -0,0 +1,100 @@
+//! Filesystem lookups that mirror the frontend's `PathUtils`.
+//!
+//! Table folders come from Windows setups more often than not, so names are
+//! matched case-insensitively.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Finds `name` directly inside `dir`, ignoring case.
+pub(crate) fn find_case_insensitive(dir: &Path, name: &str) -> Option<PathBuf> {
+    let exact = dir.join(name);
+    if exact.exists() {
+        return Some(exact);
+    }
+    fs::read_dir(dir).ok()?.flatten().map(|e| e.path()).find(|p| {
+        p.file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.eq_ignore_ascii_case(name))
+    })
+}
+
+/// Resolves a relative path such as `Music/tz/intro.mp3` one component at a time.
+pub(crate) fn resolve_case_insensitive(root: &Path, relative: &str) -> Option<PathBuf> {
+    relative
+        .split(['/', '\\'])
+        .filter(|c| !c.is_empty())
+        .try_fold(root.to_path_buf(), |dir, component| find_case_insensitive(&dir, component))
+}
+
+/// True for a non-empty directory or a non-empty regular file.
+pub(crate) fn has_content(path: &Path) -> bool {
+    if path.is_dir() {
+        fs::read_dir(path).map(|mut d| d.next().is_some()).unwrap_or(false)
+    } else {
+        fs::metadata(path).map(|m| m.len() > 0).unwrap_or(false)
+    }
+}
+
+/// The `pinmame` folder next to the table, as the frontend looks it up.
+pub(crate) fn pinmame_dir(table_dir: &Path) -> Option<PathBuf> {
+    find_case_insensitive(table_dir, "pinmame").filter(|p| p.is_dir())
+}
+
+/// The folder holding the table file (`.` for bare file names).
+pub(crate) fn table_dir(vpx_path: &Path) -> PathBuf {
+    match vpx_path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Every regular file below `dir`, recursively. Unreadable folders are skipped.
+pub(crate) fn files_under(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Files beside `vpx_path` sharing its name: `.directb2s`, `.ini`, `.pov`...
+pub(crate) fn sidecars(vpx_path: &Path) -> Vec<PathBuf> {
+    let Some(stem) = vpx_path.file_stem().and_then(OsStr::to_str) else { return Vec::new() };
+    let mut found: Vec<PathBuf> = fs::read_dir(table_dir(vpx_path))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && p.as_path() != vpx_path)
+        .filter(|p| p.file_stem().and_then(OsStr::to_str).is_some_and(|s| s.eq_ignore_ascii_case(stem)))
+        .collect();
+    found.sort();
+    found
+}
+
+/// Writes `data` next to `path` and renames it over, so a crash never leaves
+/// a half-written file behind.
+pub(crate) fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    crate::oplog::touch(path)?;
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let mut tmp = path.as_os_str().to_os_string();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(data).and_then(|_| file.sync_all())?;
+    fs::rename(&tmp, path)
+}
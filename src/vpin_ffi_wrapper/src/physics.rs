@@ -0,0 +1,231 @@
+//! Physics settings compared across a library.
+//!
+//! Every table carries its own gravity, friction, slope and so on in its
+//! GameData. Most tables land close to each other; those that don't will
+//! feel wrong on a cabinet tuned for the rest and may want a per-table
+//! override. Tables are grouped by their rounded feel settings (gravity,
+//! friction, slope) and each setting is checked for outliers with a robust
+//! z-score (distance from the median in median absolute deviations).
+
+use std::collections::BTreeMap;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::options::CallOptions;
+use crate::{index, parallel};
+
+/// Settings compared, with the step tables are grouped by (0 = reported
+/// but not grouped on).
+const PARAMETERS: &[(&str, f64)] = &[
+    ("gravity", 0.1),
+    ("friction", 0.01),
+    ("slope_min", 0.5),
+    ("slope_max", 0.5),
+    ("elasticity", 0.0),
+    ("elastic_falloff", 0.0),
+    ("scatter", 0.0),
+    ("default_scatter", 0.0),
+    ("difficulty", 0.0),
+];
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct PhysicsOptions {
+    /// Robust z-score above which a setting is an outlier.
+    threshold: f64,
+}
+
+impl Default for PhysicsOptions {
+    fn default() -> Self {
+        PhysicsOptions { threshold: 3.5 }
+    }
+}
+
+struct Physics {
+    values: Vec<f64>,
+    /// Which of VPX's global physics sets the table says to use (0 = its own).
+    override_physics: u32,
+}
+
+/// Four decimals; f32 settings print badly as f64 otherwise.
+pub(crate) fn round(value: f64) -> f64 {
+    (value * 10_000.0).round() / 10_000.0
+}
+
+fn read_physics(path: &Path) -> Result<Physics, VpxError> {
+    crate::catch_panic(path, || {
+        let mut vpx_file = crate::open_vpx(path, &CallOptions::default())?;
+        let gamedata = vpx_file.read_gamedata().map_err(|e| {
+            VpxError::new(
+                VpxErrorCode::ParseFailed,
+                format!("Failed to read gamedata for '{}': {}", path.display(), e),
+            )
+        })?;
+        let values = [
+            gamedata.gravity,
+            gamedata.friction,
+            gamedata.angle_tilt_min,
+            gamedata.angle_tilt_max,
+            gamedata.elasticity,
+            gamedata.elastic_falloff,
+            gamedata.scatter,
+            gamedata.default_scatter,
+            gamedata.global_difficulty,
+        ];
+        Ok(Physics {
+            values: values.iter().map(|v| round(*v as f64)).collect(),
+            override_physics: gamedata.override_physics,
+        })
+    })
+}
+
+fn median(sorted: &[f64]) -> f64 {
+    match sorted.len() {
+        0 => 0.0,
+        n if n % 2 == 1 => sorted[n / 2],
+        n => (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0,
+    }
+}
+
+/// The `.vpx` paths of an index (`{"tables": [{"vpxFile"}]}`) or a path
+/// list (`{"paths": [...]}`).
+fn table_paths(text: &str) -> Result<Vec<PathBuf>, VpxError> {
+    let value: Value = serde_json::from_str(text)
+        .map_err(|e| VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid tables JSON: {}", e)))?;
+    if let Some(paths) = value.get("paths").and_then(Value::as_array) {
+        return Ok(paths.iter().filter_map(Value::as_str).map(PathBuf::from).collect());
+    }
+    if value["tables"].is_array() {
+        return Ok(index::tables(&value).iter().filter_map(|t| t["vpxFile"].as_str()).map(PathBuf::from).collect());
+    }
+    Err(VpxError::new(VpxErrorCode::InvalidArgument, "Tables JSON has no \"tables\" or \"paths\" array."))
+}
+
+fn compare(paths: &[PathBuf], options: &PhysicsOptions) -> Value {
+    let read = parallel::map(paths, |path| read_physics(path));
+    let mut tables = Vec::new();
+    let mut failed = Vec::new();
+    for (path, physics) in paths.iter().zip(read) {
+        match physics {
+            Ok(physics) => tables.push((path, physics)),
+            Err(e) => failed.push(json!({ "vpx_file": path, "error": e.message })),
+        }
+    }
+
+    let mut parameters = Map::new();
+    let mut outliers = Vec::new();
+    let mut outlying: Vec<Vec<&str>> = vec![Vec::new(); tables.len()];
+    for (p, (name, _)) in PARAMETERS.iter().enumerate() {
+        let mut values: Vec<f64> = tables.iter().map(|(_, physics)| physics.values[p]).collect();
+        values.sort_by(f64::total_cmp);
+        let mid = median(&values);
+        let mut deviations: Vec<f64> = values.iter().map(|v| (v - mid).abs()).collect();
+        deviations.sort_by(f64::total_cmp);
+        // A library where most tables agree exactly has no spread; fall
+        // back to a small share of the median so exact ties aren't outliers.
+        let spread = (1.4826 * median(&deviations)).max(mid.abs() * 0.01).max(1e-6);
+        parameters.insert(
+            name.to_string(),
+            json!({
+                "median": round(mid),
+                "min": values.first().copied().map(round),
+                "max": values.last().copied().map(round),
+            }),
+        );
+        for (t, (path, physics)) in tables.iter().enumerate() {
+            let score = (physics.values[p] - mid).abs() / spread;
+            if score > options.threshold {
+                outlying[t].push(name);
+                outliers.push(json!({
+                    "vpx_file": path,
+                    "parameter": name,
+                    "value": physics.values[p],
+                    "median": round(mid),
+                    "score": round(score),
+                }));
+            }
+        }
+    }
+
+    // Grouped on the rounded feel settings.
+    let mut clusters: BTreeMap<Vec<i64>, Vec<usize>> = BTreeMap::new();
+    for (t, (_, physics)) in tables.iter().enumerate() {
+        let key = PARAMETERS
+            .iter()
+            .zip(&physics.values)
+            .filter(|((_, step), _)| *step > 0.0)
+            .map(|((_, step), value)| (value / step).round() as i64)
+            .collect();
+        clusters.entry(key).or_default().push(t);
+    }
+    let mut clusters: Vec<(Vec<i64>, Vec<usize>)> = clusters.into_iter().collect();
+    clusters.sort_by_key(|(_, members)| std::cmp::Reverse(members.len()));
+    let clusters: Vec<Value> = clusters
+        .iter()
+        .map(|(key, members)| {
+            let grouped = PARAMETERS.iter().filter(|(_, step)| *step > 0.0);
+            let profile: Map<String, Value> =
+                grouped.zip(key).map(|((name, step), k)| (name.to_string(), json!(round(*k as f64 * step)))).collect();
+            json!({
+                "profile": profile,
+                "count": members.len(),
+                "tables": members.iter().map(|&t| tables[t].0).collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+
+    let rows: Vec<Value> = tables
+        .iter()
+        .zip(&outlying)
+        .map(|((path, physics), outlying)| {
+            let values: Map<String, Value> =
+                PARAMETERS.iter().zip(&physics.values).map(|((name, _), v)| (name.to_string(), json!(v))).collect();
+            json!({
+                "vpx_file": path,
+                "physics": values,
+                "override_physics": physics.override_physics,
+                "outliers": outlying,
+            })
+        })
+        .collect();
+
+    json!({
+        "count": rows.len(),
+        "threshold": options.threshold,
+        "parameters": parameters,
+        "clusters": clusters,
+        "outliers": outliers,
+        "tables": rows,
+        "failed": failed,
+    })
+}
+
+/// Compares the physics settings of many tables: `tables_json` is an index
+/// (`{"tables": [{"vpxFile"}]}`) or `{"paths": [...]}`. `options_json`
+/// (nullable): `threshold` (robust z-score for an outlier, default 3.5).
+///
+/// Returns `{"count", "threshold", "parameters": {name: {"median", "min",
+/// "max"}}, "clusters": [{"profile", "count", "tables"}], "outliers":
+/// [{"vpx_file", "parameter", "value", "median", "score"}], "tables":
+/// [{"vpx_file", "physics", "override_physics", "outliers"}], "failed"}`,
+/// largest cluster first, or null on bad arguments. Tables are read in
+/// parallel. Release with `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_compare_physics(tables_json: *const c_char, options_json: *const c_char) -> *mut c_char {
+    let result = unsafe { crate::str_from_c(tables_json, "tables") }
+        .and_then(table_paths)
+        .and_then(|paths| {
+            let options: PhysicsOptions = unsafe { crate::library::options_from_c(options_json, &["threshold"]) }?;
+            Ok(compare(&paths, &options))
+        })
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_compare_physics", result)
+}
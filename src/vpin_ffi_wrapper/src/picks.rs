@@ -0,0 +1,243 @@
+//! "Surprise me": a random table from the index, weighted towards the ones
+//! the user likes and away from the ones picked lately. The picks are kept
+//! in the user store's top-level `picks` list (`[{"vpx_file", "picked"}]`,
+//! newest last), so the attract mode in the daemon and the button in the
+//! frontend do not keep landing on the same tables.
+
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::os::raw::c_char;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::{filter, index, library, store};
+
+/// Picks kept in the user store.
+const HISTORY_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct PickOptions {
+    /// How many of the latest picks not to land on again.
+    avoid_recent: usize,
+    /// Weight of a favorite compared to any other table.
+    favorite_weight: f64,
+    /// How much the user's rating counts: a table rated r weighs
+    /// (r / 5) ^ rating_weight, so 5 is neutral and 0 turns it off.
+    rating_weight: f64,
+    /// Pick hidden tables too.
+    include_hidden: bool,
+    /// Add the pick to the shared history.
+    record: bool,
+    /// Recent picks to avoid on top of the stored ones, oldest first.
+    history: Vec<String>,
+    /// Seed for a repeatable pick; random without.
+    seed: Option<u64>,
+}
+
+impl Default for PickOptions {
+    fn default() -> Self {
+        PickOptions {
+            avoid_recent: 10,
+            favorite_weight: 2.0,
+            rating_weight: 1.0,
+            include_hidden: false,
+            record: true,
+            history: Vec::new(),
+            seed: None,
+        }
+    }
+}
+
+const KNOWN_KEYS: &[&str] =
+    &["avoid_recent", "favorite_weight", "rating_weight", "include_hidden", "record", "history", "seed"];
+
+/// SplitMix64; plenty for picking a table.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: Option<u64>) -> Rng {
+        Rng(seed.unwrap_or_else(|| RandomState::new().hash_one(store::now())))
+    }
+
+    /// Uniform in [0, 1).
+    pub(crate) fn next(&mut self) -> f64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        (z ^ (z >> 31)) as f64 / (u64::MAX as f64 + 1.0)
+    }
+}
+
+/// How much the user likes an annotated index row: `favorite_weight` for a
+/// favorite, times (rating / 5) ^ `rating_weight` for a rated one.
+pub(crate) fn preference(row: &Value, favorite_weight: f64, rating_weight: f64) -> f64 {
+    let mut weight = 1.0;
+    if row["favorite"].as_bool() == Some(true) {
+        weight *= favorite_weight.max(0.0);
+    }
+    if let Some(rating) = row["userRating"].as_f64() {
+        weight *= (rating.max(0.5) / 5.0).powf(rating_weight);
+    }
+    weight
+}
+
+/// The stored picks, oldest first.
+fn stored_history(store_doc: &Value) -> Vec<(String, u64)> {
+    let picks = store_doc["picks"].as_array().into_iter().flatten();
+    picks
+        .filter_map(|pick| Some((pick["vpx_file"].as_str()?.to_string(), pick["picked"].as_u64().unwrap_or(0))))
+        .collect()
+}
+
+fn load_history() -> Vec<(String, u64)> {
+    if !store::configured() {
+        return Vec::new();
+    }
+    match store::load() {
+        Ok(store_doc) => stored_history(&store_doc),
+        Err(e) => {
+            log_warn!("Pick history left out: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+fn record(vpx_file: &str) -> Result<(), VpxError> {
+    store::update(|store_doc| {
+        let mut picks: Vec<Value> = store_doc["picks"].as_array().cloned().unwrap_or_default();
+        picks.push(json!({ "vpx_file": vpx_file, "picked": store::now() }));
+        let excess = picks.len().saturating_sub(HISTORY_LIMIT);
+        picks.drain(..excess);
+        store_doc["picks"] = json!(picks);
+        Ok(())
+    })
+}
+
+fn pick(index_doc: &Value, expression: Option<&str>, options: &PickOptions) -> Result<Value, VpxError> {
+    let expr = expression.filter(|e| !e.trim().is_empty()).map(filter::parse).transpose()?;
+    let candidates: Vec<(&str, f64)> = index::tables(index_doc)
+        .iter()
+        .filter(|row| options.include_hidden || row["hidden"].as_bool() != Some(true))
+        .filter(|row| expr.as_ref().is_none_or(|expr| filter::matches(row, expr)))
+        .filter_map(|row| {
+            Some((row["vpxFile"].as_str()?, preference(row, options.favorite_weight, options.rating_weight)))
+        })
+        .filter(|(vpx_file, weight)| !vpx_file.is_empty() && *weight > 0.0)
+        .collect();
+
+    // The latest distinct picks, newest first.
+    let mut recent: Vec<String> = Vec::new();
+    let history = load_history().into_iter().map(|(vpx_file, _)| vpx_file).chain(options.history.iter().cloned());
+    for vpx_file in history.collect::<Vec<_>>().into_iter().rev() {
+        if recent.len() >= options.avoid_recent {
+            break;
+        }
+        if !recent.contains(&vpx_file) {
+            recent.push(vpx_file);
+        }
+    }
+    let fresh: Vec<(&str, f64)> =
+        candidates.iter().copied().filter(|(vpx_file, _)| !recent.iter().any(|r| r == vpx_file)).collect();
+
+    let chosen = if fresh.is_empty() {
+        // Everything left was picked lately: take the one picked longest ago.
+        recent.iter().rev().find(|r| candidates.iter().any(|(vpx_file, _)| vpx_file == r)).map(String::as_str)
+    } else {
+        let total: f64 = fresh.iter().map(|(_, weight)| weight).sum();
+        let mut target = Rng::new(options.seed).next() * total;
+        let mut chosen = fresh.last().map(|(vpx_file, _)| *vpx_file);
+        for (vpx_file, weight) in &fresh {
+            if target < *weight {
+                chosen = Some(vpx_file);
+                break;
+            }
+            target -= weight;
+        }
+        chosen
+    };
+
+    if let (Some(vpx_file), true) = (chosen, options.record && store::configured()) {
+        record(vpx_file)?;
+    }
+    Ok(json!({ "vpx_file": chosen, "candidates": candidates.len(), "avoided": candidates.len() - fresh.len() }))
+}
+
+/// Picks a random table from the frontend's index (`{"tables": [...]}`)
+/// for "surprise me": among the rows passing `filter_expression` (as for
+/// `vpx_filter_index`; null or empty for all) and not hidden, weighted by
+/// the user's favorites and ratings, skipping the latest picks unless
+/// nothing else is left. The pick goes into the user store's history.
+/// `options_json` (nullable): `{"avoid_recent": 10, "favorite_weight": 2,
+/// "rating_weight": 1, "include_hidden": false, "record": true,
+/// "history": [vpx path...], "seed": null}`.
+///
+/// Returns `{"vpx_file", "candidates", "avoided"}`, `vpx_file` null when no
+/// row qualifies, or null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// All arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_pick_random_table(
+    index_json: *const c_char,
+    filter_expression: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    let result = unsafe { library::options_from_c::<PickOptions>(options_json, KNOWN_KEYS) }
+        .and_then(|options| {
+            let mut index_doc = index::parse(unsafe { crate::str_from_c(index_json, "index") }?)?;
+            store::annotate_index(&mut index_doc);
+            let expression = match filter_expression.is_null() {
+                true => None,
+                false => Some(unsafe { crate::str_from_c(filter_expression, "filter") }?),
+            };
+            pick(&index_doc, expression, &options)
+        })
+        .and_then(|picked| crate::into_c_string(picked.to_string()));
+
+    crate::envelope::finish("vpx_pick_random_table", result)
+}
+
+/// The shared pick history, `{"picks": [{"vpx_file", "picked"}]}` newest
+/// first, `picked` in Unix seconds. Empty without a user store.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+#[no_mangle]
+pub extern "C" fn vpx_get_pick_history() -> *mut c_char {
+    let result = match store::configured() {
+        true => store::load().map(|store_doc| stored_history(&store_doc)),
+        false => Ok(Vec::new()),
+    }
+    .and_then(|history| {
+        let picks: Vec<Value> = history
+            .into_iter()
+            .rev()
+            .map(|(vpx_file, picked)| json!({ "vpx_file": vpx_file, "picked": picked }))
+            .collect();
+        crate::into_c_string(json!({ "picks": picks }).to_string())
+    });
+
+    crate::envelope::finish("vpx_get_pick_history", result)
+}
+
+/// Forgets the pick history. Returns a `VpxErrorCode`.
+#[no_mangle]
+pub extern "C" fn vpx_clear_pick_history() -> i32 {
+    let result = store::update(|store_doc| {
+        if let Some(store_doc) = store_doc.as_object_mut() {
+            store_doc.remove("picks");
+        }
+        Ok(())
+    });
+
+    match result {
+        Ok(_) => VpxErrorCode::Ok as i32,
+        Err(e) => {
+            log_error!("vpx_clear_pick_history: {}", e);
+            e.code as i32
+        }
+    }
+}
@@ -0,0 +1,387 @@
+//! Playlists: named table collections, static, rule-based or both.
+//!
+//! A playlist file is JSON:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "name": "90s Stern with ROM",
+//!   "description": "",
+//!   "tables": ["/tables/AFM/afm.vpx"],
+//!   "exclude": [],
+//!   "rules": {
+//!     "match": "all",
+//!     "conditions": [
+//!       { "field": "bestManufacturer", "op": "eq", "value": "Stern" },
+//!       { "field": "bestYear", "op": "between", "value": [1990, 1999] },
+//!       { "field": "romPath", "op": "exists" }
+//!     ]
+//!   },
+//!   "sort": { "field": "bestTitle", "descending": false },
+//!   "limit": null
+//! }
+//! ```
+//!
+//! `tables` are always in, `exclude` always out, and `rules` add every index
+//! row they match. Fields are the index's camelCase names, plus the user's
+//! `favorite`, `hidden`, `kidSafe`, `userRating`, `userNote` and `userTags`
+//! from the user store; conditions can nest as `{"match": "any", "conditions":
+//! [...]}`. Comparisons ignore case and are numeric when both sides are
+//! numbers (`"1993"` counts).
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fs;
+use std::os::raw::c_char;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::config;
+use crate::error::{VpxError, VpxErrorCode};
+use crate::{index, paths, store};
+
+/// Newest playlist format this build understands.
+pub(crate) const PLAYLIST_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Playlist {
+    pub version: u32,
+    pub name: String,
+    pub description: String,
+    /// `vpxFile`s always in the playlist, in this order.
+    pub tables: Vec<String>,
+    /// `vpxFile`s never in the playlist, whatever the rules say.
+    pub exclude: Vec<String>,
+    pub rules: Option<Rules>,
+    pub sort: Option<Sort>,
+    pub limit: Option<usize>,
+}
+
+impl Default for Playlist {
+    fn default() -> Self {
+        Playlist {
+            version: PLAYLIST_VERSION,
+            name: String::new(),
+            description: String::new(),
+            tables: Vec::new(),
+            exclude: Vec::new(),
+            rules: None,
+            sort: None,
+            limit: None,
+        }
+    }
+}
+
+const KNOWN_KEYS: &[&str] = &["version", "name", "description", "tables", "exclude", "rules", "sort", "limit"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Match {
+    #[default]
+    All,
+    Any,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Rules {
+    #[serde(rename = "match", default)]
+    pub mode: Match,
+    pub conditions: Vec<Node>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub(crate) enum Node {
+    Group(Rules),
+    Condition(Condition),
+}
+
+// By hand rather than untagged, so a bad condition reports what is wrong
+// with it instead of "did not match any variant".
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        let value = Value::deserialize(deserializer)?;
+        if value.get("conditions").is_some() {
+            Rules::deserialize(value).map(Node::Group).map_err(D::Error::custom)
+        } else {
+            Condition::deserialize(value).map(Node::Condition).map_err(D::Error::custom)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Condition {
+    pub field: String,
+    pub op: Op,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Op {
+    Eq,
+    Ne,
+    Contains,
+    StartsWith,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    /// `value` is `[low, high]`, both inclusive.
+    Between,
+    /// `value` is a list; any element matches.
+    In,
+    /// Present and not empty, `false` or `0`.
+    Exists,
+    Missing,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Sort {
+    pub field: String,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+fn invalid(message: String) -> VpxError {
+    VpxError::new(VpxErrorCode::InvalidArgument, message)
+}
+
+fn validate_rules(rules: &Rules) -> Result<(), VpxError> {
+    for node in &rules.conditions {
+        match node {
+            Node::Group(group) => validate_rules(group)?,
+            Node::Condition(c) => {
+                let ok = match c.op {
+                    Op::Between => c.value.as_array().is_some_and(|v| v.len() == 2),
+                    Op::In => c.value.is_array(),
+                    Op::Exists | Op::Missing => true,
+                    _ => !c.value.is_null(),
+                };
+                if !ok {
+                    return Err(invalid(format!("Playlist condition on '{}' has no usable value for {:?}", c.field, c.op)));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parses and validates a playlist document.
+pub(crate) fn parse(playlist_json: &str) -> Result<Playlist, VpxError> {
+    let value: Value =
+        serde_json::from_str(playlist_json).map_err(|e| invalid(format!("Invalid playlist JSON: {}", e)))?;
+    let Some(object) = value.as_object() else {
+        return Err(invalid("Playlist must be a JSON object.".to_string()));
+    };
+    config::warn_unknown_keys("playlist", object, KNOWN_KEYS);
+    let playlist = Playlist::deserialize(&value).map_err(|e| invalid(format!("Invalid playlist: {}", e)))?;
+    if playlist.version > PLAYLIST_VERSION {
+        return Err(invalid(format!(
+            "Playlist version {} is newer than supported ({})",
+            playlist.version, PLAYLIST_VERSION
+        )));
+    }
+    if let Some(rules) = &playlist.rules {
+        validate_rules(rules)?;
+    }
+    Ok(playlist)
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+pub(crate) fn as_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.to_lowercase(),
+        Value::Null => String::new(),
+        other => other.to_string().to_lowercase(),
+    }
+}
+
+/// Orders two field values: numerically when both are numbers, else as
+/// case-insensitive text.
+pub(crate) fn compare(a: &Value, b: &Value) -> Ordering {
+    match (as_number(a), as_number(b)) {
+        (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+        _ => as_text(a).cmp(&as_text(b)),
+    }
+}
+
+pub(crate) fn is_present(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::String(s) => !s.trim().is_empty(),
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::Array(a) => !a.is_empty(),
+        Value::Object(o) => !o.is_empty(),
+    }
+}
+
+fn condition_matches(row: &Value, c: &Condition) -> bool {
+    let field = row.get(&c.field).unwrap_or(&Value::Null);
+    match c.op {
+        Op::Eq => compare(field, &c.value) == Ordering::Equal,
+        Op::Ne => compare(field, &c.value) != Ordering::Equal,
+        Op::Contains => as_text(field).contains(&as_text(&c.value)),
+        Op::StartsWith => as_text(field).starts_with(&as_text(&c.value)),
+        Op::Gt => is_present(field) && compare(field, &c.value) == Ordering::Greater,
+        Op::Gte => is_present(field) && compare(field, &c.value) != Ordering::Less,
+        Op::Lt => is_present(field) && compare(field, &c.value) == Ordering::Less,
+        Op::Lte => is_present(field) && compare(field, &c.value) != Ordering::Greater,
+        Op::Between => {
+            let bounds = c.value.as_array().map(Vec::as_slice).unwrap_or_default();
+            let [low, high] = bounds else { return false };
+            is_present(field) && compare(field, low) != Ordering::Less && compare(field, high) != Ordering::Greater
+        }
+        Op::In => c.value.as_array().is_some_and(|values| values.iter().any(|v| compare(field, v) == Ordering::Equal)),
+        Op::Exists => is_present(field),
+        Op::Missing => !is_present(field),
+    }
+}
+
+pub(crate) fn rules_match(row: &Value, rules: &Rules) -> bool {
+    let node_matches = |node: &Node| match node {
+        Node::Group(group) => rules_match(row, group),
+        Node::Condition(c) => condition_matches(row, c),
+    };
+    match rules.mode {
+        Match::All => rules.conditions.iter().all(node_matches),
+        Match::Any => rules.conditions.iter().any(node_matches),
+    }
+}
+
+/// The playlist's tables among `index` rows, as `vpxFile`s in playlist order.
+pub(crate) fn resolve(playlist: &Playlist, index_doc: &Value) -> Value {
+    let rows = index::tables(index_doc);
+    let excluded: HashSet<&str> = playlist.exclude.iter().map(String::as_str).collect();
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut selected: Vec<&Value> = Vec::new();
+    let mut missing = Vec::new();
+
+    for vpx_file in &playlist.tables {
+        if excluded.contains(vpx_file.as_str()) || !seen.insert(vpx_file.as_str()) {
+            continue;
+        }
+        match index::position(index_doc, vpx_file) {
+            Some(i) => selected.push(&rows[i]),
+            None => missing.push(vpx_file.as_str()),
+        }
+    }
+    if let Some(rules) = &playlist.rules {
+        for row in rows {
+            let Some(vpx_file) = row["vpxFile"].as_str() else { continue };
+            if !excluded.contains(vpx_file) && !seen.contains(vpx_file) && rules_match(row, rules) {
+                seen.insert(vpx_file);
+                selected.push(row);
+            }
+        }
+    }
+    if let Some(sort) = &playlist.sort {
+        let null = Value::Null;
+        selected.sort_by(|a, b| {
+            let order = compare(a.get(&sort.field).unwrap_or(&null), b.get(&sort.field).unwrap_or(&null));
+            if sort.descending { order.reverse() } else { order }
+        });
+    }
+    if let Some(limit) = playlist.limit {
+        selected.truncate(limit);
+    }
+    let tables: Vec<&Value> = selected.iter().map(|row| &row["vpxFile"]).collect();
+
+    json!({
+        "name": playlist.name,
+        "count": tables.len(),
+        "tables": tables,
+        "missing": missing,
+    })
+}
+
+fn read_playlist(path: &Path) -> Result<Value, VpxError> {
+    let text = fs::read_to_string(path).map_err(|e| {
+        VpxError::new(VpxErrorCode::OpenFailed, format!("Failed to read playlist '{}': {}", path.display(), e))
+    })?;
+    let playlist = parse(&text)?;
+    serde_json::to_value(&playlist)
+        .map_err(|e| VpxError::new(VpxErrorCode::AllocFailed, format!("Failed to serialize playlist: {}", e)))
+}
+
+fn write_playlist(path: &Path, playlist_json: &str) -> Result<(), VpxError> {
+    let mut playlist = parse(playlist_json)?;
+    playlist.version = PLAYLIST_VERSION;
+    let text = serde_json::to_vec_pretty(&playlist)
+        .map_err(|e| VpxError::new(VpxErrorCode::AllocFailed, format!("Failed to serialize playlist: {}", e)))?;
+    paths::write_atomic(path, &text).map_err(|e| {
+        VpxError::new(VpxErrorCode::OpenFailed, format!("Failed to write playlist '{}': {}", path.display(), e))
+    })
+}
+
+/// Reads a playlist file, validated and with every field filled in.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `playlist_path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_read_playlist(playlist_path: *const c_char) -> *mut c_char {
+    let result = unsafe { crate::path_from_c(playlist_path) }
+        .and_then(|path| read_playlist(&path))
+        .and_then(|playlist| crate::into_c_string(playlist.to_string()));
+
+    crate::envelope::finish("vpx_read_playlist", result)
+}
+
+/// Validates `playlist_json` and writes it to `playlist_path` (atomically,
+/// stamped with the current format version). Returns a `VpxErrorCode`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_write_playlist(playlist_path: *const c_char, playlist_json: *const c_char) -> i32 {
+    let result = unsafe { crate::path_from_c(playlist_path) }
+        .and_then(|path| Ok((path, unsafe { crate::str_from_c(playlist_json, "playlist") }?)))
+        .and_then(|(path, playlist)| {
+            let summary = format!("Write playlist {}", path.display());
+            crate::oplog::record("playlist", summary, || write_playlist(&path, playlist))
+        });
+
+    match result {
+        Ok(()) => VpxErrorCode::Ok as i32,
+        Err(e) => {
+            log_error!("vpx_write_playlist: {}", e);
+            e.code as i32
+        }
+    }
+}
+
+/// Resolves a playlist against the frontend's index (`{"tables": [...]}`,
+/// as in `asapcab_index.json`): `{"name", "count", "tables": [vpxFile...],
+/// "missing": [static entries not in the index]}`.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_resolve_playlist(playlist_json: *const c_char, index_json: *const c_char) -> *mut c_char {
+    let result = unsafe { crate::str_from_c(playlist_json, "playlist") }
+        .and_then(parse)
+        .and_then(|playlist| Ok((playlist, index::parse(unsafe { crate::str_from_c(index_json, "index") }?)?)))
+        .and_then(|(playlist, mut index_doc)| {
+            store::annotate_index(&mut index_doc);
+            crate::into_c_string(resolve(&playlist, &index_doc).to_string())
+        });
+
+    crate::envelope::finish("vpx_resolve_playlist", result)
+}
@@ -0,0 +1,163 @@
+//! Scan profiles.
+//!
+//! A full scan of a big library on a slow drive takes long enough that
+//! users want a quick one at startup and the thorough one overnight. A
+//! profile names the extraction passes a scan runs on each table:
+//!
+//! - `"quick"`: the table info only;
+//! - `"full"` (default): plus the script's ROM name and player count;
+//! - `"deep"`: plus the script analysis and a SHA-256 of the file.
+//!
+//! The profile comes from the call's `"profile"` option, else from the
+//! config's `scan_profile_dirs` for the deepest folder holding the table,
+//! else from its `scan_profile`. TableInfoBatch jobs and
+//! `vpx_get_scan_metadata` follow it.
+
+use std::os::raw::c_char;
+use std::path::Path;
+
+use serde_json::{json, Map, Value};
+
+use crate::config::{self, Config};
+use crate::error::{VpxError, VpxErrorCode};
+use crate::handle::OpenTable;
+use crate::options::CallOptions;
+use crate::{hashes, script};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Pass {
+    TableInfo,
+    /// ROM name and player count from the script.
+    Script,
+    /// `vpx_get_script_analysis` of the script.
+    Analysis,
+    /// SHA-256 of the table file.
+    Hash,
+}
+
+impl Pass {
+    fn as_str(self) -> &'static str {
+        match self {
+            Pass::TableInfo => "table_info",
+            Pass::Script => "script",
+            Pass::Analysis => "analysis",
+            Pass::Hash => "hash",
+        }
+    }
+}
+
+pub(crate) struct Profile {
+    pub name: &'static str,
+    pub passes: &'static [Pass],
+}
+
+impl Profile {
+    pub fn runs(&self, pass: Pass) -> bool {
+        self.passes.contains(&pass)
+    }
+}
+
+const PROFILES: &[Profile] = &[
+    Profile { name: "quick", passes: &[Pass::TableInfo] },
+    Profile { name: "full", passes: &[Pass::TableInfo, Pass::Script] },
+    Profile { name: "deep", passes: &[Pass::TableInfo, Pass::Script, Pass::Analysis, Pass::Hash] },
+];
+
+/// The profile called `name`, or `InvalidArgument`.
+pub(crate) fn find(name: &str) -> Result<&'static Profile, VpxError> {
+    PROFILES.iter().find(|profile| profile.name == name).ok_or_else(|| {
+        let names: Vec<&str> = PROFILES.iter().map(|profile| profile.name).collect();
+        VpxError::new(
+            VpxErrorCode::InvalidArgument,
+            format!("Unknown scan profile '{}', expected one of {}", name, names.join(", ")),
+        )
+    })
+}
+
+/// Fails if the config names a profile that doesn't exist.
+pub(crate) fn check_config(config: &Config) -> Result<(), VpxError> {
+    find(&config.scan_profile)?;
+    config.scan_profile_dirs.values().try_for_each(|name| find(name).map(|_| ()))
+}
+
+/// The profile a scan of the table at `path` runs with.
+pub(crate) fn for_table(path: &Path, options: &CallOptions) -> &'static Profile {
+    let config = config::get();
+    let by_dir = config
+        .scan_profile_dirs
+        .iter()
+        .filter(|(dir, _)| path.starts_with(dir))
+        .max_by_key(|(dir, _)| Path::new(dir).components().count())
+        .map(|(_, name)| name);
+    let name = options.profile.as_ref().or(by_dir).unwrap_or(&config.scan_profile);
+    // Names were checked when the config and options were parsed.
+    find(name).unwrap_or(&PROFILES[1])
+}
+
+/// What the passes of `profile` beyond the table info find: `"script"`
+/// (`{"rom_name", "players"}`), `"analysis"` and `"sha256"`, each only when
+/// its pass runs. A pass that fails is logged and left out.
+pub(crate) fn run_passes(table: &mut OpenTable, path: &Path, profile: &Profile) -> Map<String, Value> {
+    let mut found = Map::new();
+    if profile.runs(Pass::Script) || profile.runs(Pass::Analysis) {
+        match table.code_ref() {
+            Ok(code) => {
+                if profile.runs(Pass::Script) {
+                    let facts = json!({ "rom_name": script::rom_name(code), "players": script::max_players(code) });
+                    found.insert("script".to_string(), facts);
+                }
+                if profile.runs(Pass::Analysis) {
+                    found.insert("analysis".to_string(), script::analyze(code));
+                }
+            }
+            Err(e) => log_warn!("Script passes skipped for '{}': {}", path.display(), e),
+        }
+    }
+    if profile.runs(Pass::Hash) {
+        match hashes::sha256_file(path) {
+            Ok(sha256) => _ = found.insert("sha256".to_string(), json!(sha256)),
+            Err(e) => log_warn!("Hash pass skipped for '{}': {}", path.display(), e),
+        }
+    }
+    found
+}
+
+/// The table info of the table at `path` and what the other passes of
+/// `profile` find, under the call's timeout.
+pub(crate) fn scan(
+    path: &Path,
+    options: &CallOptions,
+    profile: &'static Profile,
+) -> Result<(Value, Map<String, Value>), VpxError> {
+    let (owned, options) = (path.to_path_buf(), options.clone());
+    crate::cancel::with_timeout(path, options.timeout_ms, move || {
+        let mut table = OpenTable::open(&owned, &options)?;
+        let table_info = table.table_info()?;
+        Ok((table_info, run_passes(&mut table, &owned, profile)))
+    })
+}
+
+/// The scan profiles and which ones apply: `{"default", "directories":
+/// {"<folder>": "<profile>"}, "profiles": [{"name", "passes"}]}`, from the
+/// config's `scan_profile` and `scan_profile_dirs`.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+#[no_mangle]
+pub extern "C" fn vpx_get_scan_profiles() -> *mut c_char {
+    let config = config::get();
+    let profiles: Vec<Value> = PROFILES
+        .iter()
+        .map(|profile| {
+            let passes: Vec<&str> = profile.passes.iter().map(|pass| pass.as_str()).collect();
+            json!({ "name": profile.name, "passes": passes })
+        })
+        .collect();
+    let report = json!({
+        "default": config.scan_profile,
+        "directories": config.scan_profile_dirs,
+        "profiles": profiles,
+    });
+    let result = crate::into_c_string(report.to_string());
+
+    crate::envelope::finish("vpx_get_scan_profiles", result)
+}
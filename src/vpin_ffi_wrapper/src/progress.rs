@@ -0,0 +1,78 @@
+//! Job progress reporting.
+//!
+//! Completion callbacks only say a job is over; a first-run scan of a
+//! whole library is one batch job that runs for minutes. The callback from
+//! `vpx_register_progress_callback` hears how far each job has got while
+//! it runs, so the frontend can draw a real progress bar.
+//!
+//! The worker marks the job it is running on its thread, so code deep in a
+//! read reports with `report` without being handed the job id.
+
+use std::cell::Cell;
+use std::ffi::{c_void, CString};
+use std::os::raw::c_char;
+use std::sync::Mutex;
+
+/// Receives `current` of `total` done in `stage` for job `job_id`, on the
+/// job's worker thread. `stage` is only valid during the call.
+pub type VpxProgressCallback =
+    Option<extern "C" fn(job_id: u64, current: u64, total: u64, stage: *const c_char, userdata: *mut c_void)>;
+
+#[derive(Clone, Copy)]
+struct ProgressSink {
+    callback: VpxProgressCallback,
+    userdata: *mut c_void,
+}
+
+// The frontend owns `userdata` and promises it can be used from any thread.
+unsafe impl Send for ProgressSink {}
+
+static SINK: Mutex<Option<ProgressSink>> = Mutex::new(None);
+
+thread_local! {
+    /// The job this worker is running, 0 for none.
+    static CURRENT_JOB: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Runs `f` as job `job_id`, so what it reports is attributed to that job.
+pub(crate) fn in_job<T>(job_id: u64, f: impl FnOnce() -> T) -> T {
+    struct Restore(u64);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CURRENT_JOB.with(|current| current.set(self.0));
+        }
+    }
+    let _restore = Restore(CURRENT_JOB.with(|current| current.replace(job_id)));
+    f()
+}
+
+/// The job running on this thread, 0 for none.
+pub(crate) fn current_job() -> u64 {
+    CURRENT_JOB.with(Cell::get)
+}
+
+/// Tells the callback, if there is one, how far the current job has got.
+/// Does nothing outside a job.
+pub(crate) fn report(current: u64, total: u64, stage: &str) {
+    let job_id = current_job();
+    if job_id == 0 {
+        return;
+    }
+    // Called outside the lock, so the callback may itself call into the wrapper.
+    let sink = *SINK.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((callback, userdata)) = sink.and_then(|sink| Some((sink.callback?, sink.userdata))) {
+        let stage = CString::new(stage).unwrap_or_default();
+        callback(job_id, current, total, stage.as_ptr(), userdata);
+    }
+}
+
+/// Reports the progress of running jobs to `callback` (with `userdata`
+/// passed back untouched); null stops it. Stages are `"read"` (bytes of the
+/// file loaded so far with `io_strategy` `"memory"`), `"table_info"` and
+/// `"gamedata"` (files done: 0 or 1 of 1, or of a batch's paths). The
+/// callback runs on the worker threads, also while being replaced, so
+/// `userdata` has to outlive the registration.
+#[no_mangle]
+pub extern "C" fn vpx_register_progress_callback(callback: VpxProgressCallback, userdata: *mut c_void) {
+    *SINK.lock().unwrap_or_else(|e| e.into_inner()) = callback.is_some().then_some(ProgressSink { callback, userdata });
+}
@@ -0,0 +1,226 @@
+//! Media provenance and staleness.
+//!
+//! Screenshots and videos of a table are captured once and then kept, so
+//! after an update to the table they quietly show the old version. Media
+//! stamped with `vpx_stamp_media_provenance` gets a sidecar,
+//! `<media>.provenance.json`, recording what the table was when it was
+//! captured: its save date and revision and the hash of its script.
+//! `vpx_media_is_stale` compares that with the table as it is now, so the
+//! frontend can suggest capturing it again.
+//!
+//! Media the wrapper makes itself (table cards) is stamped as it is
+//! written, and a sidecar follows its media when the naming migration moves,
+//! copies or converts it.
+
+use std::fs;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::handle::OpenTable;
+use crate::options::CallOptions;
+use crate::{paths, store};
+
+const FORMAT: &str = "vpx-media-provenance";
+const VERSION: u32 = 1;
+const SUFFIX: &str = ".provenance.json";
+
+/// The sidecar holding the provenance of `media`.
+pub(crate) fn sidecar(media: &Path) -> PathBuf {
+    let mut name = media.as_os_str().to_os_string();
+    name.push(SUFFIX);
+    PathBuf::from(name)
+}
+
+/// What media of the table at `vpx_path` goes stale with.
+fn table_facts(vpx_path: &Path) -> Result<Value, VpxError> {
+    let mut table = OpenTable::open(vpx_path, &CallOptions::default())?;
+    let table_info = table.table_info()?;
+    let script_sha256 = crate::sha256_hex(table.code_ref()?.as_bytes());
+    Ok(json!({
+        "table_save_date": table_info["table_save_date"],
+        "table_save_rev": table_info["table_save_rev"],
+        "script_sha256": script_sha256,
+    }))
+}
+
+/// Records `media` as made from the table at `vpx_path` now, by
+/// `generator`, and returns what was written.
+pub(crate) fn stamp(media: &Path, vpx_path: &Path, generator: &str) -> Result<Value, VpxError> {
+    crate::safe_mode::check("stamping media provenance")?;
+    if !media.is_file() {
+        return Err(VpxError::new(VpxErrorCode::FileNotFound, format!("No media file '{}'", media.display())));
+    }
+    let mut provenance = json!({
+        "format": FORMAT,
+        "version": VERSION,
+        "source": vpx_path,
+        "generated": store::now(),
+        "generator": generator,
+        "wrapper_version": env!("CARGO_PKG_VERSION"),
+    });
+    if let (Some(provenance), Value::Object(facts)) = (provenance.as_object_mut(), table_facts(vpx_path)?) {
+        provenance.extend(facts);
+    }
+    let sidecar = sidecar(media);
+    let data = serde_json::to_vec_pretty(&provenance)
+        .map_err(|e| VpxError::new(VpxErrorCode::AllocFailed, format!("JSON serialization failed: {}", e)))?;
+    paths::write_atomic(&sidecar, &data).map_err(|e| {
+        VpxError::new(VpxErrorCode::OpenFailed, format!("Failed to write '{}': {}", sidecar.display(), e))
+    })?;
+    Ok(provenance)
+}
+
+/// The provenance recorded for `media`, if it has any.
+pub(crate) fn read(media: &Path) -> Result<Option<Value>, VpxError> {
+    let sidecar = sidecar(media);
+    let data = match fs::read(&sidecar) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(VpxError::new(
+                VpxErrorCode::OpenFailed,
+                format!("Failed to read '{}': {}", sidecar.display(), e),
+            ))
+        }
+    };
+    match serde_json::from_slice::<Value>(&data) {
+        Ok(provenance) if provenance["format"] == FORMAT => Ok(Some(provenance)),
+        Ok(_) => Err(VpxError::new(
+            VpxErrorCode::ParseFailed,
+            format!("'{}' is not a media provenance file", sidecar.display()),
+        )),
+        Err(e) => Err(VpxError::new(VpxErrorCode::ParseFailed, format!("Invalid '{}': {}", sidecar.display(), e))),
+    }
+}
+
+/// Carries the provenance of `from` over to `to`, its media having been
+/// moved there (or copied, with `keep`). Media without any is left alone.
+pub(crate) fn follow(from: &Path, to: &Path, keep: bool) {
+    let (source, dest) = (sidecar(from), sidecar(to));
+    if !source.is_file() {
+        return;
+    }
+    let carried = match keep {
+        true => fs::read(&source).and_then(|data| paths::write_atomic(&dest, &data)).map_err(|e| e.to_string()),
+        false => crate::relocate::move_path(&source, &dest).map_err(|e| e.message),
+    };
+    if let Err(e) = carried {
+        log_warn!("Provenance of '{}' not carried to '{}': {}", from.display(), to.display(), e);
+    }
+}
+
+fn modified(path: &Path) -> Result<SystemTime, VpxError> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).map_err(|e| {
+        let code = match e.kind() {
+            std::io::ErrorKind::NotFound => VpxErrorCode::FileNotFound,
+            _ => VpxErrorCode::OpenFailed,
+        };
+        VpxError::new(code, format!("Failed to read '{}': {}", path.display(), e))
+    })
+}
+
+/// Whether `media` shows an older version of the table at `vpx_path`.
+fn staleness(vpx_path: &Path, media: &Path) -> Result<Value, VpxError> {
+    let media_modified = modified(media)?;
+    let Some(provenance) = read(media)? else {
+        // Nothing recorded: all there is to go on is which file is newer.
+        let stale = media_modified < modified(vpx_path)?;
+        let reasons: &[&str] = if stale { &["media_older_than_table"] } else { &[] };
+        return Ok(json!({ "stale": stale, "reasons": reasons, "provenance": null, "current": null }));
+    };
+    let current = table_facts(vpx_path)?;
+    let mut reasons = Vec::new();
+    // Facts missing from the sidecar (null) can't tell either way.
+    let changed = |key: &str| !provenance[key].is_null() && provenance[key] != current[key];
+    if changed("table_save_date") || changed("table_save_rev") {
+        reasons.push("table_saved");
+    }
+    if changed("script_sha256") {
+        reasons.push("script_changed");
+    }
+    Ok(json!({ "stale": !reasons.is_empty(), "reasons": reasons, "provenance": provenance, "current": current }))
+}
+
+/// Records, in `<media_path>.provenance.json`, that the media at
+/// `media_path` shows the table at `vpx_file_path` as it is now: its save
+/// date, save revision and script hash, with the time. Call it after
+/// capturing a screenshot or video of the table.
+///
+/// Returns the provenance written, or null on failure. `error_code`
+/// (nullable) as for `vpx_get_table_info_as_json_err`. Release with
+/// `vpx_free_string`.
+///
+/// # Safety
+/// `vpx_file_path` and `media_path` must be null or valid NUL-terminated
+/// strings, `error_code` null or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_stamp_media_provenance(
+    vpx_file_path: *const c_char,
+    media_path: *const c_char,
+    error_code: *mut i32,
+) -> *mut c_char {
+    let result = unsafe { crate::path_from_c(vpx_file_path) }
+        .and_then(|vpx_path| Ok((vpx_path, unsafe { crate::path_from_c(media_path) }?)))
+        .and_then(|(vpx_path, media)| stamp(&media, &vpx_path, "frontend"))
+        .and_then(|provenance| crate::into_c_string(provenance.to_string()));
+    unsafe { crate::set_error_code(error_code, &result) };
+
+    crate::envelope::finish("vpx_stamp_media_provenance", result)
+}
+
+/// The provenance recorded for the media at `media_path`:
+/// `{"provenance": {"format", "version", "source", "generated" (Unix
+/// seconds), "generator" ("frontend" for `vpx_stamp_media_provenance`,
+/// `"table_cards"`...), "wrapper_version", "table_save_date",
+/// "table_save_rev", "script_sha256"}}`, or `{"provenance": null}` for media
+/// without a sidecar.
+///
+/// Returns null on failure (an unreadable or foreign sidecar). `error_code`
+/// (nullable) as for `vpx_get_table_info_as_json_err`. Release with
+/// `vpx_free_string`.
+///
+/// # Safety
+/// `media_path` must be null or a valid NUL-terminated string, `error_code`
+/// null or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_read_media_provenance(media_path: *const c_char, error_code: *mut i32) -> *mut c_char {
+    let result = unsafe { crate::path_from_c(media_path) }
+        .and_then(|media| read(&media))
+        .and_then(|provenance| crate::into_c_string(json!({ "provenance": provenance }).to_string()));
+    unsafe { crate::set_error_code(error_code, &result) };
+
+    crate::envelope::finish("vpx_read_media_provenance", result)
+}
+
+/// Whether the media at `media_path` was captured from an older version of
+/// the table at `vpx_file_path`: `{"stale", "reasons", "provenance",
+/// "current"}`. With a provenance sidecar the table's save date, revision
+/// and script hash are compared with what it records (`"table_saved"`,
+/// `"script_changed"`); without one `provenance` and `current` are null and
+/// the media is stale if its file is older than the table's
+/// (`"media_older_than_table"`).
+///
+/// Returns null on failure. `error_code` (nullable) as for
+/// `vpx_get_table_info_as_json_err`. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `vpx_file_path` and `media_path` must be null or valid NUL-terminated
+/// strings, `error_code` null or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_media_is_stale(
+    vpx_file_path: *const c_char,
+    media_path: *const c_char,
+    error_code: *mut i32,
+) -> *mut c_char {
+    let result = unsafe { crate::path_from_c(vpx_file_path) }
+        .and_then(|vpx_path| Ok((vpx_path, unsafe { crate::path_from_c(media_path) }?)))
+        .and_then(|(vpx_path, media)| staleness(&vpx_path, &media))
+        .and_then(|report| crate::into_c_string(report.to_string()));
+    unsafe { crate::set_error_code(error_code, &result) };
+
+    crate::envelope::finish("vpx_media_is_stale", result)
+}
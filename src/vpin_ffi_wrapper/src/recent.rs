@@ -0,0 +1,131 @@
+//! "What's new": tables added or updated since a point in time.
+//!
+//! The index records when each table file and its folder last changed
+//! (`fileLastModified`, `folderLastModified`, Unix seconds), which is enough
+//! to find recent changes. Given the previous index as well, additions are
+//! told apart from updates and the changed fields are named.
+
+use std::collections::{BTreeSet, HashSet};
+use std::os::raw::c_char;
+
+use serde_json::{json, Value};
+
+use crate::error::VpxError;
+use crate::index;
+
+/// Bookkeeping fields that change on every scan without saying anything.
+const IGNORED_FIELDS: &[&str] = &["fileLastModified", "folderLastModified", "jsonOwner", "matchConfidence"];
+
+fn timestamp(row: &Value, field: &str) -> u64 {
+    row[field].as_u64().unwrap_or(0)
+}
+
+fn changed_fields(before: &Value, after: &Value) -> Vec<String> {
+    let empty = serde_json::Map::new();
+    let (before, after) = (before.as_object().unwrap_or(&empty), after.as_object().unwrap_or(&empty));
+    let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    keys.into_iter()
+        .filter(|key| !IGNORED_FIELDS.contains(&key.as_str()))
+        .filter(|key| before.get(*key) != after.get(*key))
+        .cloned()
+        .collect()
+}
+
+fn recent_changes(index_doc: &Value, since: u64, previous: Option<&Value>) -> Value {
+    let mut changes: Vec<(u64, Value)> = Vec::new();
+    for row in index::tables(index_doc) {
+        let Some(vpx_file) = row["vpxFile"].as_str() else { continue };
+        let (file_time, folder_time) = (timestamp(row, "fileLastModified"), timestamp(row, "folderLastModified"));
+        let before = previous.and_then(|p| index::position(p, vpx_file).map(|i| &index::tables(p)[i]));
+        let fields = before.map(|before| changed_fields(before, row)).unwrap_or_default();
+
+        // Newer than the previous scan's timestamps, or else than `since`.
+        let (file_since, folder_since) = match before {
+            Some(b) => (timestamp(b, "fileLastModified") + 1, timestamp(b, "folderLastModified") + 1),
+            None => (since, since),
+        };
+        let mut what = Vec::new();
+        if file_time >= file_since {
+            what.push("table_file");
+        }
+        if folder_time >= folder_since {
+            what.push("folder");
+        }
+        let touched = !what.is_empty();
+        let kind = match (previous, before) {
+            (Some(_), None) => "added",
+            // New media shows up as a newer folder with no field changed.
+            (Some(_), Some(_)) if !fields.is_empty() || touched => "updated",
+            (Some(_), Some(_)) => continue,
+            // Without the previous index only the timestamps can tell.
+            (None, _) if what.is_empty() => continue,
+            (None, _) => "changed",
+        };
+        let modified = file_time.max(folder_time);
+        changes.push((
+            modified,
+            json!({
+                "vpx_file": vpx_file,
+                "title": row["bestTitle"],
+                "kind": kind,
+                "modified": modified,
+                "what": what,
+                "fields": fields,
+            }),
+        ));
+    }
+    // Newest first; ties keep index order.
+    changes.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+
+    let mut result = json!({
+        "since": since,
+        "count": changes.len(),
+        "changes": changes.into_iter().map(|(_, change)| change).collect::<Vec<_>>(),
+    });
+    if let Some(previous) = previous {
+        let current: HashSet<&str> = index::tables(index_doc).iter().filter_map(|r| r["vpxFile"].as_str()).collect();
+        let removed: Vec<&str> = index::tables(previous)
+            .iter()
+            .filter_map(|r| r["vpxFile"].as_str())
+            .filter(|vpx_file| !current.contains(vpx_file))
+            .collect();
+        result["removed"] = json!(removed);
+    }
+    result
+}
+
+/// Tables added or updated since `since_timestamp` (Unix seconds), newest
+/// first: `{"since", "count", "changes": [{"vpx_file", "title", "kind",
+/// "modified", "what": ["table_file", "folder"], "fields": [...]}]}`.
+///
+/// With only `index_json`, a table counts when its file or folder changed
+/// since then (`kind` "changed"). With `previous_index_json` (nullable) as
+/// well, `kind` is "added" or "updated" (a changed field or a newer
+/// file/folder, whatever `since_timestamp` says), `fields` names what
+/// differs and `removed` lists tables that are gone.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// Both JSON arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_recent_changes(
+    index_json: *const c_char,
+    since_timestamp: u64,
+    previous_index_json: *const c_char,
+) -> *mut c_char {
+    let previous = || -> Result<Option<Value>, VpxError> {
+        if previous_index_json.is_null() {
+            return Ok(None);
+        }
+        index::parse(unsafe { crate::str_from_c(previous_index_json, "previous index") }?).map(Some)
+    };
+    let result = unsafe { crate::str_from_c(index_json, "index") }
+        .and_then(index::parse)
+        .and_then(|index_doc| Ok((index_doc, previous()?)))
+        .and_then(|(index_doc, previous)| {
+            crate::into_c_string(recent_changes(&index_doc, since_timestamp, previous.as_ref()).to_string())
+        });
+
+    crate::envelope::finish("vpx_get_recent_changes", result)
+}
@@ -0,0 +1,69 @@
+//! Registry of the objects C holds by handle, and the wrapper's threading
+//! rules.
+//!
+//! Every export may be called from any thread, concurrently with any other.
+//! Process-wide state is behind a lock (configuration, job queue, stores,
+//! journal, callbacks) or per thread (last error, cancellation and job
+//! scope). What C used to hold as a raw pointer, handles and cancellation
+//! tokens, it now holds as an id into a `Registry`: a call looks the id up
+//! and keeps the object alive until it returns, so closing or freeing from
+//! another thread in the meantime is safe, and a stale or doubled close is
+//! an error rather than a crash.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// Live objects by id. Ids start at 1 and are never reused, so 0 (null on
+/// the C side) is never valid.
+pub(crate) struct Registry<T> {
+    entries: Mutex<Entries<T>>,
+}
+
+struct Entries<T> {
+    next: u64,
+    live: BTreeMap<u64, Arc<T>>,
+}
+
+impl<T> Registry<T> {
+    pub(crate) const fn new() -> Self {
+        Registry { entries: Mutex::new(Entries { next: 1, live: BTreeMap::new() }) }
+    }
+
+    fn entries(&self) -> std::sync::MutexGuard<'_, Entries<T>> {
+        // Nothing runs under this lock that could leave the map half-updated.
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Registers `value` under a new id.
+    pub(crate) fn insert(&self, value: T) -> u64 {
+        let mut entries = self.entries();
+        let id = entries.next;
+        entries.next += 1;
+        entries.live.insert(id, Arc::new(value));
+        id
+    }
+
+    /// The object under `id`, kept alive for as long as the caller holds it.
+    pub(crate) fn get(&self, id: u64) -> Option<Arc<T>> {
+        self.entries().live.get(&id).cloned()
+    }
+
+    /// Unregisters `id`; the object goes once the last call using it returns.
+    pub(crate) fn remove(&self, id: u64) -> Option<Arc<T>> {
+        self.entries().live.remove(&id)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries().live.len()
+    }
+}
+
+/// The C pointer for `id`: an opaque value, never dereferenced.
+pub(crate) fn to_ptr<P>(id: u64) -> *mut P {
+    std::ptr::without_provenance_mut(id as usize)
+}
+
+/// The id behind a pointer from `to_ptr`; 0 for null.
+pub(crate) fn from_ptr<P>(ptr: *const P) -> u64 {
+    ptr.addr() as u64
+}
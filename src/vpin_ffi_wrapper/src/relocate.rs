@@ -0,0 +1,266 @@
+//! Moving and renaming tables without losing what hangs off their name.
+//!
+//! The frontend ties media, stats and overrides to the table path, so a
+//! rename by hand orphans all of them. Here the table, its companions and
+//! any files named after it move together, the index row and thumbnail
+//! cache follow, and a failure part-way puts the files back.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::library::{self, LibraryConfig};
+use crate::{index, locks, oplog, paths, store};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct MoveOptions {
+    /// Plan only: report the renames, change nothing.
+    dry_run: bool,
+}
+
+const KNOWN_KEYS: &[&str] = &["dry_run"];
+
+fn move_error(from: &Path, to: &Path, e: std::io::Error) -> VpxError {
+    VpxError::new(
+        VpxErrorCode::OpenFailed,
+        format!("Failed to move '{}' to '{}': {}", from.display(), to.display(), e),
+    )
+}
+
+/// `rename`, falling back to copy and delete for files on another drive.
+pub(crate) fn move_path(from: &Path, to: &Path) -> Result<(), VpxError> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent).map_err(|e| move_error(from, to, e))?;
+    }
+    match fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(_) if from.is_file() => fs::copy(from, to)
+            .and_then(|_| fs::remove_file(from))
+            .map_err(|e| move_error(from, to, e)),
+        Err(e) => Err(move_error(from, to, e)),
+    }?;
+    oplog::moved(from, to);
+    Ok(())
+}
+
+/// `path` with a leading `from` swapped for `to`, if it starts with it.
+fn rebase(path: &Path, from: &Path, to: &Path) -> Option<PathBuf> {
+    path.strip_prefix(from).ok().map(|rest| to.join(rest))
+}
+
+/// `path` with its file stem replaced when it matches `old_stem`.
+fn restem(path: &Path, old_stem: &str, new_stem: &str) -> Option<PathBuf> {
+    let stem = path.file_stem().and_then(OsStr::to_str)?;
+    if !stem.eq_ignore_ascii_case(old_stem) {
+        return None;
+    }
+    let name = match path.extension().and_then(OsStr::to_str) {
+        Some(extension) => format!("{}.{}", new_stem, extension),
+        None => new_stem.to_string(),
+    };
+    Some(path.with_file_name(name))
+}
+
+struct Plan {
+    /// Folder rename done first, when the table owns its folder and moves.
+    folder: Option<(PathBuf, PathBuf)>,
+    /// File moves, with paths as they are after the folder rename.
+    files: Vec<(PathBuf, PathBuf)>,
+    /// Every original path and where it ends up, for the index and cache.
+    mapping: Vec<(PathBuf, PathBuf)>,
+}
+
+fn plan(old_path: &Path, new_path: &Path, library: &LibraryConfig) -> Result<Plan, VpxError> {
+    let invalid = |message: String| VpxError::new(VpxErrorCode::InvalidArgument, message);
+    if !old_path.is_file() {
+        return Err(VpxError::new(VpxErrorCode::OpenFailed, format!("'{}' not found", old_path.display())));
+    }
+    if !new_path.extension().and_then(OsStr::to_str).is_some_and(|e| e.eq_ignore_ascii_case("vpx")) {
+        return Err(invalid(format!("'{}' is not a .vpx path", new_path.display())));
+    }
+    if new_path.exists() {
+        return Err(invalid(format!("'{}' already exists", new_path.display())));
+    }
+    library.ensure_writable(old_path)?;
+    library.ensure_writable(new_path)?;
+    let (old_dir, new_dir) = (paths::table_dir(old_path), paths::table_dir(new_path));
+    let old_stem = old_path.file_stem().and_then(OsStr::to_str).unwrap_or_default().to_string();
+    let new_stem = new_path.file_stem().and_then(OsStr::to_str).unwrap_or_default().to_string();
+
+    // A folder holding other tables, nested ones included, never moves whole.
+    let owns_folder = library.owns_folder(old_path);
+    let moves_folder = owns_folder && old_dir != new_dir && !paths::has_content(&new_dir);
+
+    let mut plan = Plan { folder: None, files: Vec::new(), mapping: Vec::new() };
+    if moves_folder {
+        plan.folder = Some((old_dir.clone(), new_dir.clone()));
+        // Files named after the table anywhere in its folder (media too).
+        for file in paths::files_under(&old_dir) {
+            let moved = rebase(&file, &old_dir, &new_dir).unwrap_or_else(|| file.clone());
+            let target = restem(&moved, &old_stem, &new_stem).unwrap_or_else(|| moved.clone());
+            if target != moved {
+                plan.files.push((moved, target.clone()));
+            }
+            plan.mapping.push((file, target));
+        }
+        plan.mapping.push((old_dir, new_dir));
+    } else {
+        // Only the table and its same-named companions leave a shared folder.
+        let mut files = vec![old_path.to_path_buf()];
+        files.extend(paths::sidecars(old_path));
+        if owns_folder {
+            files.extend(
+                paths::files_under(&old_dir)
+                    .into_iter()
+                    .filter(|f| f.parent() != Some(old_dir.as_path()) && restem(f, &old_stem, &new_stem).is_some()),
+            );
+        }
+        for file in files {
+            let rebased = rebase(&file, &old_dir, &new_dir).unwrap_or_else(|| file.clone());
+            let target = restem(&rebased, &old_stem, &new_stem).unwrap_or(rebased);
+            if target.exists() {
+                return Err(invalid(format!("'{}' already exists", target.display())));
+            }
+            plan.files.push((file.clone(), target.clone()));
+            plan.mapping.push((file, target));
+        }
+    }
+    Ok(plan)
+}
+
+/// Rewrites every path in an index row that points at a moved file or folder.
+pub(crate) fn fix_paths(value: &mut Value, mapping: &[(PathBuf, PathBuf)]) {
+    match value {
+        Value::String(s) => {
+            let path = Path::new(s.as_str());
+            let fixed = mapping.iter().find_map(|(from, to)| match path == from {
+                true => Some(to.clone()),
+                false => rebase(path, from, to),
+            });
+            if let Some(fixed) = fixed {
+                *s = fixed.to_string_lossy().into_owned();
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| fix_paths(v, mapping)),
+        Value::Object(map) => map.values_mut().for_each(|v| fix_paths(v, mapping)),
+        _ => {}
+    }
+}
+
+fn undo(done: &[(PathBuf, PathBuf)]) {
+    for (from, to) in done.iter().rev() {
+        if let Err(e) = move_path(to, from) {
+            log_error!("move: failed to roll back: {}", e);
+        }
+    }
+}
+
+fn relocate(old_path: &Path, new_path: &Path, library: &LibraryConfig, options: &MoveOptions) -> Result<Value, VpxError> {
+    let plan = plan(old_path, new_path, library)?;
+    let mut thumbnails = Vec::new();
+    if let Some(cache_dir) = &library.preview_cache_dir {
+        for (from, to) in plan.mapping.iter().filter(|(from, _)| !from.is_dir()) {
+            for thumbnail in library::cached_thumbnails(cache_dir, std::slice::from_ref(from)) {
+                let name = thumbnail.file_name().and_then(OsStr::to_str).unwrap_or_default();
+                let suffix = &name[library::thumbnail_prefix(from).len()..];
+                thumbnails.push((thumbnail.clone(), cache_dir.join(format!("{}{}", library::thumbnail_prefix(to), suffix))));
+            }
+        }
+    }
+    let index_doc = library.index_path.as_ref().map(|p| index::load(p)).transpose()?.and_then(|doc| {
+        index::position(&doc, &old_path.to_string_lossy()).map(|_| doc)
+    });
+
+    let pairs = |moves: &[(PathBuf, PathBuf)]| -> Vec<Value> {
+        moves.iter().map(|(from, to)| json!({ "from": from, "to": to })).collect()
+    };
+    let report = json!({
+        "ok": true,
+        "dry_run": options.dry_run,
+        "from": old_path,
+        "to": new_path,
+        "folder": plan.folder.as_ref().map(|(from, to)| json!({ "from": from, "to": to })),
+        "files": pairs(&plan.files),
+        "cache": pairs(&thumbnails),
+        "index_row": index_doc.is_some(),
+    });
+    if options.dry_run {
+        return Ok(report);
+    }
+    crate::safe_mode::check("moving tables")?;
+    locks::check_not_in_use(old_path)?;
+
+    let mut done: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let moves = plan.folder.iter().chain(&plan.files);
+    for (from, to) in moves {
+        if let Err(e) = move_path(from, to) {
+            undo(&done);
+            return Err(e);
+        }
+        done.push((from.clone(), to.clone()));
+    }
+    if let (Some(mut doc), Some(index_path)) = (index_doc, &library.index_path) {
+        if let Some(i) = index::position(&doc, &old_path.to_string_lossy()) {
+            fix_paths(&mut index::tables_mut(&mut doc)[i], &plan.mapping);
+        }
+        if let Err(e) = index::save(index_path, &doc) {
+            undo(&done);
+            return Err(e);
+        }
+    }
+    if store::configured() {
+        if let Err(e) = store::rename_table(&old_path.to_string_lossy(), &new_path.to_string_lossy()) {
+            log_warn!("move: user store not updated: {}", e);
+        }
+    }
+    // Thumbnails can always be regenerated, so a failure here isn't worth a rollback.
+    for (from, to) in &thumbnails {
+        match fs::rename(from, to) {
+            Ok(()) => oplog::moved(from, to),
+            Err(e) => log_warn!("move: thumbnail '{}': {}", from.display(), e),
+        }
+    }
+    Ok(report)
+}
+
+/// Moves or renames a table to `new_path` (a `.vpx` path), taking along its
+/// same-named companions (`.directb2s`, `.ini`, `.pov`...) and any files
+/// in its folder named after it. A table alone in its folder (no other
+/// table in subfolders either, and below a table root) moves with the
+/// folder. The index row, user store entry (stats) and cached thumbnails
+/// follow.
+/// A failed move puts every file back, and a table another program has
+/// open is not moved (`FileInUse`).
+///
+/// `library_config_json` (nullable): `tables_path`, `index_path`,
+/// `preview_cache_dir`. `options_json` (nullable): `dry_run`. Returns
+/// `{"ok", "dry_run", "from", "to", "folder", "files", "cache",
+/// "index_row"}`, or null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// All arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_move_table(
+    old_path: *const c_char,
+    new_path: *const c_char,
+    library_config_json: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    let result = unsafe { crate::path_from_c(old_path) }
+        .and_then(|old| Ok((old, unsafe { crate::path_from_c(new_path) }?)))
+        .and_then(|(old, new)| Ok((old, new, unsafe { LibraryConfig::from_c(library_config_json) }?)))
+        .and_then(|(old, new, library)| {
+            let options: MoveOptions = unsafe { crate::library::options_from_c(options_json, KNOWN_KEYS) }?;
+            let summary = format!("Move {} to {}", old.display(), new.display());
+            oplog::record("move", summary, || relocate(&old, &new, &library, &options))
+        })
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_move_table", result)
+}
@@ -0,0 +1,181 @@
+//! Table uninstall: everything a table leaves on disk and in the index.
+//!
+//! A folder below a table root holding a single table, subfolders
+//! included, belongs to it, media and `pinmame` included, and goes as a
+//! whole. In a folder shared by several tables only
+//! the table file and its same-named sidecars are touched. Media paths are
+//! also looked up in the thumbnail cache, which the frontend names after a
+//! SHA-1 of the path.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::library::{self, LibraryConfig};
+use crate::{index, locks, oplog, paths, store};
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct RemoveOptions {
+    /// List only (the default): deleting has to be asked for.
+    dry_run: bool,
+    /// Keep images, videos and sounds, removing only the table.
+    keep_media: bool,
+    /// Leave the index row and the user store entry alone.
+    keep_index: bool,
+}
+
+impl Default for RemoveOptions {
+    fn default() -> Self {
+        RemoveOptions { dry_run: true, keep_media: false, keep_index: false }
+    }
+}
+
+const KNOWN_KEYS: &[&str] = &["dry_run", "keep_media", "keep_index"];
+
+const MEDIA_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "apng", "mp4", "mkv", "avi", "webm", "mp3", "ogg", "wav"];
+
+fn has_extension_in(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|e| extensions.iter().any(|x| x.eq_ignore_ascii_case(e)))
+}
+
+/// What a file is to the table, for the listing.
+fn kind(path: &Path, vpx_path: &Path) -> &'static str {
+    if path == vpx_path {
+        "table"
+    } else if has_extension_in(path, MEDIA_EXTENSIONS) {
+        "media"
+    } else if path.components().any(|c| c.as_os_str().eq_ignore_ascii_case("pinmame")) {
+        "pinmame"
+    } else {
+        "sidecar"
+    }
+}
+
+fn remove(vpx_path: &Path, library: &LibraryConfig, options: &RemoveOptions) -> Result<Value, VpxError> {
+    if !vpx_path.is_file() {
+        return Err(VpxError::new(VpxErrorCode::OpenFailed, format!("'{}' not found", vpx_path.display())));
+    }
+    library.ensure_writable(vpx_path)?;
+    if !options.dry_run {
+        crate::safe_mode::check("removing tables")?;
+        locks::check_not_in_use(vpx_path)?;
+    }
+    let table_dir = paths::table_dir(vpx_path);
+    // Never take a whole folder holding another table, nested ones included,
+    // or one outside the library.
+    let owns_folder = library.owns_folder(vpx_path);
+
+    let mut candidates = if owns_folder { paths::files_under(&table_dir) } else { paths::sidecars(vpx_path) };
+    if !owns_folder {
+        candidates.insert(0, vpx_path.to_path_buf());
+    }
+    let (kept, files): (Vec<PathBuf>, Vec<PathBuf>) = candidates
+        .into_iter()
+        .partition(|p| options.keep_media && kind(p, vpx_path) == "media");
+    let media: Vec<PathBuf> = files.iter().filter(|p| kind(p, vpx_path) == "media").cloned().collect();
+    let cache = match &library.preview_cache_dir {
+        Some(dir) => library::cached_thumbnails(dir, &media),
+        None => Vec::new(),
+    };
+    let remove_folder = owns_folder && kept.is_empty();
+
+    let size = |p: &Path| fs::metadata(p).map(|m| m.len()).unwrap_or(0);
+    let bytes: u64 = files.iter().chain(&cache).map(|p| size(p)).sum();
+    let listing: Vec<Value> = files
+        .iter()
+        .map(|p| json!({ "path": p, "kind": kind(p, vpx_path), "size": size(p) }))
+        .chain(cache.iter().map(|p| json!({ "path": p, "kind": "cache", "size": size(p) })))
+        .collect();
+
+    let mut errors = Vec::new();
+    let mut index_row_removed = false;
+    if !options.dry_run {
+        if remove_folder {
+            if let Err(e) = oplog::discard(&table_dir) {
+                errors.push(format!("{}: {}", table_dir.display(), e));
+            }
+        } else {
+            for file in &files {
+                if let Err(e) = oplog::discard(file) {
+                    errors.push(format!("{}: {}", file.display(), e));
+                }
+            }
+        }
+        for file in &cache {
+            if let Err(e) = oplog::discard(file) {
+                errors.push(format!("{}: {}", file.display(), e));
+            }
+        }
+    }
+    if let (Some(index_path), false) = (&library.index_path, options.keep_index) {
+        let mut index_doc = index::load(index_path)?;
+        let vpx_file = vpx_path.to_string_lossy();
+        index_row_removed = index::position(&index_doc, &vpx_file).is_some();
+        if index_row_removed && !options.dry_run {
+            index::remove(&mut index_doc, &vpx_file);
+            index::save(index_path, &index_doc)?;
+        }
+    }
+    // Play stats and other user data keyed by the path go with the row.
+    if store::configured() && !options.keep_index && !options.dry_run {
+        if let Err(e) = store::remove_table(&vpx_path.to_string_lossy()) {
+            errors.push(e.to_string());
+        }
+    }
+    for error in &errors {
+        log_warn!("remove: {}", error);
+    }
+
+    Ok(json!({
+        "ok": errors.is_empty(),
+        "dry_run": options.dry_run,
+        "vpx_file": vpx_path,
+        "folder": table_dir,
+        "folder_removed": remove_folder,
+        "shared_folder": !owns_folder,
+        "files": listing,
+        "kept": kept,
+        "index_row": index_row_removed,
+        "bytes": bytes,
+        "errors": errors,
+    }))
+}
+
+/// Lists, and with `"dry_run": false` removes, everything belonging to a
+/// table: the `.vpx`, its same-named sidecars, and when the folder is below
+/// a table root and holds no other table (in subfolders either) the whole
+/// folder (media, `pinmame`, PuP...). Thumbnails in
+/// `preview_cache_dir`, the index row and the user store entry go too. A
+/// table another program has open is left alone (`FileInUse`).
+///
+/// `library_config_json` (nullable): `tables_path`, `index_path`,
+/// `preview_cache_dir`. `options_json` (nullable): `dry_run` (default
+/// true), `keep_media`, `keep_index`. Returns the listing, or null on
+/// failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// All arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_remove_table(
+    vpx_file_path: *const c_char,
+    library_config_json: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    let result = unsafe { crate::path_from_c(vpx_file_path) }
+        .and_then(|path| Ok((path, unsafe { LibraryConfig::from_c(library_config_json) }?)))
+        .and_then(|(path, library)| {
+            let options: RemoveOptions = unsafe { crate::library::options_from_c(options_json, KNOWN_KEYS) }?;
+            oplog::record("remove", format!("Remove {}", path.display()), || remove(&path, &library, &options))
+        })
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_remove_table", result)
+}
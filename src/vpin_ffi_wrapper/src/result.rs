@@ -0,0 +1,188 @@
+//! Opaque result objects.
+//!
+//! A `VpxResult` carries the outcome of one call: an error code, the JSON
+//! payload on success, the error message on failure and how long the call
+//! took. C only ever sees a pointer and goes through the accessors below, so
+//! fields can be added here without breaking the ABI.
+
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr;
+use std::time::Instant;
+
+use crate::compress::Compression;
+use crate::error::{VpxError, VpxErrorCode};
+use crate::options::CallOptions;
+
+pub struct VpxResult {
+    code: VpxErrorCode,
+    json: Option<CString>,
+    /// The JSON payload instead of `json` when the call asked for `"compress"`.
+    compressed: Option<Vec<u8>>,
+    error_message: Option<CString>,
+    elapsed_ms: u64,
+}
+
+/// Builds a CString, dropping any interior NUL bytes instead of failing.
+fn lossy_c_string(s: String) -> CString {
+    CString::new(s).unwrap_or_else(|e| {
+        let mut bytes = e.into_vec();
+        bytes.retain(|&b| b != 0);
+        CString::new(bytes).unwrap_or_default()
+    })
+}
+
+impl VpxResult {
+    pub(crate) fn new(result: Result<String, VpxError>, started: Instant) -> Self {
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        match result {
+            Ok(json) => match CString::new(json) {
+                Ok(json) => VpxResult {
+                    code: VpxErrorCode::Ok,
+                    json: Some(json),
+                    compressed: None,
+                    error_message: None,
+                    elapsed_ms,
+                },
+                Err(e) => VpxResult {
+                    code: VpxErrorCode::AllocFailed,
+                    json: None,
+                    compressed: None,
+                    error_message: Some(lossy_c_string(format!("CString conversion failed: {}", e))),
+                    elapsed_ms,
+                },
+            },
+            Err(e) => VpxResult {
+                code: e.code,
+                json: None,
+                compressed: None,
+                error_message: Some(lossy_c_string(e.message)),
+                elapsed_ms,
+            },
+        }
+    }
+
+    /// Moves the JSON payload into `compressed` if `options` ask for it; a
+    /// failure to compress becomes the result's error.
+    pub(crate) fn compressed(mut self, options: &CallOptions) -> Self {
+        if options.compress == Compression::None {
+            return self;
+        }
+        let Some(json) = self.json.take() else { return self };
+        match crate::compress::encode(json.into_bytes(), options) {
+            Ok(bytes) => self.compressed = Some(bytes),
+            Err(e) => {
+                self.code = e.code;
+                self.error_message = Some(lossy_c_string(e.message));
+            }
+        }
+        self
+    }
+
+    pub(crate) fn into_raw(self) -> *mut VpxResult {
+        let len = std::mem::size_of::<VpxResult>()
+            + self.json.as_ref().map_or(0, |json| json.as_bytes().len())
+            + self.compressed.as_ref().map_or(0, Vec::len);
+        let content = match (&self.json, &self.error_message) {
+            (Some(json), _) => json.as_bytes().to_vec(),
+            (None, Some(message)) => message.as_bytes().to_vec(),
+            (None, None) => Vec::new(),
+        };
+        let ptr = Box::into_raw(Box::new(self));
+        crate::allocations::track(ptr, crate::allocations::Kind::Result, len, &content);
+        ptr
+    }
+}
+
+/// True if the call succeeded and a JSON payload is available, plain or
+/// compressed.
+///
+/// # Safety
+/// `result` must be null or a live pointer returned by this library.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_result_is_ok(result: *const VpxResult) -> bool {
+    match unsafe { result.as_ref() } {
+        Some(r) => r.code == VpxErrorCode::Ok,
+        None => false,
+    }
+}
+
+/// The JSON payload, or null on failure or when it was compressed. Owned by
+/// the result: do not free it, and do not use it after `vpx_result_free`.
+///
+/// # Safety
+/// `result` must be null or a live pointer returned by this library.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_result_get_json(result: *const VpxResult) -> *const c_char {
+    match unsafe { result.as_ref() }.and_then(|r| r.json.as_ref()) {
+        Some(json) => json.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// The compressed JSON payload of a job submitted with `"compress"` in its
+/// options, header included, with its length in `len` (nullable); null
+/// otherwise. Owned by the result, as for `vpx_result_get_json`.
+///
+/// # Safety
+/// `result` must be null or a live pointer returned by this library, `len`
+/// null or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_result_get_compressed(result: *const VpxResult, len: *mut usize) -> *const u8 {
+    let compressed = unsafe { result.as_ref() }.and_then(|r| r.compressed.as_ref());
+    if let Some(len) = unsafe { len.as_mut() } {
+        *len = compressed.map_or(0, Vec::len);
+    }
+    compressed.map_or(ptr::null(), |bytes| bytes.as_ptr())
+}
+
+/// One of the `VpxErrorCode` values. A null result reports `NullArgument`.
+///
+/// # Safety
+/// `result` must be null or a live pointer returned by this library.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_result_get_error_code(result: *const VpxResult) -> i32 {
+    match unsafe { result.as_ref() } {
+        Some(r) => r.code as i32,
+        None => VpxErrorCode::NullArgument as i32,
+    }
+}
+
+/// Human readable error message, or null on success. Owned by the result.
+///
+/// # Safety
+/// `result` must be null or a live pointer returned by this library.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_result_get_error_message(result: *const VpxResult) -> *const c_char {
+    match unsafe { result.as_ref() }.and_then(|r| r.error_message.as_ref()) {
+        Some(msg) => msg.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Wall-clock time the call took, in milliseconds.
+///
+/// # Safety
+/// `result` must be null or a live pointer returned by this library.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_result_get_elapsed_ms(result: *const VpxResult) -> u64 {
+    match unsafe { result.as_ref() } {
+        Some(r) => r.elapsed_ms,
+        None => 0,
+    }
+}
+
+/// Releases a result and everything it owns.
+///
+/// # Safety
+/// `result` must be null or a pointer returned by this library, not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_result_free(result: *mut VpxResult) {
+    if result.is_null() {
+        return;
+    }
+    crate::allocations::untrack(result);
+    unsafe {
+        drop(Box::from_raw(result));
+    }
+}
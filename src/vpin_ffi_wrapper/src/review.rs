@@ -0,0 +1,116 @@
+//! The user's own rating and note per table, kept in the user store's
+//! `review` section: `{"rating", "note", "updated"}`. The rating is 0-10
+//! (five stars in halves) or null when unrated, independent of anything
+//! the author or VPS says about the table.
+
+use std::os::raw::c_char;
+
+use serde_json::{json, Map, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::store;
+
+/// Highest rating.
+const MAX_RATING: u64 = 10;
+
+const KNOWN_KEYS: &[&str] = &["rating", "note"];
+
+/// A table's review with every key present.
+fn review_in(entry: &Value) -> Value {
+    let review = &entry["review"];
+    json!({
+        "rating": review["rating"].as_u64(),
+        "note": review["note"].as_str().unwrap_or_default(),
+        "updated": review["updated"].as_u64().unwrap_or(0),
+    })
+}
+
+/// Index fields for a table's review: `userRating` and `userNote`, left
+/// out when unset so filters treat them as absent.
+pub(crate) fn row_fields(entry: &Value) -> Vec<(&'static str, Value)> {
+    let review = review_in(entry);
+    let mut fields = Vec::new();
+    if let Some(rating) = review["rating"].as_u64() {
+        fields.push(("userRating", json!(rating)));
+    }
+    if let Some(note) = review["note"].as_str().filter(|n| !n.is_empty()) {
+        fields.push(("userNote", json!(note)));
+    }
+    fields
+}
+
+fn invalid(message: &str) -> VpxError {
+    VpxError::new(VpxErrorCode::InvalidArgument, message)
+}
+
+/// Applies `{"rating": 0-10 | null, "note": "..." | null}` to a table's
+/// review; null clears, a missing key keeps the value.
+fn set(vpx_file: &str, change: &Map<String, Value>) -> Result<Value, VpxError> {
+    let rating = match change.get("rating") {
+        None => None,
+        Some(Value::Null) => Some(Value::Null),
+        Some(value) => match value.as_u64() {
+            Some(rating) if rating <= MAX_RATING => Some(json!(rating)),
+            _ => return Err(invalid("\"rating\" must be a whole number from 0 to 10, or null.")),
+        },
+    };
+    let note = match change.get("note") {
+        None => None,
+        Some(Value::Null) => Some(json!("")),
+        Some(Value::String(note)) => Some(json!(note.trim())),
+        Some(_) => return Err(invalid("\"note\" must be a string or null.")),
+    };
+    store::update(|store_doc| {
+        let section = store::section_mut(store_doc, vpx_file, "review");
+        if let Some(rating) = rating {
+            section["rating"] = rating;
+        }
+        if let Some(note) = note {
+            section["note"] = note;
+        }
+        section["updated"] = json!(store::now());
+        Ok(review_in(&store_doc["tables"][vpx_file]))
+    })
+}
+
+/// A table's review, `{"rating", "note", "updated"}`: `rating` 0-10 or null
+/// when unrated, `note` "" without one, `updated` the Unix time of the last
+/// change (0 = never).
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `vpx_file_path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_table_review(vpx_file_path: *const c_char) -> *mut c_char {
+    let result = unsafe { crate::str_from_c(vpx_file_path, "path") }
+        .and_then(|path| Ok(review_in(&store::load()?["tables"][path])))
+        .and_then(|review| crate::into_c_string(review.to_string()));
+
+    crate::envelope::finish("vpx_get_table_review", result)
+}
+
+/// Changes a table's rating and note: `review_json` is `{"rating": 0-10,
+/// "note": "..."}`, either key optional, null clearing it. Returns a
+/// `VpxErrorCode`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_set_table_review(vpx_file_path: *const c_char, review_json: *const c_char) -> i32 {
+    let result = unsafe { crate::str_from_c(vpx_file_path, "path") }.and_then(|path| {
+        if review_json.is_null() {
+            return Err(VpxError::new(VpxErrorCode::NullArgument, "review is null"));
+        }
+        let change: Map<String, Value> = unsafe { crate::library::options_from_c(review_json, KNOWN_KEYS) }?;
+        crate::oplog::record("review", format!("Set review of {}", path), || set(path, &change))
+    });
+
+    match result {
+        Ok(_) => VpxErrorCode::Ok as i32,
+        Err(e) => {
+            log_error!("vpx_set_table_review: {}", e);
+            e.code as i32
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! Library-wide ROM audit.
+//!
+//! PinMAME tables name their ROM in the script (`cGameName`) and load
+//! `roms/<name>.zip` from the PinMAME folder. The index already carries that
+//! name as `romName`; tables scanned without it have their script read. A
+//! ROM counts as present only when PinMAME would actually load it: the file
+//! name matches exactly (Linux file systems are case-sensitive) and the zip
+//! opens with something in it.
+
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::VpxError;
+use crate::options::CallOptions;
+use crate::{index, library, paths, script};
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct AuditOptions {
+    /// Read the script of tables whose row has no `romName`.
+    read_scripts: bool,
+    /// List zips in the ROM folders that no table asks for.
+    list_unused: bool,
+}
+
+impl Default for AuditOptions {
+    fn default() -> Self {
+        AuditOptions { read_scripts: true, list_unused: true }
+    }
+}
+
+const KNOWN_KEYS: &[&str] = &["read_scripts", "list_unused"];
+
+/// Why a ROM file that exists would still not load.
+fn mismatch(rom: &str, file: &Path) -> Option<String> {
+    let name = file.file_name().and_then(OsStr::to_str).unwrap_or_default();
+    if name != format!("{}.zip", rom) {
+        return Some(format!("File is named '{}', the script asks for '{}.zip'", name, rom));
+    }
+    match File::open(file).map_err(|e| e.to_string()).and_then(|f| zip::ZipArchive::new(f).map_err(|e| e.to_string())) {
+        Ok(archive) if archive.is_empty() => Some("Zip is empty".to_string()),
+        Ok(_) => None,
+        Err(e) => Some(format!("Not a valid zip: {}", e)),
+    }
+}
+
+/// The ROM folder a table loads from: `roms_dir`, else its own
+/// `pinmame/roms`.
+fn rom_folder(roms_dir: Option<&Path>, vpx_file: &Path) -> Option<PathBuf> {
+    match roms_dir {
+        Some(dir) => Some(dir.to_path_buf()),
+        None => paths::pinmame_dir(&paths::table_dir(vpx_file)).and_then(|p| paths::find_case_insensitive(&p, "roms")),
+    }
+}
+
+#[derive(Default)]
+struct Rom {
+    tables: Vec<Value>,
+    folders: Vec<PathBuf>,
+}
+
+fn audit(index_doc: &Value, roms_dir: Option<&Path>, options: &AuditOptions) -> Value {
+    let rows = index::tables(index_doc);
+    let rom_names = crate::parallel::map(rows, |row| {
+        let named = row["romName"].as_str().map(str::trim).filter(|r| !r.is_empty()).map(str::to_string);
+        named.or_else(|| {
+            let vpx_file = row["vpxFile"].as_str().filter(|_| options.read_scripts)?;
+            script::rom_name(&crate::read_gamedata_code(Path::new(vpx_file), &CallOptions::default()).ok()?)
+        })
+    });
+
+    // Keyed by lowercase name so `tz_94h` and `TZ_94H` are one ROM.
+    let mut roms: BTreeMap<String, (String, Rom)> = BTreeMap::new();
+    let mut without_rom = 0;
+    for (row, rom_name) in rows.iter().zip(rom_names) {
+        let Some(rom_name) = rom_name else {
+            without_rom += 1;
+            continue;
+        };
+        let vpx_file = row["vpxFile"].as_str().unwrap_or_default();
+        let (_, rom) = roms.entry(rom_name.to_lowercase()).or_insert_with(|| (rom_name.clone(), Rom::default()));
+        rom.tables.push(json!({ "vpx_file": vpx_file, "title": row["bestTitle"] }));
+        if let Some(folder) = rom_folder(roms_dir, Path::new(vpx_file)) {
+            if !rom.folders.contains(&folder) {
+                rom.folders.push(folder);
+            }
+        }
+    }
+
+    let (mut present, mut missing, mut mismatched) = (0, Vec::new(), 0);
+    let listed: Vec<Value> = roms
+        .values()
+        .map(|(name, rom)| {
+            let files: Vec<PathBuf> =
+                rom.folders.iter().filter_map(|f| paths::find_case_insensitive(f, &format!("{}.zip", name))).collect();
+            let problem = files.iter().find_map(|f| mismatch(name, f));
+            let (status, reason) = match (files.first(), problem) {
+                (None, _) => {
+                    missing.push(name.clone());
+                    ("missing", None)
+                }
+                (Some(_), Some(reason)) => {
+                    mismatched += 1;
+                    ("mismatched", Some(reason))
+                }
+                (Some(_), None) => {
+                    present += 1;
+                    ("present", None)
+                }
+            };
+            let size: u64 = files.iter().filter_map(|f| fs::metadata(f).ok()).map(|m| m.len()).sum();
+            json!({
+                "rom": name,
+                "status": status,
+                "reason": reason,
+                "paths": files,
+                "size": size,
+                "shared": rom.tables.len() > 1,
+                "tables": rom.tables,
+            })
+        })
+        .collect();
+
+    let mut unused = Vec::new();
+    if options.list_unused {
+        let folders: Vec<PathBuf> = match roms_dir {
+            Some(dir) => vec![dir.to_path_buf()],
+            None => {
+                let mut folders: Vec<PathBuf> = roms.values().flat_map(|(_, rom)| rom.folders.clone()).collect();
+                folders.sort();
+                folders.dedup();
+                folders
+            }
+        };
+        for folder in folders {
+            let mut files: Vec<PathBuf> =
+                fs::read_dir(&folder).into_iter().flatten().flatten().map(|e| e.path()).collect();
+            files.sort();
+            unused.extend(files.into_iter().filter(|f| {
+                f.extension().and_then(OsStr::to_str).is_some_and(|e| e.eq_ignore_ascii_case("zip"))
+                    && !roms.contains_key(&f.file_stem().and_then(OsStr::to_str).unwrap_or_default().to_lowercase())
+            }));
+        }
+    }
+
+    json!({
+        "tables": rows.len(),
+        "tables_without_rom": without_rom,
+        "required": roms.len(),
+        "present": present,
+        "missing": missing,
+        "mismatched": mismatched,
+        "roms": listed,
+        "unused": unused,
+    })
+}
+
+/// Audits the ROMs a whole library needs. Each table's ROM comes from its
+/// `romName` in `index_json` (the frontend index), or from its script when
+/// the row has none, and is looked up in `roms_dir`, or in the table's own
+/// `pinmame/roms` when `roms_dir` is null.
+///
+/// A ROM is "present", "missing", or "mismatched" when the zip exists but
+/// PinMAME would not load it (wrong case in the file name, not a zip, or
+/// empty). `options_json` (nullable): `read_scripts` (default true),
+/// `list_unused` (default true).
+///
+/// Returns `{"tables", "tables_without_rom", "required", "present",
+/// "missing": [rom names], "mismatched", "roms": [{"rom", "status",
+/// "reason", "paths", "size", "shared", "tables": [{"vpx_file", "title"}]}],
+/// "unused": [paths]}`, or null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// All arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_audit_roms(
+    index_json: *const c_char,
+    roms_dir: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    let roms_dir = || -> Result<Option<PathBuf>, VpxError> {
+        match roms_dir.is_null() {
+            true => Ok(None),
+            false => unsafe { crate::path_from_c(roms_dir) }.map(Some),
+        }
+    };
+    let result = unsafe { crate::str_from_c(index_json, "index") }
+        .and_then(index::parse)
+        .and_then(|index_doc| {
+            let options: AuditOptions = unsafe { library::options_from_c(options_json, KNOWN_KEYS) }?;
+            Ok(audit(&index_doc, roms_dir()?.as_deref(), &options))
+        })
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_audit_roms", result)
+}
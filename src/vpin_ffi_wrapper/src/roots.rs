@@ -0,0 +1,283 @@
+//! Libraries split across several table roots (internal SSD, external
+//! drive...).
+//!
+//! Roots are listed in `library_config_json`'s `roots`, highest precedence
+//! first. The same table in two roots (the same path below the root, ignoring
+//! case) is a conflict: one copy is picked by the `conflict` policy and the
+//! others are reported as shadowed. A missing root (drive unplugged) is
+//! reported, not an error. Tables whose `.vpxscan.json` says `skip` are
+//! left out.
+//!
+//! A root can be a read-only share (`read_only`) used by several cabinets,
+//! with files written for its tables kept in a local `overlay`. Index paths
+//! can be virtualized as `root://<label>/<path below the root>` so one index
+//! works wherever each cabinet mounts the share.
+
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::library::{LibraryConfig, LibraryRoot};
+use crate::{paths, scan};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Conflict {
+    /// The copy in the root listed first.
+    #[default]
+    First,
+    /// The most recently modified copy.
+    Newest,
+    /// The biggest copy.
+    Largest,
+}
+
+impl Conflict {
+    fn as_str(self) -> &'static str {
+        match self {
+            Conflict::First => "first",
+            Conflict::Newest => "newest",
+            Conflict::Largest => "largest",
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ScanOptions {
+    conflict: Conflict,
+}
+
+struct Found {
+    path: PathBuf,
+    root: usize,
+    modified: u64,
+    size: u64,
+}
+
+fn is_vpx(path: &Path) -> bool {
+    path.extension().and_then(OsStr::to_str).is_some_and(|e| e.eq_ignore_ascii_case("vpx"))
+}
+
+/// What identifies a table across roots: its path below the root, ignoring case.
+fn table_key(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative.to_string_lossy().replace('\\', "/").to_lowercase()
+}
+
+fn root_label(root: &LibraryRoot) -> String {
+    root.label.clone().unwrap_or_else(|| root.path.to_string_lossy().into_owned())
+}
+
+const VIRTUAL_SCHEME: &str = "root://";
+
+/// A root's name in virtual paths: its label, else its position.
+fn virtual_name(root: &LibraryRoot, position: usize) -> String {
+    root.label.clone().unwrap_or_else(|| position.to_string())
+}
+
+fn to_virtual(path: &str, roots: &[LibraryRoot]) -> Option<String> {
+    roots.iter().enumerate().find_map(|(i, root)| {
+        let relative = Path::new(path).strip_prefix(&root.path).ok()?.to_string_lossy().replace('\\', "/");
+        Some(format!("{}{}/{}", VIRTUAL_SCHEME, virtual_name(root, i), relative))
+    })
+}
+
+fn to_real(text: &str, roots: &[LibraryRoot]) -> Option<PathBuf> {
+    let rest = text.strip_prefix(VIRTUAL_SCHEME)?;
+    let (name, relative) = rest.split_once('/').unwrap_or((rest, ""));
+    let (_, root) = roots.iter().enumerate().find(|(i, root)| virtual_name(root, *i) == name)?;
+    Some(relative.split('/').filter(|c| !c.is_empty()).fold(root.path.clone(), |path, c| path.join(c)))
+}
+
+fn map_strings(value: &mut Value, f: &dyn Fn(&str) -> Option<String>) {
+    match value {
+        Value::String(s) => {
+            if let Some(mapped) = f(s) {
+                *s = mapped;
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| map_strings(v, f)),
+        Value::Object(map) => map.values_mut().for_each(|v| map_strings(v, f)),
+        _ => {}
+    }
+}
+
+fn virtualize_index(index_doc: &mut Value, roots: &[LibraryRoot], virtualize: bool) {
+    for row in crate::index::tables_mut(index_doc) {
+        match virtualize {
+            true => map_strings(row, &|s| to_virtual(s, roots)),
+            false => map_strings(row, &|s| to_real(s, roots).map(|p| p.to_string_lossy().into_owned())),
+        }
+    }
+}
+
+fn resolve(path: &str, library: &LibraryConfig) -> Value {
+    let roots = library.table_roots();
+    let real = to_real(path, &roots).unwrap_or_else(|| PathBuf::from(path));
+    let root = library.root_of(&real).map(|(root, _)| root);
+    json!({
+        "path": real,
+        "virtual": to_virtual(&real.to_string_lossy(), &roots),
+        "root": root.as_ref().map(root_label),
+        "read_only": root.as_ref().is_some_and(|r| r.read_only),
+        "read_path": library.readable_path(&real),
+        "write_path": library.writable_path(&real).ok(),
+    })
+}
+
+fn scan(roots: &[LibraryRoot], options: &ScanOptions) -> Result<Value, VpxError> {
+    if roots.is_empty() {
+        return Err(VpxError::new(
+            VpxErrorCode::InvalidArgument,
+            "Library config has no \"roots\" or \"tables_path\".",
+        ));
+    }
+
+    let mut by_key: BTreeMap<String, Vec<Found>> = BTreeMap::new();
+    let mut root_reports = Vec::new();
+    let mut skipped = Vec::new();
+    for (i, root) in roots.iter().enumerate() {
+        let available = root.enabled && root.path.is_dir();
+        let mut count = 0;
+        if available {
+            for path in paths::files_under(&root.path).into_iter().filter(|p| is_vpx(p)) {
+                let overrides = scan::overrides_for(&path);
+                if overrides.merged.skip == Some(true) {
+                    skipped.push(json!({ "vpx_file": path, "override_files": overrides.files }));
+                    continue;
+                }
+                let metadata = fs::metadata(&path).ok();
+                let modified = metadata
+                    .as_ref()
+                    .and_then(|m| m.modified().ok())
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |d| d.as_secs());
+                let size = metadata.map_or(0, |m| m.len());
+                by_key.entry(table_key(&root.path, &path)).or_default().push(Found { path, root: i, modified, size });
+                count += 1;
+            }
+        }
+        root_reports.push(json!({
+            "path": root.path,
+            "label": root_label(root),
+            "enabled": root.enabled,
+            "read_only": root.read_only,
+            "available": available,
+            "tables": count,
+        }));
+    }
+
+    let mut tables = Vec::new();
+    let mut conflicts = Vec::new();
+    for (key, mut copies) in by_key {
+        // Roots are scanned in order, so ties go to the earlier root.
+        let chosen = match options.conflict {
+            Conflict::First => 0,
+            Conflict::Newest => (0..copies.len()).rev().max_by_key(|&i| copies[i].modified).unwrap_or(0),
+            Conflict::Largest => (0..copies.len()).rev().max_by_key(|&i| copies[i].size).unwrap_or(0),
+        };
+        let winner = copies.remove(chosen);
+        tables.push(json!({ "vpx_file": winner.path, "root": root_label(&roots[winner.root]), "key": key }));
+        if !copies.is_empty() {
+            let shadowed: Vec<Value> = copies
+                .iter()
+                .map(|c| json!({ "vpx_file": c.path, "root": root_label(&roots[c.root]) }))
+                .collect();
+            conflicts.push(json!({
+                "key": key,
+                "chosen": winner.path,
+                "shadowed": shadowed,
+                "policy": options.conflict.as_str(),
+            }));
+        }
+    }
+
+    Ok(json!({
+        "roots": root_reports,
+        "count": tables.len(),
+        "tables": tables,
+        "conflicts": conflicts,
+        "skipped": skipped,
+    }))
+}
+
+/// Lists the tables of a multi-root library. `library_config_json` gives
+/// `roots: [{"path", "enabled": true, "label"}]` in precedence order (or
+/// just `tables_path`). `options_json` (nullable): `conflict` = `"first"`
+/// (default), `"newest"` or `"largest"`, for tables found in several roots.
+///
+/// Returns `{"roots": [{"path", "label", "enabled", "available", "tables"}],
+/// "count", "tables": [{"vpx_file", "root", "key"}], "conflicts": [{"key",
+/// "chosen", "shadowed", "policy"}], "skipped": [{"vpx_file",
+/// "override_files"}]}`, or null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_scan_library_roots(
+    library_config_json: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    let result = unsafe { LibraryConfig::from_c(library_config_json) }
+        .and_then(|library| {
+            let options: ScanOptions = unsafe { crate::library::options_from_c(options_json, &["conflict"]) }?;
+            scan(&library.table_roots(), &options)
+        })
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_scan_library_roots", result)
+}
+
+/// Rewrites every path in an index (`{"tables": [...]}`) that lies under a
+/// root: to `root://<label>/...` when `to_virtual`, back to this cabinet's
+/// real paths otherwise. Roots without a label are named by position.
+///
+/// Returns the index, or null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// Both JSON arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_virtualize_index(
+    index_json: *const c_char,
+    library_config_json: *const c_char,
+    to_virtual: bool,
+) -> *mut c_char {
+    let result = unsafe { crate::str_from_c(index_json, "index") }
+        .and_then(crate::index::parse)
+        .and_then(|index_doc| Ok((index_doc, unsafe { LibraryConfig::from_c(library_config_json) }?)))
+        .and_then(|(mut index_doc, library)| {
+            virtualize_index(&mut index_doc, &library.table_roots(), to_virtual);
+            crate::into_c_string(index_doc.to_string())
+        });
+
+    crate::envelope::finish("vpx_virtualize_index", result)
+}
+
+/// Where a library file (real or `root://` path) is on this cabinet:
+/// `{"path", "virtual", "root", "read_only", "read_path", "write_path"}`.
+/// For a read-only root, `write_path` is the same place in its overlay
+/// (null without one) and `read_path` prefers an existing overlay copy.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_resolve_library_path(
+    path: *const c_char,
+    library_config_json: *const c_char,
+) -> *mut c_char {
+    let result = unsafe { crate::str_from_c(path, "path") }
+        .and_then(|path| Ok((path, unsafe { LibraryConfig::from_c(library_config_json) }?)))
+        .and_then(|(path, library)| crate::into_c_string(resolve(path, &library).to_string()));
+
+    crate::envelope::finish("vpx_resolve_library_path", result)
+}
@@ -0,0 +1,228 @@
+//! "How to play" text for the frontend's overlay.
+//!
+//! Authors put the rules in the table info's rules field, or in the
+//! description when they don't bother with the former, typed in the VPX
+//! editor with whatever line endings, tabs and bullet characters came
+//! along. The summary is that text cleaned up and capped, headed by the
+//! few game facts the script declares (players, balls, free play, replay).
+
+use std::os::raw::c_char;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::library;
+use crate::tags::optional_json;
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct RulesOptions {
+    /// Longest text returned, in characters.
+    max_chars: usize,
+    /// Most lines returned, after wrapping.
+    max_lines: usize,
+    /// Wrap lines at this many characters; 0 leaves them.
+    wrap: usize,
+}
+
+impl Default for RulesOptions {
+    fn default() -> Self {
+        RulesOptions { max_chars: 2000, max_lines: 40, wrap: 0 }
+    }
+}
+
+const KNOWN_KEYS: &[&str] = &["max_chars", "max_lines", "wrap"];
+
+/// Script constants the facts come from, by the names scripts use.
+const BALL_CONSTANTS: &[&str] = &["BallsPerGame", "Balls_Per_Game", "cBallsPerGame", "MaxBalls"];
+const PLAYER_CONSTANTS: &[&str] = &["MaxPlayers", "Max_Players", "cMaxPlayers"];
+const FREE_PLAY_CONSTANTS: &[&str] = &["FreePlay", "Free_Play", "cFreePlay"];
+const REPLAY_CONSTANTS: &[&str] = &["Replay1", "ReplayScore", "Replay_Score", "Replay"];
+
+/// Characters authors start list items with.
+const BULLETS: &[char] = &['-', '*', '•', '·', '◦', '‣'];
+
+/// A script constant's value from an outline's `constants` (or a merged
+/// document that has them).
+fn constant<'a>(script: &'a Value, names: &[&str]) -> Option<&'a Value> {
+    script["constants"].as_array()?.iter().find_map(|c| {
+        let name = c["name"].as_str()?;
+        names.iter().any(|n| n.eq_ignore_ascii_case(name)).then_some(&c["value"])
+    })
+}
+
+/// Thousands separators for a score.
+fn grouped(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn facts(script: &Value) -> Vec<String> {
+    let mut facts = Vec::new();
+    let players = script["max_players"].as_u64().or_else(|| constant(script, PLAYER_CONSTANTS)?.as_u64());
+    match players {
+        Some(1) => facts.push("1 player".to_string()),
+        Some(n) if (2..=8).contains(&n) => facts.push(format!("1-{} players", n)),
+        _ => {}
+    }
+    if let Some(balls) = constant(script, BALL_CONSTANTS).and_then(Value::as_u64).filter(|n| (1..=10).contains(n)) {
+        facts.push(format!("{} ball{} per game", balls, if balls == 1 { "" } else { "s" }));
+    }
+    let free_play =
+        constant(script, FREE_PLAY_CONSTANTS).and_then(|v| v.as_bool().or_else(|| v.as_i64().map(|n| n != 0)));
+    if free_play == Some(true) {
+        facts.push("Free play".to_string());
+    }
+    if let Some(replay) = constant(script, REPLAY_CONSTANTS).and_then(Value::as_u64).filter(|n| *n >= 1000) {
+        facts.push(format!("Replay at {}", grouped(replay)));
+    }
+    facts
+}
+
+/// Unified line endings and bullets, no control characters, trailing
+/// spaces or runs of blank lines.
+fn normalize(text: &str) -> String {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.split('\n') {
+        let line: String = line.chars().map(|c| if c == '\t' { ' ' } else { c }).filter(|c| !c.is_control()).collect();
+        let line = line.trim_end();
+        let trimmed = line.trim_start();
+        let line = match trimmed.strip_prefix(BULLETS) {
+            Some(item) if item.starts_with(' ') => format!("- {}", item.trim_start()),
+            _ => line.to_string(),
+        };
+        if line.is_empty() && lines.last().is_none_or(|l| l.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/// `line` wrapped at `width` on spaces; list items continue indented.
+fn wrap_line(line: &str, width: usize) -> Vec<String> {
+    let indent = if line.starts_with("- ") { "  " } else { "" };
+    let mut out = Vec::new();
+    let mut current = String::new();
+    for word in line.split_whitespace() {
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > width {
+            out.push(std::mem::take(&mut current));
+            current.push_str(indent);
+        } else if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    out.push(current);
+    out
+}
+
+/// `text` capped at `max_lines` and `max_chars`, cut at a line or sentence
+/// end where one is close. Returns whether anything was cut.
+fn cap(text: &str, options: &RulesOptions) -> (String, bool) {
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    if options.wrap > 0 {
+        lines = lines
+            .iter()
+            .flat_map(|l| if l.is_empty() { vec![String::new()] } else { wrap_line(l, options.wrap) })
+            .collect();
+    }
+    let mut truncated = false;
+    if lines.len() > options.max_lines {
+        lines.truncate(options.max_lines);
+        truncated = true;
+    }
+    let mut text = lines.join("\n");
+    if text.chars().count() > options.max_chars {
+        let limit = options.max_chars.saturating_sub(1);
+        let cut: String = text.chars().take(limit).collect();
+        let boundary = cut.rfind('\n').into_iter().chain(cut.rfind(". ").map(|i| i + 1)).max();
+        text = match boundary {
+            Some(end) if end > cut.len() / 2 => cut[..end].trim_end().to_string(),
+            _ => cut.trim_end().to_string(),
+        };
+        truncated = true;
+    }
+    if truncated {
+        text.push('…');
+    }
+    (text, truncated)
+}
+
+/// The summary for a table info document and a script document (both
+/// optional, `Value::Null` when missing).
+fn summarize(table_info: &Value, script: &Value, options: &RulesOptions) -> Value {
+    let field = |key: &str| table_info[key].as_str().map(normalize).filter(|t| !t.is_empty());
+    let (source, body) = [("rules", "table_rules"), ("description", "table_description"), ("blurb", "table_blurb")]
+        .iter()
+        .find_map(|(source, key)| Some((*source, field(key)?)))
+        .unwrap_or(("none", String::new()));
+    let facts = facts(script);
+
+    let mut text = facts.join(" · ");
+    if !body.is_empty() {
+        if !text.is_empty() {
+            text.push_str("\n\n");
+        }
+        text.push_str(&body);
+    }
+    let (text, truncated) = cap(&text, options);
+    json!({
+        "title": field("table_name"),
+        "facts": facts,
+        "source": source,
+        "text": text,
+        "truncated": truncated,
+    })
+}
+
+/// The summary text alone, wrapped at `wrap` and capped at `max_lines`,
+/// for a table info document (script facts left out).
+pub(crate) fn summary_text(table_info: &Value, wrap: usize, max_lines: usize) -> String {
+    let options = RulesOptions { max_lines, wrap, ..RulesOptions::default() };
+    summarize(table_info, &Value::Null, &options)["text"].as_str().unwrap_or_default().to_string()
+}
+
+/// Assembles the "how to play" text of a table: the rules from the table
+/// info (else the description, else the blurb), cleaned up, headed by the
+/// players, balls per game, free play and replay the script declares.
+///
+/// `table_info_json` is a `vpx_get_table_info_as_json` document;
+/// `script_json` a `vpx_get_script_analysis` or `vpx_get_script_outline`
+/// document, or both merged (the facts other than players come from the
+/// outline's constants); either may be null. `options_json` (nullable):
+/// `max_chars` (default 2000), `max_lines` (default 40), `wrap` (line width,
+/// default 0 for none).
+///
+/// Returns `{"title", "facts", "source": "rules" | "description" | "blurb"
+/// | "none", "text", "truncated"}`, or null on bad JSON. Cut text ends with
+/// `…`. Release with `vpx_free_string`.
+///
+/// # Safety
+/// All arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_generate_rules_summary(
+    table_info_json: *const c_char,
+    script_json: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    let result = unsafe { optional_json(table_info_json, "table info") }
+        .and_then(|info| Ok((info, unsafe { optional_json(script_json, "script") }?)))
+        .and_then(|(info, script)| {
+            let options: RulesOptions = unsafe { library::options_from_c(options_json, KNOWN_KEYS) }?;
+            crate::into_c_string(summarize(&info, &script, &options).to_string())
+        });
+
+    crate::envelope::finish("vpx_generate_rules_summary", result)
+}
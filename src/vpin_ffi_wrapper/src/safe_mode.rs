@@ -0,0 +1,38 @@
+//! Safe mode for kiosk cabinets.
+//!
+//! With `safe_mode` in the config, the calls that change table files or
+//! write files to disk fail with `PermissionDenied` before touching
+//! anything. That covers moving, removing, importing and optimizing tables,
+//! media migration, bundles, cards, diagnostic bundles, provenance stamps
+//! and undo/redo. Dry runs still work. The frontend's own data (index, user
+//! store, playlists, journal, log file) is written as usual. Once on, safe
+//! mode stays on for the life of the process, so no later
+//! `vpx_wrapper_init` can switch it off again.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::{VpxError, VpxErrorCode};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turns safe mode on if `requested`; returns whether it is on.
+pub(crate) fn request(requested: bool) -> bool {
+    let was = ENABLED.fetch_or(requested, Ordering::Relaxed);
+    if was && !requested {
+        log_warn!("safe_mode stays on until the process exits");
+    }
+    was || requested
+}
+
+pub(crate) fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Fails with `PermissionDenied` in safe mode; `what` names the refused
+/// action for the message.
+pub(crate) fn check(what: &str) -> Result<(), VpxError> {
+    match enabled() {
+        true => Err(VpxError::new(VpxErrorCode::PermissionDenied, format!("Safe mode is on: {} is disabled", what))),
+        false => Ok(()),
+    }
+}
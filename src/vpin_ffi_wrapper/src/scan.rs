@@ -0,0 +1,314 @@
+//! Scan metadata for one table, with user overrides.
+//!
+//! Heuristics never get every table right, so a table can carry an override
+//! next to it: `<table>.vpxscan.json` for that table, or `.vpxscan.json` in
+//! its folder for every table there (the per-table file wins key by key):
+//!
+//! ```json
+//! { "title": "Twilight Zone", "rom": "tz_94h", "manufacturer": "Bally", "year": "1993",
+//!   "skip": false, "skip_steps": ["vps_match"] }
+//! ```
+//!
+//! `skip` leaves the table out of library scans; `skip_steps` is passed
+//! through for the frontend's own steps. Every merged field says where its
+//! value came from, and which values are user edits a rescan must keep.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::config;
+use crate::error::{VpxError, VpxErrorCode};
+use crate::handle::OpenTable;
+use crate::options::CallOptions;
+use crate::profiles::{self, Pass};
+use crate::{flags, paths, script};
+
+/// Name of the per-folder override; per-table ones are `<stem>` + this.
+const OVERRIDE_FILE: &str = ".vpxscan.json";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct ScanOverride {
+    pub title: Option<String>,
+    pub rom: Option<String>,
+    pub manufacturer: Option<String>,
+    pub year: Option<String>,
+    pub author: Option<String>,
+    pub version: Option<String>,
+    pub skip: Option<bool>,
+    pub skip_steps: Option<Vec<String>>,
+}
+
+const KNOWN_KEYS: &[&str] = &["title", "rom", "manufacturer", "year", "author", "version", "skip", "skip_steps"];
+
+impl ScanOverride {
+    /// `other`'s keys over this one's.
+    fn layer(self, other: ScanOverride) -> ScanOverride {
+        ScanOverride {
+            title: other.title.or(self.title),
+            rom: other.rom.or(self.rom),
+            manufacturer: other.manufacturer.or(self.manufacturer),
+            year: other.year.or(self.year),
+            author: other.author.or(self.author),
+            version: other.version.or(self.version),
+            skip: other.skip.or(self.skip),
+            skip_steps: other.skip_steps.or(self.skip_steps),
+        }
+    }
+
+    fn field(&self, name: &str) -> Option<&String> {
+        match name {
+            "title" => self.title.as_ref(),
+            "rom_name" => self.rom.as_ref(),
+            "manufacturer" => self.manufacturer.as_ref(),
+            "year" => self.year.as_ref(),
+            "author" => self.author.as_ref(),
+            "version" => self.version.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// The overrides that apply to a table, layered, with the files they came
+/// from and any that could not be read.
+pub(crate) struct Overrides {
+    pub merged: ScanOverride,
+    pub files: Vec<PathBuf>,
+    pub warnings: Vec<String>,
+}
+
+fn read_override(path: &Path) -> Result<ScanOverride, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let value: Value = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let Some(object) = value.as_object() else {
+        return Err(format!("{}: not a JSON object", path.display()));
+    };
+    config::warn_unknown_keys(&path.to_string_lossy(), object, KNOWN_KEYS);
+    ScanOverride::deserialize(&value).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Folder override, then the table's own.
+pub(crate) fn overrides_for(vpx_path: &Path) -> Overrides {
+    let dir = paths::table_dir(vpx_path);
+    let stem = vpx_path.file_stem().and_then(OsStr::to_str).unwrap_or_default();
+    let candidates = [
+        paths::find_case_insensitive(&dir, OVERRIDE_FILE),
+        paths::find_case_insensitive(&dir, &format!("{}{}", stem, OVERRIDE_FILE)),
+    ];
+    let mut overrides = Overrides { merged: ScanOverride::default(), files: Vec::new(), warnings: Vec::new() };
+    for path in candidates.into_iter().flatten() {
+        match read_override(&path) {
+            Ok(layer) => {
+                overrides.merged = std::mem::take(&mut overrides.merged).layer(layer);
+                overrides.files.push(path);
+            }
+            Err(warning) => {
+                log_warn!("scan override ignored: {}", warning);
+                overrides.warnings.push(warning);
+            }
+        }
+    }
+    overrides
+}
+
+/// Title, manufacturer and year from a `Title (Manufacturer Year)` name,
+/// the usual table naming.
+pub(crate) fn from_filename(name: &str) -> (String, Option<String>, Option<String>) {
+    let Some((title, rest)) = name.split_once('(') else { return (name.trim().to_string(), None, None) };
+    let inside = rest.split(')').next().unwrap_or_default();
+    let mut words: Vec<&str> = inside.split_whitespace().collect();
+    let year = match words.last() {
+        Some(w) if w.len() == 4 && w.chars().all(|c| c.is_ascii_digit()) => words.pop().map(String::from),
+        _ => None,
+    };
+    let manufacturer = Some(words.join(" ")).filter(|m| !m.is_empty());
+    (title.trim().to_string(), manufacturer, year)
+}
+
+/// Metadata fields reported, in output order.
+const FIELDS: &[&str] = &[
+    "title",
+    "manufacturer",
+    "year",
+    "rom_name",
+    "author",
+    "version",
+    "players",
+    "table_type",
+    "vps_id",
+    "description",
+];
+
+/// Where values come from, lowest precedence first: the file name, the
+/// table itself, its script, the VPS match, the `.vpxscan.json` sidecar and
+/// the user's edits (the frontend's `<table>.json`).
+pub(crate) const SOURCES: &[&str] = &["filename", "vpx", "script", "vps", "sidecar", "user"];
+
+/// VPS match fields as the frontend's index names them.
+const VPS_KEYS: &[(&str, &str)] = &[
+    ("title", "vpsName"),
+    ("manufacturer", "vpsManufacturer"),
+    ("year", "vpsYear"),
+    ("author", "vpsAuthors"),
+    ("version", "vpsVersion"),
+    ("players", "vpsPlayers"),
+    ("table_type", "vpsType"),
+    ("vps_id", "vpsId"),
+];
+
+/// Fields of the frontend's per-table override, written by its editor.
+const USER_KEYS: &[(&str, &str)] =
+    &[("title", "bestTitle"), ("manufacturer", "bestManufacturer"), ("year", "bestYear"), ("vps_id", "vpsId")];
+
+/// Empty strings and nulls are no value.
+pub(crate) fn present(value: &Value) -> Option<Value> {
+    match value {
+        Value::Null => None,
+        Value::String(s) if s.trim().is_empty() => None,
+        Value::String(s) => Some(json!(s.trim())),
+        other => Some(other.clone()),
+    }
+}
+
+/// The frontend's `<table>.json` beside the `.vpx`, if readable.
+fn user_edits(vpx_path: &Path) -> Option<Value> {
+    let text = fs::read_to_string(vpx_path.with_extension("json")).ok()?;
+    serde_json::from_str(&text).ok().filter(Value::is_object)
+}
+
+fn scan_metadata(vpx_path: &Path, vps_match: Option<&Value>, options: &CallOptions) -> Result<Value, VpxError> {
+    // The metadata is built from the whole table info, whatever "fields" says.
+    let mut table = OpenTable::open(vpx_path, &CallOptions { fields: None, ..options.clone() })?;
+    let info = table.table_info()?;
+    let profile = profiles::for_table(vpx_path, options);
+    let code = match profile.runs(Pass::Script) {
+        true => table.code().unwrap_or_default(),
+        false => String::new(),
+    };
+    // The script facts are already candidates below.
+    let mut found = profiles::run_passes(&mut table, vpx_path, profile);
+    found.remove("script");
+    let overrides = overrides_for(vpx_path);
+    let user = user_edits(vpx_path);
+
+    // The file name, or the folder's when the file is just a short name.
+    let stem = vpx_path.file_stem().and_then(OsStr::to_str).unwrap_or_default();
+    let folder = paths::table_dir(vpx_path);
+    let folder_name = folder.file_name().and_then(OsStr::to_str).unwrap_or_default();
+    let name = if !stem.contains('(') && folder_name.contains('(') { folder_name } else { stem };
+    let (file_title, file_manufacturer, file_year) = from_filename(name);
+
+    let mut candidates: Vec<(&str, &str, Option<Value>)> = vec![
+        ("title", "filename", Some(json!(file_title))),
+        ("manufacturer", "filename", file_manufacturer.map(Value::from)),
+        ("year", "filename", file_year.map(Value::from)),
+        ("title", "vpx", Some(info["table_name"].clone())),
+        ("author", "vpx", Some(info["author_name"].clone())),
+        ("version", "vpx", Some(info["table_version"].clone())),
+        ("description", "vpx", Some(info["table_description"].clone())),
+        ("rom_name", "script", script::rom_name(&code).map(Value::from)),
+        ("players", "script", script::max_players(&code).map(Value::from)),
+    ];
+    for (field, key) in VPS_KEYS {
+        candidates.push((field, "vps", vps_match.map(|m| m[*key].clone())));
+    }
+    for field in FIELDS {
+        candidates.push((field, "sidecar", overrides.merged.field(field).map(|v| json!(v))));
+    }
+    for (field, key) in USER_KEYS {
+        candidates.push((field, "user", user.as_ref().map(|u| u[*key].clone())));
+    }
+
+    let mut fields = Map::new();
+    let mut sources = Map::new();
+    let mut provenance = Map::new();
+    let mut protected = Vec::new();
+    for field in FIELDS {
+        let mut found: Vec<(&str, Value)> = candidates
+            .iter()
+            .filter(|(f, _, _)| f == field)
+            .filter_map(|(_, source, value)| Some((*source, present(value.as_ref()?)?)))
+            .collect();
+        found.sort_by_key(|(source, _)| SOURCES.iter().position(|s| s == source));
+        let chosen = found.last().cloned();
+        let source = chosen.as_ref().map(|(source, _)| *source);
+        if source == Some("user") {
+            protected.push(*field);
+        }
+        fields.insert(field.to_string(), chosen.map_or(Value::Null, |(_, value)| value));
+        sources.insert(field.to_string(), json!(source));
+        provenance.insert(
+            field.to_string(),
+            json!({
+                "source": source,
+                "protected": source == Some("user"),
+                "candidates": found.iter().map(|(s, v)| json!({ "source": s, "value": v })).collect::<Vec<_>>(),
+            }),
+        );
+    }
+
+    let mut metadata = json!({
+        "vpx_file": vpx_path,
+        "profile": profile.name,
+        "fields": fields,
+        "sources": sources,
+        "provenance": provenance,
+        "protected": protected,
+        "skip": overrides.merged.skip.unwrap_or(false),
+        "skip_steps": overrides.merged.skip_steps.unwrap_or_default(),
+        "flags": flags::of(vpx_path),
+        "override_files": overrides.files,
+        "warnings": overrides.warnings,
+    });
+    if let Some(metadata) = metadata.as_object_mut() {
+        metadata.extend(found);
+    }
+    Ok(metadata)
+}
+
+/// The metadata a scan settles on for a table: `fields` (see [`FIELDS`])
+/// picked by precedence from the file name, the table info, the script, the
+/// VPS match, `.vpxscan.json` and the user's edits (see [`SOURCES`]).
+/// `sources` names where each value came from; `provenance` adds every
+/// candidate value per field and whether it is `protected`: a user edit a
+/// rescan must not overwrite (also listed in `protected`). Also `skip`,
+/// `skip_steps`, the user's `flags` (see `vpx_get_table_flags`),
+/// `override_files` and `warnings` (unreadable sidecars, which are ignored).
+/// `profile` names the scan profile it ran with: the script is only read
+/// from `"full"` on, and `"deep"` adds `analysis` and `sha256`.
+///
+/// `vps_match_json` (nullable) is the frontend's VPS match with its index
+/// names (`vpsName`, `vpsManufacturer`, `vpsYear`, ...). `options_json` is
+/// as for the `_ex` calls and may be null. Returns null on failure. Release
+/// with `vpx_free_string`.
+///
+/// # Safety
+/// All arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_scan_metadata(
+    vpx_file_path: *const c_char,
+    vps_match_json: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    let vps_match = || -> Result<Option<Value>, VpxError> {
+        if vps_match_json.is_null() {
+            return Ok(None);
+        }
+        let text = unsafe { crate::str_from_c(vps_match_json, "VPS match") }?;
+        match serde_json::from_str::<Value>(text) {
+            Ok(value) if value.is_object() => Ok(Some(value)),
+            _ => Err(VpxError::new(VpxErrorCode::InvalidArgument, "VPS match must be a JSON object.")),
+        }
+    };
+    let result = unsafe { CallOptions::from_c(options_json) }
+        .and_then(|options| Ok((unsafe { crate::path_from_c(vpx_file_path) }?, vps_match()?, options)))
+        .and_then(|(path, vps_match, options)| scan_metadata(&path, vps_match.as_ref(), &options))
+        .and_then(|metadata| crate::into_c_string(metadata.to_string()));
+
+    crate::envelope::finish("vpx_get_scan_metadata", result)
+}
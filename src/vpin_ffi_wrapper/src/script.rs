@@ -0,0 +1,256 @@
+//! Heuristics over the embedded VBScript.
+//!
+//! Tables follow a handful of conventions (`Const cGameName = "..."`,
+//! `LoadVPM`, `PlayMusic "..."`) and these helpers look for them in the
+//! script's tokens (see `vbs`), so comments, strings and `:`-joined
+//! statements can't fool them. `vpx_get_script_analysis` bundles them
+//! into one JSON document; `vpx_get_script_outline` returns the parsed
+//! declarations.
+
+use std::os::raw::c_char;
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::options::CallOptions;
+use crate::vbs::{self, Statement, Token};
+
+fn push_unique(found: &mut Vec<String>, literal: &str) {
+    if !literal.trim().is_empty() && !found.iter().any(|f| f.eq_ignore_ascii_case(literal)) {
+        found.push(literal.to_string());
+    }
+}
+
+/// The values the script assigns to any of `names`, as `(name as
+/// written, value tokens)`, in script order.
+fn assigned<'a>(statements: &'a [Statement], names: &[&str]) -> Vec<(&'a str, &'a [Token])> {
+    statements
+        .iter()
+        .flat_map(vbs::assignments)
+        .filter(|(name, _)| names.iter().any(|n| n.eq_ignore_ascii_case(name)))
+        .collect()
+}
+
+/// The ROM set name assigned to `cGameName`, if the script sets one.
+pub(crate) fn rom_name(code: &str) -> Option<String> {
+    rom_name_in(&vbs::tokenize(code))
+}
+
+fn rom_name_in(statements: &[Statement]) -> Option<String> {
+    assigned(statements, &["cgamename"]).into_iter().find_map(|(_, value)| {
+        value.iter().find_map(Token::string).filter(|s| !s.trim().is_empty()).map(str::to_string)
+    })
+}
+
+/// True when the script drives a PinMAME controller.
+pub(crate) fn uses_pinmame(code: &str) -> bool {
+    uses_pinmame_in(&vbs::tokenize(code))
+}
+
+fn uses_pinmame_in(statements: &[Statement]) -> bool {
+    statements.iter().flat_map(|s| &s.tokens).any(|token| match token {
+        Token::Ident(_) => token.is("loadvpm"),
+        Token::Str(s) => s.eq_ignore_ascii_case("vpinmame.controller"),
+        _ => false,
+    })
+}
+
+/// Files passed to `PlayMusic`, relative to the music folder.
+pub(crate) fn music_references(code: &str) -> Vec<String> {
+    music_references_in(&vbs::tokenize(code))
+}
+
+fn music_references_in(statements: &[Statement]) -> Vec<String> {
+    let mut found = Vec::new();
+    for statement in statements {
+        let Some(pos) = statement.tokens.iter().position(|t| t.is("playmusic")) else { continue };
+        if let Some(name) = statement.tokens[pos..].iter().find_map(Token::string) {
+            if !name.is_empty() && !found.iter().any(|f| f == name) {
+                found.push(name.to_string());
+            }
+        }
+    }
+    found
+}
+
+/// External `.vbs` files the script loads (`LoadVPM`, `ExecuteGlobal GetTextFile`, ...).
+pub(crate) fn script_references(code: &str) -> Vec<String> {
+    script_references_in(&vbs::tokenize(code))
+}
+
+fn script_references_in(statements: &[Statement]) -> Vec<String> {
+    let mut found = Vec::new();
+    for literal in statements.iter().flat_map(|s| &s.tokens).filter_map(Token::string) {
+        if literal.to_ascii_lowercase().ends_with(".vbs") {
+            push_unique(&mut found, literal);
+        }
+    }
+    found
+}
+
+/// True when a name or string in the script contains one of `needles`
+/// (lowercase).
+fn mentions(statements: &[Statement], needles: &[&str]) -> bool {
+    statements.iter().flat_map(|s| &s.tokens).any(|token| match token {
+        Token::Ident(s) | Token::Str(s) => {
+            let lower = s.to_ascii_lowercase();
+            needles.iter().any(|n| lower.contains(n))
+        }
+        _ => false,
+    })
+}
+
+/// The value of a `MaxPlayers = N` style constant, as EM and original
+/// scripts declare it.
+pub(crate) fn max_players(code: &str) -> Option<u32> {
+    max_players_in(&vbs::tokenize(code))
+}
+
+fn max_players_in(statements: &[Statement]) -> Option<u32> {
+    assigned(statements, &["maxplayers", "max_players", "cmaxplayers"])
+        .into_iter()
+        .find_map(|(_, value)| vbs::literal_value(value).as_u64().map(|n| n as u32).filter(|n| (1..=8).contains(n)))
+}
+
+/// True when the script drives score reels, which only EM tables have.
+fn uses_score_reels(statements: &[Statement]) -> bool {
+    statements.iter().any(|statement| {
+        statement.tokens.windows(3).any(|window| match window {
+            [Token::Ident(object), dot, member] => {
+                dot.is_symbol(".")
+                    && object.to_ascii_lowercase().contains("reel")
+                    && (member.is("setvalue") || member.is("addvalue") || member.is("resettozero"))
+            }
+            _ => false,
+        })
+    })
+}
+
+/// Color grade (LUT) images the script switches between: literals in
+/// statements setting `ColorGradeImage`, and any literal named like a LUT.
+pub(crate) fn lut_references(code: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    for statement in vbs::tokenize(code) {
+        let sets_lut = statement.tokens.iter().any(|t| t.is("colorgradeimage"));
+        for literal in statement.tokens.iter().filter_map(Token::string) {
+            if sets_lut || literal.to_ascii_lowercase().starts_with("lut") {
+                push_unique(&mut found, literal);
+            }
+        }
+    }
+    found
+}
+
+/// True when the script changes the color grade at runtime.
+pub(crate) fn switches_lut(code: &str) -> bool {
+    mentions(&vbs::tokenize(code), &["colorgradeimage"])
+}
+
+/// True when the script reads the player's day/night setting (`NightDay`)
+/// to adjust its own lighting.
+pub(crate) fn reads_night_day(code: &str) -> bool {
+    mentions(&vbs::tokenize(code), &["nightday"])
+}
+
+/// The option a script offers for its VR room, as `(name, value)`: the
+/// first `VRRoom = 1`, `Const VR_Room = 0`... style numeric setting.
+pub(crate) fn vr_toggle(code: &str) -> Option<(String, String)> {
+    let statements = vbs::tokenize(code);
+    statements.iter().flat_map(vbs::assignments).find_map(|(name, value)| {
+        let lower = name.to_ascii_lowercase();
+        let vr_name = lower.starts_with("vr") && (lower.contains("room") || lower.contains("mode"));
+        let value = vbs::literal_value(value).as_i64()?;
+        vr_name.then(|| (name.to_string(), value.to_string()))
+    })
+}
+
+/// True when the script checks whether VPX runs in VR (`RenderingMode`).
+pub(crate) fn detects_vr(code: &str) -> bool {
+    mentions(&vbs::tokenize(code), &["renderingmode"])
+}
+
+/// The script heuristics as one JSON object.
+pub(crate) fn analyze(code: &str) -> Value {
+    let statements = vbs::tokenize(code);
+    json!({
+        "rom_name": rom_name_in(&statements),
+        "uses_pinmame": uses_pinmame_in(&statements),
+        "uses_b2s": mentions(&statements, &["b2s.server"]),
+        "uses_ultradmd": mentions(&statements, &["ultradmd"]),
+        "uses_pup": mentions(&statements, &["pinupplayer", "puppack"]),
+        "uses_score_reels": uses_score_reels(&statements),
+        "max_players": max_players_in(&statements),
+        "music": music_references_in(&statements),
+        "scripts": script_references_in(&statements),
+        "line_count": code.lines().count(),
+    })
+}
+
+fn read_script_analysis(path: &Path) -> Result<Value, VpxError> {
+    crate::catch_panic(path, || {
+        let mut vpx_file = crate::open_vpx(path, &CallOptions::default())?;
+        let gamedata = vpx_file.read_gamedata().map_err(|e| {
+            VpxError::new(VpxErrorCode::ParseFailed, format!("Failed to read gamedata for '{}': {}", path.display(), e))
+        })?;
+        let mut analysis = analyze(&gamedata.code.string);
+        // Playfield size in VP units, mostly to tell widebodies apart.
+        analysis["playfield"] = json!({
+            "width": gamedata.right - gamedata.left,
+            "height": gamedata.bottom - gamedata.top,
+        });
+        Ok(analysis)
+    })
+}
+
+/// Runs the script heuristics over the table at `vpx_file_path`: ROM name,
+/// PinMAME/B2S/UltraDMD/PuP usage, score reels, player count, referenced
+/// music and scripts, plus the playfield size from GameData.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `vpx_file_path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_script_analysis(vpx_file_path: *const c_char) -> *mut c_char {
+    let result = unsafe { crate::path_from_c(vpx_file_path) }
+        .and_then(|path| read_script_analysis(&path))
+        .and_then(|analysis| crate::into_c_string(analysis.to_string()));
+
+    crate::envelope::finish("vpx_get_script_analysis", result)
+}
+
+fn read_script_outline(path: &Path) -> Result<Value, VpxError> {
+    crate::catch_panic(path, || {
+        let mut vpx_file = crate::open_vpx(path, &CallOptions::default())?;
+        let gamedata = vpx_file.read_gamedata().map_err(|e| {
+            VpxError::new(VpxErrorCode::ParseFailed, format!("Failed to read gamedata for '{}': {}", path.display(), e))
+        })?;
+        let code = &gamedata.code.string;
+        let mut outline = vbs::outline(&vbs::tokenize(code)).to_json();
+        outline["line_count"] = json!(code.lines().count());
+        Ok(outline)
+    })
+}
+
+/// Parses the script of the table at `vpx_file_path` and returns its
+/// outline: `{"line_count", "statements", "procedures": [{"kind", "name",
+/// "params", "class", "public", "start_line", "end_line"}], "classes",
+/// "constants": [{"name", "value", "line", "procedure"}], "variables"
+/// (script and class level), "includes": [{"file", "line"}], "objects":
+/// [{"name", "members", "uses", "first_line"}] (most used first),
+/// "created_objects": [{"prog_id", "variable", "line"}]}`. Constant values
+/// are numbers, strings or booleans when literal, else their source text.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `vpx_file_path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_script_outline(vpx_file_path: *const c_char) -> *mut c_char {
+    let result = unsafe { crate::path_from_c(vpx_file_path) }
+        .and_then(|path| read_script_outline(&path))
+        .and_then(|outline| crate::into_c_string(outline.to_string()));
+
+    crate::envelope::finish("vpx_get_script_outline", result)
+}
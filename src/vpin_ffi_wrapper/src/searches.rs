@@ -0,0 +1,109 @@
+//! Saved searches: named smart-filter expressions kept in the user store's
+//! top-level `searches` object (`{"Unplayed EMs": {"expression",
+//! "updated"}}`), so a custom view made in the frontend shows up in the CLI
+//! and the other way round.
+
+use std::os::raw::c_char;
+
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::{filter, store};
+
+/// The saved searches sorted by name ignoring case, `[{"name",
+/// "expression", "updated"}]`.
+fn searches_in(store_doc: &Value) -> Vec<Value> {
+    let mut searches: Vec<Value> = store_doc["searches"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, search)| {
+            Some(json!({
+                "name": name,
+                "expression": search["expression"].as_str()?,
+                "updated": search["updated"].as_u64().unwrap_or(0),
+            }))
+        })
+        .collect();
+    searches.sort_by_key(|search| search["name"].as_str().unwrap_or_default().to_lowercase());
+    searches
+}
+
+/// Saves `expression` under `name`, replacing a search of that name. The
+/// expression must parse, so a broken view never gets stored.
+fn save(name: &str, expression: &str) -> Result<(), VpxError> {
+    filter::parse(expression)?;
+    store::update(|store_doc| {
+        if !store_doc["searches"].is_object() {
+            store_doc["searches"] = json!({});
+        }
+        store_doc["searches"][name] = json!({ "expression": expression.trim(), "updated": store::now() });
+        Ok(())
+    })
+}
+
+/// Drops the search called `name`; true if there was one.
+fn delete(name: &str) -> Result<bool, VpxError> {
+    store::update(|store_doc| Ok(store_doc["searches"].as_object_mut().is_some_and(|s| s.remove(name).is_some())))
+}
+
+fn search_name(name: &str) -> Result<&str, VpxError> {
+    match name.trim() {
+        "" => Err(VpxError::new(VpxErrorCode::InvalidArgument, "Search name is empty.")),
+        name => Ok(name),
+    }
+}
+
+/// Saves the smart-filter `expression` (as `vpx_filter_index` takes it)
+/// under `name`, replacing a saved search with the same name. Returns a
+/// `VpxErrorCode`; an expression that does not parse is `InvalidArgument`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_save_search(name: *const c_char, expression: *const c_char) -> i32 {
+    let result = unsafe { crate::str_from_c(name, "name") }.and_then(search_name).and_then(|name| {
+        let expression = unsafe { crate::str_from_c(expression, "expression") }?;
+        crate::oplog::record("search", format!("Save search {}", name), || save(name, expression))
+    });
+
+    match result {
+        Ok(_) => VpxErrorCode::Ok as i32,
+        Err(e) => {
+            log_error!("vpx_save_search: {}", e);
+            e.code as i32
+        }
+    }
+}
+
+/// Deletes the saved search called `name`. Returns a `VpxErrorCode`;
+/// deleting one that does not exist is not an error.
+///
+/// # Safety
+/// `name` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_delete_search(name: *const c_char) -> i32 {
+    let result = unsafe { crate::str_from_c(name, "name") }
+        .and_then(search_name)
+        .and_then(|name| crate::oplog::record("search", format!("Delete search {}", name), || delete(name)));
+
+    match result {
+        Ok(_) => VpxErrorCode::Ok as i32,
+        Err(e) => {
+            log_error!("vpx_delete_search: {}", e);
+            e.code as i32
+        }
+    }
+}
+
+/// The saved searches, `{"searches": [{"name", "expression", "updated"}]}`
+/// sorted by name, `updated` the Unix time they were last saved.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+#[no_mangle]
+pub extern "C" fn vpx_list_searches() -> *mut c_char {
+    let result = store::load()
+        .and_then(|store_doc| crate::into_c_string(json!({ "searches": searches_in(&store_doc) }).to_string()));
+
+    crate::envelope::finish("vpx_list_searches", result)
+}
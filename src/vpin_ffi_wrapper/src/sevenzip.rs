@@ -0,0 +1,146 @@
+//! 7z and RAR packs, through the 7-Zip command line tool.
+//!
+//! There is no 7z or RAR decoder among the dependencies, so these packs are
+//! read the way the editor makes them: by running `7z` (or `7zz`, the name
+//! upstream 7-Zip uses on Linux and macOS) found on `PATH`, or in its usual
+//! folder on Windows. Members are listed from `7z l -slt`, read one at a
+//! time through `7z x -so`, or unpacked together into a folder. Names
+//! are never taken as wildcards (`-spd`), and stdin is closed so an
+//! encrypted pack fails instead of waiting for a password. Without the
+//! tool every call fails with `FileNotFound` saying what to install.
+
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+use crate::error::{VpxError, VpxErrorCode};
+
+const TOOL_NAMES: &[&str] = &["7z", "7zz"];
+
+/// The line `7z l -slt` puts between the archive's properties and its members.
+const LISTING_START: &str = "----------";
+
+/// One file in a pack.
+pub(crate) struct Member {
+    /// The name as the archive has it, to pass back to `7z`.
+    pub name: String,
+    /// `name` as a relative path, None when it is absolute or climbs out
+    /// with `..` (like `ZipFile::enclosed_name`).
+    pub relative: Option<PathBuf>,
+    /// Unpacked size in bytes.
+    pub size: u64,
+}
+
+/// Whether `path` is a `.7z` or `.rar` pack (parts of a split one aside).
+pub(crate) fn is_pack(path: &Path) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|e| e.eq_ignore_ascii_case("7z") || e.eq_ignore_ascii_case("rar"))
+}
+
+fn tool() -> Option<PathBuf> {
+    let mut dirs: Vec<PathBuf> = env::var_os("PATH").map(|path| env::split_paths(&path).collect()).unwrap_or_default();
+    if cfg!(windows) {
+        dirs.extend(
+            ["ProgramFiles", "ProgramFiles(x86)"]
+                .iter()
+                .filter_map(env::var_os)
+                .map(|dir| PathBuf::from(dir).join("7-Zip")),
+        );
+    }
+    dirs.iter()
+        .flat_map(|dir| TOOL_NAMES.iter().map(move |name| dir.join(format!("{}{}", name, env::consts::EXE_SUFFIX))))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Whether 7-Zip was found, so 7z and RAR packs can be read.
+pub(crate) fn available() -> bool {
+    tool().is_some()
+}
+
+/// Runs `7z <args> -- <archive> <names>`, failing with the tool's last
+/// error line.
+fn run(archive: &Path, args: &[&OsStr], names: &[&str]) -> Result<Output, VpxError> {
+    let tool = tool().ok_or_else(|| {
+        VpxError::new(
+            VpxErrorCode::FileNotFound,
+            format!("Reading '{}' needs 7-Zip (7z), which isn't installed", archive.display()),
+        )
+    })?;
+    let output = Command::new(&tool)
+        .args(args)
+        .arg("-sccUTF-8")
+        .arg("--")
+        .arg(archive)
+        .args(names)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| VpxError::new(VpxErrorCode::OpenFailed, format!("Failed to run '{}': {}", tool.display(), e)))?;
+    if !output.status.success() {
+        // p7zip writes some errors to stdout.
+        let messages = [&output.stderr, &output.stdout].map(|out| String::from_utf8_lossy(out).into_owned());
+        let reason = messages
+            .iter()
+            .find_map(|out| out.lines().map(str::trim).rfind(|line| !line.is_empty()))
+            .unwrap_or("7z failed");
+        return Err(VpxError::new(
+            VpxErrorCode::ParseFailed,
+            format!("Failed to read archive '{}': {}", archive.display(), reason),
+        ));
+    }
+    Ok(output)
+}
+
+/// `name` as a relative path, or None if it is absolute or climbs out.
+fn enclosed(name: &str) -> Option<PathBuf> {
+    let relative: PathBuf = name.split(['/', '\\']).filter(|part| !part.is_empty() && *part != ".").collect();
+    let safe = relative.components().all(|c| matches!(c, Component::Normal(_)));
+    (safe && !relative.as_os_str().is_empty() && !name.starts_with(['/', '\\'])).then_some(relative)
+}
+
+/// The files in `archive`, folders left out.
+pub(crate) fn list(archive: &Path) -> Result<Vec<Member>, VpxError> {
+    let output = run(archive, &["l".as_ref(), "-slt".as_ref()], &[])?;
+    let listing = String::from_utf8_lossy(&output.stdout);
+    let Some((_, members)) = listing.split_once(LISTING_START) else {
+        return Err(VpxError::new(
+            VpxErrorCode::ParseFailed,
+            format!("Failed to read archive '{}': no member listing", archive.display()),
+        ));
+    };
+    let mut files = Vec::new();
+    for block in members.replace("\r\n", "\n").split("\n\n") {
+        let field =
+            |key: &str| block.lines().find_map(|line| line.strip_prefix(key).and_then(|rest| rest.strip_prefix(" = ")));
+        let Some(name) = field("Path") else { continue };
+        let folder = field("Folder") == Some("+") || field("Attributes").is_some_and(|a| a.starts_with('D'));
+        if folder {
+            continue;
+        }
+        files.push(Member {
+            name: name.to_string(),
+            relative: enclosed(name),
+            size: field("Size").and_then(|s| s.parse().ok()).unwrap_or(0),
+        });
+    }
+    Ok(files)
+}
+
+/// One member's contents.
+pub(crate) fn read(archive: &Path, member: &Member) -> Result<Vec<u8>, VpxError> {
+    let output = run(archive, &["x".as_ref(), "-so".as_ref(), "-spd".as_ref()], &[&member.name])?;
+    Ok(output.stdout)
+}
+
+/// Checks every member's CRC.
+pub(crate) fn test(archive: &Path) -> Result<(), VpxError> {
+    run(archive, &["t".as_ref(), "-bd".as_ref()], &[]).map(|_| ())
+}
+
+/// Unpacks every member into `dir`, keeping the archive's folders.
+pub(crate) fn extract_all(archive: &Path, dir: &Path) -> Result<(), VpxError> {
+    let mut out = OsString::from("-o");
+    out.push(dir);
+    run(archive, &["x".as_ref(), "-y".as_ref(), "-bd".as_ref(), out.as_os_str()], &[]).map(|_| ())
+}
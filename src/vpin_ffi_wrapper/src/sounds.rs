@@ -0,0 +1,408 @@
+//! Loudness of the sound samples embedded in a table.
+//!
+//! Authors record their mechanical sounds at very different levels, which
+//! is barely noticeable on desktop speakers and jarring on a cabinet with
+//! exciters (SSF). Each PCM WAV sample is measured for its peak and its
+//! loudness (ITU-R BS.1770: K-weighted, gated, in LUFS), and can be scaled
+//! towards a target in a copy of the table. Compressed samples (OGG, MP3)
+//! are listed but not measured. Gain is always capped so a sample never
+//! clips.
+
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use vpin::vpx::sound::{SoundData, WaveForm};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::options::CallOptions;
+use crate::textures::{check_output, file_size, save_copy};
+use crate::{library, oplog};
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+
+/// Gain is capped so the scaled peak stays at or under full scale.
+const CEILING_DBFS: f64 = 0.0;
+/// Gains smaller than this are not worth rewriting a sample for.
+const MIN_GAIN_DB: f64 = 0.1;
+/// BS.1770 gating: 400 ms blocks overlapping by 75%, an absolute gate and
+/// one relative to the loudness of the blocks above it.
+const BLOCK_SECONDS: f64 = 0.4;
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const RELATIVE_GATE_LU: f64 = -10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Mode {
+    Peak,
+    Lufs,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct SoundOptions {
+    /// What samples are compared and normalized by.
+    mode: Mode,
+    /// dBFS for `peak`, LUFS for `lufs`; defaults to -1 and -18.
+    target: Option<f64>,
+    /// Most a quiet sample is boosted by, so noise isn't blown up.
+    max_gain_db: f64,
+    /// Distance from the table's median level that flags a sample.
+    outlier_db: f64,
+    /// Where to write a copy with the samples normalized.
+    output_path: Option<PathBuf>,
+    /// Replace an existing file at `output_path`.
+    overwrite: bool,
+}
+
+impl Default for SoundOptions {
+    fn default() -> Self {
+        SoundOptions {
+            mode: Mode::Lufs,
+            target: None,
+            max_gain_db: 12.0,
+            outlier_db: 6.0,
+            output_path: None,
+            overwrite: false,
+        }
+    }
+}
+
+impl SoundOptions {
+    fn target(&self) -> f64 {
+        self.target.unwrap_or(match self.mode {
+            Mode::Peak => -1.0,
+            Mode::Lufs => -18.0,
+        })
+    }
+}
+
+const KNOWN_KEYS: &[&str] = &["mode", "target", "max_gain_db", "outlier_db", "output_path", "overwrite"];
+
+/// Two decimals; more is noise for levels in dB.
+fn db(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+fn to_db(amplitude: f64) -> f64 {
+    20.0 * amplitude.log10()
+}
+
+/// The sample's format from its path; vpin treats no extension as WAV too.
+fn format_name(sound: &SoundData) -> String {
+    let file = sound.path.rsplit(['/', '\\']).next().unwrap_or_default();
+    match file.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() && !ext.is_empty() => ext.to_ascii_lowercase(),
+        _ => "wav".to_string(),
+    }
+}
+
+/// Interleaved samples in -1.0..1.0.
+fn decode(wave_form: &WaveForm, data: &[u8]) -> Result<Vec<f64>, String> {
+    let samples = match (wave_form.format_tag, wave_form.bits_per_sample) {
+        (WAVE_FORMAT_PCM, 8) => data.iter().map(|b| (*b as f64 - 128.0) / 128.0).collect(),
+        (WAVE_FORMAT_PCM, 16) => {
+            data.chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]]) as f64 / 32768.0).collect()
+        }
+        (WAVE_FORMAT_PCM, 24) => data
+            .chunks_exact(3)
+            .map(|b| (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f64 / 8_388_608.0)
+            .collect(),
+        (WAVE_FORMAT_PCM, 32) => data
+            .chunks_exact(4)
+            .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64 / 2_147_483_648.0)
+            .collect(),
+        (WAVE_FORMAT_IEEE_FLOAT, 32) => {
+            data.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64).collect()
+        }
+        (tag, bits) => return Err(format!("unsupported wave format {} with {} bits", tag, bits)),
+    };
+    Ok(samples)
+}
+
+/// The reverse of `decode`, clamping to full scale.
+fn encode(wave_form: &WaveForm, samples: &[f64]) -> Vec<u8> {
+    let scaled = |s: f64, full: f64| (s.clamp(-1.0, 1.0) * full).round().clamp(-full, full - 1.0);
+    let mut data = Vec::with_capacity(samples.len() * wave_form.bits_per_sample as usize / 8);
+    for &s in samples {
+        match (wave_form.format_tag, wave_form.bits_per_sample) {
+            (WAVE_FORMAT_PCM, 8) => data.push((scaled(s, 128.0) + 128.0) as u8),
+            (WAVE_FORMAT_PCM, 16) => data.extend_from_slice(&(scaled(s, 32768.0) as i16).to_le_bytes()),
+            (WAVE_FORMAT_PCM, 24) => data.extend_from_slice(&(scaled(s, 8_388_608.0) as i32).to_le_bytes()[..3]),
+            (WAVE_FORMAT_PCM, _) => data.extend_from_slice(&(scaled(s, 2_147_483_648.0) as i32).to_le_bytes()),
+            _ => data.extend_from_slice(&(s.clamp(-1.0, 1.0) as f32).to_le_bytes()),
+        }
+    }
+    data
+}
+
+/// A second-order IIR filter, coefficients normalized by `a0`.
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+}
+
+impl Biquad {
+    fn apply(&self, x: &mut [f64]) {
+        let (mut x1, mut x2, mut y1, mut y2) = (0.0, 0.0, 0.0, 0.0);
+        for v in x.iter_mut() {
+            let y = self.b[0] * *v + self.b[1] * x1 + self.b[2] * x2 - self.a[0] * y1 - self.a[1] * y2;
+            (x2, x1, y2, y1) = (x1, *v, y1, y);
+            *v = y;
+        }
+    }
+}
+
+/// The BS.1770 K-weighting filters (head shelf, then high-pass) designed
+/// for `rate`, so samples needn't be 48 kHz.
+fn k_weighting(rate: f64) -> [Biquad; 2] {
+    let shelf = {
+        let (gain, q, fc) = (4.0_f64, std::f64::consts::FRAC_1_SQRT_2, 1500.0);
+        let a = 10_f64.powf(gain / 40.0);
+        let w0 = 2.0 * std::f64::consts::PI * fc / rate;
+        let (cos, alpha) = (w0.cos(), w0.sin() / (2.0 * q));
+        let a0 = (a + 1.0) - (a - 1.0) * cos + 2.0 * a.sqrt() * alpha;
+        Biquad {
+            b: [
+                a * ((a + 1.0) + (a - 1.0) * cos + 2.0 * a.sqrt() * alpha) / a0,
+                -2.0 * a * ((a - 1.0) + (a + 1.0) * cos) / a0,
+                a * ((a + 1.0) + (a - 1.0) * cos - 2.0 * a.sqrt() * alpha) / a0,
+            ],
+            a: [2.0 * ((a - 1.0) - (a + 1.0) * cos) / a0, ((a + 1.0) - (a - 1.0) * cos - 2.0 * a.sqrt() * alpha) / a0],
+        }
+    };
+    let high_pass = {
+        let (q, fc) = (0.5, 38.0);
+        let w0 = 2.0 * std::f64::consts::PI * fc / rate;
+        let (cos, alpha) = (w0.cos(), w0.sin() / (2.0 * q));
+        let a0 = 1.0 + alpha;
+        Biquad {
+            b: [(1.0 + cos) / 2.0 / a0, -(1.0 + cos) / a0, (1.0 + cos) / 2.0 / a0],
+            a: [-2.0 * cos / a0, (1.0 - alpha) / a0],
+        }
+    };
+    [shelf, high_pass]
+}
+
+fn block_loudness(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+/// Integrated loudness in LUFS, or None for silence. Samples shorter than
+/// a block are measured as one block.
+fn loudness(samples: &[f64], channels: usize, rate: u32) -> Option<f64> {
+    let frames = samples.len() / channels;
+    if frames == 0 || rate == 0 {
+        return None;
+    }
+    // Running sums of the K-weighted signal's energy, per channel.
+    let filters = k_weighting(rate as f64);
+    let energy: Vec<Vec<f64>> = (0..channels)
+        .map(|c| {
+            let mut channel: Vec<f64> = samples.iter().skip(c).step_by(channels).copied().collect();
+            filters.iter().for_each(|f| f.apply(&mut channel));
+            let mut sum = 0.0;
+            std::iter::once(0.0)
+                .chain(channel.iter().map(|v| {
+                    sum += v * v;
+                    sum
+                }))
+                .collect()
+        })
+        .collect();
+
+    let block = ((BLOCK_SECONDS * rate as f64) as usize).clamp(1, frames);
+    let step = (block / 4).max(1);
+    let powers: Vec<f64> = (0..=frames - block)
+        .step_by(step)
+        .map(|start| energy.iter().map(|e| (e[start + block] - e[start]) / block as f64).sum())
+        .collect();
+
+    let mean = |powers: &[f64]| powers.iter().sum::<f64>() / powers.len() as f64;
+    let above: Vec<f64> = powers.into_iter().filter(|p| block_loudness(*p) > ABSOLUTE_GATE_LUFS).collect();
+    if above.is_empty() {
+        return None;
+    }
+    let gate = block_loudness(mean(&above)) + RELATIVE_GATE_LU;
+    let gated: Vec<f64> = above.into_iter().filter(|p| block_loudness(*p) > gate).collect();
+    Some(block_loudness(mean(&gated)))
+}
+
+struct Measured {
+    samples: Vec<f64>,
+    peak_dbfs: Option<f64>,
+    loudness_lufs: Option<f64>,
+}
+
+fn measure(sound: &SoundData) -> Result<Measured, String> {
+    if format_name(sound) != "wav" {
+        return Err("compressed samples are not measured".to_string());
+    }
+    let channels = sound.wave_form.channels.max(1) as usize;
+    let samples = decode(&sound.wave_form, &sound.data)?;
+    let peak = samples.iter().fold(0.0_f64, |peak, s| peak.max(s.abs()));
+    Ok(Measured {
+        peak_dbfs: Some(peak).filter(|p| *p > 0.0).map(to_db),
+        loudness_lufs: loudness(&samples, channels, sound.wave_form.samples_per_sec),
+        samples,
+    })
+}
+
+impl Measured {
+    fn level(&self, mode: Mode) -> Option<f64> {
+        match mode {
+            Mode::Peak => self.peak_dbfs,
+            Mode::Lufs => self.loudness_lufs,
+        }
+    }
+
+    /// What bringing the sample to `target` takes, within the caps, or None
+    /// for silence and samples already there.
+    fn gain_db(&self, options: &SoundOptions) -> Option<f64> {
+        let (level, peak) = (self.level(options.mode)?, self.peak_dbfs?);
+        let gain = (options.target() - level).min(options.max_gain_db).min(CEILING_DBFS - peak);
+        Some(gain).filter(|g| g.abs() >= MIN_GAIN_DB)
+    }
+}
+
+fn median(values: &mut [f64]) -> Option<f64> {
+    values.sort_by(f64::total_cmp);
+    match values.len() {
+        0 => None,
+        n if n % 2 == 1 => Some(values[n / 2]),
+        n => Some((values[n / 2 - 1] + values[n / 2]) / 2.0),
+    }
+}
+
+/// The report for `sounds`, and the measured samples by index for those
+/// that could be decoded.
+fn analyze(path: &Path, sounds: &[SoundData], options: &SoundOptions) -> (Value, Vec<Option<Measured>>) {
+    let measured: Vec<Result<Measured, String>> = sounds.iter().map(measure).collect();
+    let mut levels: Vec<f64> = measured.iter().flatten().filter_map(|m| m.level(options.mode)).collect();
+    let median_level = median(&mut levels);
+
+    let mut loud = Vec::new();
+    let mut quiet = Vec::new();
+    let rows: Vec<Value> = sounds
+        .iter()
+        .zip(&measured)
+        .map(|(sound, measured)| {
+            let wave = &sound.wave_form;
+            let mut row = json!({
+                "name": sound.name,
+                "format": format_name(sound),
+                "channels": wave.channels,
+                "sample_rate": wave.samples_per_sec,
+                "bits": wave.bits_per_sample,
+            });
+            match measured {
+                Ok(m) => {
+                    let frames = m.samples.len() / wave.channels.max(1) as usize;
+                    let deviation = m.level(options.mode).zip(median_level).map(|(level, mid)| level - mid);
+                    match deviation {
+                        Some(d) if d > options.outlier_db => loud.push(sound.name.clone()),
+                        Some(d) if d < -options.outlier_db => quiet.push(sound.name.clone()),
+                        _ => {}
+                    }
+                    row["duration"] = json!(crate::physics::round(frames as f64 / wave.samples_per_sec.max(1) as f64));
+                    row["peak_dbfs"] = json!(m.peak_dbfs.map(db));
+                    row["loudness_lufs"] = json!(m.loudness_lufs.map(db));
+                    row["deviation_db"] = json!(deviation.map(db));
+                    row["gain_db"] = json!(m.gain_db(options).map(db));
+                }
+                Err(reason) => row["skipped"] = json!(reason),
+            }
+            row
+        })
+        .collect();
+
+    let report = json!({
+        "vpx_file": path,
+        "mode": options.mode,
+        "target": options.target(),
+        "sounds": rows.len(),
+        "measured": measured.iter().filter(|m| m.is_ok()).count(),
+        "median_level": median_level.map(db),
+        "loud": loud,
+        "quiet": quiet,
+        "samples": rows,
+        "output": Value::Null,
+    });
+    (report, measured.into_iter().map(Result::ok).collect())
+}
+
+/// Scales every sample that needs it and writes the table to `output`.
+fn write_normalized(path: &Path, output: &Path, options: &SoundOptions) -> Result<Value, VpxError> {
+    let mut vpx = vpin::vpx::read(path).map_err(|e| {
+        VpxError::new(VpxErrorCode::ParseFailed, format!("Failed to read table for '{}': {}", path.display(), e))
+    })?;
+    let (mut report, measured) = analyze(path, &vpx.sounds, options);
+    let mut adjusted = Vec::new();
+    for (sound, measured) in vpx.sounds.iter_mut().zip(measured) {
+        let Some(measured) = measured else { continue };
+        let Some(gain) = measured.gain_db(options) else { continue };
+        let factor = 10_f64.powf(gain / 20.0);
+        let scaled: Vec<f64> = measured.samples.iter().map(|s| s * factor).collect();
+        sound.data = encode(&sound.wave_form, &scaled);
+        adjusted.push(json!({ "name": sound.name, "gain_db": db(gain) }));
+    }
+    save_copy(&vpx, output)?;
+
+    report["output"] = json!({
+        "path": output,
+        "file_size_before": file_size(path),
+        "file_size_after": file_size(output),
+        "adjusted": adjusted,
+    });
+    Ok(report)
+}
+
+fn sounds_report(path: &Path, options: &SoundOptions) -> Result<Value, VpxError> {
+    let Some(output) = &options.output_path else {
+        return crate::catch_panic(path, || {
+            let mut vpx_file = crate::open_vpx(path, &CallOptions::default())?;
+            let sounds = vpx_file.read_sounds().map_err(|e| {
+                VpxError::new(
+                    VpxErrorCode::ParseFailed,
+                    format!("Failed to read sounds for '{}': {}", path.display(), e),
+                )
+            })?;
+            Ok(analyze(path, &sounds, options).0)
+        });
+    };
+    check_output(path, output, options.overwrite)?;
+    let summary = format!("Normalize sounds of {} into {}", path.display(), output.display());
+    oplog::record("sounds", summary, || crate::catch_panic(path, || write_normalized(path, output, options)))
+}
+
+/// Measures the sound samples of the table at `vpx_file_path` and, with an
+/// `output_path`, writes a copy with them normalized. `options_json`
+/// (nullable): `mode` (`"lufs"`, the default, or `"peak"`), `target` (-18
+/// LUFS or -1 dBFS by default), `max_gain_db` (boost limit, default 12),
+/// `outlier_db` (distance from the median that lists a sample as loud or
+/// quiet, default 6), `output_path` and `overwrite`.
+///
+/// Returns `{"vpx_file", "mode", "target", "sounds", "measured",
+/// "median_level", "loud", "quiet", "samples": [{"name", "format",
+/// "channels", "sample_rate", "bits", "duration", "peak_dbfs",
+/// "loudness_lufs", "deviation_db", "gain_db"} or {..., "skipped"}],
+/// "output": null | {"path", "file_size_before", "file_size_after",
+/// "adjusted": [{"name", "gain_db"}]}}`, or null on failure. `gain_db` is
+/// what normalizing would apply (null when nothing would change). The
+/// table itself is never changed. Release with `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_analyze_sounds(vpx_file_path: *const c_char, options_json: *const c_char) -> *mut c_char {
+    let result = unsafe { crate::path_from_c(vpx_file_path) }
+        .and_then(|path| {
+            let options: SoundOptions = unsafe { library::options_from_c(options_json, KNOWN_KEYS) }?;
+            sounds_report(&path, &options)
+        })
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_analyze_sounds", result)
+}
@@ -0,0 +1,47 @@
+//! Where a VPX file is read from.
+//!
+//! vpin works on anything `Read + Seek + Write`; `VpxSource` lets the rest of
+//! the wrapper hold either a file on disk or a copy of it in memory behind
+//! one type.
+
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+
+pub(crate) enum VpxSource {
+    File(File),
+    Memory(Cursor<Vec<u8>>),
+}
+
+impl Read for VpxSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            VpxSource::File(f) => f.read(buf),
+            VpxSource::Memory(c) => c.read(buf),
+        }
+    }
+}
+
+impl Seek for VpxSource {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            VpxSource::File(f) => f.seek(pos),
+            VpxSource::Memory(c) => c.seek(pos),
+        }
+    }
+}
+
+impl Write for VpxSource {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            VpxSource::File(f) => f.write(buf),
+            VpxSource::Memory(c) => c.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            VpxSource::File(f) => f.flush(),
+            VpxSource::Memory(c) => c.flush(),
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! Play statistics per table, kept in the user store's `stats` section:
+//! `{"launch_count", "play_time_seconds", "last_played"}` (Unix seconds).
+
+use std::os::raw::c_char;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::{index, store};
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct TableStats {
+    pub launch_count: u64,
+    pub play_time_seconds: u64,
+    /// 0 = never.
+    pub last_played: u64,
+}
+
+const KNOWN_KEYS: &[&str] = &["launch_count", "play_time_seconds", "last_played"];
+
+/// The stats of every table in the store that has any.
+pub(crate) fn all(store_doc: &Value) -> Vec<(String, TableStats)> {
+    store::tables(store_doc)
+        .into_iter()
+        .flatten()
+        .filter_map(|(vpx_file, entry)| {
+            let stats = TableStats::deserialize(entry.get("stats")?).ok()?;
+            Some((vpx_file.clone(), stats))
+        })
+        .collect()
+}
+
+fn get(vpx_file: &str) -> Result<TableStats, VpxError> {
+    let store_doc = store::load()?;
+    Ok(store_doc["tables"][vpx_file]
+        .get("stats")
+        .and_then(|stats| TableStats::deserialize(stats).ok())
+        .unwrap_or_default())
+}
+
+fn write(vpx_file: &str, stats: TableStats) -> Result<(), VpxError> {
+    store::update(|store_doc| {
+        *store::section_mut(store_doc, vpx_file, "stats") = json!(stats);
+        Ok(())
+    })
+}
+
+fn record_play(vpx_file: &str, seconds: u64) -> Result<TableStats, VpxError> {
+    store::update(|store_doc| {
+        let section = store::section_mut(store_doc, vpx_file, "stats");
+        let mut stats = TableStats::deserialize(&*section).unwrap_or_default();
+        stats.launch_count += 1;
+        stats.play_time_seconds += seconds;
+        stats.last_played = store::now();
+        *section = json!(stats);
+        Ok(stats)
+    })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct SummaryOptions {
+    /// Entries per ranking.
+    limit: usize,
+}
+
+impl Default for SummaryOptions {
+    fn default() -> Self {
+        SummaryOptions { limit: 10 }
+    }
+}
+
+fn summary(index_json: Option<&str>, options: &SummaryOptions) -> Result<Value, VpxError> {
+    let store_doc = store::load()?;
+    let played: Vec<(String, TableStats)> = all(&store_doc).into_iter().filter(|(_, s)| s.launch_count > 0).collect();
+    let ranking = |key: fn(&TableStats) -> u64| -> Vec<Value> {
+        let mut ranked: Vec<&(String, TableStats)> = played.iter().collect();
+        ranked.sort_by_key(|(_, stats)| std::cmp::Reverse(key(stats)));
+        ranked
+            .into_iter()
+            .take(options.limit)
+            .map(|(vpx_file, stats)| {
+                let mut entry = json!(stats);
+                entry["vpx_file"] = json!(vpx_file);
+                entry
+            })
+            .collect()
+    };
+    let mut result = json!({
+        "tables_played": played.len(),
+        "total_launches": played.iter().map(|(_, s)| s.launch_count).sum::<u64>(),
+        "total_play_time_seconds": played.iter().map(|(_, s)| s.play_time_seconds).sum::<u64>(),
+        "most_played": ranking(|s| s.launch_count),
+        "most_time": ranking(|s| s.play_time_seconds),
+        "recently_played": ranking(|s| s.last_played),
+    });
+    // Knowing what was never played needs the whole library.
+    if let Some(index_json) = index_json {
+        let index_doc = index::parse(index_json)?;
+        let never: Vec<&Value> = index::tables(&index_doc)
+            .iter()
+            .map(|row| &row["vpxFile"])
+            .filter(|vpx_file| {
+                let vpx_file = vpx_file.as_str().unwrap_or_default();
+                !played.iter().any(|(played, _)| played == vpx_file)
+            })
+            .collect();
+        result["never_played"] = json!(never);
+    }
+    Ok(result)
+}
+
+/// Records one finished session of a table: one more launch, `seconds` more
+/// play time, last played now. Returns a `VpxErrorCode`.
+///
+/// # Safety
+/// `vpx_file_path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_record_play(vpx_file_path: *const c_char, seconds: u64) -> i32 {
+    let result = unsafe { crate::str_from_c(vpx_file_path, "path") }.and_then(|path| record_play(path, seconds));
+
+    match result {
+        Ok(_) => VpxErrorCode::Ok as i32,
+        Err(e) => {
+            log_error!("vpx_record_play: {}", e);
+            e.code as i32
+        }
+    }
+}
+
+/// A table's stats, `{"launch_count", "play_time_seconds", "last_played"}`,
+/// all 0 for a table never played.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `vpx_file_path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_table_stats(vpx_file_path: *const c_char) -> *mut c_char {
+    let result = unsafe { crate::str_from_c(vpx_file_path, "path") }
+        .and_then(get)
+        .and_then(|stats| crate::into_c_string(json!(stats).to_string()));
+
+    crate::envelope::finish("vpx_get_table_stats", result)
+}
+
+/// Replaces a table's stats (e.g. when migrating from the frontend's index
+/// counters). Missing keys are 0. Returns a `VpxErrorCode`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_set_table_stats(vpx_file_path: *const c_char, stats_json: *const c_char) -> i32 {
+    let result = unsafe { crate::str_from_c(vpx_file_path, "path") }
+        .and_then(|path| {
+            if stats_json.is_null() {
+                return Err(VpxError::new(VpxErrorCode::NullArgument, "stats is null"));
+            }
+            let stats: TableStats = unsafe { crate::library::options_from_c(stats_json, KNOWN_KEYS) }?;
+            crate::oplog::record("stats", format!("Set stats of {}", path), || write(path, stats))
+        });
+
+    match result {
+        Ok(()) => VpxErrorCode::Ok as i32,
+        Err(e) => {
+            log_error!("vpx_set_table_stats: {}", e);
+            e.code as i32
+        }
+    }
+}
+
+/// Aggregated stats: totals plus `most_played`, `most_time` and
+/// `recently_played` rankings (each entry is the stats plus `vpx_file`).
+/// With `index_json` (the frontend index, nullable), `never_played` lists
+/// the library's tables without a launch. `options_json` (nullable):
+/// `limit` per ranking, default 10.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_stats_summary(index_json: *const c_char, options_json: *const c_char) -> *mut c_char {
+    let index_text = match index_json.is_null() {
+        true => Ok(None),
+        false => unsafe { crate::str_from_c(index_json, "index") }.map(Some),
+    };
+    let result = index_text
+        .and_then(|index_text| {
+            let options: SummaryOptions = unsafe { crate::library::options_from_c(options_json, &["limit"]) }?;
+            summary(index_text, &options)
+        })
+        .and_then(|summary| crate::into_c_string(summary.to_string()));
+
+    crate::envelope::finish("vpx_get_stats_summary", result)
+}
@@ -0,0 +1,170 @@
+//! The wrapper's user store: per-table data that belongs to the user rather
+//! than to the table file, kept in one JSON file (`user_store_path`).
+//!
+//! ```json
+//! { "version": 1, "tables": { "/tables/AFM/afm.vpx": { "stats": { ... } } } }
+//! ```
+//!
+//! Each section of a table entry is owned by one module (`stats`, `flags`,
+//! `review`, `tags` by `tagging`, ...), as are the top-level `searches`
+//! and `picks`.
+//! Every change reloads the file, edits it and writes it back atomically
+//! under a lock, so threads of one process never lose each other's edits.
+//! The lock is per process only: when the frontend, CLI or daemon write at
+//! the same moment, the last writer's whole file wins and the other's
+//! changes since it loaded are lost, whichever sections they touched.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Map, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::{config, flags, index, paths, review, tagging};
+
+/// Newest store format this build understands.
+pub(crate) const STORE_VERSION: u64 = 1;
+
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Whether a user store is configured at all.
+pub(crate) fn configured() -> bool {
+    !config::get().user_store_path.is_empty()
+}
+
+fn store_path() -> Result<PathBuf, VpxError> {
+    let path = config::get().user_store_path;
+    if path.is_empty() {
+        return Err(VpxError::new(
+            VpxErrorCode::InvalidArgument,
+            "No user store configured (set \"user_store_path\" with vpx_wrapper_init).",
+        ));
+    }
+    Ok(PathBuf::from(path))
+}
+
+fn load_from(path: &std::path::Path) -> Result<Value, VpxError> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(json!({ "version": STORE_VERSION, "tables": {} })),
+        Err(e) => {
+            return Err(VpxError::new(
+                VpxErrorCode::OpenFailed,
+                format!("Failed to read user store '{}': {}", path.display(), e),
+            ))
+        }
+    };
+    let mut store: Value = serde_json::from_str(&text).map_err(|e| {
+        VpxError::new(VpxErrorCode::ParseFailed, format!("Invalid user store '{}': {}", path.display(), e))
+    })?;
+    if !store.is_object() {
+        return Err(VpxError::new(
+            VpxErrorCode::ParseFailed,
+            format!("Invalid user store '{}': not a JSON object", path.display()),
+        ));
+    }
+    if store["version"].as_u64().unwrap_or(0) > STORE_VERSION {
+        return Err(VpxError::new(
+            VpxErrorCode::ParseFailed,
+            format!("User store '{}' is from a newer version", path.display()),
+        ));
+    }
+    if !store["tables"].is_object() {
+        store["tables"] = json!({});
+    }
+    Ok(store)
+}
+
+/// The whole store, read fresh from disk.
+pub(crate) fn load() -> Result<Value, VpxError> {
+    let path = store_path()?;
+    let _guard = STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    load_from(&path)
+}
+
+/// Applies `edit` to the store and saves it. Nothing is written if `edit` fails.
+pub(crate) fn update<R>(edit: impl FnOnce(&mut Value) -> Result<R, VpxError>) -> Result<R, VpxError> {
+    let path = store_path()?;
+    let _guard = STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut store = load_from(&path)?;
+    let result = edit(&mut store)?;
+    store["version"] = json!(STORE_VERSION);
+    let text = serde_json::to_vec_pretty(&store)
+        .map_err(|e| VpxError::new(VpxErrorCode::AllocFailed, format!("Failed to serialize user store: {}", e)))?;
+    paths::write_atomic(&path, &text).map_err(|e| {
+        VpxError::new(VpxErrorCode::OpenFailed, format!("Failed to write user store '{}': {}", path.display(), e))
+    })?;
+    Ok(result)
+}
+
+/// All table entries, keyed by `.vpx` path.
+pub(crate) fn tables(store: &Value) -> Option<&Map<String, Value>> {
+    store["tables"].as_object()
+}
+
+/// A table's entry, created empty if missing. One that isn't an object (a
+/// hand edit gone wrong) is replaced, as indexing into it would panic.
+pub(crate) fn entry_mut<'a>(store: &'a mut Value, vpx_file: &str) -> &'a mut Value {
+    let entry = &mut store["tables"][vpx_file];
+    if !entry.is_object() {
+        *entry = json!({});
+    }
+    entry
+}
+
+/// One section of a table's entry, created empty if missing.
+pub(crate) fn section_mut<'a>(store: &'a mut Value, vpx_file: &str, section: &str) -> &'a mut Value {
+    let entry = entry_mut(store, vpx_file);
+    if !entry[section].is_object() {
+        entry[section] = json!({});
+    }
+    &mut entry[section]
+}
+
+/// Adds the user's data to the rows of a frontend index, as the fields
+/// filters and playlists see (`flags::row_fields`, `review::row_fields`,
+/// `tagging::row_fields`). Without a user store the index stays as it is;
+/// an unreadable one is logged and skipped.
+pub(crate) fn annotate_index(index_doc: &mut Value) {
+    if !configured() {
+        return;
+    }
+    let store = match load() {
+        Ok(store) => store,
+        Err(e) => {
+            log_warn!("User data left out of the index: {}", e);
+            return;
+        }
+    };
+    for row in index::tables_mut(index_doc) {
+        let entry = row["vpxFile"].as_str().map_or(&Value::Null, |vpx_file| &store["tables"][vpx_file]);
+        let fields = [flags::row_fields(entry), review::row_fields(entry), tagging::row_fields(entry)];
+        for (field, value) in fields.into_iter().flatten() {
+            row[field] = value;
+        }
+    }
+}
+
+/// Moves a table's entry to its new path (after a move or rename).
+pub(crate) fn rename_table(from: &str, to: &str) -> Result<(), VpxError> {
+    update(|store| {
+        if let Some(tables) = store["tables"].as_object_mut() {
+            if let Some(entry) = tables.remove(from) {
+                tables.insert(to.to_string(), entry);
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Drops a table's entry; true if there was one.
+pub(crate) fn remove_table(vpx_file: &str) -> Result<bool, VpxError> {
+    update(|store| Ok(store["tables"].as_object_mut().is_some_and(|t| t.remove(vpx_file).is_some())))
+}
+
+/// Seconds since the Unix epoch.
+pub(crate) fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
@@ -0,0 +1,119 @@
+//! Streaming a script out in chunks.
+//!
+//! Some tables carry multi-megabyte scripts, and the frontend only wants to
+//! feed them to a viewer or a hash. Returning one C string means one more
+//! copy of the whole script, and a `strlen` over it on the other side.
+//! Streaming hands the script to a callback in chunks straight from the
+//! parsed string, each valid UTF-8 on its own, much like paging through it
+//! with `vpx_handle_get_gamedata_code_chunk`.
+
+use std::ffi::c_void;
+use std::os::raw::c_char;
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::handle::VpxHandle;
+use crate::options::CallOptions;
+
+/// Chunk size when the caller passes 0.
+const DEFAULT_CHUNK: usize = 64 * 1024;
+/// The longest UTF-8 character, so every chunk holds at least one.
+const MIN_CHUNK: usize = 4;
+
+/// Receives one chunk of a streamed script: `len` bytes of UTF-8 at `data`,
+/// valid during the call only. Returning false stops the stream.
+pub type VpxChunkCallback = Option<extern "C" fn(data: *const u8, len: usize, userdata: *mut c_void) -> bool>;
+
+/// Where a chunk of at most `max_len` bytes from `start` ends, backing off
+/// so it doesn't split a character.
+pub(crate) fn chunk_end(code: &str, start: usize, max_len: usize) -> usize {
+    let mut end = start.saturating_add(max_len).min(code.len());
+    while !code.is_char_boundary(end) {
+        end -= 1;
+    }
+    end
+}
+
+/// Hands `code` to `callback` in chunks of up to `chunk_size` bytes.
+fn stream(code: &str, chunk_size: usize, callback: VpxChunkCallback, userdata: *mut c_void) -> Result<(), VpxError> {
+    let Some(callback) = callback else {
+        return Err(VpxError::new(VpxErrorCode::NullArgument, "Chunk callback is null."));
+    };
+    let chunk_size = match chunk_size {
+        0 => DEFAULT_CHUNK,
+        size => size.max(MIN_CHUNK),
+    };
+    let mut start = 0;
+    while start < code.len() {
+        let end = chunk_end(code, start, chunk_size);
+        let chunk = &code.as_bytes()[start..end];
+        if !callback(chunk.as_ptr(), chunk.len(), userdata) {
+            return Err(VpxError::new(VpxErrorCode::Cancelled, "Script stream stopped by the callback."));
+        }
+        start = end;
+    }
+    Ok(())
+}
+
+fn code_result(func: &str, result: Result<(), VpxError>) -> i32 {
+    match result {
+        Ok(()) => VpxErrorCode::Ok as i32,
+        Err(e) => {
+            log_error!("{}: {}", func, e);
+            e.code as i32
+        }
+    }
+}
+
+/// Reads the script of the table at `vpx_file_path` and hands it to
+/// `chunk_cb` in chunks of up to `chunk_size` bytes (0 for 64 KiB), in
+/// order and on the calling thread, with `userdata` passed back untouched.
+/// Chunks never split a character. A callback returning false stops the
+/// stream with `Cancelled`; an empty script calls it not at all. Per-call
+/// options as for the `_ex` calls.
+///
+/// Returns a `VpxErrorCode`.
+///
+/// # Safety
+/// `vpx_file_path` and `options_json` must be null or valid NUL-terminated
+/// strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_stream_gamedata_code(
+    vpx_file_path: *const c_char,
+    options_json: *const c_char,
+    chunk_size: usize,
+    chunk_cb: VpxChunkCallback,
+    userdata: *mut c_void,
+) -> i32 {
+    let result = unsafe { CallOptions::from_c(options_json) }
+        .and_then(|options| Ok((unsafe { crate::path_from_c(vpx_file_path) }?, options)))
+        .and_then(|(path, options)| {
+            if chunk_cb.is_none() {
+                return Err(VpxError::new(VpxErrorCode::NullArgument, "Chunk callback is null."));
+            }
+            stream(&crate::read_gamedata_code(&path, &options)?, chunk_size, chunk_cb, userdata)
+        });
+
+    code_result("vpx_stream_gamedata_code", result)
+}
+
+/// `vpx_stream_gamedata_code` for an open table. The handle is busy until
+/// the stream ends, so the callback must not query the same handle.
+///
+/// Returns a `VpxErrorCode`.
+///
+/// # Safety
+/// `handle` must be null or from `vpx_open`; closed ones are rejected.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_handle_stream_gamedata_code(
+    handle: *const VpxHandle,
+    chunk_size: usize,
+    chunk_cb: VpxChunkCallback,
+    userdata: *mut c_void,
+) -> i32 {
+    let result = match chunk_cb {
+        Some(_) => crate::handle::with_table(handle, |table| stream(table.code_ref()?, chunk_size, chunk_cb, userdata)),
+        None => Err(VpxError::new(VpxErrorCode::NullArgument, "Chunk callback is null.")),
+    };
+
+    code_result("vpx_handle_stream_gamedata_code", result)
+}
@@ -0,0 +1,153 @@
+//! The user's own tags per table, kept in the user store's `tags` section
+//! as a list (`["horror", "to fix"]`), apart from the inferred ones in
+//! `tags`. The frontend tags a multi-selection at once, so tags are added
+//! and removed for many tables in one store write and one undo step.
+
+use std::collections::BTreeMap;
+use std::os::raw::c_char;
+
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::store;
+use crate::tags::optional_json;
+
+/// A tag as stored: trimmed, inner whitespace single.
+fn clean(tag: &str) -> String {
+    tag.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn tags_in(entry: &Value) -> Vec<String> {
+    entry["tags"].as_array().into_iter().flatten().filter_map(Value::as_str).map(str::to_string).collect()
+}
+
+/// Index fields for a table's tags: `userTags`, left out without any.
+pub(crate) fn row_fields(entry: &Value) -> Vec<(&'static str, Value)> {
+    let tags = tags_in(entry);
+    match tags.is_empty() {
+        true => Vec::new(),
+        false => vec![("userTags", json!(tags))],
+    }
+}
+
+/// A JSON array of strings; null is empty.
+fn string_list(value: &Value, what: &str) -> Result<Vec<String>, VpxError> {
+    let invalid = || VpxError::new(VpxErrorCode::InvalidArgument, format!("{} must be an array of strings.", what));
+    match value {
+        Value::Null => Ok(Vec::new()),
+        Value::Array(items) => items.iter().map(|item| item.as_str().map(str::to_string).ok_or_else(invalid)).collect(),
+        _ => Err(invalid()),
+    }
+}
+
+/// Removes `remove` from and adds `add` to every table's tags, ignoring
+/// case; a tag in both ends up added. Tags are kept sorted.
+fn apply(vpx_files: &[String], add: &[String], remove: &[String]) -> Result<Value, VpxError> {
+    let add: Vec<String> = add.iter().map(|t| clean(t)).filter(|t| !t.is_empty()).collect();
+    let remove: Vec<String> = remove.iter().map(|t| clean(t).to_lowercase()).collect();
+    store::update(|store_doc| {
+        let mut changed = 0;
+        let mut results = Vec::new();
+        let mut seen = Vec::new();
+        for vpx_file in vpx_files.iter().filter(|f| !f.is_empty()) {
+            if seen.contains(&vpx_file) {
+                continue;
+            }
+            seen.push(vpx_file);
+            let before = tags_in(&store_doc["tables"][vpx_file.as_str()]);
+            let mut tags: Vec<String> =
+                before.iter().filter(|t| !remove.contains(&t.to_lowercase())).cloned().collect();
+            for tag in &add {
+                if !tags.iter().any(|t| t.to_lowercase() == tag.to_lowercase()) {
+                    tags.push(tag.clone());
+                }
+            }
+            tags.sort_by_key(|t| t.to_lowercase());
+            if tags != before {
+                changed += 1;
+                let entry = store::entry_mut(store_doc, vpx_file);
+                match tags.is_empty() {
+                    true => _ = entry.as_object_mut().and_then(|e| e.remove("tags")),
+                    false => entry["tags"] = json!(tags),
+                }
+            }
+            results.push(json!({ "vpx_file": vpx_file, "tags": tags }));
+        }
+        Ok(json!({ "tables": results.len(), "changed": changed, "results": results }))
+    })
+}
+
+/// Every tag in use with the number of tables that have it.
+fn all_tags(store_doc: &Value) -> Value {
+    let mut counts: BTreeMap<String, (String, usize)> = BTreeMap::new();
+    for entry in store::tables(store_doc).into_iter().flat_map(|tables| tables.values()) {
+        for tag in tags_in(entry) {
+            counts.entry(tag.to_lowercase()).or_insert_with(|| (tag, 0)).1 += 1;
+        }
+    }
+    let tags: Vec<Value> = counts.into_values().map(|(tag, count)| json!({ "tag": tag, "count": count })).collect();
+    json!({ "tags": tags })
+}
+
+/// Adds and removes the user's tags on several tables at once:
+/// `table_ids_json` is an array of `.vpx` paths, `add_tags_json` and
+/// `remove_tags_json` arrays of tags (either nullable). Tags compare
+/// ignoring case and are kept sorted; one in both lists is added. All
+/// tables change in one user store write, undone as one operation.
+///
+/// Returns `{"tables", "changed", "results": [{"vpx_file", "tags"}]}`, or
+/// null on failure, with nothing changed. Release with `vpx_free_string`.
+///
+/// # Safety
+/// All arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_apply_tags(
+    table_ids_json: *const c_char,
+    add_tags_json: *const c_char,
+    remove_tags_json: *const c_char,
+) -> *mut c_char {
+    let lists = || -> Result<[Vec<String>; 3], VpxError> {
+        if table_ids_json.is_null() {
+            return Err(VpxError::new(VpxErrorCode::NullArgument, "table ids is null"));
+        }
+        Ok([
+            string_list(&unsafe { optional_json(table_ids_json, "table ids") }?, "Table ids")?,
+            string_list(&unsafe { optional_json(add_tags_json, "add tags") }?, "Tags to add")?,
+            string_list(&unsafe { optional_json(remove_tags_json, "remove tags") }?, "Tags to remove")?,
+        ])
+    };
+    let result = lists()
+        .and_then(|[vpx_files, add, remove]| {
+            let summary = format!("Tag {} table(s)", vpx_files.len());
+            crate::oplog::record("tags", summary, || apply(&vpx_files, &add, &remove))
+        })
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_apply_tags", result)
+}
+
+/// A table's own tags, `{"tags": [...]}`.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `vpx_file_path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_table_tags(vpx_file_path: *const c_char) -> *mut c_char {
+    let result = unsafe { crate::str_from_c(vpx_file_path, "path") }
+        .and_then(|path| Ok(tags_in(&store::load()?["tables"][path])))
+        .and_then(|tags| crate::into_c_string(json!({ "tags": tags }).to_string()));
+
+    crate::envelope::finish("vpx_get_table_tags", result)
+}
+
+/// Every tag the user has given a table, `{"tags": [{"tag", "count"}]}`
+/// sorted by tag, for a tag picker.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+#[no_mangle]
+pub extern "C" fn vpx_list_user_tags() -> *mut c_char {
+    let result = store::load().and_then(|store_doc| crate::into_c_string(all_tags(&store_doc).to_string()));
+
+    crate::envelope::finish("vpx_list_user_tags", result)
+}
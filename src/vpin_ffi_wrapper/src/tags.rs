@@ -0,0 +1,148 @@
+//! Tag inference for the frontend's automatic categories.
+//!
+//! Tags come from the table info (`TableType`, name, blurb, description) and
+//! from `vpx_get_script_analysis`. Either input can be missing; each rule
+//! only fires on what it can see, so a tag is a hint, never a guarantee.
+
+use std::os::raw::c_char;
+
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+
+/// Standard playfields are 20.25" (952 VPU) wide, widebodies 23.25" or more.
+const WIDEBODY_MIN_WIDTH_VPU: f64 = 1050.0;
+
+const KIDS_KEYWORDS: &[&str] = &[
+    "kids", "cartoon", "disney", "pixar", "sesame", "spongebob", "scooby", "looney",
+    "muppet", "pokemon", "toy story", "snoopy", "peanuts", "barbie",
+];
+
+/// Table info text the keyword rules look at, lowercased.
+fn table_text(table_info: &Value) -> String {
+    ["table_name", "table_blurb", "table_description"]
+        .iter()
+        .filter_map(|key| table_info[*key].as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn table_type(table_info: &Value) -> Option<String> {
+    let properties = &table_info["properties"];
+    ["TableType", "tabletype", "Type"]
+        .iter()
+        .find_map(|key| properties[*key].as_str())
+        .map(|t| t.trim().to_ascii_lowercase())
+        .filter(|t| !t.is_empty())
+}
+
+/// Player count from the script, or from "4 player" / "four-player" text.
+fn player_count(analysis: &Value, text: &str) -> Option<u64> {
+    if let Some(players) = analysis["max_players"].as_u64() {
+        return Some(players);
+    }
+    let words = ["one", "two", "three", "four", "five", "six"];
+    (1..=6u64).find(|n| {
+        let word = words[*n as usize - 1];
+        [format!("{} player", n), format!("{}-player", n), format!("{} player", word), format!("{}-player", word)]
+            .iter()
+            .any(|pattern| text.contains(pattern.as_str()))
+    })
+}
+
+/// Infers tags from a table info document and a script analysis (both
+/// optional, `Value::Null` when missing).
+pub(crate) fn infer_tags(table_info: &Value, analysis: &Value) -> Vec<String> {
+    let mut tags: Vec<&str> = Vec::new();
+    let text = table_text(table_info);
+
+    match table_type(table_info).as_deref() {
+        Some("em" | "electro-mechanical" | "electromechanical") => tags.push("EM"),
+        Some("ss" | "solid state" | "solid-state") => tags.push("solid-state"),
+        Some("pm" | "pure mechanical" | "pure-mechanical") => tags.push("pure-mechanical"),
+        Some("original" | "orig" | "og") => tags.push("original"),
+        _ => {
+            // No TableType: fall back on what the script drives.
+            if analysis["uses_pinmame"].as_bool() == Some(true) {
+                tags.push("solid-state");
+            } else if analysis["uses_score_reels"].as_bool() == Some(true) {
+                tags.push("EM");
+            }
+        }
+    }
+    // Originals are listed with "Original" as their manufacturer.
+    let company = table_info["properties"]["CompanyName"].as_str().unwrap_or_default();
+    if company.trim().eq_ignore_ascii_case("original") && !tags.contains(&"original") {
+        tags.push("original");
+    }
+
+    match player_count(analysis, &text) {
+        Some(1) => tags.push("single-player"),
+        Some(2) => tags.push("2-player"),
+        Some(3) => tags.push("3-player"),
+        Some(4) => tags.push("4-player"),
+        Some(n) if n > 4 => tags.push("multi-player"),
+        _ => {}
+    }
+
+    let width = analysis["playfield"]["width"].as_f64().unwrap_or(0.0);
+    if width >= WIDEBODY_MIN_WIDTH_VPU || text.contains("widebody") || text.contains("wide body") {
+        tags.push("widebody");
+    }
+
+    let adult = !crate::content::keyword_hits(&text).is_empty();
+    if !adult && KIDS_KEYWORDS.iter().any(|k| text.contains(k)) {
+        tags.push("kids-friendly");
+    }
+
+    if analysis["uses_pup"].as_bool() == Some(true) {
+        tags.push("pup");
+    }
+    if analysis["uses_ultradmd"].as_bool() == Some(true) {
+        tags.push("ultradmd");
+    }
+    if analysis["uses_b2s"].as_bool() == Some(true) {
+        tags.push("b2s");
+    }
+
+    tags.into_iter().map(str::to_string).collect()
+}
+
+/// Parses an optional JSON argument; null gives `Value::Null`.
+///
+/// # Safety
+/// `s` must be null or a valid NUL-terminated string.
+pub(crate) unsafe fn optional_json(s: *const c_char, what: &str) -> Result<Value, VpxError> {
+    if s.is_null() {
+        return Ok(Value::Null);
+    }
+    let text = unsafe { crate::str_from_c(s, what) }?;
+    serde_json::from_str(text).map_err(|e| {
+        VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid {} JSON: {}", what, e))
+    })
+}
+
+/// Infers category tags (`"EM"`, `"solid-state"`, `"original"`,
+/// `"4-player"`, `"widebody"`, `"kids-friendly"`, ...) for one table.
+///
+/// `table_info_json` is a `vpx_get_table_info_as_json` document and
+/// `script_analysis_json` a `vpx_get_script_analysis` one; either may be
+/// null. Returns `{"tags": [...]}`, or null on bad JSON. Release with
+/// `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_infer_table_tags(
+    table_info_json: *const c_char,
+    script_analysis_json: *const c_char,
+) -> *mut c_char {
+    let result = unsafe { optional_json(table_info_json, "table info") }
+        .and_then(|info| Ok((info, unsafe { optional_json(script_analysis_json, "script analysis") }?)))
+        .and_then(|(info, analysis)| {
+            crate::into_c_string(json!({ "tags": infer_tags(&info, &analysis) }).to_string())
+        });
+
+    crate::envelope::finish("vpx_infer_table_tags", result)
+}
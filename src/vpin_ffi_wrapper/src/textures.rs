@@ -0,0 +1,507 @@
+//! Texture audit for low-VRAM cabinets.
+//!
+//! Some tables ship 4K or 8K playfield textures that a mini-PC GPU can't
+//! hold. The audit lists every embedded image, flags those whose longer
+//! side is over `max_size`, and estimates what downscaling them would save
+//! (compressed size scales roughly with pixel count). It can also write a
+//! downscaled copy of the table.
+//!
+//! Optimizing goes further for Raspberry Pi class machines: big textures
+//! are capped in size and re-encoded lossy, opaque ones as JPEG at the
+//! given quality. Textures with transparency stay lossless (PNG, or WebP
+//! for WebP sources), since the image encoder here only writes lossless
+//! WebP. Either way the original table is never changed.
+
+use std::fs;
+use std::io::Cursor;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use vpin::vpx::image::{ImageData, ImageDataJpeg};
+use vpin::vpx::lzw;
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::options::CallOptions;
+use crate::{library, oplog};
+
+/// JPEG quality for re-encoded JPEG textures, close to what authors export.
+const JPEG_QUALITY: u8 = 90;
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct AuditOptions {
+    /// Longest side allowed, in pixels.
+    max_size: u32,
+    /// Where to write a copy with the oversized textures downscaled.
+    output_path: Option<PathBuf>,
+    /// Replace an existing file at `output_path`.
+    overwrite: bool,
+}
+
+impl Default for AuditOptions {
+    fn default() -> Self {
+        AuditOptions { max_size: 2048, output_path: None, overwrite: false }
+    }
+}
+
+const KNOWN_KEYS: &[&str] = &["max_size", "output_path", "overwrite"];
+
+fn parse_error(path: &Path, what: &str, e: std::io::Error) -> VpxError {
+    VpxError::new(VpxErrorCode::ParseFailed, format!("Failed to read {} for '{}': {}", what, path.display(), e))
+}
+
+/// Stored size of an image's data (linked images have none).
+fn data_len(image: &ImageData) -> usize {
+    match (&image.jpeg, &image.bits) {
+        (Some(jpeg), _) => jpeg.data.len(),
+        (None, Some(bits)) => bits.lzw_compressed_data.len(),
+        (None, None) => 0,
+    }
+}
+
+fn format_name(image: &ImageData) -> String {
+    match image.bits {
+        Some(_) => "bmp".to_string(),
+        None => image.ext().to_ascii_lowercase(),
+    }
+}
+
+/// `width` x `height` scaled down to fit `max_size`, keeping the aspect.
+fn fit(width: u32, height: u32, max_size: u32) -> (u32, u32) {
+    let longest = width.max(height);
+    if longest <= max_size || max_size == 0 {
+        return (width, height);
+    }
+    let scale = max_size as f64 / longest as f64;
+    let scaled = |side: u32| ((side as f64 * scale).round() as u32).max(1);
+    (scaled(width), scaled(height))
+}
+
+fn is_oversized(image: &ImageData, max_size: u32) -> bool {
+    !image.is_link() && data_len(image) > 0 && image.width.max(image.height) > max_size
+}
+
+fn audit_entry(image: &ImageData, max_size: u32) -> Value {
+    let (target_width, target_height) = fit(image.width, image.height, max_size);
+    let oversized = is_oversized(image, max_size);
+    let pixels = image.width as f64 * image.height as f64;
+    let bytes = data_len(image);
+    let estimated = match oversized && pixels > 0.0 {
+        true => (bytes as f64 * (target_width as f64 * target_height as f64) / pixels).round() as usize,
+        false => bytes,
+    };
+    json!({
+        "name": image.name,
+        "format": format_name(image),
+        "width": image.width,
+        "height": image.height,
+        "bytes": bytes,
+        "oversized": oversized,
+        "target_width": target_width,
+        "target_height": target_height,
+        "estimated_bytes": estimated,
+    })
+}
+
+/// VPX bitmaps are LZW-compressed BGRA.
+fn decode_bits(data: &[u8], width: u32, height: u32) -> Result<DynamicImage, String> {
+    let rgba: Vec<u8> = lzw::from_lzw_blocks(data).chunks_exact(4).flat_map(|p| [p[2], p[1], p[0], p[3]]).collect();
+    RgbaImage::from_raw(width, height, rgba)
+        .map(DynamicImage::ImageRgba8)
+        .ok_or_else(|| "bitmap data does not match its size".to_string())
+}
+
+fn encode_bits(image: &DynamicImage) -> Vec<u8> {
+    let bgra: Vec<u8> = image.to_rgba8().chunks_exact(4).flat_map(|p| [p[2], p[1], p[0], p[3]]).collect();
+    lzw::to_lzw_blocks(&bgra)
+}
+
+fn encode(image: &DynamicImage, format: &str, quality: u8) -> Result<Vec<u8>, String> {
+    let mut out = Cursor::new(Vec::new());
+    let result = match format {
+        "png" => image.write_to(&mut out, ImageFormat::Png),
+        "webp" => image.write_to(&mut out, ImageFormat::WebP),
+        "jpg" | "jpeg" => image.to_rgb8().write_with_encoder(JpegEncoder::new_with_quality(&mut out, quality)),
+        other => return Err(format!("can't re-encode {} textures", other)),
+    };
+    result.map(|_| out.into_inner()).map_err(|e| e.to_string())
+}
+
+/// Downscales one oversized texture in place, in its own format.
+fn downscale(image: &mut ImageData, max_size: u32) -> Result<(), String> {
+    let (width, height) = fit(image.width, image.height, max_size);
+    let format = format_name(image);
+    match (&mut image.jpeg, &mut image.bits) {
+        (Some(jpeg), _) => {
+            let decoded = image::load_from_memory(&jpeg.data).map_err(|e| e.to_string())?;
+            let resized = decoded.resize_exact(width, height, FilterType::Lanczos3);
+            jpeg.data = encode(&resized, &format, JPEG_QUALITY)?;
+        }
+        (None, Some(bits)) => {
+            let decoded = decode_bits(&bits.lzw_compressed_data, image.width, image.height)?;
+            bits.lzw_compressed_data = encode_bits(&decoded.resize_exact(width, height, FilterType::Lanczos3));
+        }
+        (None, None) => return Err("image has no data".to_string()),
+    }
+    image.width = width;
+    image.height = height;
+    Ok(())
+}
+
+pub(crate) fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+/// Refuses to write over the table itself, or over another file unless asked.
+pub(crate) fn check_output(path: &Path, output: &Path, overwrite: bool) -> Result<(), VpxError> {
+    if fs::canonicalize(path).ok() == fs::canonicalize(output).ok() {
+        return Err(VpxError::new(VpxErrorCode::InvalidArgument, "The output must not be the table itself."));
+    }
+    if output.exists() && !overwrite {
+        return Err(VpxError::new(
+            VpxErrorCode::InvalidArgument,
+            format!("'{}' already exists (pass \"overwrite\": true to replace it)", output.display()),
+        ));
+    }
+    Ok(())
+}
+
+/// Writes `vpx` to `output` through a temporary file.
+pub(crate) fn save_copy(vpx: &vpin::vpx::VPX, output: &Path) -> Result<(), VpxError> {
+    let write_error = |e: std::io::Error| {
+        VpxError::new(VpxErrorCode::OpenFailed, format!("Failed to write '{}': {}", output.display(), e))
+    };
+    if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).map_err(write_error)?;
+    }
+    crate::safe_mode::check("writing tables")?;
+    crate::locks::check_not_in_use(output)?;
+    oplog::touch(output).map_err(write_error)?;
+    let mut tmp = output.as_os_str().to_os_string();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    vpin::vpx::write(&tmp, vpx).and_then(|_| fs::rename(&tmp, output)).map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        write_error(e)
+    })
+}
+
+/// Writes a copy of the table at `path` with its oversized textures
+/// downscaled to `output`.
+fn write_downscaled(path: &Path, output: &Path, max_size: u32) -> Result<Value, VpxError> {
+    let mut vpx = vpin::vpx::read(path).map_err(|e| parse_error(path, "table", e))?;
+    let mut downscaled = Vec::new();
+    let mut failed = Vec::new();
+    for image in vpx.images.iter_mut().filter(|image| is_oversized(image, max_size)) {
+        match downscale(image, max_size) {
+            Ok(()) => downscaled.push(image.name.clone()),
+            Err(error) => failed.push(json!({ "name": image.name, "error": error })),
+        }
+    }
+
+    save_copy(&vpx, output)?;
+
+    Ok(json!({
+        "path": output,
+        "file_size_before": file_size(path),
+        "file_size_after": file_size(output),
+        "downscaled": downscaled,
+        "failed": failed,
+    }))
+}
+
+fn audit(path: &Path, options: &AuditOptions) -> Result<Value, VpxError> {
+    let images = crate::catch_panic(path, || {
+        let mut vpx_file = crate::open_vpx(path, &CallOptions::default())?;
+        vpx_file.read_images().map_err(|e| parse_error(path, "images", e))
+    })?;
+    let textures: Vec<Value> = images.iter().map(|image| audit_entry(image, options.max_size)).collect();
+    let sum = |key: &str, oversized_only: bool| -> u64 {
+        textures
+            .iter()
+            .filter(|t| !oversized_only || t["oversized"] == true)
+            .filter_map(|t| t[key].as_u64())
+            .sum()
+    };
+    let total_bytes = sum("bytes", false);
+    let estimated_bytes = sum("estimated_bytes", false);
+
+    let output = match &options.output_path {
+        None => Value::Null,
+        Some(output) => {
+            check_output(path, output, options.overwrite)?;
+            let summary = format!("Downscale textures of {} into {}", path.display(), output.display());
+            oplog::record("textures", summary, || {
+                crate::catch_panic(path, || write_downscaled(path, output, options.max_size))
+            })?
+        }
+    };
+
+    Ok(json!({
+        "vpx_file": path,
+        "max_size": options.max_size,
+        "images": textures.len(),
+        "oversized": textures.iter().filter(|t| t["oversized"] == true).count(),
+        "total_bytes": total_bytes,
+        "oversized_bytes": sum("bytes", true),
+        "estimated_bytes_after": estimated_bytes,
+        "estimated_savings": total_bytes.saturating_sub(estimated_bytes),
+        "textures": textures,
+        "output": output,
+    }))
+}
+
+/// Audits the textures of the table at `vpx_file_path`. `options_json`
+/// (nullable): `max_size` (longest side allowed, default 2048),
+/// `output_path` (write a copy with oversized textures downscaled there,
+/// in their own format) and `overwrite`.
+///
+/// Returns `{"vpx_file", "max_size", "images", "oversized", "total_bytes",
+/// "oversized_bytes", "estimated_bytes_after", "estimated_savings",
+/// "textures": [{"name", "format", "width", "height", "bytes", "oversized",
+/// "target_width", "target_height", "estimated_bytes"}], "output": null |
+/// {"path", "file_size_before", "file_size_after", "downscaled", "failed"}}`,
+/// or null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_audit_textures(vpx_file_path: *const c_char, options_json: *const c_char) -> *mut c_char {
+    let result = unsafe { crate::path_from_c(vpx_file_path) }
+        .and_then(|path| {
+            let options: AuditOptions = unsafe { library::options_from_c(options_json, KNOWN_KEYS) }?;
+            audit(&path, &options)
+        })
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_audit_textures", result)
+}
+
+/// What the table uses as its playfield and backglass art, by image name
+/// (empty names for none).
+fn art_roles(gamedata: &vpin::vpx::gamedata::GameData) -> [(&'static str, String); 4] {
+    [
+        ("playfield", gamedata.image.clone()),
+        ("backglass_desktop", gamedata.backglass_image_full_desktop.clone()),
+        ("backglass_fullscreen", gamedata.backglass_image_full_fullscreen.clone()),
+        ("backglass_single_screen", gamedata.backglass_image_full_single_screen.clone().unwrap_or_default()),
+    ]
+}
+
+fn list_images(path: &Path) -> Result<Value, VpxError> {
+    let (images, gamedata) = crate::catch_panic(path, || {
+        let mut vpx_file = crate::open_vpx(path, &CallOptions::default())?;
+        let images = vpx_file.read_images().map_err(|e| parse_error(path, "images", e))?;
+        let gamedata = vpx_file.read_gamedata().map_err(|e| parse_error(path, "gamedata", e))?;
+        Ok((images, gamedata))
+    })?;
+    let roles = art_roles(&gamedata);
+    let entries: Vec<Value> = images
+        .iter()
+        .map(|image| {
+            let used_as: Vec<&str> = roles
+                .iter()
+                .filter(|(_, name)| !name.is_empty() && name.eq_ignore_ascii_case(&image.name))
+                .map(|(role, _)| *role)
+                .collect();
+            json!({
+                "name": image.name,
+                "format": format_name(image),
+                "width": image.width,
+                "height": image.height,
+                "bytes": data_len(image),
+                "linked": image.is_link(),
+                "original_path": image.path,
+                "used_as": used_as,
+            })
+        })
+        .collect();
+    // A role is embedded when it names an image the table carries data for.
+    let art: serde_json::Map<String, Value> = roles
+        .iter()
+        .map(|(role, name)| {
+            let embedded = images.iter().any(|image| {
+                !name.is_empty() && image.name.eq_ignore_ascii_case(name) && !image.is_link() && data_len(image) > 0
+            });
+            let name = (!name.is_empty()).then_some(name.as_str());
+            (role.to_string(), json!({ "image": name, "embedded": embedded }))
+        })
+        .collect();
+
+    Ok(json!({
+        "vpx_file": path,
+        "count": entries.len(),
+        "total_bytes": images.iter().map(data_len).sum::<usize>(),
+        "art": art,
+        "images": entries,
+    }))
+}
+
+/// Lists the images embedded in the table at `vpx_file_path`, with what
+/// the table uses for its playfield and backglass, so a frontend can tell
+/// whether it needs external media for them.
+///
+/// Returns `{"vpx_file", "count", "total_bytes", "art": {"playfield",
+/// "backglass_desktop", "backglass_fullscreen", "backglass_single_screen":
+/// {"image": name | null, "embedded"}}, "images": [{"name", "format",
+/// "width", "height", "bytes", "linked", "original_path", "used_as"}]}`,
+/// or null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `vpx_file_path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_images_list_as_json(vpx_file_path: *const c_char) -> *mut c_char {
+    let result = unsafe { crate::path_from_c(vpx_file_path) }
+        .and_then(|path| list_images(&path))
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_get_images_list_as_json", result)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct OptimizeOptions {
+    /// Longest side allowed, in pixels.
+    max_size: u32,
+    /// Textures at least this big are re-encoded even when not oversized.
+    min_bytes: usize,
+    overwrite: bool,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        OptimizeOptions { max_size: 2048, min_bytes: 256 * 1024, overwrite: false }
+    }
+}
+
+const OPTIMIZE_KEYS: &[&str] = &["max_size", "min_bytes", "overwrite"];
+
+/// The extension of a texture path swapped for `format`.
+fn with_format(path: &str, format: &str) -> String {
+    match path.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() && !ext.contains(['/', '\\']) => format!("{}.{}", stem, format),
+        _ => format!("{}.{}", path, format),
+    }
+}
+
+/// Re-encodes one texture, capped at `max_size`: opaque ones as JPEG at
+/// `quality`, others losslessly. Returns `(format, bytes)` after, or None
+/// when the result would not be smaller and no resize was needed.
+fn optimize(image: &mut ImageData, quality: u8, max_size: u32) -> Result<Option<(String, usize)>, String> {
+    let decoded = match (&image.jpeg, &image.bits) {
+        (Some(jpeg), _) => image::load_from_memory(&jpeg.data).map_err(|e| e.to_string())?,
+        (None, Some(bits)) => decode_bits(&bits.lzw_compressed_data, image.width, image.height)?,
+        (None, None) => return Err("image has no data".to_string()),
+    };
+    let resize = is_oversized(image, max_size);
+    let (width, height) = fit(image.width, image.height, max_size);
+    let resized = match resize {
+        true => decoded.resize_exact(width, height, FilterType::Lanczos3),
+        false => decoded,
+    };
+    let transparent = resized.color().has_alpha() && resized.to_rgba8().pixels().any(|p| p.0[3] != 255);
+    let format = match (transparent, format_name(image).as_str()) {
+        (false, _) => "jpg",
+        (true, "webp") => "webp",
+        (true, _) => "png",
+    };
+    let data = encode(&resized, format, quality)?;
+    if !resize && data.len() >= data_len(image) {
+        return Ok(None);
+    }
+
+    let path = with_format(&image.path, format);
+    let (name, internal_name) = match image.jpeg.take() {
+        Some(jpeg) => (jpeg.name, jpeg.internal_name),
+        None => (image.name.clone(), None),
+    };
+    let bytes = data.len();
+    image.jpeg = Some(ImageDataJpeg { path: path.clone(), name, internal_name, data });
+    image.bits = None;
+    image.path = path;
+    image.width = width;
+    image.height = height;
+    Ok(Some((format.to_string(), bytes)))
+}
+
+fn write_optimized(path: &Path, output: &Path, quality: u8, options: &OptimizeOptions) -> Result<Value, VpxError> {
+    let mut vpx = vpin::vpx::read(path).map_err(|e| parse_error(path, "table", e))?;
+    let mut textures = Vec::new();
+    let mut failed = Vec::new();
+    for image in vpx.images.iter_mut() {
+        let bytes = data_len(image);
+        if image.is_link() || bytes == 0 || (!is_oversized(image, options.max_size) && bytes < options.min_bytes) {
+            continue;
+        }
+        let before = json!({ "format": format_name(image), "width": image.width, "height": image.height, "bytes": bytes });
+        match optimize(image, quality, options.max_size) {
+            Ok(Some((format, after_bytes))) => textures.push(json!({
+                "name": image.name,
+                "before": before,
+                "after": { "format": format, "width": image.width, "height": image.height, "bytes": after_bytes },
+                "lossy": format == "jpg",
+            })),
+            Ok(None) => {}
+            Err(error) => failed.push(json!({ "name": image.name, "error": error })),
+        }
+    }
+    save_copy(&vpx, output)?;
+
+    let (before, after) = (file_size(path), file_size(output));
+    Ok(json!({
+        "vpx_file": path,
+        "output": output,
+        "quality": quality,
+        "max_size": options.max_size,
+        "file_size_before": before,
+        "file_size_after": after,
+        "saved_bytes": before.saturating_sub(after),
+        "textures": textures,
+        "failed": failed,
+    }))
+}
+
+/// Writes a lighter copy of the table at `vpx_file_path` to `dest_path`:
+/// textures over `max_size` or `min_bytes` are capped in size and
+/// re-encoded, opaque ones as JPEG at `quality` (1-100), transparent ones
+/// losslessly. A texture is only replaced when that makes it smaller or it
+/// had to be resized. `options_json` (nullable): `max_size` (default 2048),
+/// `min_bytes` (default 262144) and `overwrite`.
+///
+/// Returns `{"vpx_file", "output", "quality", "max_size",
+/// "file_size_before", "file_size_after", "saved_bytes", "textures":
+/// [{"name", "before", "after": {"format", "width", "height", "bytes"},
+/// "lossy"}], "failed"}`, or null on failure. Release with
+/// `vpx_free_string`.
+///
+/// # Safety
+/// The path and JSON arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_optimize_textures(
+    vpx_file_path: *const c_char,
+    dest_path: *const c_char,
+    quality: u8,
+    options_json: *const c_char,
+) -> *mut c_char {
+    let result = unsafe { crate::path_from_c(vpx_file_path) }
+        .and_then(|path| Ok((path, unsafe { crate::path_from_c(dest_path) }?)))
+        .and_then(|(path, dest)| {
+            if !(1..=100).contains(&quality) {
+                return Err(VpxError::new(VpxErrorCode::InvalidArgument, "quality must be between 1 and 100"));
+            }
+            let options: OptimizeOptions = unsafe { library::options_from_c(options_json, OPTIMIZE_KEYS) }?;
+            check_output(&path, &dest, options.overwrite)?;
+            let summary = format!("Optimize textures of {} into {}", path.display(), dest.display());
+            oplog::record("textures", summary, || {
+                crate::catch_panic(&path, || write_optimized(&path, &dest, quality, &options))
+            })
+        })
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_optimize_textures", result)
+}
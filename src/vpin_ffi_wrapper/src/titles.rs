@@ -0,0 +1,289 @@
+//! Display names from table metadata.
+//!
+//! The wheel, search results and exports all show a table's name, and each
+//! used to build it its own way. A template such as
+//! `{title} ({manufacturer} {year})` is rendered here once: fields take
+//! fallbacks (`{title|filename}`, `{year|"????"}`), and whatever a missing
+//! field leaves behind (`Title ( 1995)`, `Title ()`) is tidied away.
+
+use std::os::raw::c_char;
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::index;
+
+/// Used when no template is given.
+const DEFAULT_TEMPLATE: &str = "{title} ({manufacturer} {year})";
+
+/// Template names and the metadata keys they read, first present wins:
+/// scan metadata and table info names, then the frontend index's.
+const ALIASES: &[(&str, &[&str])] = &[
+    ("title", &["title", "bestTitle", "vpsName", "table_name", "tableName"]),
+    ("manufacturer", &["manufacturer", "bestManufacturer", "vpsManufacturer", "tableManufacturer"]),
+    ("year", &["year", "bestYear", "vpsYear", "tableYear"]),
+    ("author", &["author", "author_name", "vpsAuthors", "tableAuthor"]),
+    ("version", &["version", "table_version", "bestVersion", "vpsVersion", "tableVersion"]),
+    ("rom", &["rom_name", "rom", "romName", "tableRom"]),
+    ("type", &["table_type", "vpsType", "tableType"]),
+];
+
+#[derive(Debug)]
+enum Part {
+    Text(String),
+    Field { choices: Vec<Choice>, filter: Option<Filter> },
+}
+
+#[derive(Debug)]
+enum Choice {
+    Key(String),
+    Literal(String),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Filter {
+    Upper,
+    Lower,
+}
+
+fn syntax_error(position: usize, message: &str) -> VpxError {
+    VpxError::new(VpxErrorCode::InvalidArgument, format!("Title template, character {}: {}", position + 1, message))
+}
+
+/// Splits a template into text and `{field|fallback:filter}` parts. `{{`
+/// and `}}` are literal braces.
+fn parse(template: &str) -> Result<Vec<Part>, VpxError> {
+    let chars: Vec<char> = template.chars().collect();
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '{' if chars.get(i + 1) == Some(&'{') => {
+                text.push('{');
+                i += 2;
+            }
+            '}' if chars.get(i + 1) == Some(&'}') => {
+                text.push('}');
+                i += 2;
+            }
+            '}' => return Err(syntax_error(i, "'}' without '{' (write '}}' for a brace)")),
+            '{' => {
+                let start = i;
+                let mut inner = String::new();
+                let mut quoted = false;
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(syntax_error(start, "'{' is never closed")),
+                        Some('"') => quoted = !quoted,
+                        Some('}') if !quoted => break,
+                        Some(_) => {}
+                    }
+                    inner.push(chars[i]);
+                    i += 1;
+                }
+                i += 1;
+                if !text.is_empty() {
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                }
+                parts.push(field(&inner, start)?);
+            }
+            c => {
+                text.push(c);
+                i += 1;
+            }
+        }
+    }
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+    Ok(parts)
+}
+
+fn field(inner: &str, position: usize) -> Result<Part, VpxError> {
+    // The filter follows the last ':' outside quotes.
+    let mut quoted = false;
+    let colon = inner.char_indices().filter(|(_, c)| {
+        if *c == '"' {
+            quoted = !quoted;
+        }
+        *c == ':' && !quoted
+    });
+    let (choices, filter) = match colon.last() {
+        Some((at, _)) => {
+            let filter = match inner[at + 1..].trim() {
+                "upper" => Filter::Upper,
+                "lower" => Filter::Lower,
+                other => return Err(syntax_error(position, &format!("unknown filter '{}' (upper or lower)", other))),
+            };
+            (&inner[..at], Some(filter))
+        }
+        None => (inner, None),
+    };
+    let choices = split_choices(choices)
+        .into_iter()
+        .map(|choice| {
+            let choice = choice.trim();
+            match choice.strip_prefix('"').and_then(|c| c.strip_suffix('"')) {
+                Some(literal) => Ok(Choice::Literal(literal.to_string())),
+                None if choice.is_empty() => Err(syntax_error(position, "empty field name")),
+                None => Ok(Choice::Key(choice.to_string())),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Part::Field { choices, filter })
+}
+
+/// `a|b|"c|d"` split on the bars outside quotes.
+fn split_choices(text: &str) -> Vec<&str> {
+    let mut choices = Vec::new();
+    let (mut start, mut quoted) = (0, false);
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '|' if !quoted => {
+                choices.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    choices.push(&text[start..]);
+    choices
+}
+
+/// A metadata value as text; empty strings, nulls and zeros (unknown years)
+/// are no value.
+fn text_of(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::String(s) => s.trim().to_string(),
+        Value::Number(n) if n.as_f64() == Some(0.0) => return None,
+        Value::Number(n) => n.to_string(),
+        Value::Array(items) => items.iter().filter_map(text_of).collect::<Vec<_>>().join(", "),
+        _ => return None,
+    };
+    Some(text).filter(|t| !t.is_empty())
+}
+
+/// A template field's value in `metadata`, through the aliases.
+pub(crate) fn lookup(metadata: &Value, key: &str) -> Option<String> {
+    if key == "filename" {
+        let path = ["vpx_file", "vpxFile", "path"].iter().find_map(|k| metadata[k].as_str())?;
+        return Path::new(path).file_stem().and_then(|s| s.to_str()).map(str::to_string);
+    }
+    match ALIASES.iter().find(|(name, _)| *name == key) {
+        Some((_, keys)) => keys.iter().find_map(|k| text_of(&metadata[k])),
+        None => text_of(&metadata[key]),
+    }
+}
+
+/// Words that only separate two fields.
+const SEPARATORS: &[&str] = &["-", "–", "|", "/", "·"];
+
+/// Drops what missing fields leave behind: empty brackets, spaces inside
+/// brackets, doubled spaces and dangling separators.
+fn tidy(text: &str) -> String {
+    let mut text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    loop {
+        let before = text.clone();
+        for (open, close) in [("(", ")"), ("[", "]")] {
+            text = text.replace(&format!("{} ", open), open).replace(&format!(" {}", close), close);
+            text = text.replace(&format!("{}{}", open, close), "");
+        }
+        text = text.replace("  ", " ").replace(" ,", ",").replace(",)", ")").replace("(, ", "(");
+        text = text.trim_end_matches(',').trim_start_matches(", ").to_string();
+        // A lone separator at either end, before a bracket or next to another.
+        let words: Vec<&str> = text.split(' ').collect();
+        let lone = |i: usize| words.get(i).is_some_and(|w| SEPARATORS.contains(w));
+        text = (0..words.len())
+            .filter(|&i| {
+                !lone(i) || !(i == 0 || i + 1 == words.len() || lone(i + 1) || words[i + 1].starts_with(['(', '[']))
+            })
+            .map(|i| words[i])
+            .collect::<Vec<_>>()
+            .join(" ");
+        text = text.trim().to_string();
+        if text == before {
+            return text;
+        }
+    }
+}
+
+/// The template rendered for one table, with the fields that had no value.
+fn render(parts: &[Part], metadata: &Value) -> (String, Vec<String>) {
+    let mut out = String::new();
+    let mut missing = Vec::new();
+    for part in parts {
+        match part {
+            Part::Text(text) => out.push_str(text),
+            Part::Field { choices, filter } => {
+                let value = choices.iter().find_map(|choice| match choice {
+                    Choice::Key(key) => lookup(metadata, key),
+                    Choice::Literal(text) => Some(text.clone()),
+                });
+                match (value, filter) {
+                    (Some(v), Some(Filter::Upper)) => out.push_str(&v.to_uppercase()),
+                    (Some(v), Some(Filter::Lower)) => out.push_str(&v.to_lowercase()),
+                    (Some(v), None) => out.push_str(&v),
+                    (None, _) => {
+                        if let Some(Choice::Key(key)) = choices.first() {
+                            missing.push(key.clone());
+                        }
+                    }
+                }
+            }
+        }
+    }
+    (tidy(&out), missing)
+}
+
+fn format_titles(metadata: &Value, template: &str) -> Result<Value, VpxError> {
+    let parts = parse(template)?;
+    let titles = |tables: &[Value]| -> Vec<Value> { tables.iter().map(|t| json!(render(&parts, t).0)).collect() };
+    Ok(match metadata {
+        Value::Array(tables) => json!({ "titles": titles(tables) }),
+        Value::Object(_) if metadata["tables"].is_array() => json!({ "titles": titles(index::tables(metadata)) }),
+        Value::Object(_) => {
+            let (title, missing) = render(&parts, metadata);
+            json!({ "title": title, "missing": missing })
+        }
+        _ => return Err(VpxError::new(VpxErrorCode::InvalidArgument, "Metadata must be a JSON object or array.")),
+    })
+}
+
+/// Formats a table's display name from its metadata with `template`
+/// (null for `{title} ({manufacturer} {year})`).
+///
+/// Fields are `{title}`, `{manufacturer}`, `{year}`, `{author}`,
+/// `{version}`, `{rom}`, `{type}` and `{filename}`, read from scan metadata,
+/// table info or index rows alike, or any other metadata key as is.
+/// `{a|b|"text"}` takes the first with a value; `:upper` or `:lower` after
+/// the choices changes case; `{{` and `}}` are literal braces. Brackets,
+/// spaces and separators a missing field leaves are removed.
+///
+/// `metadata_json` is one table's metadata, an array of them or an index
+/// (`{"tables": [...]}`). Returns `{"title", "missing": [fields]}` for one
+/// table, `{"titles": [...]}` in order for several, or null on bad JSON or
+/// template. Release with `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_format_table_title(metadata_json: *const c_char, template: *const c_char) -> *mut c_char {
+    let template = match template.is_null() {
+        true => Ok(DEFAULT_TEMPLATE),
+        false => unsafe { crate::str_from_c(template, "template") },
+    };
+    let result = template
+        .and_then(|template| Ok((template, unsafe { crate::str_from_c(metadata_json, "metadata") }?)))
+        .and_then(|(template, text)| {
+            let metadata: Value = serde_json::from_str(text)
+                .map_err(|e| VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid metadata JSON: {}", e)))?;
+            format_titles(&metadata, template)
+        })
+        .and_then(|titles| crate::into_c_string(titles.to_string()));
+
+    crate::envelope::finish("vpx_format_table_title", result)
+}
@@ -0,0 +1,563 @@
+//! A VBScript tokenizer and a lightweight parser for table scripts.
+//!
+//! The tokenizer knows enough of the language to never be fooled by
+//! comments, strings, `Rem`, `:` statement separators or `_` line
+//! continuations. The parser doesn't build expressions; it walks the
+//! statements for declarations (subs, functions, properties, classes,
+//! constants, variables) and for what the script uses from outside
+//! (included `.vbs` files, objects and their members, `CreateObject`).
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token {
+    /// Names and keywords, as written.
+    Ident(String),
+    /// String literals, with `""` unescaped.
+    Str(String),
+    /// Numeric literals (`&HFF` included) and `#date#` literals.
+    Number(String),
+    /// Operators and punctuation.
+    Symbol(String),
+}
+
+impl Token {
+    /// True for the name or keyword `word`, in any case.
+    pub(crate) fn is(&self, word: &str) -> bool {
+        matches!(self, Token::Ident(name) if name.eq_ignore_ascii_case(word))
+    }
+
+    pub(crate) fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self, Token::Symbol(s) if s == symbol)
+    }
+
+    pub(crate) fn ident(&self) -> Option<&str> {
+        match self {
+            Token::Ident(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn string(&self) -> Option<&str> {
+        match self {
+            Token::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The token as it could appear in source.
+    fn text(&self) -> String {
+        match self {
+            Token::Str(s) => format!("\"{}\"", s.replace('"', "\"\"")),
+            Token::Ident(s) | Token::Number(s) | Token::Symbol(s) => s.clone(),
+        }
+    }
+}
+
+/// One statement: a line, or part of one split by `:`, or several joined
+/// by `_`.
+#[derive(Debug)]
+pub(crate) struct Statement {
+    /// 1-based line the statement starts on.
+    pub(crate) line: usize,
+    pub(crate) tokens: Vec<Token>,
+}
+
+#[derive(Default)]
+struct Lexer {
+    statements: Vec<Statement>,
+    tokens: Vec<Token>,
+    start: usize,
+}
+
+impl Lexer {
+    fn push(&mut self, token: Token, line: usize) {
+        if self.tokens.is_empty() {
+            self.start = line;
+        }
+        self.tokens.push(token);
+    }
+
+    fn end(&mut self) {
+        if !self.tokens.is_empty() {
+            self.statements.push(Statement { line: self.start, tokens: std::mem::take(&mut self.tokens) });
+        }
+    }
+}
+
+/// True when a `.` after `tokens` is member access rather than the start
+/// of a number like `.5`.
+fn follows_value(tokens: &[Token]) -> bool {
+    match tokens.last() {
+        Some(Token::Symbol(s)) => s == ")",
+        Some(_) => true,
+        None => false,
+    }
+}
+
+/// Splits `code` into statements of tokens, dropping comments.
+pub(crate) fn tokenize(code: &str) -> Vec<Statement> {
+    let chars: Vec<char> = code.chars().collect();
+    let at = |i: usize| chars.get(i).copied().unwrap_or('\0');
+    let to_line_end = |mut i: usize| {
+        while i < chars.len() && chars[i] != '\n' {
+            i += 1;
+        }
+        i
+    };
+    let mut lexer = Lexer::default();
+    let (mut i, mut line) = (0, 1);
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\n' => {
+                lexer.end();
+                line += 1;
+                i += 1;
+            }
+            ':' => {
+                lexer.end();
+                i += 1;
+            }
+            '\'' => i = to_line_end(i),
+            '"' => {
+                let mut literal = String::new();
+                i += 1;
+                loop {
+                    match at(i) {
+                        '"' if at(i + 1) == '"' => {
+                            literal.push('"');
+                            i += 2;
+                        }
+                        '"' => {
+                            i += 1;
+                            break;
+                        }
+                        // Unterminated; VBScript would refuse the line.
+                        '\n' | '\0' => break,
+                        c => {
+                            literal.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+                lexer.push(Token::Str(literal), line);
+            }
+            '_' if chars[i + 1..to_line_end(i)].iter().all(|c| c.is_whitespace()) => {
+                // Line continuation: the statement goes on on the next line.
+                i = to_line_end(i) + 1;
+                line += 1;
+            }
+            c if c.is_ascii_alphabetic() => {
+                let start = i;
+                while at(i).is_ascii_alphanumeric() || at(i) == '_' {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                if word.eq_ignore_ascii_case("rem") && lexer.tokens.is_empty() {
+                    i = to_line_end(i);
+                } else {
+                    lexer.push(Token::Ident(word), line);
+                }
+            }
+            '[' => {
+                let end = (i + 1..to_line_end(i)).find(|&j| chars[j] == ']').unwrap_or(to_line_end(i));
+                lexer.push(Token::Ident(chars[i + 1..end].iter().collect()), line);
+                i = if at(end) == ']' { end + 1 } else { end };
+            }
+            c if c.is_ascii_digit() || (c == '.' && at(i + 1).is_ascii_digit() && !follows_value(&lexer.tokens)) => {
+                let start = i;
+                while at(i).is_ascii_digit() || at(i) == '.' {
+                    i += 1;
+                }
+                if matches!(at(i), 'e' | 'E') && (at(i + 1).is_ascii_digit() || matches!(at(i + 1), '+' | '-')) {
+                    i += 2;
+                    while at(i).is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+                lexer.push(Token::Number(chars[start..i].iter().collect()), line);
+            }
+            '&' if matches!(at(i + 1), 'h' | 'H' | 'o' | 'O') && at(i + 2).is_ascii_hexdigit() => {
+                let start = i;
+                i += 2;
+                while at(i).is_ascii_hexdigit() {
+                    i += 1;
+                }
+                // A trailing `&` marks a Long.
+                if at(i) == '&' {
+                    i += 1;
+                }
+                lexer.push(Token::Number(chars[start..i].iter().collect()), line);
+            }
+            '#' if (i + 1..to_line_end(i)).any(|j| chars[j] == '#') => {
+                let end = (i + 1..to_line_end(i)).find(|&j| chars[j] == '#').unwrap_or(i);
+                lexer.push(Token::Number(chars[i..=end].iter().collect()), line);
+                i = end + 1;
+            }
+            c if c.is_whitespace() => i += 1,
+            '<' if matches!(at(i + 1), '=' | '>') => {
+                lexer.push(Token::Symbol(chars[i..i + 2].iter().collect()), line);
+                i += 2;
+            }
+            '>' if at(i + 1) == '=' => {
+                lexer.push(Token::Symbol(">=".to_string()), line);
+                i += 2;
+            }
+            c => {
+                lexer.push(Token::Symbol(c.to_string()), line);
+                i += 1;
+            }
+        }
+    }
+    lexer.end();
+    lexer.statements
+}
+
+/// `tokens` split on commas outside parentheses.
+pub(crate) fn split_list(tokens: &[Token]) -> Vec<&[Token]> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0i32, 0);
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Symbol(s) if s == "(" => depth += 1,
+            Token::Symbol(s) if s == ")" => depth -= 1,
+            Token::Symbol(s) if s == "," && depth <= 0 => {
+                parts.push(&tokens[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&tokens[start..]);
+    parts
+}
+
+/// `tokens` without leading `Public`/`Private` (and `Default`), and
+/// whether the declaration is public.
+fn strip_visibility(tokens: &[Token]) -> (bool, &[Token]) {
+    match tokens {
+        [first, rest @ ..] if first.is("private") => (false, rest),
+        [first, second, rest @ ..] if first.is("public") && second.is("default") => (true, rest),
+        [first, rest @ ..] if first.is("public") => (true, rest),
+        _ => (true, tokens),
+    }
+}
+
+/// The `name = value` pairs a statement assigns: a plain or `Set`
+/// assignment, or each constant of a `Const` list.
+pub(crate) fn assignments(statement: &Statement) -> Vec<(&str, &[Token])> {
+    let (_, tokens) = strip_visibility(&statement.tokens);
+    let groups = match tokens.first() {
+        Some(first) if first.is("const") => split_list(&tokens[1..]),
+        Some(first) if first.is("set") => vec![&tokens[1..]],
+        _ => vec![tokens],
+    };
+    groups
+        .into_iter()
+        .filter_map(|group| match group {
+            [Token::Ident(name), eq, value @ ..] if eq.is_symbol("=") && !value.is_empty() => {
+                Some((name.as_str(), value))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// A literal value as JSON: numbers, strings and booleans as themselves,
+/// anything else as its source text.
+pub(crate) fn literal_value(tokens: &[Token]) -> Value {
+    let number = |text: &str, negative: bool| {
+        let sign = if negative { -1 } else { 1 };
+        let lower = text.trim_end_matches('&').to_ascii_lowercase();
+        if let Some(hex) = lower.strip_prefix("&h") {
+            return i64::from_str_radix(hex, 16).ok().map(|n| json!(sign * n));
+        }
+        if let Some(octal) = lower.strip_prefix("&o") {
+            return i64::from_str_radix(octal, 8).ok().map(|n| json!(sign * n));
+        }
+        lower
+            .parse::<i64>()
+            .map(|n| json!(sign * n))
+            .ok()
+            .or_else(|| lower.parse::<f64>().ok().map(|n| json!(sign as f64 * n)))
+    };
+    let value = match tokens {
+        [Token::Number(n)] => number(n, false),
+        [minus, Token::Number(n)] if minus.is_symbol("-") => number(n, true),
+        [Token::Str(s)] => Some(json!(s)),
+        [t] if t.is("true") => Some(json!(true)),
+        [t] if t.is("false") => Some(json!(false)),
+        _ => None,
+    };
+    value.unwrap_or_else(|| json!(source_text(tokens)))
+}
+
+/// Tokens joined back into readable source.
+pub(crate) fn source_text(tokens: &[Token]) -> String {
+    let mut text = String::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let tight = i == 0
+            || token.is_symbol(".")
+            || token.is_symbol(")")
+            || token.is_symbol(",")
+            || token.is_symbol("(")
+            || tokens[i - 1].is_symbol(".")
+            || tokens[i - 1].is_symbol("(");
+        if !tight {
+            text.push(' ');
+        }
+        text.push_str(&token.text());
+    }
+    text
+}
+
+#[derive(Debug)]
+pub(crate) struct Procedure {
+    /// `sub`, `function`, `property get`, `property let` or `property set`.
+    pub(crate) kind: String,
+    pub(crate) name: String,
+    pub(crate) params: Vec<String>,
+    pub(crate) class: Option<String>,
+    pub(crate) public: bool,
+    pub(crate) start_line: usize,
+    /// None when the script never closes it.
+    pub(crate) end_line: Option<usize>,
+    /// The statements of the body, as indices into the tokenized script.
+    pub(crate) body: Range<usize>,
+}
+
+/// Parameter names from the tokens after a procedure name.
+fn params(tokens: &[Token]) -> Vec<String> {
+    let Some(inner) = tokens.strip_prefix(&[Token::Symbol("(".to_string())]) else { return Vec::new() };
+    let inner = inner.iter().position(|t| t.is_symbol(")")).map_or(inner, |end| &inner[..end]);
+    split_list(inner)
+        .into_iter()
+        .filter_map(|group| {
+            group
+                .iter()
+                .filter_map(Token::ident)
+                .find(|n| !["byval", "byref", "optional"].iter().any(|m| n.eq_ignore_ascii_case(m)))
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// How the script uses one object (`Controller`, `Light1`, `B2SController`...).
+#[derive(Debug)]
+struct ObjectUse {
+    name: String,
+    members: Vec<String>,
+    uses: usize,
+    first_line: usize,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Outline {
+    pub(crate) procedures: Vec<Procedure>,
+    classes: Vec<Value>,
+    constants: Vec<Value>,
+    variables: Vec<Value>,
+    includes: Vec<(String, usize)>,
+    /// By lowercase name.
+    objects: BTreeMap<String, ObjectUse>,
+    created_objects: Vec<Value>,
+    statements: usize,
+}
+
+/// The declarations and outside references of a tokenized script.
+pub(crate) fn outline(statements: &[Statement]) -> Outline {
+    let mut outline = Outline { statements: statements.len(), ..Outline::default() };
+    let mut open: Option<Procedure> = None;
+    let mut class: Option<(String, usize)> = None;
+    // Objects of the `With` blocks the statement is in, innermost last.
+    let mut with: Vec<String> = Vec::new();
+    for (index, statement) in statements.iter().enumerate() {
+        let tokens = &statement.tokens;
+        let (public, rest) = strip_visibility(tokens);
+        let declared = rest.len() < tokens.len();
+        match rest {
+            [kind, Token::Ident(name), tail @ ..] if kind.is("sub") || kind.is("function") => {
+                open = Some(Procedure {
+                    kind: kind.ident().unwrap_or_default().to_ascii_lowercase(),
+                    name: name.clone(),
+                    params: params(tail),
+                    class: class.as_ref().map(|(name, _)| name.clone()),
+                    public,
+                    start_line: statement.line,
+                    end_line: None,
+                    body: index + 1..index + 1,
+                });
+            }
+            [property, access, Token::Ident(name), tail @ ..]
+                if property.is("property") && (access.is("get") || access.is("let") || access.is("set")) =>
+            {
+                open = Some(Procedure {
+                    kind: format!("property {}", access.ident().unwrap_or_default().to_ascii_lowercase()),
+                    name: name.clone(),
+                    params: params(tail),
+                    class: class.as_ref().map(|(name, _)| name.clone()),
+                    public,
+                    start_line: statement.line,
+                    end_line: None,
+                    body: index + 1..index + 1,
+                });
+            }
+            [end, what, ..] if end.is("end") && (what.is("sub") || what.is("function") || what.is("property")) => {
+                if let Some(mut procedure) = open.take() {
+                    procedure.end_line = Some(statement.line);
+                    procedure.body.end = index;
+                    outline.procedures.push(procedure);
+                }
+            }
+            [keyword, Token::Ident(name), ..] if keyword.is("class") => class = Some((name.clone(), statement.line)),
+            [end, what, ..] if end.is("end") && what.is("class") => {
+                if let Some((name, start_line)) = class.take() {
+                    outline.classes.push(json!({ "name": name, "start_line": start_line, "end_line": statement.line }));
+                }
+            }
+            [keyword, list @ ..] if keyword.is("const") => {
+                for group in split_list(list) {
+                    if let [Token::Ident(name), eq, value @ ..] = group {
+                        if eq.is_symbol("=") {
+                            outline.constants.push(json!({
+                                "name": name,
+                                "value": literal_value(value),
+                                "line": statement.line,
+                                "procedure": open.as_ref().map(|p| &p.name),
+                            }));
+                        }
+                    }
+                }
+            }
+            [first, ..] if open.is_none() && (first.is("dim") || declared) => {
+                let list = if first.is("dim") { &rest[1..] } else { rest };
+                for group in split_list(list) {
+                    if let Some(Token::Ident(name)) = group.first() {
+                        outline.variables.push(json!({
+                            "name": name,
+                            "line": statement.line,
+                            "array": group.iter().any(|t| t.is_symbol("(")),
+                            "class": class.as_ref().map(|(name, _)| name),
+                        }));
+                    }
+                }
+            }
+            _ => {}
+        }
+        match tokens.as_slice() {
+            [keyword, Token::Ident(object), ..] if keyword.is("with") => with.push(object.clone()),
+            [keyword, what, ..] if keyword.is("end") && what.is("with") => {
+                with.pop();
+            }
+            _ => {}
+        }
+        collect_references(&mut outline, statement, with.last().map(String::as_str));
+    }
+    if let Some(procedure) = open {
+        outline.procedures.push(Procedure { body: procedure.body.start..statements.len(), ..procedure });
+    }
+    outline
+}
+
+/// Included files, `Object.Member` uses and `CreateObject` calls in one statement.
+fn collect_references(outline: &mut Outline, statement: &Statement, with: Option<&str>) {
+    let tokens = &statement.tokens;
+    for literal in tokens.iter().filter_map(Token::string) {
+        if literal.to_ascii_lowercase().ends_with(".vbs")
+            && !outline.includes.iter().any(|(file, _)| file.eq_ignore_ascii_case(literal))
+        {
+            outline.includes.push((literal.to_string(), statement.line));
+        }
+    }
+    let mut used = |object: &str, member: &str| {
+        let entry = outline.objects.entry(object.to_ascii_lowercase()).or_insert_with(|| ObjectUse {
+            name: object.to_string(),
+            members: Vec::new(),
+            uses: 0,
+            first_line: statement.line,
+        });
+        if !entry.members.iter().any(|m| m.eq_ignore_ascii_case(member)) {
+            entry.members.push(member.to_string());
+        }
+        entry.uses += 1;
+    };
+    for (k, window) in tokens.windows(2).enumerate() {
+        // `.Member` inside a `With` block.
+        if let (Some(object), [dot, Token::Ident(member)]) = (with, window) {
+            if dot.is_symbol(".") && !follows_value(&tokens[..k]) {
+                used(object, member);
+            }
+        }
+    }
+    for (k, window) in tokens.windows(3).enumerate() {
+        match window {
+            [Token::Ident(object), dot, Token::Ident(member)]
+                if dot.is_symbol(".")
+                    && !object.eq_ignore_ascii_case("me")
+                    && (k == 0 || !tokens[k - 1].is_symbol(".")) =>
+            {
+                used(object, member)
+            }
+            [create, paren, Token::Str(prog_id)] if create.is("createobject") && paren.is_symbol("(") => {
+                let variable = match tokens.as_slice() {
+                    [set, Token::Ident(name), eq, ..] if set.is("set") && eq.is_symbol("=") => Some(name),
+                    _ => None,
+                };
+                outline
+                    .created_objects
+                    .push(json!({ "prog_id": prog_id, "variable": variable, "line": statement.line }));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Outline {
+    /// The value of the script constant `name`, if it declares one.
+    pub(crate) fn constant(&self, name: &str) -> Option<&Value> {
+        self.constants
+            .iter()
+            .find(|c| c["name"].as_str().is_some_and(|n| n.eq_ignore_ascii_case(name)))
+            .map(|c| &c["value"])
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        let procedures: Vec<Value> = self
+            .procedures
+            .iter()
+            .map(|p| {
+                json!({
+                    "kind": p.kind,
+                    "name": p.name,
+                    "params": p.params,
+                    "class": p.class,
+                    "public": p.public,
+                    "start_line": p.start_line,
+                    "end_line": p.end_line,
+                })
+            })
+            .collect();
+        let mut objects: Vec<&ObjectUse> = self.objects.values().collect();
+        objects.sort_by_key(|o| std::cmp::Reverse(o.uses));
+        let objects: Vec<Value> = objects
+            .into_iter()
+            .map(|o| json!({ "name": o.name, "members": o.members, "uses": o.uses, "first_line": o.first_line }))
+            .collect();
+        let includes: Vec<Value> =
+            self.includes.iter().map(|(file, line)| json!({ "file": file, "line": line })).collect();
+        json!({
+            "statements": self.statements,
+            "procedures": procedures,
+            "classes": self.classes,
+            "constants": self.constants,
+            "variables": self.variables,
+            "includes": includes,
+            "objects": objects,
+            "created_objects": self.created_objects,
+        })
+    }
+}
@@ -0,0 +1,122 @@
+//! VR room detection.
+//!
+//! Tables ship their VR room (walls, cabinet, backbox) as ordinary items
+//! named by convention (`VR_Cab`, `VRRoomFloor`...) and grouped in a
+//! collection or layer of the same kind. VPX 10.8 adds part groups shown
+//! only in VR. Scripts usually expose the room as a numeric option
+//! (`Const VRRoom = 0`) and check `RenderingMode` to turn it on by themselves.
+
+use std::os::raw::c_char;
+use std::path::Path;
+
+use serde_json::{json, Value};
+use vpin::vpx::gameitem::GameItemEnum;
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::options::CallOptions;
+use crate::script;
+
+/// Part group visibility for VR (`VisibilityMask::VirtualReality`).
+const VISIBLE_IN_VR: u32 = 0x0080;
+/// And for the desktop/cabinet playfield view.
+const VISIBLE_ON_PLAYFIELD: u32 = 0x0001;
+
+/// How many VR item names are listed; rooms can have hundreds.
+const NAMES_LISTED: usize = 20;
+
+fn is_vr_name(name: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    name.starts_with("VR") || lower.starts_with("vr_") || lower.starts_with("vrroom")
+}
+
+fn is_vr_group(name: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    lower == "vr"
+        || lower.starts_with("vr_")
+        || lower.starts_with("vr ")
+        || lower.contains("vrroom")
+        || lower.contains("vr room")
+}
+
+fn push_unique(list: &mut Vec<String>, name: &str) {
+    if !list.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+        list.push(name.to_string());
+    }
+}
+
+fn read_vr(path: &Path, options: &CallOptions) -> Result<Value, VpxError> {
+    crate::catch_panic(path, || {
+        let parse_error = |what: &str, e: std::io::Error| {
+            VpxError::new(VpxErrorCode::ParseFailed, format!("Failed to read {} for '{}': {}", what, path.display(), e))
+        };
+        let mut vpx_file = crate::open_vpx(path, options)?;
+        let gamedata = vpx_file.read_gamedata().map_err(|e| parse_error("gamedata", e))?;
+        let items = vpx_file.read_gameitems().map_err(|e| parse_error("game items", e))?;
+        let collections = vpx_file.read_collections().map_err(|e| parse_error("collections", e))?;
+
+        let mut vr_items = 0;
+        let mut names = Vec::new();
+        let mut groups = Vec::new();
+        for item in &items {
+            if is_vr_name(item.name()) {
+                vr_items += 1;
+                if names.len() < NAMES_LISTED {
+                    names.push(item.name().to_string());
+                }
+            }
+            match item {
+                GameItemEnum::Primitive(primitive) => {
+                    for group in [&primitive.editor_layer_name, &primitive.part_group_name].into_iter().flatten() {
+                        if is_vr_group(group) {
+                            push_unique(&mut groups, group);
+                        }
+                    }
+                }
+                GameItemEnum::PartGroup(group)
+                    if group.visibility_mask & VISIBLE_IN_VR != 0
+                        && group.visibility_mask & VISIBLE_ON_PLAYFIELD == 0 =>
+                {
+                    push_unique(&mut groups, &group.name);
+                }
+                _ => {}
+            }
+        }
+        for collection in &collections {
+            if is_vr_group(&collection.name) || is_vr_name(&collection.name) {
+                push_unique(&mut groups, &collection.name);
+            }
+        }
+
+        let code = &gamedata.code.string;
+        let toggle = script::vr_toggle(code);
+        Ok(json!({
+            "vr_room": vr_items > 0 || !groups.is_empty() || toggle.is_some(),
+            "vr_items": vr_items,
+            "vr_item_names": names,
+            "vr_groups": groups,
+            "vr_toggle": toggle.map(|(name, value)| json!({ "name": name, "value": value })),
+            "script_detects_vr": script::detects_vr(code),
+        }))
+    })
+}
+
+/// Whether the table at `vpx_file_path` has a VR room: `{"vr_room",
+/// "vr_items" (items named `VR...`), "vr_item_names" (the first 20),
+/// "vr_groups" (VR collections, layers and VR-only part groups),
+/// "vr_toggle": {"name", "value"} or null, "script_detects_vr"}`. The
+/// toggle is the script option that turns the room on, with its default.
+///
+/// `options_json` is as for the `_ex` calls and may be null. Returns null
+/// on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_vr_info(vpx_file_path: *const c_char, options_json: *const c_char) -> *mut c_char {
+    let result = unsafe { CallOptions::from_c(options_json) }
+        .and_then(|options| Ok((unsafe { crate::path_from_c(vpx_file_path) }?, options)))
+        .and_then(|(path, options)| read_vr(&path, &options))
+        .and_then(|vr| crate::into_c_string(vr.to_string()));
+
+    crate::envelope::finish("vpx_get_vr_info", result)
+}
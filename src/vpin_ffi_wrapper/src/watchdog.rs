@@ -0,0 +1,191 @@
+//! Launch watchdog: did playing change the table files?
+//!
+//! VPX saves some tables back on exit (script settings, editor autosave) and
+//! a crash mid-write leaves a corrupt file. The frontend takes a manifest of
+//! the files before launching and checks them against it after VPX exits,
+//! then refreshes the metadata of exactly the tables that changed.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::os::raw::c_char;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::library;
+use crate::tags::optional_json;
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct VerifyOptions {
+    /// Hash files whose size and modification time match, to catch a
+    /// rewrite that kept both (off: trust the metadata).
+    hash: bool,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        VerifyOptions { hash: true }
+    }
+}
+
+const KNOWN_KEYS: &[&str] = &["hash"];
+
+/// The manifest entry of one file.
+#[derive(Debug, Deserialize)]
+struct FileState {
+    size: u64,
+    modified: u64,
+    sha256: Option<String>,
+}
+
+impl FileState {
+    fn to_json(&self) -> Value {
+        json!({ "size": self.size, "modified": self.modified, "sha256": self.sha256 })
+    }
+}
+
+/// The file's state now; `None` if it is gone.
+fn state(path: &Path, hash: bool) -> io::Result<Option<FileState>> {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let modified = metadata.modified().ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map_or(0, |d| d.as_secs());
+    let sha256 = match hash {
+        true => Some(crate::hashes::sha256_file(path)?),
+        false => None,
+    };
+    Ok(Some(FileState { size: metadata.len(), modified, sha256 }))
+}
+
+fn path_list(value: &Value) -> Result<Vec<String>, VpxError> {
+    let invalid = || VpxError::new(VpxErrorCode::InvalidArgument, "Paths must be an array of strings.");
+    value.as_array().ok_or_else(invalid)?.iter().map(|p| p.as_str().map(str::to_string).ok_or_else(invalid)).collect()
+}
+
+fn manifest(paths: &[String]) -> Value {
+    let mut files = Map::new();
+    let mut errors = Vec::new();
+    for path in paths {
+        match state(Path::new(path), true) {
+            Ok(Some(state)) => _ = files.insert(path.clone(), state.to_json()),
+            Ok(None) => errors.push(json!({ "path": path, "error": "not found" })),
+            Err(e) => errors.push(json!({ "path": path, "error": e.to_string() })),
+        }
+    }
+    json!({ "files": files, "errors": errors })
+}
+
+fn verify(paths: Option<Vec<String>>, baseline: &Value, options: &VerifyOptions) -> Result<Value, VpxError> {
+    let Some(files) = baseline["files"].as_object() else {
+        return Err(VpxError::new(VpxErrorCode::InvalidArgument, "Baseline manifest has no \"files\" object."));
+    };
+    let baseline: BTreeMap<&str, FileState> =
+        files.iter().filter_map(|(path, entry)| Some((path.as_str(), FileState::deserialize(entry).ok()?))).collect();
+    let paths = paths.unwrap_or_else(|| baseline.keys().map(|p| p.to_string()).collect());
+
+    let (mut changed, mut unchanged, mut errors) = (Vec::new(), 0, Vec::new());
+    let mut files = Map::new();
+    for path in &paths {
+        let before = baseline.get(path.as_str());
+        let same_metadata = |now: &FileState| before.is_some_and(|b| b.size == now.size && b.modified == now.modified);
+        let now = match state(Path::new(path), false) {
+            Ok(Some(now)) if options.hash || !same_metadata(&now) => state(Path::new(path), true),
+            // Trusted as it was, hash included, for the next baseline.
+            Ok(Some(now)) => Ok(Some(FileState { sha256: before.and_then(|b| b.sha256.clone()), ..now })),
+            now => now,
+        };
+        let reason = match (before, &now) {
+            (_, Err(e)) => {
+                errors.push(json!({ "path": path, "error": e.to_string() }));
+                continue;
+            }
+            (None, Ok(None)) => continue,
+            (None, Ok(Some(_))) => Some("new"),
+            (Some(_), Ok(None)) => Some("missing"),
+            (Some(before), Ok(Some(now))) if before.size != now.size => Some("size"),
+            (Some(before), Ok(Some(now))) => match (&before.sha256, &now.sha256) {
+                (Some(a), Some(b)) if a != b => Some("content"),
+                _ if before.modified != now.modified => Some("modified"),
+                _ => None,
+            },
+        };
+        if let Ok(Some(now)) = &now {
+            files.insert(path.clone(), now.to_json());
+        }
+        match reason {
+            Some(reason) => changed.push(json!({ "path": path, "reason": reason })),
+            None => unchanged += 1,
+        }
+    }
+    Ok(json!({
+        "changed": changed,
+        "unchanged": unchanged,
+        "errors": errors,
+        "manifest": { "files": files },
+    }))
+}
+
+/// Takes the baseline for `vpx_verify_unchanged` before a launch:
+/// `paths_json` is an array of file paths (the `.vpx` and whatever else the
+/// table may write).
+///
+/// Returns `{"files": {"<path>": {"size", "modified", "sha256"}}, "errors":
+/// [{"path", "error"}]}`, or null on failure. Release with
+/// `vpx_free_string`.
+///
+/// # Safety
+/// `paths_json` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_build_file_manifest(paths_json: *const c_char) -> *mut c_char {
+    let result = unsafe { crate::str_from_c(paths_json, "paths") }
+        .and_then(|text| {
+            serde_json::from_str::<Value>(text)
+                .map_err(|e| VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid paths JSON: {}", e)))
+        })
+        .and_then(|paths| path_list(&paths))
+        .and_then(|paths| crate::into_c_string(manifest(&paths).to_string()));
+
+    crate::envelope::finish("vpx_build_file_manifest", result)
+}
+
+/// Checks files against a `vpx_build_file_manifest` baseline after VPX
+/// exits. `paths_json` (nullable for every file in the baseline) names the
+/// files to check; `options_json` (nullable): `{"hash": true}`, false to
+/// trust matching size and modification time.
+///
+/// Returns `{"changed": [{"path", "reason": "size" | "content" | "modified"
+/// | "missing" | "new"}], "unchanged", "errors", "manifest"}`, `manifest`
+/// the checked files as they are now (the next baseline), or null on
+/// failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// All arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_verify_unchanged(
+    paths_json: *const c_char,
+    baseline_manifest_json: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    let result = unsafe { library::options_from_c::<VerifyOptions>(options_json, KNOWN_KEYS) }
+        .and_then(|options| {
+            let paths = match unsafe { optional_json(paths_json, "paths") }? {
+                Value::Null => None,
+                paths => Some(path_list(&paths)?),
+            };
+            let baseline: Value =
+                serde_json::from_str(unsafe { crate::str_from_c(baseline_manifest_json, "baseline") }?).map_err(
+                    |e| VpxError::new(VpxErrorCode::InvalidArgument, format!("Invalid baseline JSON: {}", e)),
+                )?;
+            verify(paths, &baseline, &options)
+        })
+        .and_then(|report| crate::into_c_string(report.to_string()));
+
+    crate::envelope::finish("vpx_verify_unchanged", result)
+}
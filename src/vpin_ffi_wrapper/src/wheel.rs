@@ -0,0 +1,197 @@
+//! Sorted and grouped wheel order.
+//!
+//! The wheel lists tables alphabetically, or by manufacturer or decade,
+//! with a jump point per group. Sorting names the way a player expects is
+//! the tricky part: "The Addams Family" files under A, "Élvira" next to
+//! "Elvira", "Terminator 2" before "Terminator 10", and `'Genie'` ignores
+//! its quotes. This computes the order and groups once for the frontend.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::os::raw::c_char;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::collation::{self, Collator};
+use crate::error::{VpxError, VpxErrorCode};
+use crate::{index, library, titles};
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct WheelOptions {
+    /// Leading words sorted past ("The Getaway" sorts as "Getaway").
+    articles: Vec<String>,
+    /// Compare digit runs as numbers ("2" before "10").
+    natural_numbers: bool,
+    /// Collation rules (`vpx_make_sort_key`); empty for the root order.
+    locale: String,
+}
+
+impl Default for WheelOptions {
+    fn default() -> Self {
+        WheelOptions { articles: vec!["the".to_string()], natural_numbers: true, locale: String::new() }
+    }
+}
+
+const KNOWN_KEYS: &[&str] = &["articles", "natural_numbers", "locale"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Grouping {
+    Alpha,
+    Manufacturer,
+    Decade,
+}
+
+/// The label of tables a grouping has no value for, listed last.
+const UNKNOWN: &str = "Unknown";
+
+struct Entry<'a> {
+    row: &'a Value,
+    title: String,
+    sort: String,
+    year: Option<u32>,
+    group: String,
+}
+
+fn year_of(row: &Value) -> Option<u32> {
+    let year = titles::lookup(row, "year")?;
+    let digits: String = year.chars().filter(char::is_ascii_digit).take(4).collect();
+    digits.parse().ok().filter(|y| (1900..=2100).contains(y))
+}
+
+fn group_of(entry: &Entry, grouping: Grouping, collator: &Collator) -> String {
+    match grouping {
+        Grouping::Alpha => collator.initial(&entry.title).unwrap_or_else(|| UNKNOWN.to_string()),
+        Grouping::Manufacturer => titles::lookup(entry.row, "manufacturer").unwrap_or_else(|| UNKNOWN.to_string()),
+        Grouping::Decade => entry.year.map(|y| format!("{}s", y / 10 * 10)).unwrap_or_else(|| UNKNOWN.to_string()),
+    }
+}
+
+/// Orders groups: `#` before letters, decades by year, manufacturers by
+/// name; unknown last.
+fn compare_groups(a: &str, b: &str, collator: &Collator) -> Ordering {
+    match (a == UNKNOWN, b == UNKNOWN) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => match (a == "#", b == "#") {
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            _ => collator.key(a).cmp(&collator.key(b)),
+        },
+    }
+}
+
+fn build(index_doc: &Value, grouping: Grouping, options: &WheelOptions) -> Value {
+    let collator = Collator::new(&options.locale, &options.articles, options.natural_numbers);
+    let mut entries: Vec<Entry> = index::tables(index_doc)
+        .iter()
+        .map(|row| {
+            let title = titles::lookup(row, "title").or_else(|| titles::lookup(row, "filename")).unwrap_or_default();
+            let sort = collator.key(&title);
+            let mut entry = Entry { row, year: year_of(row), title, sort, group: String::new() };
+            entry.group = group_of(&entry, grouping, &collator);
+            entry
+        })
+        .collect();
+
+    // Manufacturers spelled differently ("WILLIAMS", "Williams") are one
+    // group, labelled as most of its tables spell it.
+    if grouping == Grouping::Manufacturer {
+        let mut spellings: HashMap<String, HashMap<&str, usize>> = HashMap::new();
+        for entry in &entries {
+            *spellings.entry(collation::fold(&entry.group)).or_default().entry(entry.group.as_str()).or_default() += 1;
+        }
+        let labels: HashMap<String, String> = spellings
+            .into_iter()
+            .map(|(key, variants)| {
+                let label = variants.into_iter().max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(a.0))).map(|(s, _)| s);
+                (key, label.unwrap_or_default().to_string())
+            })
+            .collect();
+        for entry in &mut entries {
+            entry.group = labels[&collation::fold(&entry.group)].clone();
+        }
+    }
+
+    entries.sort_by(|a, b| {
+        compare_groups(&a.group, &b.group, &collator)
+            .then_with(|| a.sort.cmp(&b.sort))
+            .then_with(|| a.year.cmp(&b.year))
+            .then_with(|| a.title.cmp(&b.title))
+    });
+
+    let mut groups: Vec<Value> = Vec::new();
+    for (position, entry) in entries.iter().enumerate() {
+        match groups.last_mut() {
+            Some(last) if last["label"].as_str() == Some(entry.group.as_str()) => {
+                last["count"] = json!(last["count"].as_u64().unwrap_or(0) + 1);
+            }
+            _ => groups.push(json!({ "label": entry.group, "start": position, "count": 1 })),
+        }
+    }
+    let tables: Vec<Value> = entries
+        .iter()
+        .map(|e| json!({ "vpx_file": e.row["vpxFile"], "title": e.title, "sort_key": e.sort, "group": e.group }))
+        .collect();
+    json!({
+        "locale": collator.name(),
+        "grouping": match grouping {
+            Grouping::Alpha => "alpha",
+            Grouping::Manufacturer => "manufacturer",
+            Grouping::Decade => "decade",
+        },
+        "tables": tables,
+        "groups": groups,
+    })
+}
+
+/// Sorts the tables of `index_json` (the frontend index) for the wheel and
+/// groups them by `grouping`: `"alpha"` (first letter, `#` for digits and
+/// symbols; null means this), `"manufacturer"` or `"decade"`. Titles sort
+/// by `vpx_make_sort_key`: accents folded, punctuation ignored, a leading
+/// "The" moved past and numbers by value; groups by `#` then letters, by
+/// name or by decade, with "Unknown" last.
+///
+/// `options_json` (nullable): `articles` (default `["the"]`),
+/// `natural_numbers` (default true), `locale` (collation rules, default
+/// the root order).
+///
+/// Returns `{"locale", "grouping", "tables": [{"vpx_file", "title",
+/// "sort_key", "group"}], "groups": [{"label", "start", "count"}]}` in wheel
+/// order,
+/// `start` being the group's first position in `tables`, or null on
+/// failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// All arguments must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_build_wheel_index(
+    index_json: *const c_char,
+    grouping: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    let grouping = match grouping.is_null() {
+        true => Ok("alpha"),
+        false => unsafe { crate::str_from_c(grouping, "grouping") },
+    };
+    let grouping = grouping.and_then(|g| match g {
+        "alpha" => Ok(Grouping::Alpha),
+        "manufacturer" => Ok(Grouping::Manufacturer),
+        "decade" => Ok(Grouping::Decade),
+        other => Err(VpxError::new(
+            VpxErrorCode::InvalidArgument,
+            format!("Unknown grouping '{}' (alpha, manufacturer or decade)", other),
+        )),
+    });
+    let result = grouping
+        .and_then(|grouping| Ok((grouping, unsafe { crate::str_from_c(index_json, "index") }?)))
+        .and_then(|(grouping, text)| {
+            let index_doc = index::parse(text)?;
+            let options: WheelOptions = unsafe { library::options_from_c(options_json, KNOWN_KEYS) }?;
+            crate::into_c_string(build(&index_doc, grouping, &options).to_string())
+        });
+
+    crate::envelope::finish("vpx_build_wheel_index", result)
+}
@@ -0,0 +1,104 @@
+//! UTF-16 path entry points for Windows builds.
+//!
+//! The frontend holds paths as `wchar_t` strings on Windows, and the ANSI
+//! code page they would otherwise go through mangles anything outside it.
+//! These variants take the path as NUL-terminated UTF-16 and hand it on as
+//! UTF-8 to the regular calls, so both take the same code path.
+
+use std::ffi::CString;
+use std::os::raw::c_char;
+
+use crate::error::{VpxError, VpxErrorCode};
+use crate::handle::VpxHandle;
+
+/// A UTF-16 code unit, `vpx_utf16_t` in C: `wchar_t` on Windows so paths
+/// pass without a cast, `uint16_t` elsewhere.
+pub type VpxUtf16 = u16;
+
+/// The NUL-terminated UTF-16 string at `path` as a UTF-8 C string.
+///
+/// # Safety
+/// `path` must be null or a valid NUL-terminated UTF-16 string.
+unsafe fn path_from_wide(path: *const u16) -> Result<CString, VpxError> {
+    if path.is_null() {
+        return Err(VpxError::new(VpxErrorCode::NullArgument, "Input file path is null."));
+    }
+    let mut len = 0;
+    while unsafe { *path.add(len) } != 0 {
+        len += 1;
+    }
+    let units = unsafe { std::slice::from_raw_parts(path, len) };
+    let path = String::from_utf16(units)
+        .map_err(|e| VpxError::new(VpxErrorCode::InvalidUtf8Path, format!("Invalid UTF-16 in path: {}", e)))?;
+    // Without interior NULs: the length is up to the first one.
+    CString::new(path).map_err(|e| VpxError::new(VpxErrorCode::InvalidUtf8Path, e.to_string()))
+}
+
+/// Reports a path that could not be converted, the way the regular call
+/// reports a bad path.
+///
+/// # Safety
+/// `error_code` must be null or valid for a write.
+unsafe fn bad_path<T>(func: &str, e: VpxError, error_code: *mut i32) -> *mut T {
+    if let Some(out) = unsafe { error_code.as_mut() } {
+        *out = e.code as i32;
+    }
+    log_error!("{}: {}", func, e);
+    std::ptr::null_mut()
+}
+
+/// `vpx_get_table_info_as_json_err` with the path as UTF-16.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// `vpx_file_path` must be null or a valid NUL-terminated UTF-16 string,
+/// `options_json` null or a valid NUL-terminated string, `error_code` null
+/// or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_table_info_as_json_w(
+    vpx_file_path: *const VpxUtf16,
+    options_json: *const c_char,
+    error_code: *mut i32,
+) -> *mut c_char {
+    match unsafe { path_from_wide(vpx_file_path) } {
+        Ok(path) => unsafe { crate::vpx_get_table_info_as_json_err(path.as_ptr(), options_json, error_code) },
+        Err(e) => unsafe { bad_path("vpx_get_table_info_as_json_w", e, error_code) },
+    }
+}
+
+/// `vpx_get_gamedata_code_err` with the path as UTF-16.
+///
+/// Returns null on failure. Release with `vpx_free_string`.
+///
+/// # Safety
+/// As for `vpx_get_table_info_as_json_w`.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_get_gamedata_code_w(
+    vpx_file_path: *const VpxUtf16,
+    options_json: *const c_char,
+    error_code: *mut i32,
+) -> *mut c_char {
+    match unsafe { path_from_wide(vpx_file_path) } {
+        Ok(path) => unsafe { crate::vpx_get_gamedata_code_err(path.as_ptr(), options_json, error_code) },
+        Err(e) => unsafe { bad_path("vpx_get_gamedata_code_w", e, error_code) },
+    }
+}
+
+/// `vpx_open_err` with the path as UTF-16.
+///
+/// Returns null on failure.
+///
+/// # Safety
+/// As for `vpx_get_table_info_as_json_w`.
+#[no_mangle]
+pub unsafe extern "C" fn vpx_open_w(
+    vpx_file_path: *const VpxUtf16,
+    options_json: *const c_char,
+    error_code: *mut i32,
+) -> *mut VpxHandle {
+    match unsafe { path_from_wide(vpx_file_path) } {
+        Ok(path) => unsafe { crate::handle::vpx_open_err(path.as_ptr(), options_json, error_code) },
+        Err(e) => unsafe { bad_path("vpx_open_w", e, error_code) },
+    }
+}